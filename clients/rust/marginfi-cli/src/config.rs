@@ -1,16 +1,25 @@
 use solana_sdk::signature::Signature;
 
+use crate::{
+    signer::SocketSigner,
+    squads::SquadsConfig,
+    utils::{find_lookup_tables, load_lookup_tables},
+};
+
 use {
     anchor_client::{Client, Cluster, Program},
-    clap::Parser,
+    clap::{clap_derive::ArgEnum, Parser},
+    log::warn,
+    marginfi::prelude::MarginfiGroup,
     serde::{Deserialize, Serialize},
+    solana_remote_wallet::remote_keypair::RemoteKeypair,
     solana_sdk::{
+        address_lookup_table_account::AddressLookupTableAccount,
         commitment_config::CommitmentConfig,
         pubkey::Pubkey,
         signature::{Keypair, Signer},
     },
-    std::ops::Deref,
-    std::str::FromStr,
+    std::{cell::OnceCell, rc::Rc, str::FromStr},
 };
 
 #[derive(Default, Debug, Parser)]
@@ -39,35 +48,129 @@ pub struct GlobalOptions {
         default_value_t = false
     )]
     pub skip_confirmation: bool,
+
+    /// Compute unit price in micro-lamports, or `auto[:<percentile>]` to estimate it from
+    /// recent prioritization fees. Overrides the profile setting.
+    #[clap(global = true, long)]
+    pub compute_unit_price: Option<PriorityFee>,
+
+    /// Compute unit limit, simulated when unset. Overrides the profile setting.
+    #[clap(global = true, long)]
+    pub compute_unit_limit: Option<u32>,
+
+    /// Output format of read commands.
+    #[clap(global = true, long, arg_enum, default_value = "text")]
+    pub output: OutputFormat,
 }
 
-#[derive(Copy, Clone, Debug)]
-pub enum TxMode {
-    DryRun,
-    Multisig,
-    Normal,
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ArgEnum)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    /// Structured JSON, with raw and UI amounts.
+    Json,
 }
 
-pub enum CliSigner {
-    Keypair(Keypair),
+const DEFAULT_PRIORITY_FEE_PERCENTILE: u8 = 75;
+
+/// Compute unit price of transactions.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum PriorityFee {
+    /// Price in micro-lamports.
+    Fixed(u64),
+    /// Percentile of the prioritization fees recently paid to write the transaction accounts.
+    Estimated { percentile: u8 },
 }
 
-impl CliSigner {
-    pub fn pubkey(&self) -> Pubkey {
+impl FromStr for PriorityFee {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "auto" => Ok(Self::Estimated {
+                percentile: DEFAULT_PRIORITY_FEE_PERCENTILE,
+            }),
+            None => Ok(Self::Fixed(s.parse().map_err(|_| {
+                anyhow::anyhow!(
+                    "Invalid compute unit price {}, expected micro-lamports or auto",
+                    s
+                )
+            })?)),
+            Some(("auto", percentile)) => match percentile.parse() {
+                Ok(percentile) if percentile <= 100 => Ok(Self::Estimated { percentile }),
+                _ => Err(anyhow::anyhow!("Invalid percentile {}", percentile)),
+            },
+            Some(_) => Err(anyhow::anyhow!("Invalid compute unit price {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for PriorityFee {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            CliSigner::Keypair(keypair) => keypair.pubkey(),
+            PriorityFee::Fixed(price) => write!(f, "{}", price),
+            PriorityFee::Estimated { percentile } => write!(f, "auto:{}", percentile),
         }
     }
 }
 
-pub fn clone_keypair(keypair: &Keypair) -> Keypair {
-    Keypair::from_bytes(&keypair.to_bytes()).unwrap()
+impl TryFrom<String> for PriorityFee {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<PriorityFee> for String {
+    fn from(fee: PriorityFee) -> Self {
+        fee.to_string()
+    }
+}
+
+/// Compute budget instructions added to sent transactions.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ComputeBudgetOptions {
+    pub compute_unit_price: Option<PriorityFee>,
+    /// Simulated when unset.
+    pub compute_unit_limit: Option<u32>,
 }
 
-impl Clone for CliSigner {
-    fn clone(&self) -> Self {
+#[derive(Copy, Clone, Debug)]
+pub enum TxMode<'a> {
+    DryRun {
+        lookup_tables: &'a [AddressLookupTableAccount],
+    },
+    Multisig,
+    /// Propose transactions to a Squads multisig, `member` creates the proposals.
+    Squads {
+        squads: SquadsConfig,
+        member: &'a CliSigner,
+        compute_budget: ComputeBudgetOptions,
+        lookup_tables: &'a [AddressLookupTableAccount],
+    },
+    Normal {
+        compute_budget: ComputeBudgetOptions,
+        lookup_tables: &'a [AddressLookupTableAccount],
+    },
+}
+
+/// Signer of a profile, see [`crate::signer`] for the supported sources.
+pub enum CliSigner {
+    Keypair(Keypair),
+    /// Hardware wallet, e.g. `usb://ledger`.
+    RemoteWallet(RemoteKeypair),
+    /// External signer process, e.g. `unix:///run/mfi-signer.sock`.
+    Socket(SocketSigner),
+}
+
+impl CliSigner {
+    pub fn pubkey(&self) -> Pubkey {
         match self {
-            CliSigner::Keypair(keypair) => CliSigner::Keypair(clone_keypair(keypair)),
+            CliSigner::Keypair(keypair) => keypair.pubkey(),
+            CliSigner::RemoteWallet(keypair) => keypair.pubkey(),
+            CliSigner::Socket(signer) => signer.pubkey(),
         }
     }
 }
@@ -83,36 +186,39 @@ impl Signer for CliSigner {
     ) -> Result<Signature, solana_sdk::signature::SignerError> {
         match self {
             CliSigner::Keypair(keypair) => Ok(keypair.try_sign_message(message)?),
+            CliSigner::RemoteWallet(keypair) => keypair.try_sign_message(message),
+            CliSigner::Socket(signer) => signer.try_sign_message(message),
         }
     }
 
     fn is_interactive(&self) -> bool {
         match self {
             CliSigner::Keypair(_) => true,
-        }
-    }
-}
-
-impl Deref for CliSigner {
-    type Target = Keypair;
-
-    fn deref(&self) -> &Self::Target {
-        match self {
-            CliSigner::Keypair(keypair) => keypair,
+            CliSigner::RemoteWallet(keypair) => keypair.is_interactive(),
+            CliSigner::Socket(signer) => signer.is_interactive(),
         }
     }
 }
 
 pub struct Config {
     pub cluster: Cluster,
-    pub fee_payer: Keypair,
+    pub fee_payer: CliSigner,
     pub multisig: Option<Pubkey>,
+    /// Squads multisig whose vault is `multisig`, admin transactions are proposed to it.
+    pub squads: Option<SquadsConfig>,
     pub program_id: Pubkey,
     pub commitment: CommitmentConfig,
     pub dry_run: bool,
-    pub client: Client<CliSigner>,
-    pub mfi_program: Program<CliSigner>,
-    pub lip_program: Program<CliSigner>,
+    pub output: OutputFormat,
+    pub compute_budget: ComputeBudgetOptions,
+    /// Lookup tables of sent transactions, those of the group admin when empty.
+    pub lookup_tables: Vec<Pubkey>,
+    pub marginfi_group: Option<Pubkey>,
+    /// Loaded on the first transaction.
+    pub lookup_table_accounts: OnceCell<Vec<AddressLookupTableAccount>>,
+    pub client: Client<Rc<Keypair>>,
+    pub mfi_program: Program<Rc<Keypair>>,
+    pub lip_program: Program<Rc<Keypair>>,
 }
 
 impl Config {
@@ -132,15 +238,54 @@ impl Config {
 
     pub fn get_tx_mode(&self) -> TxMode {
         if self.dry_run {
-            TxMode::DryRun
+            TxMode::DryRun {
+                lookup_tables: self.get_lookup_tables(),
+            }
+        } else if let Some(squads) = self.squads {
+            TxMode::Squads {
+                squads,
+                member: &self.fee_payer,
+                compute_budget: self.compute_budget,
+                lookup_tables: self.get_lookup_tables(),
+            }
         } else if self.multisig.is_some() {
             TxMode::Multisig
         } else {
-            TxMode::Normal
+            TxMode::Normal {
+                compute_budget: self.compute_budget,
+                lookup_tables: self.get_lookup_tables(),
+            }
         }
     }
 
-    pub fn get_signers(&self, explicit_fee_payer: bool) -> Vec<&Keypair> {
+    /// Lookup tables of the profile, or of the group admin if none is set.
+    /// Transactions are sent without lookup tables if they can't be loaded.
+    pub fn get_lookup_tables(&self) -> &[AddressLookupTableAccount] {
+        self.lookup_table_accounts.get_or_init(|| {
+            let rpc_client = self.mfi_program.rpc();
+
+            let addresses = if !self.lookup_tables.is_empty() {
+                Ok(self.lookup_tables.clone())
+            } else if let Some(marginfi_group) = self.marginfi_group {
+                self.mfi_program
+                    .account::<MarginfiGroup>(marginfi_group)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|group| find_lookup_tables(&rpc_client, &group.admin))
+            } else {
+                Ok(vec![])
+            };
+
+            match addresses.and_then(|addresses| load_lookup_tables(&rpc_client, &addresses)) {
+                Ok(lookup_tables) => lookup_tables,
+                Err(err) => {
+                    warn!("Failed to load lookup tables: {:?}", err);
+                    vec![]
+                }
+            }
+        })
+    }
+
+    pub fn get_signers(&self, explicit_fee_payer: bool) -> Vec<&dyn Signer> {
         if explicit_fee_payer || self.multisig.is_none() {
             vec![&self.fee_payer]
         } else {
@@ -150,7 +295,7 @@ impl Config {
 
     /// Get the authority keypair for signing transactions.
     /// This errors if the authority is a multisig.
-    pub fn get_non_ms_authority_keypair(&self) -> anyhow::Result<&Keypair> {
+    pub fn get_non_ms_authority_keypair(&self) -> anyhow::Result<&CliSigner> {
         if self.multisig.is_none() {
             Ok(&self.fee_payer)
         } else {
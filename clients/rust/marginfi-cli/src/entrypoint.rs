@@ -1,18 +1,28 @@
 use crate::{
-    config::GlobalOptions,
-    processor::{self, group::process_update_lookup_tables, process_set_user_flag},
+    config::{GlobalOptions, PriorityFee},
+    processor::{
+        self,
+        accounts::{AccountFilters, AccountSortKey, AccountsFormat},
+        crank::AccrualCrankOptions,
+        group::process_update_lookup_tables,
+        process_set_user_flag,
+        rates::RatesFormat,
+        simulate::{BankAmountArg, PriceShockArg, SimulationScenario},
+    },
     profile::{load_profile, Profile},
 };
 use anchor_client::Cluster;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use clap::{clap_derive::ArgEnum, Parser};
-#[cfg(feature = "admin")]
 use fixed::types::I80F48;
 #[cfg(any(feature = "admin", feature = "dev"))]
-use marginfi::state::marginfi_group::{BankConfigOpt, InterestRateConfigOpt};
+use marginfi::state::marginfi_group::BankConfigOpt;
 use marginfi::state::{
     marginfi_account::FLASHLOAN_ENABLED_FLAG,
-    marginfi_group::{BankOperationalState, RiskTier},
+    marginfi_group::{
+        BankBadDebtMode, BankBorrowMode, BankOperationalState, InterestRateConfigOpt, RiskTier,
+    },
+    order::OrderTrigger,
     price::OracleSetup,
 };
 #[cfg(feature = "dev")]
@@ -23,6 +33,7 @@ use marginfi::{
         marginfi_group::{Bank, BankConfig, InterestRateConfig, OracleConfig, WrappedI80F48},
     },
 };
+use serde::Deserialize;
 use solana_sdk::{commitment_config::CommitmentLevel, pubkey::Pubkey};
 
 #[cfg(feature = "dev")]
@@ -53,6 +64,10 @@ pub enum Command {
         #[clap(subcommand)]
         subcmd: ProfileCommand,
     },
+    Multisig {
+        #[clap(subcommand)]
+        subcmd: MultisigCommand,
+    },
     #[cfg(feature = "dev")]
     InspectPadding {},
     #[cfg(feature = "dev")]
@@ -78,6 +93,27 @@ pub enum GroupCommand {
         marginfi_group: Option<Pubkey>,
     },
     GetAll {},
+    /// List the marginfi accounts of the group with their health
+    Accounts {
+        marginfi_group: Option<Pubkey>,
+        #[clap(long, help = "Only accounts with a balance in the bank, repeatable")]
+        bank: Vec<Pubkey>,
+        #[clap(
+            long,
+            help = "Only accounts with a maintenance health below the USD value"
+        )]
+        health_below: Option<f64>,
+        #[clap(long, help = "Only accounts with liabilities above the USD value")]
+        min_liabilities: Option<f64>,
+        #[clap(long, action, help = "Only bankrupt accounts")]
+        bankrupt: bool,
+        #[clap(long, arg_enum, default_value = "health")]
+        sort_by: AccountSortKey,
+        #[clap(long)]
+        limit: Option<usize>,
+        #[clap(long, arg_enum, default_value = "table")]
+        format: AccountsFormat,
+    },
     #[cfg(feature = "admin")]
     Create {
         admin: Option<Pubkey>,
@@ -89,6 +125,12 @@ pub enum GroupCommand {
         admin: Option<Pubkey>,
     },
     #[cfg(feature = "admin")]
+    ConfigureFlashloanCpiProgram {
+        program_id: Pubkey,
+        #[clap(long, action, help = "Remove the program from the allowlist")]
+        disallow: bool,
+    },
+    #[cfg(feature = "admin")]
     AddBank {
         #[clap(long)]
         mint: Pubkey,
@@ -128,10 +170,51 @@ pub enum GroupCommand {
         #[clap(long, arg_enum)]
         oracle_type: OracleTypeArg,
     },
+    /// Apply the group bank configuration described in a TOML or YAML file
+    #[cfg(feature = "admin")]
+    Apply {
+        #[clap(long)]
+        file: String,
+        /// Only print the changes
+        #[clap(long, action)]
+        plan: bool,
+    },
     #[cfg(feature = "admin")]
     HandleBankruptcy {
         accounts: Vec<Pubkey>,
     },
+    /// Periodically accrue the interest of stale banks, collect bank fees and settle emissions
+    AccrualCrank {
+        #[clap(long, default_value_t = 300, help = "Seconds between rounds")]
+        interval: u64,
+        #[clap(
+            long,
+            default_value_t = 3600,
+            help = "Accrue banks not updated for this many seconds"
+        )]
+        max_staleness: u64,
+        #[clap(
+            long,
+            default_value_t = 100.,
+            help = "Collect the fees of banks with outstanding fees above this USD value"
+        )]
+        min_fees_usd: f64,
+        #[clap(
+            long,
+            help = "Settle the emissions of balances not updated for this many seconds"
+        )]
+        settle_emissions_after: Option<u64>,
+        #[clap(long, action, help = "Run a single round")]
+        once: bool,
+    },
+    /// Periodically handle the bad debt of every bankrupt account in the group
+    #[cfg(feature = "admin")]
+    BankruptcyCrank {
+        #[clap(long, default_value_t = 60, help = "Seconds between scans")]
+        interval: u64,
+        #[clap(long, action, help = "Scan and handle bankruptcies once")]
+        once: bool,
+    },
     #[cfg(feature = "admin")]
     UpdateLookupTable {
         #[clap(short = 't', long)]
@@ -139,7 +222,8 @@ pub enum GroupCommand {
     },
 }
 
-#[derive(Clone, Copy, Debug, Parser, ArgEnum)]
+#[derive(Clone, Copy, Debug, Parser, ArgEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum RiskTierArg {
     Collateral,
     Isolated,
@@ -154,7 +238,8 @@ impl From<RiskTierArg> for RiskTier {
     }
 }
 
-#[derive(Clone, Copy, Debug, Parser, ArgEnum)]
+#[derive(Clone, Copy, Debug, Parser, ArgEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum OracleTypeArg {
     PythEma,
     Switchboard,
@@ -169,7 +254,8 @@ impl From<OracleTypeArg> for OracleSetup {
     }
 }
 
-#[derive(Clone, Copy, Debug, Parser, ArgEnum)]
+#[derive(Clone, Copy, Debug, Parser, ArgEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum BankOperationalStateArg {
     Paused,
     Operational,
@@ -186,6 +272,38 @@ impl From<BankOperationalStateArg> for BankOperationalState {
     }
 }
 
+#[derive(Clone, Copy, Debug, Parser, ArgEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BankBorrowModeArg {
+    Permissionless,
+    Permissioned,
+}
+
+impl From<BankBorrowModeArg> for BankBorrowMode {
+    fn from(val: BankBorrowModeArg) -> Self {
+        match val {
+            BankBorrowModeArg::Permissionless => BankBorrowMode::Permissionless,
+            BankBorrowModeArg::Permissioned => BankBorrowMode::Permissioned,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Parser, ArgEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BankBadDebtModeArg {
+    Socialize,
+    Defer,
+}
+
+impl From<BankBadDebtModeArg> for BankBadDebtMode {
+    fn from(val: BankBadDebtModeArg) -> Self {
+        match val {
+            BankBadDebtModeArg::Socialize => BankBadDebtMode::Socialize,
+            BankBadDebtModeArg::Defer => BankBadDebtMode::Defer,
+        }
+    }
+}
+
 #[derive(Debug, Parser)]
 pub enum BankCommand {
     Get {
@@ -230,6 +348,10 @@ pub enum BankCommand {
         pf_fa: Option<f64>,
         #[clap(long, help = "Protocol IR fee")]
         pf_ir: Option<f64>,
+        #[clap(long, help = "Insurance flashloan fee")]
+        if_fl: Option<f64>,
+        #[clap(long, help = "Protocol flashloan fee")]
+        pf_fl: Option<f64>,
         #[clap(long, arg_enum, help = "Bank risk tier")]
         risk_tier: Option<RiskTierArg>,
         #[clap(long, arg_enum, help = "Bank oracle type")]
@@ -238,6 +360,96 @@ pub enum BankCommand {
         oracle_key: Option<Pubkey>,
         #[clap(long, help = "Soft USD init limit")]
         usd_init_limit: Option<u64>,
+        #[clap(long, help = "USD deposit limit, 0 to disable")]
+        usd_deposit_limit: Option<u64>,
+        #[clap(long, help = "USD borrow limit, 0 to disable")]
+        usd_borrow_limit: Option<u64>,
+        #[clap(long, help = "Outflow limit window in seconds, 0 to disable")]
+        outflow_limit_window: Option<u64>,
+        #[clap(long, help = "Max net outflow per window, 0 to disable")]
+        outflow_limit_ui: Option<f64>,
+        #[clap(long, help = "Max net USD outflow per window, 0 to disable")]
+        usd_outflow_limit: Option<u64>,
+        #[clap(long, arg_enum, help = "Bank borrow mode")]
+        borrow_mode: Option<BankBorrowModeArg>,
+        #[clap(long, arg_enum, help = "Handling of uninsured bad debt")]
+        bad_debt_mode: Option<BankBadDebtModeArg>,
+    },
+    #[cfg(feature = "admin")]
+    ResetOutflow {
+        bank_pk: Pubkey,
+    },
+    #[cfg(feature = "admin")]
+    SocializeBadDebt {
+        bank_pk: Pubkey,
+    },
+    #[cfg(feature = "admin")]
+    CreateBorrowCredential {
+        bank_pk: Pubkey,
+        marginfi_account_pk: Pubkey,
+        #[clap(long, help = "Per account borrow limit, no limit if omitted")]
+        borrow_limit_ui: Option<f64>,
+    },
+    #[cfg(feature = "admin")]
+    UpdateBorrowCredential {
+        bank_pk: Pubkey,
+        marginfi_account_pk: Pubkey,
+        #[clap(long, help = "Per account borrow limit, no limit if omitted")]
+        borrow_limit_ui: Option<f64>,
+    },
+    #[cfg(feature = "admin")]
+    RevokeBorrowCredential {
+        bank_pk: Pubkey,
+        marginfi_account_pk: Pubkey,
+    },
+    #[cfg(feature = "admin")]
+    SetupInsuranceStaking {
+        bank_pk: Pubkey,
+        #[clap(long, help = "Unstake cooldown in seconds")]
+        unstake_cooldown: u64,
+    },
+    InsuranceStake {
+        bank_pk: Pubkey,
+        ui_amount: f64,
+    },
+    InsuranceRequestUnstake {
+        bank_pk: Pubkey,
+        #[clap(help = "Share tokens to unstake, all if omitted")]
+        ui_shares: Option<f64>,
+        #[clap(long, default_value_t = 0, help = "Index of the unstake request")]
+        request_index: u64,
+    },
+    InsuranceUnstake {
+        bank_pk: Pubkey,
+        #[clap(long, default_value_t = 0, help = "Index of the unstake request")]
+        request_index: u64,
+    },
+    /// Move the insurance staking of a depleted insurance fund to a new share mint
+    #[cfg(feature = "admin")]
+    ResetInsuranceStaking {
+        bank_pk: Pubkey,
+    },
+    /// Tabulate the bank rates across utilization, with the proposed interest rate parameters
+    InspectRates {
+        bank_pk: Pubkey,
+        #[clap(long, help = "Proposed optimal utilization rate")]
+        opr_ur: Option<f64>,
+        #[clap(long, help = "Proposed plateau interest rate")]
+        p_ir: Option<f64>,
+        #[clap(long, help = "Proposed max interest rate")]
+        m_ir: Option<f64>,
+        #[clap(long, help = "Proposed insurance fee fixed APR")]
+        if_fa: Option<f64>,
+        #[clap(long, help = "Proposed insurance IR fee")]
+        if_ir: Option<f64>,
+        #[clap(long, help = "Proposed protocol fixed fee APR")]
+        pf_fa: Option<f64>,
+        #[clap(long, help = "Proposed protocol IR fee")]
+        pf_ir: Option<f64>,
+        #[clap(long, default_value_t = 20, help = "Utilization steps from 0 to 100%")]
+        steps: u16,
+        #[clap(long, arg_enum, default_value = "table")]
+        format: RatesFormat,
     },
     #[cfg(feature = "dev")]
     InspectPriceOracle {
@@ -284,6 +496,7 @@ pub enum ProfileCommand {
         name: String,
         #[clap(long)]
         cluster: Cluster,
+        /// Keypair file, `usb://ledger` hardware wallet or `unix://<socket>` external signer
         #[clap(long)]
         keypair_path: Option<String>,
         #[clap(long)]
@@ -298,6 +511,20 @@ pub enum ProfileCommand {
         group: Option<Pubkey>,
         #[clap(long)]
         account: Option<Pubkey>,
+        /// Squads multisig to propose admin transactions to, the keypair must be a member
+        #[clap(long)]
+        squads_multisig: Option<Pubkey>,
+        #[clap(long)]
+        squads_vault_index: Option<u8>,
+        /// Compute unit price in micro-lamports, or `auto[:<percentile>]` to estimate it
+        #[clap(long)]
+        compute_unit_price: Option<PriorityFee>,
+        /// Compute unit limit, simulated when unset
+        #[clap(long)]
+        compute_unit_limit: Option<u32>,
+        /// Lookup tables of transactions, those of the group admin are used when unset
+        #[clap(long = "lookup-table")]
+        lookup_tables: Vec<Pubkey>,
     },
     Show,
     List,
@@ -308,6 +535,7 @@ pub enum ProfileCommand {
         name: String,
         #[clap(long)]
         cluster: Option<Cluster>,
+        /// Keypair file, `usb://ledger` hardware wallet or `unix://<socket>` external signer
         #[clap(long)]
         keypair_path: Option<String>,
         #[clap(long)]
@@ -322,6 +550,47 @@ pub enum ProfileCommand {
         group: Option<Pubkey>,
         #[clap(long)]
         account: Option<Pubkey>,
+        /// Squads multisig to propose admin transactions to, the keypair must be a member
+        #[clap(long)]
+        squads_multisig: Option<Pubkey>,
+        #[clap(long)]
+        squads_vault_index: Option<u8>,
+        /// Compute unit price in micro-lamports, or `auto[:<percentile>]` to estimate it
+        #[clap(long)]
+        compute_unit_price: Option<PriorityFee>,
+        /// Compute unit limit, simulated when unset
+        #[clap(long)]
+        compute_unit_limit: Option<u32>,
+        /// Lookup tables of transactions, those of the group admin are used when unset
+        #[clap(long = "lookup-table")]
+        lookup_tables: Vec<Pubkey>,
+    },
+}
+
+/// Manage proposals of the profile Squads multisig
+#[derive(Debug, Parser)]
+pub enum MultisigCommand {
+    /// Show members, threshold and the approval status of recent proposals
+    Status {
+        #[clap(
+            long,
+            default_value_t = 10,
+            help = "Number of recent proposals to show"
+        )]
+        last: u64,
+    },
+    Approve {
+        transaction_index: u64,
+    },
+    Execute {
+        transaction_index: u64,
+    },
+    /// Batch the instructions of serialized transactions, as printed in multisig mode, into one proposal
+    Propose {
+        #[clap(required = true)]
+        transactions: Vec<String>,
+        #[clap(long)]
+        memo: Option<String>,
     },
 }
 
@@ -334,6 +603,25 @@ pub enum AccountCommand {
     Get {
         account: Option<Pubkey>,
     },
+    /// Show health, liquidation prices and max borrows after hypothetical actions and price moves.
+    /// Actions are applied in order: deposits, repays, withdrawals, borrows.
+    Simulate {
+        account: Option<Pubkey>,
+        #[clap(long, value_name = "BANK=UI_AMOUNT")]
+        deposit: Vec<BankAmountArg>,
+        #[clap(long, value_name = "BANK=UI_AMOUNT")]
+        repay: Vec<BankAmountArg>,
+        #[clap(long, value_name = "BANK=UI_AMOUNT")]
+        withdraw: Vec<BankAmountArg>,
+        #[clap(long, value_name = "BANK=UI_AMOUNT")]
+        borrow: Vec<BankAmountArg>,
+        #[clap(
+            long,
+            value_name = "BANK_OR_MINT=PERCENT",
+            help = "Price move of a bank or mint, e.g. <SOL mint>=-20 for a 20% drop"
+        )]
+        price_shock: Vec<PriceShockArg>,
+    },
     Deposit {
         bank: Pubkey,
         ui_amount: f64,
@@ -348,6 +636,38 @@ pub enum AccountCommand {
         bank: Pubkey,
         ui_amount: f64,
     },
+    TransferBalance {
+        bank: Pubkey,
+        destination_account: Pubkey,
+        ui_amount: f64,
+        #[clap(short = 'a', long = "all")]
+        transfer_all: bool,
+    },
+    CreateOrder {
+        #[clap(long)]
+        asset_bank: Pubkey,
+        #[clap(long)]
+        liability_bank: Pubkey,
+        #[clap(long, arg_enum)]
+        trigger: OrderTriggerArg,
+        #[clap(
+            long,
+            help = "Maintenance health ratio, or asset price in liability tokens"
+        )]
+        trigger_value: f64,
+        #[clap(
+            long,
+            default_value = "0.01",
+            help = "Keeper tip, as a share of the repaid value"
+        )]
+        keeper_tip: f64,
+    },
+    CloseOrder {
+        order: Pubkey,
+    },
+    ExecuteOrder {
+        order: Pubkey,
+    },
     Liquidate {
         #[clap(long)]
         liquidatee_marginfi_account: Pubkey,
@@ -366,6 +686,23 @@ pub enum AccountCommand {
     },
 }
 
+#[derive(Clone, Copy, Debug, Parser, ArgEnum)]
+pub enum OrderTriggerArg {
+    HealthBelow,
+    PriceBelow,
+    PriceAbove,
+}
+
+impl From<OrderTriggerArg> for OrderTrigger {
+    fn from(val: OrderTriggerArg) -> Self {
+        match val {
+            OrderTriggerArg::HealthBelow => OrderTrigger::HealthBelow,
+            OrderTriggerArg::PriceBelow => OrderTrigger::PriceBelow,
+            OrderTriggerArg::PriceAbove => OrderTrigger::PriceAbove,
+        }
+    }
+}
+
 #[derive(Debug, Parser)]
 #[cfg(feature = "lip")]
 pub enum LipCommand {
@@ -379,7 +716,8 @@ pub fn entry(opts: Opts) -> Result<()> {
     match opts.command {
         Command::Group { subcmd } => group(subcmd, &opts.cfg_override),
         Command::Bank { subcmd } => bank(subcmd, &opts.cfg_override),
-        Command::Profile { subcmd } => profile(subcmd),
+        Command::Profile { subcmd } => profile(subcmd, &opts.cfg_override),
+        Command::Multisig { subcmd } => multisig(subcmd, &opts.cfg_override),
         #[cfg(feature = "dev")]
         Command::InspectPadding {} => inspect_padding(),
         #[cfg(feature = "dev")]
@@ -401,7 +739,7 @@ pub fn entry(opts: Opts) -> Result<()> {
     }
 }
 
-fn profile(subcmd: ProfileCommand) -> Result<()> {
+fn profile(subcmd: ProfileCommand, global_options: &GlobalOptions) -> Result<()> {
     match subcmd {
         ProfileCommand::Create {
             name,
@@ -413,6 +751,11 @@ fn profile(subcmd: ProfileCommand) -> Result<()> {
             commitment,
             group,
             account,
+            squads_multisig,
+            squads_vault_index,
+            compute_unit_price,
+            compute_unit_limit,
+            lookup_tables,
         } => processor::create_profile(
             name,
             cluster,
@@ -423,9 +766,14 @@ fn profile(subcmd: ProfileCommand) -> Result<()> {
             commitment,
             group,
             account,
+            squads_multisig,
+            squads_vault_index,
+            compute_unit_price,
+            compute_unit_limit,
+            lookup_tables,
         ),
-        ProfileCommand::Show => processor::show_profile(),
-        ProfileCommand::List => processor::list_profiles(),
+        ProfileCommand::Show => processor::show_profile(global_options.output),
+        ProfileCommand::List => processor::list_profiles(global_options.output),
         ProfileCommand::Set { name } => processor::set_profile(name),
         ProfileCommand::Update {
             cluster,
@@ -437,6 +785,11 @@ fn profile(subcmd: ProfileCommand) -> Result<()> {
             group,
             name,
             account,
+            squads_multisig,
+            squads_vault_index,
+            compute_unit_price,
+            compute_unit_limit,
+            lookup_tables,
         } => processor::configure_profile(
             name,
             cluster,
@@ -447,10 +800,40 @@ fn profile(subcmd: ProfileCommand) -> Result<()> {
             commitment,
             group,
             account,
+            squads_multisig,
+            squads_vault_index,
+            compute_unit_price,
+            compute_unit_limit,
+            lookup_tables,
         ),
     }
 }
 
+fn multisig(subcmd: MultisigCommand, global_options: &GlobalOptions) -> Result<()> {
+    let profile = load_profile()?;
+    let config = profile.get_config(Some(global_options))?;
+
+    if !global_options.skip_confirmation {
+        match subcmd {
+            MultisigCommand::Status { .. } => (),
+            _ => get_consent(&subcmd, &profile)?,
+        }
+    }
+
+    match subcmd {
+        MultisigCommand::Status { last } => processor::multisig::multisig_status(&config, last),
+        MultisigCommand::Approve { transaction_index } => {
+            processor::multisig::multisig_approve(&config, transaction_index)
+        }
+        MultisigCommand::Execute { transaction_index } => {
+            processor::multisig::multisig_execute(&config, transaction_index)
+        }
+        MultisigCommand::Propose { transactions, memo } => {
+            processor::multisig::multisig_propose(&config, transactions, memo)
+        }
+    }
+}
+
 fn group(subcmd: GroupCommand, global_options: &GlobalOptions) -> Result<()> {
     let profile = load_profile()?;
     let config = profile.get_config(Some(global_options))?;
@@ -459,6 +842,11 @@ fn group(subcmd: GroupCommand, global_options: &GlobalOptions) -> Result<()> {
         match subcmd {
             GroupCommand::Get { marginfi_group: _ } => (),
             GroupCommand::GetAll {} => (),
+            GroupCommand::Accounts { .. } => (),
+            GroupCommand::AccrualCrank { .. } => get_consent(&subcmd, &profile)?,
+            // Confirmed after the plan is printed
+            #[cfg(feature = "admin")]
+            GroupCommand::Apply { .. } => (),
             #[cfg(feature = "admin")]
             _ => get_consent(&subcmd, &profile)?,
         }
@@ -469,6 +857,30 @@ fn group(subcmd: GroupCommand, global_options: &GlobalOptions) -> Result<()> {
             processor::group_get(config, marginfi_group.or(profile.marginfi_group))
         }
         GroupCommand::GetAll {} => processor::group_get_all(config),
+        GroupCommand::Accounts {
+            marginfi_group,
+            bank,
+            health_below,
+            min_liabilities,
+            bankrupt,
+            sort_by,
+            limit,
+            format,
+        } => processor::accounts::group_accounts(
+            &config,
+            marginfi_group
+                .or(profile.marginfi_group)
+                .ok_or_else(|| anyhow!("Marginfi group not specified"))?,
+            AccountFilters {
+                banks: bank,
+                health_below,
+                min_liabilities,
+                bankrupt,
+            },
+            sort_by,
+            limit,
+            format,
+        ),
         #[cfg(feature = "admin")]
         GroupCommand::Create {
             admin,
@@ -477,6 +889,13 @@ fn group(subcmd: GroupCommand, global_options: &GlobalOptions) -> Result<()> {
         #[cfg(feature = "admin")]
         GroupCommand::Update { admin } => processor::group_configure(config, profile, admin),
         #[cfg(feature = "admin")]
+        GroupCommand::ConfigureFlashloanCpiProgram {
+            program_id,
+            disallow,
+        } => {
+            processor::group_configure_flashloan_cpi_program(config, profile, program_id, !disallow)
+        }
+        #[cfg(feature = "admin")]
         GroupCommand::AddBank {
             mint: bank_mint,
             seed,
@@ -519,9 +938,38 @@ fn group(subcmd: GroupCommand, global_options: &GlobalOptions) -> Result<()> {
             risk_tier,
         ),
         #[cfg(feature = "admin")]
+        GroupCommand::Apply { file, plan } => processor::apply::group_apply(
+            &config,
+            &profile,
+            file,
+            plan,
+            global_options.skip_confirmation,
+        ),
+        #[cfg(feature = "admin")]
         GroupCommand::HandleBankruptcy { accounts } => {
             processor::handle_bankruptcy_for_accounts(&config, &profile, accounts)
         }
+        GroupCommand::AccrualCrank {
+            interval,
+            max_staleness,
+            min_fees_usd,
+            settle_emissions_after,
+            once,
+        } => processor::crank::accrual_crank(
+            &config,
+            &profile,
+            AccrualCrankOptions {
+                max_staleness,
+                min_fees_usd,
+                settle_emissions_after,
+            },
+            interval,
+            once,
+        ),
+        #[cfg(feature = "admin")]
+        GroupCommand::BankruptcyCrank { interval, once } => {
+            processor::crank::bankruptcy_crank(&config, &profile, interval, once)
+        }
         #[cfg(feature = "admin")]
         GroupCommand::UpdateLookupTable {
             existing_token_lookup_tables,
@@ -535,7 +983,9 @@ fn bank(subcmd: BankCommand, global_options: &GlobalOptions) -> Result<()> {
 
     if !global_options.skip_confirmation {
         match subcmd {
-            BankCommand::Get { .. } | BankCommand::GetAll { .. } => (),
+            BankCommand::Get { .. }
+            | BankCommand::GetAll { .. }
+            | BankCommand::InspectRates { .. } => (),
             #[cfg(feature = "dev")]
             BankCommand::InspectPriceOracle { .. } => (),
             #[allow(unreachable_patterns)]
@@ -563,10 +1013,19 @@ fn bank(subcmd: BankCommand, global_options: &GlobalOptions) -> Result<()> {
             if_ir,
             pf_fa,
             pf_ir,
+            if_fl,
+            pf_fl,
             risk_tier,
             oracle_type,
             oracle_key,
             usd_init_limit,
+            usd_deposit_limit,
+            usd_borrow_limit,
+            outflow_limit_window,
+            outflow_limit_ui,
+            usd_outflow_limit,
+            borrow_mode,
+            bad_debt_mode,
         } => {
             let bank = config
                 .mfi_program
@@ -610,12 +1069,116 @@ fn bank(subcmd: BankCommand, global_options: &GlobalOptions) -> Result<()> {
                         insurance_ir_fee: if_ir.map(|x| I80F48::from_num(x).into()),
                         protocol_fixed_fee_apr: pf_fa.map(|x| I80F48::from_num(x).into()),
                         protocol_ir_fee: pf_ir.map(|x| I80F48::from_num(x).into()),
+                        insurance_flashloan_fee: if_fl.map(|x| I80F48::from_num(x).into()),
+                        protocol_flashloan_fee: pf_fl.map(|x| I80F48::from_num(x).into()),
                     }),
                     risk_tier: risk_tier.map(|x| x.into()),
                     total_asset_value_init_limit: usd_init_limit,
+                    total_deposit_value_limit: usd_deposit_limit,
+                    total_borrow_value_limit: usd_borrow_limit,
+                    outflow_limit_window,
+                    outflow_limit: outflow_limit_ui.map(|ui_amount| {
+                        spl_token::ui_amount_to_amount(ui_amount, bank.mint_decimals)
+                    }),
+                    outflow_value_limit: usd_outflow_limit,
+                    borrow_mode: borrow_mode.map(|x| x.into()),
+                    bad_debt_mode: bad_debt_mode.map(|x| x.into()),
                 },
             )
         }
+        #[cfg(feature = "admin")]
+        BankCommand::ResetOutflow { bank_pk } => {
+            processor::bank_reset_outflow(config, profile, bank_pk)
+        }
+        #[cfg(feature = "admin")]
+        BankCommand::SocializeBadDebt { bank_pk } => {
+            processor::bank_socialize_bad_debt(config, profile, bank_pk)
+        }
+        #[cfg(feature = "admin")]
+        BankCommand::CreateBorrowCredential {
+            bank_pk,
+            marginfi_account_pk,
+            borrow_limit_ui,
+        } => processor::bank_create_borrow_credential(
+            config,
+            profile,
+            bank_pk,
+            marginfi_account_pk,
+            borrow_limit_ui,
+        ),
+        #[cfg(feature = "admin")]
+        BankCommand::UpdateBorrowCredential {
+            bank_pk,
+            marginfi_account_pk,
+            borrow_limit_ui,
+        } => processor::bank_update_borrow_credential(
+            config,
+            profile,
+            bank_pk,
+            marginfi_account_pk,
+            borrow_limit_ui,
+        ),
+        #[cfg(feature = "admin")]
+        BankCommand::RevokeBorrowCredential {
+            bank_pk,
+            marginfi_account_pk,
+        } => {
+            processor::bank_revoke_borrow_credential(config, profile, bank_pk, marginfi_account_pk)
+        }
+        #[cfg(feature = "admin")]
+        BankCommand::SetupInsuranceStaking {
+            bank_pk,
+            unstake_cooldown,
+        } => processor::bank_setup_insurance_staking(config, profile, bank_pk, unstake_cooldown),
+        BankCommand::InsuranceStake { bank_pk, ui_amount } => {
+            processor::bank_insurance_stake(&config, &profile, bank_pk, ui_amount)
+        }
+        BankCommand::InsuranceRequestUnstake {
+            bank_pk,
+            ui_shares,
+            request_index,
+        } => processor::bank_insurance_request_unstake(
+            &config,
+            &profile,
+            bank_pk,
+            ui_shares,
+            request_index,
+        ),
+        BankCommand::InsuranceUnstake {
+            bank_pk,
+            request_index,
+        } => processor::bank_insurance_unstake(&config, &profile, bank_pk, request_index),
+        #[cfg(feature = "admin")]
+        BankCommand::ResetInsuranceStaking { bank_pk } => {
+            processor::bank_reset_insurance_staking(config, profile, bank_pk)
+        }
+        BankCommand::InspectRates {
+            bank_pk,
+            opr_ur,
+            p_ir,
+            m_ir,
+            if_fa,
+            if_ir,
+            pf_fa,
+            pf_ir,
+            steps,
+            format,
+        } => processor::rates::bank_inspect_rates(
+            &config,
+            bank_pk,
+            InterestRateConfigOpt {
+                optimal_utilization_rate: opr_ur.map(|x| I80F48::from_num(x).into()),
+                plateau_interest_rate: p_ir.map(|x| I80F48::from_num(x).into()),
+                max_interest_rate: m_ir.map(|x| I80F48::from_num(x).into()),
+                insurance_fee_fixed_apr: if_fa.map(|x| I80F48::from_num(x).into()),
+                insurance_ir_fee: if_ir.map(|x| I80F48::from_num(x).into()),
+                protocol_fixed_fee_apr: pf_fa.map(|x| I80F48::from_num(x).into()),
+                protocol_ir_fee: pf_ir.map(|x| I80F48::from_num(x).into()),
+                ..InterestRateConfigOpt::default()
+            },
+            steps,
+            format,
+        ),
         #[cfg(feature = "dev")]
         BankCommand::InspectPriceOracle { bank_pk } => {
             processor::bank_inspect_price_oracle(config, bank_pk)
@@ -775,6 +1338,7 @@ fn process_account_subcmd(subcmd: AccountCommand, global_options: &GlobalOptions
     if !global_options.skip_confirmation {
         match subcmd {
             AccountCommand::Get { .. } | AccountCommand::List => (),
+            AccountCommand::Simulate { .. } => (),
             _ => get_consent(&subcmd, &profile)?,
         }
     }
@@ -787,6 +1351,25 @@ fn process_account_subcmd(subcmd: AccountCommand, global_options: &GlobalOptions
         AccountCommand::Get { account } => {
             processor::marginfi_account_get(profile, &config, account)
         }
+        AccountCommand::Simulate {
+            account,
+            deposit,
+            repay,
+            withdraw,
+            borrow,
+            price_shock,
+        } => processor::simulate::marginfi_account_simulate(
+            profile,
+            &config,
+            account,
+            SimulationScenario {
+                deposits: deposit,
+                repays: repay,
+                withdrawals: withdraw,
+                borrows: borrow,
+                price_shocks: price_shock,
+            },
+        ),
         AccountCommand::Deposit { bank, ui_amount } => {
             processor::marginfi_account_deposit(&profile, &config, bank, ui_amount)
         }
@@ -798,6 +1381,40 @@ fn process_account_subcmd(subcmd: AccountCommand, global_options: &GlobalOptions
         AccountCommand::Borrow { bank, ui_amount } => {
             processor::marginfi_account_borrow(&profile, &config, bank, ui_amount)
         }
+        AccountCommand::TransferBalance {
+            bank,
+            destination_account,
+            ui_amount,
+            transfer_all,
+        } => processor::marginfi_account_transfer_balance(
+            &profile,
+            &config,
+            bank,
+            destination_account,
+            ui_amount,
+            transfer_all,
+        ),
+        AccountCommand::CreateOrder {
+            asset_bank,
+            liability_bank,
+            trigger,
+            trigger_value,
+            keeper_tip,
+        } => processor::marginfi_account_create_order(
+            &profile,
+            &config,
+            asset_bank,
+            liability_bank,
+            trigger.into(),
+            trigger_value,
+            keeper_tip,
+        ),
+        AccountCommand::CloseOrder { order } => {
+            processor::marginfi_account_close_order(&profile, &config, order)
+        }
+        AccountCommand::ExecuteOrder { order } => {
+            processor::marginfi_account_execute_order(&profile, &config, order)
+        }
         AccountCommand::Liquidate {
             asset_bank: asset_bank_pk,
             liability_bank: liability_bank_pk,
@@ -847,8 +1464,6 @@ fn process_lip_subcmd(
         LipCommand::ListCampaigns => processor::process_list_lip_campaigns(&config),
         LipCommand::ListDeposits => processor::process_list_deposits(&config),
     }
-
-    Ok(())
 }
 
 fn get_consent<T: std::fmt::Debug>(cmd: T, profile: &Profile) -> Result<()> {
@@ -3,6 +3,8 @@ mod entrypoint;
 mod macros;
 mod processor;
 mod profile;
+mod signer;
+mod squads;
 mod utils;
 
 pub use entrypoint::*;
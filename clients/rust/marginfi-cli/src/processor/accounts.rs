@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use clap::clap_derive::ArgEnum;
+use log::{info, warn};
+use marginfi::state::{
+    health::{is_bankrupt, is_liquidatable, HealthEngine},
+    marginfi_account::{MarginfiAccount, RiskRequirementType},
+    marginfi_group::Bank,
+    price::OraclePriceFeedAdapter,
+};
+use serde::Serialize;
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::pubkey::Pubkey;
+
+use super::{load_all_banks, load_price_feeds, output::print_json};
+use crate::config::{Config, OutputFormat};
+
+#[derive(Clone, Copy, Debug, ArgEnum)]
+pub enum AccountSortKey {
+    /// Lowest maintenance health first
+    Health,
+    /// Largest liabilities first
+    Liabilities,
+    /// Largest assets first
+    Assets,
+}
+
+#[derive(Clone, Copy, Debug, ArgEnum)]
+pub enum AccountsFormat {
+    Table,
+    Csv,
+}
+
+#[derive(Debug, Default)]
+pub struct AccountFilters {
+    /// Accounts with a balance in any of the banks.
+    pub banks: Vec<Pubkey>,
+    /// Maintenance health below the value, in USD.
+    pub health_below: Option<f64>,
+    /// Liabilities above the value, in USD.
+    pub min_liabilities: Option<f64>,
+    pub bankrupt: bool,
+}
+
+/// Health of a marginfi account, in USD.
+///
+/// Assets and liabilities are unweighted, the maintenance health is weighted.
+#[derive(Clone, Debug, Serialize)]
+pub struct AccountRisk {
+    pub address: String,
+    pub authority: String,
+    pub assets: f64,
+    pub liabilities: f64,
+    pub maintenance_health: f64,
+    pub liquidatable: bool,
+    pub bankrupt: bool,
+}
+
+impl AccountRisk {
+    pub fn new(
+        address: &Pubkey,
+        marginfi_account: &MarginfiAccount,
+        banks: &HashMap<Pubkey, Bank>,
+        price_feeds: &HashMap<Pubkey, OraclePriceFeedAdapter>,
+    ) -> Result<Self> {
+        let health_engine = HealthEngine::load(marginfi_account, banks, price_feeds)?;
+        let (assets, liabilities) =
+            health_engine.get_account_health_components(RiskRequirementType::Equity)?;
+        let maintenance_health =
+            health_engine.get_account_health(RiskRequirementType::Maintenance)?;
+
+        Ok(Self {
+            address: address.to_string(),
+            authority: marginfi_account.authority.to_string(),
+            assets: assets.to_num(),
+            liabilities: liabilities.to_num(),
+            maintenance_health: maintenance_health.to_num(),
+            liquidatable: liabilities.is_positive() && is_liquidatable(maintenance_health),
+            bankrupt: is_bankrupt(assets, liabilities),
+        })
+    }
+}
+
+/// Marginfi account whose health can't be computed, e.g. with a stale oracle.
+#[derive(Clone, Debug, Serialize)]
+pub struct UnknownAccountRisk {
+    pub address: String,
+    pub authority: String,
+    pub error: String,
+}
+
+/// Accounts listed by [`group_accounts`], accounts with an unknown health are kept apart as they
+/// can't be filtered or sorted.
+#[derive(Debug, Serialize)]
+struct GroupAccounts {
+    accounts: Vec<AccountRisk>,
+    unknown_health: Vec<UnknownAccountRisk>,
+}
+
+/// Load every marginfi account of a group.
+pub fn load_group_accounts(
+    config: &Config,
+    marginfi_group: Pubkey,
+) -> Result<Vec<(Pubkey, MarginfiAccount)>> {
+    info!("Loading marginfi accounts of group {}", marginfi_group);
+
+    Ok(config
+        .mfi_program
+        .accounts::<MarginfiAccount>(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+            8,
+            marginfi_group.to_bytes().to_vec(),
+        ))])?)
+}
+
+/// Compute the health of the accounts, accounts whose health can't be computed are returned apart.
+pub fn load_account_risks(
+    accounts: &[(Pubkey, MarginfiAccount)],
+    banks: &HashMap<Pubkey, Bank>,
+    price_feeds: &HashMap<Pubkey, OraclePriceFeedAdapter>,
+) -> (Vec<AccountRisk>, Vec<UnknownAccountRisk>) {
+    let mut risks = vec![];
+    let mut unknown_risks = vec![];
+
+    for (address, marginfi_account) in accounts {
+        match AccountRisk::new(address, marginfi_account, banks, price_feeds) {
+            Ok(risk) => risks.push(risk),
+            Err(err) => {
+                warn!("Failed to compute health of {}: {}", address, err);
+                unknown_risks.push(UnknownAccountRisk {
+                    address: address.to_string(),
+                    authority: marginfi_account.authority.to_string(),
+                    error: err.to_string(),
+                });
+            }
+        }
+    }
+
+    (risks, unknown_risks)
+}
+
+/// List the marginfi accounts of a group matching the filters, with their health.
+pub fn group_accounts(
+    config: &Config,
+    marginfi_group: Pubkey,
+    filters: AccountFilters,
+    sort_by: AccountSortKey,
+    limit: Option<usize>,
+    format: AccountsFormat,
+) -> Result<()> {
+    let banks = HashMap::from_iter(load_all_banks(config, Some(marginfi_group))?);
+    let price_feeds = load_price_feeds(config, &banks)?;
+
+    let accounts = load_group_accounts(config, marginfi_group)?
+        .into_iter()
+        .filter(|(_, marginfi_account)| {
+            filters.banks.is_empty()
+                || marginfi_account
+                    .lending_account
+                    .get_active_balances_iter()
+                    .any(|balance| filters.banks.contains(&balance.bank_pk))
+        })
+        .collect::<Vec<_>>();
+
+    // Accounts with an unknown health can't be matched against the filters, they are left out of the results
+    let (risks, unknown_risks) = load_account_risks(&accounts, &banks, &price_feeds);
+    let mut risks = risks
+        .into_iter()
+        .filter(|risk| {
+            filters
+                .health_below
+                .map_or(true, |health| risk.maintenance_health < health)
+                && filters
+                    .min_liabilities
+                    .map_or(true, |liabilities| risk.liabilities > liabilities)
+                && (!filters.bankrupt || risk.bankrupt)
+        })
+        .collect::<Vec<_>>();
+
+    match sort_by {
+        AccountSortKey::Health => {
+            risks.sort_by(|a, b| a.maintenance_health.total_cmp(&b.maintenance_health))
+        }
+        AccountSortKey::Liabilities => {
+            risks.sort_by(|a, b| b.liabilities.total_cmp(&a.liabilities))
+        }
+        AccountSortKey::Assets => risks.sort_by(|a, b| b.assets.total_cmp(&a.assets)),
+    }
+    if let Some(limit) = limit {
+        risks.truncate(limit);
+    }
+
+    if config.output == OutputFormat::Json {
+        return print_json(&GroupAccounts {
+            accounts: risks,
+            unknown_health: unknown_risks,
+        });
+    }
+
+    match format {
+        AccountsFormat::Csv => {
+            println!(
+                "address,authority,assets,liabilities,maintenance_health,liquidatable,bankrupt"
+            );
+            for risk in risks.iter() {
+                println!(
+                    "{},{},{},{},{},{},{}",
+                    risk.address,
+                    risk.authority,
+                    risk.assets,
+                    risk.liabilities,
+                    risk.maintenance_health,
+                    risk.liquidatable,
+                    risk.bankrupt
+                );
+            }
+
+            if !unknown_risks.is_empty() {
+                warn!(
+                    "{} accounts with an unknown health left out of the export",
+                    unknown_risks.len()
+                );
+            }
+        }
+        AccountsFormat::Table => {
+            println!(
+                "{:<44} {:>16} {:>16} {:>16}  Flags",
+                "Address", "Assets", "Liabilities", "Maint health"
+            );
+            for risk in risks.iter() {
+                println!(
+                    "{:<44} {:>16.2} {:>16.2} {:>16.2}  {}",
+                    risk.address,
+                    risk.assets,
+                    risk.liabilities,
+                    risk.maintenance_health,
+                    [
+                        (risk.liquidatable, "liquidatable"),
+                        (risk.bankrupt, "bankrupt")
+                    ]
+                    .iter()
+                    .filter(|(flag, _)| *flag)
+                    .map(|(_, name)| *name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+                );
+            }
+            println!(
+                "{} of {} accounts, total liabilities: {:.2}",
+                risks.len(),
+                accounts.len(),
+                risks.iter().map(|risk| risk.liabilities).sum::<f64>()
+            );
+
+            if !unknown_risks.is_empty() {
+                println!(
+                    "\nHealth unknown, left out of the results ({} accounts):",
+                    unknown_risks.len()
+                );
+                for unknown_risk in unknown_risks.iter() {
+                    println!("{:<44} {}", unknown_risk.address, unknown_risk.error);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
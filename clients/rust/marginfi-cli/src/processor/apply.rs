@@ -0,0 +1,804 @@
+use std::{collections::HashMap, fmt::Debug, fs, io, path::Path, str::FromStr};
+
+use anchor_spl::token::spl_token;
+use anyhow::{anyhow, bail, Result};
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use marginfi::{
+    constants::{EMISSIONS_FLAG_BORROW_ACTIVE, EMISSIONS_FLAG_LENDING_ACTIVE},
+    state::marginfi_group::{
+        Bank, BankConfig, BankConfigOpt, InterestRateConfig, InterestRateConfigOpt, OracleConfig,
+        WrappedI80F48,
+    },
+};
+use marginfi_v2_sdk::{instructions::MarginfiGroupIxBuilder, pda::find_bank_with_seed_pda};
+use serde::{Deserialize, Deserializer};
+use solana_sdk::{
+    commitment_config::CommitmentConfig, instruction::Instruction, program_pack::Pack,
+    pubkey::Pubkey,
+};
+use spl_associated_token_account::get_associated_token_address;
+
+use super::load_all_banks;
+use crate::{
+    config::Config,
+    profile::Profile,
+    utils::{calc_emissions_rate, create_oracle_key_array, process_transaction},
+    BankBadDebtModeArg, BankBorrowModeArg, BankOperationalStateArg, OracleTypeArg, RiskTierArg,
+};
+
+/// Weights and rates are stored as I80F48 converted from floats, differences below this are conversion noise.
+const RATIO_TOLERANCE: I80F48 = I80F48!(0.000001);
+
+/// Desired state of a marginfi group, loaded from a TOML or YAML file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GroupSpec {
+    #[serde(default)]
+    pub banks: Vec<BankSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BankSpec {
+    /// Label of the bank in the plan output
+    pub name: String,
+    #[serde(deserialize_with = "deserialize_pubkey")]
+    pub mint: Pubkey,
+    /// Existing bank address, only required when the group has several banks for `mint`
+    #[serde(default, deserialize_with = "deserialize_optional_pubkey")]
+    pub address: Option<Pubkey>,
+
+    pub asset_weight_init: f64,
+    pub asset_weight_maint: f64,
+    pub liability_weight_init: f64,
+    pub liability_weight_maint: f64,
+
+    pub deposit_limit_ui: f64,
+    pub borrow_limit_ui: f64,
+    /// UI USD values, 0 -> inactive
+    #[serde(default)]
+    pub total_asset_value_init_limit: u64,
+    #[serde(default)]
+    pub total_deposit_value_limit: u64,
+    #[serde(default)]
+    pub total_borrow_value_limit: u64,
+
+    /// Outflow rate limit window in seconds, 0 -> inactive
+    #[serde(default)]
+    pub outflow_limit_window: u64,
+    #[serde(default)]
+    pub outflow_limit_ui: f64,
+    /// UI USD value, 0 -> inactive
+    #[serde(default)]
+    pub outflow_value_limit: u64,
+
+    pub risk_tier: RiskTierArg,
+    pub operational_state: Option<BankOperationalStateArg>,
+    pub borrow_mode: Option<BankBorrowModeArg>,
+    pub bad_debt_mode: Option<BankBadDebtModeArg>,
+
+    pub oracle: OracleSpec,
+    pub interest_rate: InterestRateSpec,
+    /// Emissions are left unchanged if omitted
+    pub emissions: Option<EmissionsSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OracleSpec {
+    pub setup: OracleTypeArg,
+    #[serde(deserialize_with = "deserialize_pubkey")]
+    pub key: Pubkey,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InterestRateSpec {
+    pub optimal_utilization_rate: f64,
+    pub plateau_interest_rate: f64,
+    pub max_interest_rate: f64,
+    pub insurance_fee_fixed_apr: f64,
+    pub insurance_ir_fee: f64,
+    pub protocol_fixed_fee_apr: f64,
+    pub protocol_ir_fee: f64,
+    #[serde(default)]
+    pub insurance_flashloan_fee: f64,
+    #[serde(default)]
+    pub protocol_flashloan_fee: f64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmissionsSpec {
+    #[serde(deserialize_with = "deserialize_pubkey")]
+    pub mint: Pubkey,
+    #[serde(default)]
+    pub deposits: bool,
+    #[serde(default)]
+    pub borrows: bool,
+    /// Emissions tokens (UI) per bank token (UI) per year
+    pub rate: f64,
+    /// Funding transferred from the authority token account when emissions are set up,
+    /// only used for banks without emissions
+    pub total_amount_ui: Option<f64>,
+}
+
+impl GroupSpec {
+    pub fn load(path: &str) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+
+        match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("yaml" | "yml") => Ok(serde_yaml::from_str(&content)?),
+            _ => Ok(toml::from_str(&content)?),
+        }
+    }
+}
+
+impl BankSpec {
+    fn bank_config(&self, mint_decimals: u8) -> BankConfig {
+        let ratio = |value: f64| WrappedI80F48::from(I80F48::from_num(value));
+        let interest_rate = &self.interest_rate;
+
+        BankConfig {
+            asset_weight_init: ratio(self.asset_weight_init),
+            asset_weight_maint: ratio(self.asset_weight_maint),
+            liability_weight_init: ratio(self.liability_weight_init),
+            liability_weight_maint: ratio(self.liability_weight_maint),
+            deposit_limit: spl_token::ui_amount_to_amount(self.deposit_limit_ui, mint_decimals),
+            borrow_limit: spl_token::ui_amount_to_amount(self.borrow_limit_ui, mint_decimals),
+            interest_rate_config: InterestRateConfig {
+                optimal_utilization_rate: ratio(interest_rate.optimal_utilization_rate),
+                plateau_interest_rate: ratio(interest_rate.plateau_interest_rate),
+                max_interest_rate: ratio(interest_rate.max_interest_rate),
+                insurance_fee_fixed_apr: ratio(interest_rate.insurance_fee_fixed_apr),
+                insurance_ir_fee: ratio(interest_rate.insurance_ir_fee),
+                protocol_fixed_fee_apr: ratio(interest_rate.protocol_fixed_fee_apr),
+                protocol_ir_fee: ratio(interest_rate.protocol_ir_fee),
+                insurance_flashloan_fee: ratio(interest_rate.insurance_flashloan_fee),
+                protocol_flashloan_fee: ratio(interest_rate.protocol_flashloan_fee),
+                ..InterestRateConfig::default()
+            },
+            operational_state: self
+                .operational_state
+                .unwrap_or(BankOperationalStateArg::Operational)
+                .into(),
+            oracle_setup: self.oracle.setup.into(),
+            oracle_keys: create_oracle_key_array(self.oracle.key),
+            risk_tier: self.risk_tier.into(),
+            total_asset_value_init_limit: self.total_asset_value_init_limit,
+            total_deposit_value_limit: self.total_deposit_value_limit,
+            total_borrow_value_limit: self.total_borrow_value_limit,
+            borrow_mode: self
+                .borrow_mode
+                .unwrap_or(BankBorrowModeArg::Permissionless)
+                .into(),
+            bad_debt_mode: self
+                .bad_debt_mode
+                .unwrap_or(BankBadDebtModeArg::Socialize)
+                .into(),
+            ..BankConfig::default()
+        }
+    }
+
+    /// `(outflow_limit_window, outflow_limit, outflow_value_limit)`, stored on the bank outside of `BankConfig`.
+    fn outflow_limits(&self, mint_decimals: u8) -> (u64, u64, u64) {
+        (
+            self.outflow_limit_window,
+            spl_token::ui_amount_to_amount(self.outflow_limit_ui, mint_decimals),
+            self.outflow_value_limit,
+        )
+    }
+}
+
+impl EmissionsSpec {
+    fn flags(&self) -> u64 {
+        let mut flags = 0;
+
+        if self.deposits {
+            flags |= EMISSIONS_FLAG_LENDING_ACTIVE;
+        }
+
+        if self.borrows {
+            flags |= EMISSIONS_FLAG_BORROW_ACTIVE;
+        }
+
+        flags
+    }
+}
+
+fn deserialize_pubkey<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Pubkey, D::Error> {
+    let address = String::deserialize(deserializer)?;
+
+    Pubkey::from_str(&address).map_err(serde::de::Error::custom)
+}
+
+fn deserialize_optional_pubkey<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Pubkey>, D::Error> {
+    deserialize_pubkey(deserializer).map(Some)
+}
+
+/// Changes of a bank, each transaction of `transactions` is sent separately, in order.
+struct BankPlan {
+    label: String,
+    changes: Vec<String>,
+    transactions: Vec<Vec<Instruction>>,
+}
+
+/// Field by field differences between the on-chain and desired state, for the plan output.
+#[derive(Default)]
+struct ConfigDiff {
+    changes: Vec<String>,
+}
+
+impl ConfigDiff {
+    fn ratio(
+        &mut self,
+        field: &str,
+        current: WrappedI80F48,
+        desired: WrappedI80F48,
+    ) -> Option<WrappedI80F48> {
+        let current = I80F48::from(current);
+        let desired_value = I80F48::from(desired);
+
+        if (current - desired_value).abs() <= RATIO_TOLERANCE {
+            return None;
+        }
+
+        self.changes
+            .push(format!("{}: {:.6} -> {:.6}", field, current, desired_value));
+
+        Some(desired)
+    }
+
+    fn value<T: PartialEq + Debug>(&mut self, field: &str, current: T, desired: T) -> Option<T> {
+        if current == desired {
+            return None;
+        }
+
+        self.changes
+            .push(format!("{}: {:?} -> {:?}", field, current, desired));
+
+        Some(desired)
+    }
+}
+
+/// Diff the group banks against the desired state in `path`, print the plan,
+/// and send the `lending_pool_add_bank_with_seed`, `lending_pool_configure_bank` and emissions transactions applying it.
+///
+/// Banks are matched by `address` or, if omitted, by mint. Unmatched banks are created at the next free seed PDA.
+/// On-chain banks missing from the file are reported and left untouched.
+pub fn group_apply(
+    config: &Config,
+    profile: &Profile,
+    path: String,
+    plan_only: bool,
+    skip_confirmation: bool,
+) -> Result<()> {
+    let marginfi_group = profile
+        .marginfi_group
+        .ok_or_else(|| anyhow!("Marginfi group not specified in profile [{}]", profile.name))?;
+    let spec = GroupSpec::load(&path)?;
+
+    let banks = HashMap::<Pubkey, Bank>::from_iter(load_all_banks(config, Some(marginfi_group))?);
+    let builder = MarginfiGroupIxBuilder::new(config.program_id, marginfi_group);
+
+    let mut plans = vec![];
+    let mut matched_banks = vec![];
+    let mut used_seeds: HashMap<Pubkey, u64> = HashMap::new();
+
+    for bank_spec in &spec.banks {
+        let existing_bank = match bank_spec.address {
+            Some(address) => {
+                let bank = banks.get(&address).ok_or_else(|| {
+                    anyhow!("Bank {} ({}) not found in group", address, bank_spec.name)
+                })?;
+
+                if bank.mint != bank_spec.mint {
+                    bail!(
+                        "Bank {} ({}) has mint {}",
+                        address,
+                        bank_spec.name,
+                        bank.mint
+                    );
+                }
+
+                Some((address, bank))
+            }
+            None => {
+                let mut mint_banks = banks
+                    .iter()
+                    .filter(|(_, bank)| bank.mint == bank_spec.mint)
+                    .map(|(bank_pk, bank)| (*bank_pk, bank))
+                    .collect::<Vec<_>>();
+
+                if mint_banks.len() > 1 {
+                    bail!(
+                        "Group has {} banks for mint {}, set the address of {}",
+                        mint_banks.len(),
+                        bank_spec.mint,
+                        bank_spec.name
+                    );
+                }
+
+                mint_banks.pop()
+            }
+        };
+
+        let plan = match existing_bank {
+            Some((bank_pk, bank)) => {
+                if matched_banks.contains(&bank_pk) {
+                    bail!("Bank {} is described more than once", bank_pk);
+                }
+                matched_banks.push(bank_pk);
+
+                plan_bank_update(config, &builder, bank_pk, bank, bank_spec)?
+            }
+            None => {
+                let next_seed = used_seeds.entry(bank_spec.mint).or_default();
+
+                Some(plan_bank_creation(
+                    config,
+                    &builder,
+                    &marginfi_group,
+                    bank_spec,
+                    next_seed,
+                )?)
+            }
+        };
+
+        plans.extend(plan);
+    }
+
+    let mut unmanaged_banks = banks
+        .iter()
+        .filter(|(bank_pk, _)| !matched_banks.contains(bank_pk))
+        .collect::<Vec<_>>();
+    unmanaged_banks.sort_by_key(|(bank_pk, _)| **bank_pk);
+
+    for (bank_pk, bank) in unmanaged_banks {
+        println!(
+            "! Bank {} (mint: {}) is not in {}, skipping",
+            bank_pk, bank.mint, path
+        );
+    }
+
+    if plans.is_empty() {
+        println!("Group {} matches {}, nothing to do", marginfi_group, path);
+        return Ok(());
+    }
+
+    for plan in &plans {
+        println!("{}", plan.label);
+        for change in &plan.changes {
+            println!("\t{}", change);
+        }
+    }
+
+    if plan_only {
+        return Ok(());
+    }
+
+    if !skip_confirmation {
+        println!("Apply {} bank changes? (y/n)", plans.len());
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+
+        if input.trim() != "y" {
+            println!("Aborting");
+            return Ok(());
+        }
+    }
+
+    let rpc_client = config.mfi_program.rpc();
+    let signing_keypairs = config.get_signers(false);
+
+    for plan in plans {
+        for ixs in plan.transactions {
+            let sig = process_transaction(
+                &ixs,
+                &config.authority(),
+                &rpc_client,
+                config.get_tx_mode(),
+                &signing_keypairs,
+            )?;
+            println!("{}: {}", plan.label, sig);
+        }
+    }
+
+    Ok(())
+}
+
+fn plan_bank_creation(
+    config: &Config,
+    builder: &MarginfiGroupIxBuilder,
+    marginfi_group: &Pubkey,
+    bank_spec: &BankSpec,
+    next_seed: &mut u64,
+) -> Result<BankPlan> {
+    let rpc_client = config.mfi_program.rpc();
+    let mint_decimals = get_mint_decimals(config, &bank_spec.mint)?;
+
+    // Next seed whose PDA is free, seeds picked for other new banks of the same mint are skipped
+    let (bank_pk, bank_seed) = loop {
+        let seed = *next_seed;
+        *next_seed += 1;
+
+        let (pda, _) =
+            find_bank_with_seed_pda(marginfi_group, &bank_spec.mint, seed, &config.program_id);
+        if rpc_client
+            .get_account_with_commitment(&pda, CommitmentConfig::default())?
+            .value
+            .is_none()
+        {
+            break (pda, seed);
+        }
+    };
+
+    let bank_config = bank_spec.bank_config(mint_decimals);
+    let (outflow_limit_window, outflow_limit, outflow_value_limit) =
+        bank_spec.outflow_limits(mint_decimals);
+
+    let mut changes = vec![
+        format!("mint: {}", bank_spec.mint),
+        format!("seed: {}", bank_seed),
+    ];
+    changes.extend(describe_bank_config(&bank_config));
+
+    let mut transactions = vec![vec![builder.add_bank_with_seed(
+        config.authority(),
+        config.authority(),
+        bank_spec.mint,
+        bank_seed,
+        bank_config.into(),
+    )]];
+
+    // Outflow limits and emissions can't be set on creation
+    let mut setup_ixs = vec![];
+
+    if outflow_limit_window != 0 || outflow_limit != 0 || outflow_value_limit != 0 {
+        changes.push(format!(
+            "outflow_limit_window: {}, outflow_limit: {}, outflow_value_limit: {}",
+            outflow_limit_window, outflow_limit, outflow_value_limit
+        ));
+
+        setup_ixs.push(builder.configure_bank(
+            config.authority(),
+            bank_pk,
+            BankConfigOpt {
+                outflow_limit_window: Some(outflow_limit_window),
+                outflow_limit: Some(outflow_limit),
+                outflow_value_limit: Some(outflow_value_limit),
+                ..BankConfigOpt::default()
+            },
+        ));
+    }
+
+    if let Some(emissions) = &bank_spec.emissions {
+        setup_ixs.push(plan_emissions_setup(
+            config,
+            builder,
+            bank_pk,
+            emissions,
+            &mut changes,
+        )?);
+    }
+
+    if !setup_ixs.is_empty() {
+        transactions.push(setup_ixs);
+    }
+
+    Ok(BankPlan {
+        label: format!("+ Add bank {} ({})", bank_pk, bank_spec.name),
+        changes,
+        transactions,
+    })
+}
+
+fn plan_bank_update(
+    config: &Config,
+    builder: &MarginfiGroupIxBuilder,
+    bank_pk: Pubkey,
+    bank: &Bank,
+    bank_spec: &BankSpec,
+) -> Result<Option<BankPlan>> {
+    let current = &bank.config;
+    let desired = bank_spec.bank_config(bank.mint_decimals);
+    let (outflow_limit_window, outflow_limit, outflow_value_limit) =
+        bank_spec.outflow_limits(bank.mint_decimals);
+
+    let mut diff = ConfigDiff::default();
+
+    let current_ir = &current.interest_rate_config;
+    let desired_ir = &desired.interest_rate_config;
+    let interest_rate_config = InterestRateConfigOpt {
+        optimal_utilization_rate: diff.ratio(
+            "optimal_utilization_rate",
+            current_ir.optimal_utilization_rate,
+            desired_ir.optimal_utilization_rate,
+        ),
+        plateau_interest_rate: diff.ratio(
+            "plateau_interest_rate",
+            current_ir.plateau_interest_rate,
+            desired_ir.plateau_interest_rate,
+        ),
+        max_interest_rate: diff.ratio(
+            "max_interest_rate",
+            current_ir.max_interest_rate,
+            desired_ir.max_interest_rate,
+        ),
+        insurance_fee_fixed_apr: diff.ratio(
+            "insurance_fee_fixed_apr",
+            current_ir.insurance_fee_fixed_apr,
+            desired_ir.insurance_fee_fixed_apr,
+        ),
+        insurance_ir_fee: diff.ratio(
+            "insurance_ir_fee",
+            current_ir.insurance_ir_fee,
+            desired_ir.insurance_ir_fee,
+        ),
+        protocol_fixed_fee_apr: diff.ratio(
+            "protocol_fixed_fee_apr",
+            current_ir.protocol_fixed_fee_apr,
+            desired_ir.protocol_fixed_fee_apr,
+        ),
+        protocol_ir_fee: diff.ratio(
+            "protocol_ir_fee",
+            current_ir.protocol_ir_fee,
+            desired_ir.protocol_ir_fee,
+        ),
+        insurance_flashloan_fee: diff.ratio(
+            "insurance_flashloan_fee",
+            current_ir.insurance_flashloan_fee,
+            desired_ir.insurance_flashloan_fee,
+        ),
+        protocol_flashloan_fee: diff.ratio(
+            "protocol_flashloan_fee",
+            current_ir.protocol_flashloan_fee,
+            desired_ir.protocol_flashloan_fee,
+        ),
+    };
+
+    let bank_config_opt = BankConfigOpt {
+        asset_weight_init: diff.ratio(
+            "asset_weight_init",
+            current.asset_weight_init,
+            desired.asset_weight_init,
+        ),
+        asset_weight_maint: diff.ratio(
+            "asset_weight_maint",
+            current.asset_weight_maint,
+            desired.asset_weight_maint,
+        ),
+        liability_weight_init: diff.ratio(
+            "liability_weight_init",
+            current.liability_weight_init,
+            desired.liability_weight_init,
+        ),
+        liability_weight_maint: diff.ratio(
+            "liability_weight_maint",
+            current.liability_weight_maint,
+            desired.liability_weight_maint,
+        ),
+        deposit_limit: diff.value(
+            "deposit_limit",
+            current.deposit_limit,
+            desired.deposit_limit,
+        ),
+        borrow_limit: diff.value("borrow_limit", current.borrow_limit, desired.borrow_limit),
+        operational_state: diff.value(
+            "operational_state",
+            current.operational_state,
+            desired.operational_state,
+        ),
+        oracle: diff
+            .value(
+                "oracle",
+                (current.oracle_setup, current.oracle_keys[0]),
+                (desired.oracle_setup, desired.oracle_keys[0]),
+            )
+            .map(|(setup, key)| OracleConfig {
+                setup,
+                keys: create_oracle_key_array(key),
+            }),
+        interest_rate_config: (interest_rate_config != InterestRateConfigOpt::default())
+            .then_some(interest_rate_config),
+        risk_tier: diff.value("risk_tier", current.risk_tier, desired.risk_tier),
+        total_asset_value_init_limit: diff.value(
+            "total_asset_value_init_limit",
+            current.total_asset_value_init_limit,
+            desired.total_asset_value_init_limit,
+        ),
+        total_deposit_value_limit: diff.value(
+            "total_deposit_value_limit",
+            current.total_deposit_value_limit,
+            desired.total_deposit_value_limit,
+        ),
+        total_borrow_value_limit: diff.value(
+            "total_borrow_value_limit",
+            current.total_borrow_value_limit,
+            desired.total_borrow_value_limit,
+        ),
+        outflow_limit_window: diff.value(
+            "outflow_limit_window",
+            bank.outflow_limit_window,
+            outflow_limit_window,
+        ),
+        outflow_limit: diff.value("outflow_limit", bank.outflow_limit, outflow_limit),
+        outflow_value_limit: diff.value(
+            "outflow_value_limit",
+            bank.outflow_value_limit,
+            outflow_value_limit,
+        ),
+        borrow_mode: diff.value("borrow_mode", current.borrow_mode, desired.borrow_mode),
+        bad_debt_mode: diff.value(
+            "bad_debt_mode",
+            current.bad_debt_mode,
+            desired.bad_debt_mode,
+        ),
+    };
+
+    let mut changes = diff.changes;
+    let mut ixs = vec![];
+
+    if !changes.is_empty() {
+        ixs.push(builder.configure_bank(config.authority(), bank_pk, bank_config_opt));
+    }
+
+    if let Some(emissions) = &bank_spec.emissions {
+        let emissions_ix = if bank.emissions_mint == Pubkey::default() {
+            Some(plan_emissions_setup(
+                config,
+                builder,
+                bank_pk,
+                emissions,
+                &mut changes,
+            )?)
+        } else {
+            plan_emissions_update(config, builder, bank_pk, bank, emissions, &mut changes)?
+        };
+
+        ixs.extend(emissions_ix);
+    }
+
+    if ixs.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(BankPlan {
+        label: format!("~ Update bank {} ({})", bank_pk, bank_spec.name),
+        changes,
+        transactions: vec![ixs],
+    }))
+}
+
+fn plan_emissions_setup(
+    config: &Config,
+    builder: &MarginfiGroupIxBuilder,
+    bank_pk: Pubkey,
+    emissions: &EmissionsSpec,
+    changes: &mut Vec<String>,
+) -> Result<Instruction> {
+    let total_amount_ui = emissions.total_amount_ui.ok_or_else(|| {
+        anyhow!(
+            "Bank {} has no emissions, total_amount_ui is required to set them up",
+            bank_pk
+        )
+    })?;
+    let emissions_mint_decimals = get_mint_decimals(config, &emissions.mint)?;
+
+    let flags = emissions.flags();
+    let rate = calc_emissions_rate(emissions.rate, emissions_mint_decimals);
+    let total_emissions = spl_token::ui_amount_to_amount(total_amount_ui, emissions_mint_decimals);
+
+    changes.push(format!(
+        "emissions: setup (mint: {}, flags: {:b}, rate: {}, total: {})",
+        emissions.mint, flags, rate, total_emissions
+    ));
+
+    Ok(builder.setup_emissions(
+        config.authority(),
+        bank_pk,
+        emissions.mint,
+        get_associated_token_address(&config.authority(), &emissions.mint),
+        flags,
+        rate,
+        total_emissions,
+    ))
+}
+
+fn plan_emissions_update(
+    config: &Config,
+    builder: &MarginfiGroupIxBuilder,
+    bank_pk: Pubkey,
+    bank: &Bank,
+    emissions: &EmissionsSpec,
+    changes: &mut Vec<String>,
+) -> Result<Option<Instruction>> {
+    if bank.emissions_mint != emissions.mint {
+        bail!(
+            "Bank {} emits {}, the emissions mint can't be changed",
+            bank_pk,
+            bank.emissions_mint
+        );
+    }
+
+    let emissions_mint_decimals = get_mint_decimals(config, &emissions.mint)?;
+
+    let mut diff = ConfigDiff::default();
+    let emissions_flags = diff.value("emissions_flags", bank.emissions_flags, emissions.flags());
+    let emissions_rate = diff.value(
+        "emissions_rate",
+        bank.emissions_rate,
+        calc_emissions_rate(emissions.rate, emissions_mint_decimals),
+    );
+
+    if diff.changes.is_empty() {
+        return Ok(None);
+    }
+
+    changes.extend(diff.changes);
+
+    Ok(Some(builder.update_emissions_parameters(
+        config.authority(),
+        bank_pk,
+        emissions.mint,
+        get_associated_token_address(&config.authority(), &emissions.mint),
+        emissions_flags,
+        emissions_rate,
+        None,
+    )))
+}
+
+fn describe_bank_config(bank_config: &BankConfig) -> Vec<String> {
+    let ir = &bank_config.interest_rate_config;
+
+    vec![
+        format!(
+            "weights: asset init {:.6}, asset maint {:.6}, liability init {:.6}, liability maint {:.6}",
+            I80F48::from(bank_config.asset_weight_init),
+            I80F48::from(bank_config.asset_weight_maint),
+            I80F48::from(bank_config.liability_weight_init),
+            I80F48::from(bank_config.liability_weight_maint),
+        ),
+        format!(
+            "limits: deposit {}, borrow {}, asset value init ${}, deposit value ${}, borrow value ${}",
+            bank_config.deposit_limit,
+            bank_config.borrow_limit,
+            bank_config.total_asset_value_init_limit,
+            bank_config.total_deposit_value_limit,
+            bank_config.total_borrow_value_limit,
+        ),
+        format!(
+            "oracle: {:?} {}",
+            bank_config.oracle_setup, bank_config.oracle_keys[0]
+        ),
+        format!(
+            "interest rate: optimal ur {:.6}, plateau {:.6}, max {:.6}, insurance fixed {:.6}, insurance ir {:.6}, protocol fixed {:.6}, protocol ir {:.6}, insurance flashloan {:.6}, protocol flashloan {:.6}",
+            I80F48::from(ir.optimal_utilization_rate),
+            I80F48::from(ir.plateau_interest_rate),
+            I80F48::from(ir.max_interest_rate),
+            I80F48::from(ir.insurance_fee_fixed_apr),
+            I80F48::from(ir.insurance_ir_fee),
+            I80F48::from(ir.protocol_fixed_fee_apr),
+            I80F48::from(ir.protocol_ir_fee),
+            I80F48::from(ir.insurance_flashloan_fee),
+            I80F48::from(ir.protocol_flashloan_fee),
+        ),
+        format!(
+            "risk tier: {:?}, operational state: {:?}, borrow mode: {:?}, bad debt mode: {:?}",
+            bank_config.risk_tier,
+            bank_config.operational_state,
+            bank_config.borrow_mode,
+            bank_config.bad_debt_mode,
+        ),
+    ]
+}
+
+fn get_mint_decimals(config: &Config, mint: &Pubkey) -> Result<u8> {
+    let mint_account = config.mfi_program.rpc().get_account(mint)?;
+
+    Ok(spl_token::state::Mint::unpack(&mint_account.data)?.decimals)
+}
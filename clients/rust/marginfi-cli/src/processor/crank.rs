@@ -0,0 +1,511 @@
+use std::{
+    collections::{HashMap, HashSet},
+    thread::sleep,
+    time::Duration,
+};
+
+use anyhow::{bail, Result};
+use fixed::types::I80F48;
+use log::{info, warn};
+use marginfi::{
+    constants::{EMISSIONS_FLAG_BORROW_ACTIVE, EMISSIONS_FLAG_LENDING_ACTIVE},
+    state::price::{OraclePriceType, PriceAdapter},
+};
+use marginfi_v2_sdk::instructions::{MarginfiAccountIxBuilder, MarginfiGroupIxBuilder};
+use serde::Serialize;
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+
+use super::{accounts::load_group_accounts, load_clock, load_group_banks, load_price_feeds};
+use crate::{
+    config::{Config, OutputFormat},
+    profile::Profile,
+    utils::{process_transaction, EXP_10_I80F48},
+};
+
+#[cfg(feature = "admin")]
+use {
+    super::{load_all_banks, make_bankruptcy_ix, BANKRUPTCY_CHUNKS},
+    marginfi::{
+        constants::ZERO_AMOUNT_THRESHOLD, state::health::HealthEngine,
+        utils::NumTraitsWithTolerance,
+    },
+    std::{cmp::min, collections::BTreeMap},
+};
+
+const ACCRUE_CHUNK_SIZE: usize = 10;
+const COLLECT_FEES_CHUNK_SIZE: usize = 4;
+const SETTLE_EMISSIONS_CHUNK_SIZE: usize = 22;
+
+/// Bad debt of a bankrupt account in a bank.
+#[cfg(feature = "admin")]
+struct Bankruptcy {
+    marginfi_account: Pubkey,
+    /// Native amount
+    bad_debt: I80F48,
+    ix: Instruction,
+}
+
+/// Bankruptcies handled in a bank by a bankruptcy crank round, amounts in UI units.
+#[cfg(feature = "admin")]
+#[derive(Debug, Serialize)]
+struct BankruptcyReport {
+    bank: String,
+    mint: String,
+    handled: usize,
+    bankruptcies: usize,
+    bad_debt: f64,
+    /// Bad debt covered by the insurance fund
+    covered: f64,
+    /// Bad debt socialized among depositors, or deferred
+    uncovered: f64,
+    deferred: bool,
+}
+
+/// Run `round` every `interval` seconds, or once.
+///
+/// Failed rounds are logged and retried on the next interval.
+fn run_crank(
+    name: &str,
+    interval: u64,
+    once: bool,
+    mut round: impl FnMut() -> Result<()>,
+) -> Result<()> {
+    loop {
+        let result = round();
+        if once {
+            return result;
+        }
+        if let Err(err) = result {
+            warn!("{} round failed: {}", name, err);
+        }
+
+        sleep(Duration::from_secs(interval));
+    }
+}
+
+fn profile_group(profile: &Profile) -> Result<Pubkey> {
+    match profile.marginfi_group {
+        Some(marginfi_group) => Ok(marginfi_group),
+        None => bail!("Marginfi group not specified in profile [{}]", profile.name),
+    }
+}
+
+/// Handle the bad debt of every bankrupt account of the profile group, periodically unless `once`.
+#[cfg(feature = "admin")]
+pub fn bankruptcy_crank(
+    config: &Config,
+    profile: &Profile,
+    interval: u64,
+    once: bool,
+) -> Result<()> {
+    let marginfi_group = profile_group(profile)?;
+    if config.multisig.is_some() {
+        bail!("Multisig profiles can't run the crank, bankruptcies are signed by the group admin");
+    }
+
+    run_crank("Bankruptcy crank", interval, once, || {
+        bankruptcy_crank_round(config, profile, marginfi_group)
+    })
+}
+
+#[cfg(feature = "admin")]
+fn bankruptcy_crank_round(
+    config: &Config,
+    profile: &Profile,
+    marginfi_group: Pubkey,
+) -> Result<()> {
+    let rpc_client = config.mfi_program.rpc();
+
+    let banks = HashMap::from_iter(load_all_banks(config, Some(marginfi_group))?);
+    let price_feeds = load_price_feeds(config, &banks)?;
+    let accounts = load_group_accounts(config, marginfi_group)?;
+
+    // Handled bank by bank, so that insurance coverage can be tracked per bank
+    let mut bankruptcies: BTreeMap<Pubkey, Vec<Bankruptcy>> = BTreeMap::new();
+    for (address, marginfi_account) in accounts.iter() {
+        let is_bankrupt = HealthEngine::load(marginfi_account, &banks, &price_feeds)
+            .and_then(|health_engine| health_engine.is_bankrupt());
+        match is_bankrupt {
+            Ok(true) => (),
+            Ok(false) => continue,
+            Err(err) => {
+                warn!("Failed to check bankruptcy of {}: {}", address, err);
+                continue;
+            }
+        }
+
+        for balance in marginfi_account.lending_account.get_active_balances_iter() {
+            let bad_debt =
+                banks[&balance.bank_pk].get_liability_amount(balance.liability_shares.into())?;
+            if !bad_debt.is_positive_with_tolerance(ZERO_AMOUNT_THRESHOLD) {
+                continue;
+            }
+
+            info!(
+                "Handling bankruptcy of {} for bank {}",
+                address, balance.bank_pk
+            );
+
+            bankruptcies
+                .entry(balance.bank_pk)
+                .or_default()
+                .push(Bankruptcy {
+                    marginfi_account: *address,
+                    bad_debt,
+                    ix: make_bankruptcy_ix(
+                        config,
+                        profile,
+                        &banks,
+                        *address,
+                        marginfi_account,
+                        balance.bank_pk,
+                    )?,
+                });
+        }
+    }
+
+    if bankruptcies.is_empty() {
+        info!("No bankrupt accounts in {} accounts", accounts.len());
+        return Ok(());
+    }
+
+    for (bank_pk, bank_bankruptcies) in bankruptcies.iter() {
+        let bank = &banks[bank_pk];
+        let decimals = EXP_10_I80F48[bank.mint_decimals as usize];
+
+        let mut insurance_funds = I80F48::from_num(
+            rpc_client
+                .get_token_account_balance(&bank.insurance_vault)?
+                .amount
+                .parse::<u64>()?,
+        );
+        let (mut handled, mut bad_debt, mut covered, mut uncovered) =
+            (0, I80F48::ZERO, I80F48::ZERO, I80F48::ZERO);
+
+        for chunk in bank_bankruptcies.chunks(BANKRUPTCY_CHUNKS) {
+            let ixs = chunk
+                .iter()
+                .map(|bankruptcy| bankruptcy.ix.clone())
+                .collect::<Vec<_>>();
+
+            let signing_keypairs = config.get_signers(false);
+
+            match process_transaction(
+                &ixs,
+                &config.authority(),
+                &rpc_client,
+                config.get_tx_mode(),
+                &signing_keypairs,
+            ) {
+                Ok(sig) => info!("Bankruptcies handled in bank {} (sig: {})", bank_pk, sig),
+                Err(err) => {
+                    warn!(
+                        "Failed to handle bankruptcies of {:?} in bank {}: {}",
+                        chunk
+                            .iter()
+                            .map(|bankruptcy| bankruptcy.marginfi_account)
+                            .collect::<Vec<_>>(),
+                        bank_pk,
+                        err
+                    );
+                    continue;
+                }
+            }
+
+            // Mirrors the program: insurance funds cover the bad debt first
+            for bankruptcy in chunk {
+                let covered_by_insurance = min(bankruptcy.bad_debt, insurance_funds);
+                insurance_funds -= covered_by_insurance;
+
+                handled += 1;
+                bad_debt += bankruptcy.bad_debt;
+                covered += covered_by_insurance;
+                uncovered += bankruptcy.bad_debt - covered_by_insurance;
+            }
+        }
+
+        let report = BankruptcyReport {
+            bank: bank_pk.to_string(),
+            mint: bank.mint.to_string(),
+            handled,
+            bankruptcies: bank_bankruptcies.len(),
+            bad_debt: (bad_debt / decimals).to_num(),
+            covered: (covered / decimals).to_num(),
+            uncovered: (uncovered / decimals).to_num(),
+            deferred: bank.config.is_bad_debt_deferred(),
+        };
+
+        if config.output == OutputFormat::Json {
+            println!("{}", serde_json::to_string(&report)?);
+        } else {
+            println!(
+                "Bank {} (mint: {}): {} of {} bankruptcies handled, bad debt: {}, covered by insurance: {}, {}: {}",
+                report.bank,
+                report.mint,
+                report.handled,
+                report.bankruptcies,
+                report.bad_debt,
+                report.covered,
+                if report.deferred {
+                    "deferred"
+                } else {
+                    "socialized"
+                },
+                report.uncovered,
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct AccrualCrankOptions {
+    /// Banks not updated for this many seconds are accrued.
+    pub max_staleness: u64,
+    /// Fees are collected once the outstanding fees of a bank exceed this USD value.
+    pub min_fees_usd: f64,
+    /// Emissions of balances not updated for this many seconds are settled, never if unset.
+    pub settle_emissions_after: Option<u64>,
+}
+
+/// What an accrual crank round did.
+#[derive(Debug, Default, Serialize)]
+struct AccrualCrankMetrics {
+    timestamp: i64,
+    banks: usize,
+    /// Seconds since the stalest bank was updated, before the round
+    max_staleness: i64,
+    accrued_banks: usize,
+    collected_fees_banks: usize,
+    /// USD value of the collected outstanding fees
+    collected_fees_usd: f64,
+    settled_emissions_balances: usize,
+    transactions: usize,
+    failed_transactions: usize,
+}
+
+/// Accrue the interest of stale banks of the profile group, collect their fees and settle
+/// emissions, periodically unless `once`.
+pub fn accrual_crank(
+    config: &Config,
+    profile: &Profile,
+    options: AccrualCrankOptions,
+    interval: u64,
+    once: bool,
+) -> Result<()> {
+    let marginfi_group = profile_group(profile)?;
+    if config.multisig.is_some() {
+        bail!(
+            "Multisig profiles can't run the crank, transactions are signed by the profile keypair"
+        );
+    }
+
+    run_crank("Accrual crank", interval, once, || {
+        accrual_crank_round(config, marginfi_group, &options)
+    })
+}
+
+fn accrual_crank_round(
+    config: &Config,
+    marginfi_group: Pubkey,
+    options: &AccrualCrankOptions,
+) -> Result<()> {
+    let clock = load_clock(config)?;
+    let banks = load_group_banks(config, marginfi_group)?;
+    let group_ix_builder = MarginfiGroupIxBuilder::new(config.program_id, marginfi_group);
+
+    let mut metrics = AccrualCrankMetrics {
+        timestamp: clock.unix_timestamp,
+        banks: banks.len(),
+        max_staleness: banks
+            .iter()
+            .map(|(_, bank)| clock.unix_timestamp - bank.last_update)
+            .max()
+            .unwrap_or_default(),
+        ..Default::default()
+    };
+
+    // Fees are collected from the interest accrued up to now, accrued in the same transaction
+    let accrued_banks = banks
+        .iter()
+        .map(|(bank_pk, bank)| {
+            let mut bank = *bank;
+            bank.accrue_interest(clock.unix_timestamp)?;
+            Ok((*bank_pk, bank))
+        })
+        .collect::<Result<HashMap<_, _>>>()?;
+    let price_feeds = load_price_feeds(config, &accrued_banks)?;
+
+    let mut fee_banks = vec![];
+    for (bank_pk, bank) in accrued_banks.iter() {
+        let outstanding_fees = I80F48::from(bank.collected_group_fees_outstanding)
+            + I80F48::from(bank.collected_insurance_fees_outstanding);
+        if !outstanding_fees.is_positive() {
+            continue;
+        }
+
+        let price = match price_feeds
+            .get(&bank.config.oracle_keys[0])
+            .map(|price_feed| price_feed.get_price_of_type(OraclePriceType::RealTime, None))
+        {
+            Some(Ok(price)) => price,
+            _ => {
+                warn!("No price for bank {}, fees not collected", bank_pk);
+                continue;
+            }
+        };
+        let fees_usd: f64 =
+            (outstanding_fees / EXP_10_I80F48[bank.mint_decimals as usize] * price).to_num();
+
+        if fees_usd >= options.min_fees_usd {
+            fee_banks.push((*bank_pk, fees_usd));
+        }
+    }
+    let fee_bank_pks = fee_banks
+        .iter()
+        .map(|(bank_pk, _)| *bank_pk)
+        .collect::<HashSet<_>>();
+
+    // Stalest banks first
+    let mut stale_banks = banks
+        .iter()
+        .filter(|(bank_pk, bank)| {
+            clock.unix_timestamp - bank.last_update >= options.max_staleness as i64
+                && !fee_bank_pks.contains(bank_pk)
+        })
+        .collect::<Vec<_>>();
+    stale_banks.sort_by_key(|(_, bank)| bank.last_update);
+
+    let accrue_ixs = stale_banks
+        .iter()
+        .map(|(bank_pk, _)| vec![group_ix_builder.accrue_bank_interest(*bank_pk)])
+        .collect::<Vec<_>>();
+    let accrued = send_in_chunks(config, &accrue_ixs, ACCRUE_CHUNK_SIZE, &mut metrics);
+    metrics.accrued_banks += accrued;
+
+    let collect_fees_ixs = fee_banks
+        .iter()
+        .map(|(bank_pk, _)| {
+            vec![
+                group_ix_builder.accrue_bank_interest(*bank_pk),
+                group_ix_builder.collect_bank_fees(*bank_pk),
+            ]
+        })
+        .collect::<Vec<_>>();
+    // One transaction per chunk, to attribute the collected fees
+    for (chunk, fee_chunk) in collect_fees_ixs
+        .chunks(COLLECT_FEES_CHUNK_SIZE)
+        .zip(fee_banks.chunks(COLLECT_FEES_CHUNK_SIZE))
+    {
+        let collected = send_in_chunks(config, chunk, COLLECT_FEES_CHUNK_SIZE, &mut metrics);
+        if collected > 0 {
+            metrics.accrued_banks += collected;
+            metrics.collected_fees_banks += collected;
+            metrics.collected_fees_usd +=
+                fee_chunk.iter().map(|(_, fees_usd)| fees_usd).sum::<f64>();
+        }
+    }
+
+    if let Some(settle_emissions_after) = options.settle_emissions_after {
+        let emissions_banks = banks
+            .iter()
+            .filter(|(_, bank)| {
+                bank.get_emissions_flag(EMISSIONS_FLAG_LENDING_ACTIVE)
+                    || bank.get_emissions_flag(EMISSIONS_FLAG_BORROW_ACTIVE)
+            })
+            .map(|(bank_pk, _)| *bank_pk)
+            .collect::<HashSet<_>>();
+
+        if !emissions_banks.is_empty() {
+            let settle_ixs = load_group_accounts(config, marginfi_group)?
+                .iter()
+                .flat_map(|(address, marginfi_account)| {
+                    let account_ix_builder = MarginfiAccountIxBuilder::new(
+                        config.program_id,
+                        *address,
+                        marginfi_account,
+                        &accrued_banks,
+                    );
+
+                    marginfi_account
+                        .lending_account
+                        .get_active_balances_iter()
+                        .filter(|balance| {
+                            emissions_banks.contains(&balance.bank_pk)
+                                && (clock.unix_timestamp as u64).saturating_sub(balance.last_update)
+                                    >= settle_emissions_after
+                        })
+                        .map(|balance| vec![account_ix_builder.settle_emissions(balance.bank_pk)])
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+
+            let settled = send_in_chunks(
+                config,
+                &settle_ixs,
+                SETTLE_EMISSIONS_CHUNK_SIZE,
+                &mut metrics,
+            );
+            metrics.settled_emissions_balances += settled;
+        }
+    }
+
+    if config.output == OutputFormat::Json {
+        println!("{}", serde_json::to_string(&metrics)?);
+    } else {
+        println!(
+            "{} banks (stalest: {}s): accrued {}, collected fees of {} (${:.2}), settled emissions of {} balances in {} transactions ({} failed)",
+            metrics.banks,
+            metrics.max_staleness,
+            metrics.accrued_banks,
+            metrics.collected_fees_banks,
+            metrics.collected_fees_usd,
+            metrics.settled_emissions_balances,
+            metrics.transactions,
+            metrics.failed_transactions,
+        );
+    }
+
+    Ok(())
+}
+
+/// Send groups of instructions, `chunk_size` groups per transaction.
+///
+/// Returns the number of groups in landed transactions.
+fn send_in_chunks(
+    config: &Config,
+    ix_groups: &[Vec<Instruction>],
+    chunk_size: usize,
+    metrics: &mut AccrualCrankMetrics,
+) -> usize {
+    let rpc_client = config.mfi_program.rpc();
+    let signing_keypairs = config.get_signers(false);
+
+    let mut landed = 0;
+    for chunk in ix_groups.chunks(chunk_size) {
+        let ixs = chunk.concat();
+
+        let result = process_transaction(
+            &ixs,
+            &config.authority(),
+            &rpc_client,
+            config.get_tx_mode(),
+            &signing_keypairs,
+        );
+
+        metrics.transactions += 1;
+        match result {
+            Ok(sig) => {
+                info!("Crank transaction landed: {}", sig);
+                landed += chunk.len();
+            }
+            Err(err) => {
+                warn!("Crank transaction failed: {}", err);
+                metrics.failed_transactions += 1;
+            }
+        }
+    }
+
+    landed
+}
@@ -1,12 +1,10 @@
 use {
-    crate::{config::Config, profile::Profile},
+    crate::{config::Config, profile::Profile, utils::process_transaction},
     anchor_client::anchor_lang::{AnchorSerialize, InstructionData, ToAccountMetas},
     anyhow::Result,
     marginfi::state::marginfi_account::MarginfiAccount,
     solana_client::rpc_filter::{Memcmp, RpcFilterType},
-    solana_sdk::{
-        instruction::Instruction, message::Message, pubkey::Pubkey, transaction::Transaction,
-    },
+    solana_sdk::{instruction::Instruction, pubkey::Pubkey},
 };
 
 const CHUNK_SIZE: usize = 22;
@@ -64,13 +62,13 @@ pub fn claim_all_emissions_for_bank(
     println!("Sending {} txs", ixs_batches_count);
 
     for (i, ixs) in ixs_batches.enumerate() {
-        let blockhash = rpc_client.get_latest_blockhash()?;
-
-        let message = Message::new(ixs, Some(&config.authority()));
-        let mut transaction = Transaction::new_unsigned(message);
-        transaction.partial_sign(&signing_keypairs, blockhash);
-
-        let sig = rpc_client.send_and_confirm_transaction_with_spinner(&transaction)?;
+        let sig = process_transaction(
+            ixs,
+            &config.authority(),
+            &rpc_client,
+            config.get_tx_mode(),
+            &signing_keypairs,
+        )?;
 
         println!("Sent [{}/{}] {}", i + 1, ixs_batches_count, sig);
     }
@@ -1,32 +1,43 @@
 #[cfg(feature = "admin")]
+pub mod apply;
+#[cfg(feature = "admin")]
 pub mod emissions;
 
+pub mod accounts;
+pub mod crank;
 pub mod group;
+pub mod multisig;
+pub mod output;
+pub mod rates;
+pub mod simulate;
 
 use {
     crate::{
-        config::Config,
+        config::{Config, OutputFormat, PriorityFee},
         profile::{self, get_cli_config_dir, load_profile, CliConfig, Profile},
-        utils::{
-            find_bank_emssions_token_account_pda, find_bank_vault_authority_pda,
-            find_bank_vault_pda, load_observation_account_metas, process_transaction,
-            EXP_10_I80F48,
-        },
+        utils::{process_transaction, EXP_10_I80F48},
     },
     anchor_client::{
         anchor_lang::{InstructionData, ToAccountMetas},
         Cluster,
     },
-    anchor_spl::token::{self, spl_token},
+    anchor_spl::token::spl_token,
     anyhow::{anyhow, bail, Result},
     fixed::types::I80F48,
-    log::info,
+    log::{info, warn},
     marginfi::{
         prelude::MarginfiGroup,
         state::{
             marginfi_account::{BankAccountWrapper, MarginfiAccount},
-            marginfi_group::{Bank, BankVaultType},
+            marginfi_group::Bank,
+            order::{Order, OrderTrigger},
+        },
+    },
+    marginfi_v2_sdk::{
+        instructions::{
+            initialize_marginfi_account, MarginfiAccountIxBuilder, MarginfiGroupIxBuilder,
         },
+        pda::{find_bank_emissions_token_account_pda, find_order_pda},
     },
     solana_client::rpc_filter::{Memcmp, RpcFilterType},
     solana_sdk::{
@@ -35,13 +46,11 @@ use {
         commitment_config::CommitmentLevel,
         compute_budget::ComputeBudgetInstruction,
         instruction::{AccountMeta, Instruction},
-        message::Message,
         pubkey::Pubkey,
         signature::Keypair,
         signer::Signer,
         system_program,
         sysvar::{self, Sysvar},
-        transaction::Transaction,
     },
     spl_associated_token_account::instruction::create_associated_token_account_idempotent,
     std::{
@@ -53,14 +62,19 @@ use {
     },
 };
 
+use marginfi::state::price::OraclePriceFeedAdapter;
 #[cfg(feature = "dev")]
-use marginfi::state::price::{OraclePriceFeedAdapter, PriceAdapter};
+use marginfi::state::price::PriceAdapter;
 use marginfi::{constants::ZERO_AMOUNT_THRESHOLD, utils::NumTraitsWithTolerance};
+use output::{
+    print_json, AccountView, AmountView, BankView, GroupView, ProfileListView, VaultBalancesView,
+};
+use solana_account_decoder::parse_token::UiTokenAmount;
 use solana_client::rpc_client::RpcClient;
 
 #[cfg(feature = "admin")]
 use {
-    crate::utils::{calc_emissions_rate, create_oracle_key_array, find_bank_emssions_auth_pda},
+    crate::utils::{calc_emissions_rate, create_oracle_key_array},
     marginfi::{
         constants::{EMISSIONS_FLAG_BORROW_ACTIVE, EMISSIONS_FLAG_LENDING_ACTIVE},
         prelude::GroupConfig,
@@ -68,6 +82,7 @@ use {
             BankConfig, BankConfigOpt, BankOperationalState, InterestRateConfig, WrappedI80F48,
         },
     },
+    marginfi_v2_sdk::pda::find_bank_with_seed_pda,
     solana_sdk::program_pack::Pack,
     spl_associated_token_account::get_associated_token_address,
     std::io,
@@ -77,6 +92,7 @@ use {
 use {
     chrono::{DateTime, NaiveDateTime, Utc},
     liquidity_incentive_program::state::{Campaign, Deposit},
+    output::{CampaignView, DepositView},
 };
 
 // --------------------------------------------------------------------------------------------------------------------
@@ -85,6 +101,16 @@ use {
 
 pub fn group_get(config: Config, marginfi_group: Option<Pubkey>) -> Result<()> {
     if let Some(marginfi_group) = marginfi_group {
+        if config.output == OutputFormat::Json {
+            let group: MarginfiGroup = config.mfi_program.account(marginfi_group)?;
+            let banks = load_group_banks(&config, marginfi_group)?
+                .iter()
+                .map(|(address, bank)| BankView::new(address, bank))
+                .collect::<Result<Vec<_>>>()?;
+
+            return print_json(&GroupView::new(&marginfi_group, &group, Some(banks)));
+        }
+
         println!("Address: {marginfi_group}");
         println!("=============");
         print_group_banks(config, marginfi_group)?;
@@ -97,6 +123,15 @@ pub fn group_get(config: Config, marginfi_group: Option<Pubkey>) -> Result<()> {
 pub fn group_get_all(config: Config) -> Result<()> {
     let accounts: Vec<(Pubkey, MarginfiGroup)> = config.mfi_program.accounts(vec![])?;
 
+    if config.output == OutputFormat::Json {
+        return print_json(
+            &accounts
+                .iter()
+                .map(|(address, group)| GroupView::new(address, group, None))
+                .collect::<Vec<_>>(),
+        );
+    }
+
     accounts
         .iter()
         .for_each(|(address, group)| print_group(address, group));
@@ -109,18 +144,29 @@ fn print_group(address: &Pubkey, group: &MarginfiGroup) {
         r#"
 Group: {}
 Admin: {}
+Flashloan CPI Programs: {:?}
 "#,
-        address, group.admin
+        address,
+        group.admin,
+        group
+            .flashloan_cpi_programs
+            .iter()
+            .filter(|pk| pk.ne(&&Pubkey::default()))
+            .collect::<Vec<_>>()
     );
 }
 
-pub fn print_group_banks(config: Config, marginfi_group: Pubkey) -> Result<()> {
-    let banks = config
+fn load_group_banks(config: &Config, marginfi_group: Pubkey) -> Result<Vec<(Pubkey, Bank)>> {
+    Ok(config
         .mfi_program
         .accounts::<Bank>(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
             8 + size_of::<Pubkey>() + size_of::<u8>(),
             marginfi_group.to_bytes().to_vec(),
-        ))])?;
+        ))])?)
+}
+
+pub fn print_group_banks(config: Config, marginfi_group: Pubkey) -> Result<()> {
+    let banks = load_group_banks(&config, marginfi_group)?;
 
     println!("--------\nBanks:");
 
@@ -146,12 +192,28 @@ Config:
   Asset:
     Weight Init: {:?}, Maint: {:?}
     Limit: {}
+    USD Limit: {:?}
   Liab:
     Weight Init: {:?}, Maint: {:?}
     Limit: {}
+    USD Limit: {:?}
+    Borrow Mode: {:?}
+    Bad Debt Mode: {:?}
+    Unrecovered Bad Debt: {}
+  Outflow:
+    Window: {:?}s
+    Limit: {}
+    USD Limit: {:?}
+    Current: {}
+  Insurance Staking:
+    Share Mint: {:?}
+    Protocol Shares: {}
+    Cooldown Shares: {}
+    Unstake Cooldown: {:?}s
   Interest Rate Config:
     Curve: opt_ur: {:?} pl_ir: {:?} max_ir: {:?}
     Fees - Insurance: ir: {:?} fix: {:?}, Group: ir: {:?} fix: {:?}
+    Flashloan Fees - Insurance: {:?}, Group: {:?}
   Oracle Setup:
     Type: {:?}
     Keys: {:#?}
@@ -177,9 +239,24 @@ Last Update: {:?}h ago ({})
         bank.config.asset_weight_init,
         bank.config.asset_weight_maint,
         I80F48::from_num(bank.config.deposit_limit) / EXP_10_I80F48[bank.mint_decimals as usize],
+        bank.config.total_deposit_value_limit,
         bank.config.liability_weight_init,
         bank.config.liability_weight_maint,
         I80F48::from_num(bank.config.borrow_limit) / EXP_10_I80F48[bank.mint_decimals as usize],
+        bank.config.total_borrow_value_limit,
+        bank.config.borrow_mode,
+        bank.config.bad_debt_mode,
+        I80F48::from(bank.unrecovered_bad_debt) / EXP_10_I80F48[bank.mint_decimals as usize],
+        bank.outflow_limit_window,
+        I80F48::from_num(bank.outflow_limit) / EXP_10_I80F48[bank.mint_decimals as usize],
+        bank.outflow_value_limit,
+        I80F48::from(bank.outflow_window_amount) / EXP_10_I80F48[bank.mint_decimals as usize],
+        bank.insurance_share_mint,
+        I80F48::from_num(bank.insurance_protocol_shares)
+            / EXP_10_I80F48[bank.mint_decimals as usize],
+        I80F48::from_num(bank.insurance_cooldown_shares)
+            / EXP_10_I80F48[bank.mint_decimals as usize],
+        bank.insurance_unstake_cooldown,
         bank.config.interest_rate_config.optimal_utilization_rate,
         bank.config.interest_rate_config.plateau_interest_rate,
         bank.config.interest_rate_config.max_interest_rate,
@@ -187,6 +264,8 @@ Last Update: {:?}h ago ({})
         bank.config.interest_rate_config.insurance_fee_fixed_apr,
         bank.config.interest_rate_config.protocol_ir_fee,
         bank.config.interest_rate_config.protocol_fixed_fee_apr,
+        bank.config.interest_rate_config.insurance_flashloan_fee,
+        bank.config.interest_rate_config.protocol_flashloan_fee,
         bank.config.oracle_setup,
         bank.config.oracle_keys,
         bank.emissions_flags,
@@ -235,12 +314,13 @@ pub fn group_create(
         .args(marginfi::instruction::MarginfiGroupInitialize {})
         .instructions()?;
 
-    let recent_blockhash = rpc_client.get_latest_blockhash().unwrap();
-    let message = Message::new(&init_marginfi_group_ixs, Some(&config.authority()));
-    let mut transaction = Transaction::new_unsigned(message);
-    transaction.partial_sign(&signing_keypairs, recent_blockhash);
-
-    match process_transaction(&transaction, &rpc_client, config.get_tx_mode()) {
+    match process_transaction(
+        &init_marginfi_group_ixs,
+        &config.authority(),
+        &rpc_client,
+        config.get_tx_mode(),
+        &signing_keypairs,
+    ) {
         Ok(sig) => println!("marginfi group created (sig: {})", sig),
         Err(err) => {
             println!("Error during marginfi group creation:\n{:#?}", err);
@@ -278,12 +358,13 @@ pub fn group_configure(config: Config, profile: Profile, admin: Option<Pubkey>)
         })
         .instructions()?;
 
-    let recent_blockhash = rpc_client.get_latest_blockhash().unwrap();
-    let message = Message::new(&configure_marginfi_group_ixs, Some(&config.authority()));
-    let mut transaction = Transaction::new_unsigned(message);
-    transaction.partial_sign(&signing_keypairs, recent_blockhash);
-
-    match process_transaction(&transaction, &rpc_client, config.get_tx_mode()) {
+    match process_transaction(
+        &configure_marginfi_group_ixs,
+        &config.authority(),
+        &rpc_client,
+        config.get_tx_mode(),
+        &signing_keypairs,
+    ) {
         Ok(sig) => println!("marginfi group created (sig: {})", sig),
         Err(err) => println!("Error during marginfi group creation:\n{:#?}", err),
     };
@@ -291,6 +372,53 @@ pub fn group_configure(config: Config, profile: Profile, admin: Option<Pubkey>)
     Ok(())
 }
 
+#[cfg(feature = "admin")]
+pub fn group_configure_flashloan_cpi_program(
+    config: Config,
+    profile: Profile,
+    program_id: Pubkey,
+    allowed: bool,
+) -> Result<()> {
+    let rpc_client = config.mfi_program.rpc();
+
+    if profile.marginfi_group.is_none() {
+        bail!("Marginfi group not specified in profile [{}]", profile.name);
+    }
+
+    let signing_keypairs = config.get_signers(false);
+    let ixs = config
+        .mfi_program
+        .request()
+        .signer(*signing_keypairs.first().unwrap())
+        .accounts(marginfi::accounts::MarginfiGroupConfigure {
+            marginfi_group: profile.marginfi_group.unwrap(),
+            admin: config.authority(),
+        })
+        .args(
+            marginfi::instruction::MarginfiGroupConfigureFlashloanCpiProgram {
+                program_id,
+                allowed,
+            },
+        )
+        .instructions()?;
+
+    match process_transaction(
+        &ixs,
+        &config.authority(),
+        &rpc_client,
+        config.get_tx_mode(),
+        &signing_keypairs,
+    ) {
+        Ok(sig) => println!("flashloan CPI program configured (sig: {})", sig),
+        Err(err) => println!(
+            "Error during flashloan CPI program configuration:\n{:#?}",
+            err
+        ),
+    };
+
+    Ok(())
+}
+
 #[allow(clippy::too_many_arguments)]
 #[cfg(feature = "admin")]
 pub fn group_add_bank(
@@ -397,12 +525,19 @@ pub fn group_add_bank(
         )?
     };
 
-    let recent_blockhash = rpc_client.get_latest_blockhash().unwrap();
-    let message = Message::new(&add_bank_ixs, None);
-    let mut transaction = Transaction::new_unsigned(message);
-    transaction.partial_sign(&signing_keypairs, recent_blockhash);
+    let payer = if seed {
+        config.authority()
+    } else {
+        config.explicit_fee_payer()
+    };
 
-    match process_transaction(&transaction, &rpc_client, config.get_tx_mode()) {
+    match process_transaction(
+        &add_bank_ixs,
+        &payer,
+        &rpc_client,
+        config.get_tx_mode(),
+        &signing_keypairs,
+    ) {
         Ok(sig) => println!("bank created (sig: {})", sig),
         Err(err) => println!("Error during bank creation:\n{:#?}", err),
     };
@@ -437,10 +572,7 @@ fn create_bank_ix_with_seed(
     // Iterate through to find the next canonical seed
     for i in 0..u64::MAX {
         println!("Seed option enabled -- generating a PDA account");
-        let (pda, _) = Pubkey::find_program_address(
-            [group_key.as_ref(), bank_mint.as_ref(), &i.to_le_bytes()].as_slice(),
-            &marginfi::id(),
-        );
+        let (pda, _) = find_bank_with_seed_pda(&group_key, &bank_mint, i, &config.program_id);
         if rpc_client
             .get_account_with_commitment(&pda, CommitmentConfig::default())?
             .value
@@ -454,52 +586,13 @@ fn create_bank_ix_with_seed(
         }
     }
 
-    let add_bank_ixs_builder = config.mfi_program.request();
-    let add_bank_ixs = add_bank_ixs_builder
-        .accounts(marginfi::accounts::LendingPoolAddBankWithSeed {
-            marginfi_group: profile.marginfi_group.unwrap(),
-            admin: config.authority(),
+    let add_bank_ixs = vec![MarginfiGroupIxBuilder::new(config.program_id, group_key)
+        .add_bank_with_seed(
+            config.authority(),
+            config.authority(),
             bank_mint,
-            bank: bank_pda,
-            fee_vault: find_bank_vault_pda(&bank_pda, BankVaultType::Fee, &config.program_id).0,
-            fee_vault_authority: find_bank_vault_authority_pda(
-                &bank_pda,
-                BankVaultType::Fee,
-                &config.program_id,
-            )
-            .0,
-            insurance_vault: find_bank_vault_pda(
-                &bank_pda,
-                BankVaultType::Insurance,
-                &config.program_id,
-            )
-            .0,
-            insurance_vault_authority: find_bank_vault_authority_pda(
-                &bank_pda,
-                BankVaultType::Insurance,
-                &config.program_id,
-            )
-            .0,
-            liquidity_vault: find_bank_vault_pda(
-                &bank_pda,
-                BankVaultType::Liquidity,
-                &config.program_id,
-            )
-            .0,
-            liquidity_vault_authority: find_bank_vault_authority_pda(
-                &bank_pda,
-                BankVaultType::Liquidity,
-                &config.program_id,
-            )
-            .0,
-            rent: sysvar::rent::id(),
-            token_program: token::ID,
-            system_program: system_program::id(),
-            fee_payer: config.authority(),
-        })
-        .accounts(AccountMeta::new_readonly(oracle_key, false))
-        .args(marginfi::instruction::LendingPoolAddBankWithSeed {
-            bank_config: BankConfig {
+            bank_seed,
+            BankConfig {
                 asset_weight_init,
                 asset_weight_maint,
                 liability_weight_init,
@@ -514,9 +607,7 @@ fn create_bank_ix_with_seed(
                 ..BankConfig::default()
             }
             .into(),
-            bank_seed,
-        })
-        .instructions()?;
+        )];
 
     println!("Bank address (PDA): {}", bank_pda);
 
@@ -541,73 +632,31 @@ fn create_bank_ix(
     oracle_setup: crate::OracleTypeArg,
     risk_tier: crate::RiskTierArg,
 ) -> Result<Vec<Instruction>> {
-    let add_bank_ixs_builder = config.mfi_program.request();
-    let add_bank_ixs = add_bank_ixs_builder
-        .accounts(marginfi::accounts::LendingPoolAddBank {
-            marginfi_group: profile.marginfi_group.unwrap(),
-            admin: config.authority(),
-            bank: bank_keypair.pubkey(),
-            bank_mint,
-            fee_vault: find_bank_vault_pda(
-                &bank_keypair.pubkey(),
-                BankVaultType::Fee,
-                &config.program_id,
-            )
-            .0,
-            fee_vault_authority: find_bank_vault_authority_pda(
-                &bank_keypair.pubkey(),
-                BankVaultType::Fee,
-                &config.program_id,
-            )
-            .0,
-            insurance_vault: find_bank_vault_pda(
-                &bank_keypair.pubkey(),
-                BankVaultType::Insurance,
-                &config.program_id,
-            )
-            .0,
-            insurance_vault_authority: find_bank_vault_authority_pda(
-                &bank_keypair.pubkey(),
-                BankVaultType::Insurance,
-                &config.program_id,
-            )
-            .0,
-            liquidity_vault: find_bank_vault_pda(
-                &bank_keypair.pubkey(),
-                BankVaultType::Liquidity,
-                &config.program_id,
-            )
-            .0,
-            liquidity_vault_authority: find_bank_vault_authority_pda(
-                &bank_keypair.pubkey(),
-                BankVaultType::Liquidity,
-                &config.program_id,
-            )
-            .0,
-            rent: sysvar::rent::id(),
-            token_program: token::ID,
-            system_program: system_program::id(),
-            fee_payer: config.explicit_fee_payer(),
-        })
-        .accounts(AccountMeta::new_readonly(oracle_key, false))
-        .args(marginfi::instruction::LendingPoolAddBank {
-            bank_config: BankConfig {
-                asset_weight_init,
-                asset_weight_maint,
-                liability_weight_init,
-                liability_weight_maint,
-                deposit_limit,
-                borrow_limit,
-                interest_rate_config,
-                operational_state: BankOperationalState::Operational,
-                oracle_setup: oracle_setup.into(),
-                oracle_keys: create_oracle_key_array(oracle_key),
-                risk_tier: risk_tier.into(),
-                ..BankConfig::default()
-            }
-            .into(),
-        })
-        .instructions()?;
+    let add_bank_ixs =
+        vec![
+            MarginfiGroupIxBuilder::new(config.program_id, profile.marginfi_group.unwrap())
+                .add_bank(
+                    config.authority(),
+                    config.explicit_fee_payer(),
+                    bank_keypair.pubkey(),
+                    bank_mint,
+                    BankConfig {
+                        asset_weight_init,
+                        asset_weight_maint,
+                        liability_weight_init,
+                        liability_weight_maint,
+                        deposit_limit,
+                        borrow_limit,
+                        interest_rate_config,
+                        operational_state: BankOperationalState::Operational,
+                        oracle_setup: oracle_setup.into(),
+                        oracle_keys: create_oracle_key_array(oracle_key),
+                        risk_tier: risk_tier.into(),
+                        ..BankConfig::default()
+                    }
+                    .into(),
+                ),
+        ];
 
     println!("Bank address: {}", bank_keypair.pubkey());
 
@@ -713,55 +762,24 @@ fn handle_bankruptcy_for_an_account(
     bank_pk: Pubkey,
 ) -> Result<()> {
     println!("Handling bankruptcy for bank {}", bank_pk);
-    let mut handle_bankruptcy_ix = Instruction {
-        program_id: config.program_id,
-        accounts: marginfi::accounts::LendingPoolHandleBankruptcy {
-            marginfi_group: profile.marginfi_group.unwrap(),
-            admin: config.authority(),
-            bank: bank_pk,
-            marginfi_account: marginfi_account_pk,
-            liquidity_vault: find_bank_vault_pda(
-                &bank_pk,
-                BankVaultType::Liquidity,
-                &config.program_id,
-            )
-            .0,
-            insurance_vault: find_bank_vault_pda(
-                &bank_pk,
-                BankVaultType::Insurance,
-                &config.program_id,
-            )
-            .0,
-            insurance_vault_authority: find_bank_vault_authority_pda(
-                &bank_pk,
-                BankVaultType::Insurance,
-                &config.program_id,
-            )
-            .0,
-            token_program: token::ID,
-        }
-        .to_account_metas(Some(true)),
-        data: marginfi::instruction::LendingPoolHandleBankruptcy {}.data(),
-    };
-
-    handle_bankruptcy_ix
-        .accounts
-        .extend(load_observation_account_metas(
-            marginfi_account,
-            banks,
-            vec![bank_pk],
-            vec![],
-        ));
-
-    let recent_blockhash = rpc_client.get_latest_blockhash().unwrap();
+    let handle_bankruptcy_ix =
+        MarginfiGroupIxBuilder::new(config.program_id, profile.marginfi_group.unwrap())
+            .handle_bankruptcy(
+                config.authority(),
+                bank_pk,
+                (marginfi_account_pk, marginfi_account),
+                banks,
+            )?;
 
     let signing_keypairs = config.get_signers(false);
 
-    let message = Message::new(&[handle_bankruptcy_ix], Some(&config.authority()));
-    let mut transaction = Transaction::new_unsigned(message);
-    transaction.partial_sign(&signing_keypairs, recent_blockhash);
-
-    match process_transaction(&transaction, rpc_client, config.get_tx_mode()) {
+    match process_transaction(
+        &[handle_bankruptcy_ix],
+        &config.authority(),
+        rpc_client,
+        config.get_tx_mode(),
+        &signing_keypairs,
+    ) {
         Ok(sig) => println!("Bankruptcy handled (sig: {})", sig),
         Err(err) => println!("Error during bankruptcy handling:\n{:#?}", err),
     };
@@ -809,6 +827,9 @@ pub fn handle_bankruptcy_for_accounts(
             .collect::<Vec<Pubkey>>()
             .iter()
             .for_each(|bank_pk| {
+                if config.output != OutputFormat::Json {
+                    println!("Handling bankruptcy for bank {}", bank_pk);
+                }
                 instructions.push(
                     make_bankruptcy_ix(
                         config,
@@ -828,19 +849,18 @@ pub fn handle_bankruptcy_for_accounts(
     let chunks = instructions.chunks(BANKRUPTCY_CHUNKS);
 
     for chunk in chunks {
-        let recent_blockhash = rpc_client.get_latest_blockhash().unwrap();
-
         let signing_keypairs = config.get_signers(false);
 
         let mut ixs = vec![ComputeBudgetInstruction::set_compute_unit_limit(1_400_000)];
         ixs.extend_from_slice(chunk);
 
-        let message = Message::new(&ixs, Some(&config.authority()));
-
-        let mut transaction = Transaction::new_unsigned(message);
-        transaction.partial_sign(&signing_keypairs, recent_blockhash);
-
-        match process_transaction(&transaction, &rpc_client, config.get_tx_mode()) {
+        match process_transaction(
+            &ixs,
+            &config.authority(),
+            &rpc_client,
+            config.get_tx_mode(),
+            &signing_keypairs,
+        ) {
             Ok(sig) => println!("Bankruptcy handled (sig: {})", sig),
             Err(err) => println!("Error during bankruptcy handling:\n{:#?}", err),
         };
@@ -857,46 +877,14 @@ fn make_bankruptcy_ix(
     marginfi_account: &MarginfiAccount,
     bank_pk: Pubkey,
 ) -> Result<Instruction> {
-    println!("Handling bankruptcy for bank {}", bank_pk);
-    let mut handle_bankruptcy_ix = Instruction {
-        program_id: config.program_id,
-        accounts: marginfi::accounts::LendingPoolHandleBankruptcy {
-            marginfi_group: profile.marginfi_group.unwrap(),
-            admin: config.fee_payer.pubkey(),
-            bank: bank_pk,
-            marginfi_account: marginfi_account_pk,
-            liquidity_vault: find_bank_vault_pda(
-                &bank_pk,
-                BankVaultType::Liquidity,
-                &config.program_id,
-            )
-            .0,
-            insurance_vault: find_bank_vault_pda(
-                &bank_pk,
-                BankVaultType::Insurance,
-                &config.program_id,
-            )
-            .0,
-            insurance_vault_authority: find_bank_vault_authority_pda(
-                &bank_pk,
-                BankVaultType::Insurance,
-                &config.program_id,
-            )
-            .0,
-            token_program: token::ID,
-        }
-        .to_account_metas(Some(true)),
-        data: marginfi::instruction::LendingPoolHandleBankruptcy {}.data(),
-    };
-
-    handle_bankruptcy_ix
-        .accounts
-        .extend(load_observation_account_metas(
-            marginfi_account,
-            banks,
-            vec![bank_pk],
-            vec![],
-        ));
+    let handle_bankruptcy_ix =
+        MarginfiGroupIxBuilder::new(config.program_id, profile.marginfi_group.unwrap())
+            .handle_bankruptcy(
+                config.fee_payer.pubkey(),
+                bank_pk,
+                (marginfi_account_pk, marginfi_account),
+                banks,
+            )?;
 
     Ok(handle_bankruptcy_ix)
 }
@@ -920,17 +908,15 @@ pub fn process_set_user_flag(
         program_id: marginfi::id(),
     };
 
-    let recent_blockhash = rpc_client.get_latest_blockhash().unwrap();
-
     let signing_keypairs = config.get_signers(false);
 
-    let message = Message::new(&[ix], Some(&config.authority()));
-
-    let mut transaction = Transaction::new_unsigned(message);
-
-    transaction.partial_sign(&signing_keypairs, recent_blockhash);
-
-    match process_transaction(&transaction, &rpc_client, config.get_tx_mode()) {
+    match process_transaction(
+        &[ix],
+        &config.authority(),
+        &rpc_client,
+        config.get_tx_mode(),
+        &signing_keypairs,
+    ) {
         Ok(sig) => println!("User flag set (sig: {})", sig),
         Err(err) => println!("Error during user flag set:\n{:#?}", err),
     };
@@ -947,13 +933,47 @@ pub fn bank_get(config: Config, bank_pk: Option<Pubkey>) -> Result<()> {
 
     if let Some(address) = bank_pk {
         let bank: Bank = config.mfi_program.account(address)?;
-        print_bank(&address, &bank);
 
         let liquidity_vault_balance =
             rpc_client.get_token_account_balance(&bank.liquidity_vault)?;
         let fee_vault_balance = rpc_client.get_token_account_balance(&bank.fee_vault)?;
         let insurance_vault_balance =
             rpc_client.get_token_account_balance(&bank.insurance_vault)?;
+        let emissions_vault = if bank.emissions_mint != Pubkey::default() {
+            let emissions_token_account = find_bank_emissions_token_account_pda(
+                &address,
+                &bank.emissions_mint,
+                &config.program_id,
+            )
+            .0;
+            let emissions_vault_balance =
+                rpc_client.get_token_account_balance(&emissions_token_account)?;
+
+            Some((emissions_token_account, emissions_vault_balance))
+        } else {
+            None
+        };
+
+        if config.output == OutputFormat::Json {
+            let vault_balance = |balance: &UiTokenAmount| AmountView {
+                native: balance.amount.clone(),
+                ui: balance.ui_amount.unwrap_or_default(),
+            };
+
+            return print_json(&BankView {
+                vault_balances: Some(VaultBalancesView {
+                    liquidity_vault: vault_balance(&liquidity_vault_balance),
+                    fee_vault: vault_balance(&fee_vault_balance),
+                    insurance_vault: vault_balance(&insurance_vault_balance),
+                    emissions_vault: emissions_vault
+                        .as_ref()
+                        .map(|(_, balance)| vault_balance(balance)),
+                }),
+                ..BankView::new(&address, &bank)?
+            });
+        }
+
+        print_bank(&address, &bank);
 
         println!("=============");
         println!("Token balances:");
@@ -972,12 +992,7 @@ pub fn bank_get(config: Config, bank_pk: Option<Pubkey>) -> Result<()> {
             insurance_vault_balance.ui_amount.unwrap(),
             insurance_vault_balance.amount
         );
-        if bank.emissions_mint != Pubkey::default() {
-            let emissions_token_account =
-                find_bank_emssions_token_account_pda(address, bank.emissions_mint, marginfi::id())
-                    .0;
-            let emissions_vault_balance =
-                rpc_client.get_token_account_balance(&emissions_token_account)?;
+        if let Some((emissions_token_account, emissions_vault_balance)) = emissions_vault {
             println!(
                 "\temissions vault: {} (native: {} - TA: {})",
                 emissions_vault_balance.ui_amount.unwrap(),
@@ -991,6 +1006,14 @@ pub fn bank_get(config: Config, bank_pk: Option<Pubkey>) -> Result<()> {
     Ok(())
 }
 
+fn load_clock(config: &Config) -> Result<Clock> {
+    let mut clock = config.mfi_program.rpc().get_account(&sysvar::clock::ID)?;
+
+    Ok(Clock::from_account_info(
+        &(&sysvar::clock::ID, &mut clock).into_account_info(),
+    )?)
+}
+
 fn load_all_banks(config: &Config, marginfi_group: Option<Pubkey>) -> Result<Vec<(Pubkey, Bank)>> {
     info!("Loading banks for group {:?}", marginfi_group);
     let filters = match marginfi_group {
@@ -1001,8 +1024,7 @@ fn load_all_banks(config: &Config, marginfi_group: Option<Pubkey>) -> Result<Vec
         None => vec![],
     };
 
-    let mut clock = config.mfi_program.rpc().get_account(&sysvar::clock::ID)?;
-    let clock = Clock::from_account_info(&(&sysvar::clock::ID, &mut clock).into_account_info())?;
+    let clock = load_clock(config)?;
 
     let mut banks_with_addresses = config.mfi_program.accounts::<Bank>(filters)?;
 
@@ -1013,8 +1035,62 @@ fn load_all_banks(config: &Config, marginfi_group: Option<Pubkey>) -> Result<Vec
     Ok(banks_with_addresses)
 }
 
+/// Load the price feed of every bank oracle, keyed by oracle address.
+///
+/// Staleness is not checked, and oracles that fail to load are skipped.
+fn load_price_feeds(
+    config: &Config,
+    banks: &HashMap<Pubkey, Bank>,
+) -> Result<HashMap<Pubkey, OraclePriceFeedAdapter>> {
+    let oracle_banks = banks
+        .values()
+        .map(|bank| (bank.config.oracle_keys[0], bank))
+        .collect::<HashMap<_, _>>();
+    let oracle_pks = oracle_banks.keys().copied().collect::<Vec<_>>();
+
+    let mut price_feeds = HashMap::new();
+    for chunk in oracle_pks.chunks(100) {
+        let accounts = config.mfi_program.rpc().get_multiple_accounts(chunk)?;
+
+        for (oracle_pk, account) in chunk.iter().zip(accounts) {
+            let mut account = match account {
+                Some(account) => account,
+                None => {
+                    warn!("Oracle {} not found", oracle_pk);
+                    continue;
+                }
+            };
+            let oracle_ai = (oracle_pk, &mut account).into_account_info();
+
+            match OraclePriceFeedAdapter::try_from_bank_config(
+                &oracle_banks[oracle_pk].config,
+                &[oracle_ai],
+                0,
+                u64::MAX,
+            ) {
+                Ok(price_feed) => {
+                    price_feeds.insert(*oracle_pk, price_feed);
+                }
+                Err(err) => warn!("Failed to load oracle {}: {}", oracle_pk, err),
+            }
+        }
+    }
+
+    Ok(price_feeds)
+}
+
 pub fn bank_get_all(config: Config, marginfi_group: Option<Pubkey>) -> Result<()> {
     let accounts = load_all_banks(&config, marginfi_group)?;
+
+    if config.output == OutputFormat::Json {
+        return print_json(
+            &accounts
+                .iter()
+                .map(|(address, bank)| BankView::new(address, bank))
+                .collect::<Result<Vec<_>>>()?,
+        );
+    }
+
     for (address, state) in accounts {
         print_bank(&address, &state);
     }
@@ -1024,6 +1100,7 @@ pub fn bank_get_all(config: Config, marginfi_group: Option<Pubkey>) -> Result<()
 #[cfg(feature = "dev")]
 pub fn bank_inspect_price_oracle(config: Config, bank_pk: Pubkey) -> Result<()> {
     use marginfi::state::price::{OraclePriceType, PriceBias};
+    use output::OraclePriceView;
 
     let bank: Bank = config.mfi_program.account(bank_pk)?;
     let mut price_oracle_account = config
@@ -1052,6 +1129,19 @@ pub fn bank_inspect_price_oracle(config: Config, bank_pk: Pubkey) -> Result<()>
         .filter(|k| k != &&Pubkey::default())
         .collect::<Vec<_>>();
 
+    if config.output == OutputFormat::Json {
+        return print_json(&OraclePriceView {
+            bank: bank_pk.to_string(),
+            oracle_setup: format!("{:?}", bank.config.oracle_setup),
+            oracle_keys: keys.iter().map(|key| key.to_string()).collect(),
+            real_time: real_price.to_num(),
+            maint_asset: maint_asset_price.to_num(),
+            maint_liability: maint_liab_price.to_num(),
+            init_asset: init_asset_price.to_num(),
+            init_liability: init_liab_price.to_num(),
+        });
+    }
+
     println!(
         r##"
 Oracle Setup: {setup:?}
@@ -1126,41 +1216,29 @@ pub fn bank_setup_emissions(
         return Ok(());
     }
 
-    let ix = Instruction {
-        program_id: marginfi::id(),
-        accounts: marginfi::accounts::LendingPoolSetupEmissions {
-            marginfi_group: profile.marginfi_group.expect("marginfi group not set"),
-            admin: config.authority(),
-            bank,
-            emissions_mint: mint,
-            emissions_auth: find_bank_emssions_auth_pda(bank, mint, marginfi::id()).0,
-            emissions_token_account: find_bank_emssions_token_account_pda(
-                bank,
-                mint,
-                marginfi::id(),
-            )
-            .0,
-            emissions_funding_account: funding_account_ata,
-            token_program: spl_token::id(),
-            system_program: system_program::id(),
-        }
-        .to_account_metas(Some(true)),
-        data: marginfi::instruction::LendingPoolSetupEmissions {
-            flags,
-            rate,
-            total_emissions,
-        }
-        .data(),
-    };
+    let ix = MarginfiGroupIxBuilder::new(
+        config.program_id,
+        profile.marginfi_group.expect("marginfi group not set"),
+    )
+    .setup_emissions(
+        config.authority(),
+        bank,
+        mint,
+        funding_account_ata,
+        flags,
+        rate,
+        total_emissions,
+    );
 
-    let recent_blockhash = rpc_client.get_latest_blockhash().unwrap();
     let signing_keypairs = config.get_signers(false);
 
-    let message = Message::new(&[ix], Some(&config.authority()));
-    let mut transaction = Transaction::new_unsigned(message);
-    transaction.partial_sign(&signing_keypairs, recent_blockhash);
-
-    match process_transaction(&transaction, &rpc_client, config.get_tx_mode()) {
+    match process_transaction(
+        &[ix],
+        &config.authority(),
+        &rpc_client,
+        config.get_tx_mode(),
+        &signing_keypairs,
+    ) {
         Ok(sig) => println!("Tx succeded (sig: {})", sig),
         Err(err) => println!("Error :\n{:#?}", err),
     };
@@ -1243,39 +1321,29 @@ pub fn bank_update_emissions(
         return Ok(());
     }
 
-    let ix = Instruction {
-        program_id: marginfi::id(),
-        accounts: marginfi::accounts::LendingPoolUpdateEmissionsParameters {
-            marginfi_group: profile.marginfi_group.expect("marginfi group not set"),
-            admin: config.authority(),
-            bank: bank_pk,
-            emissions_mint: emission_mint,
-            emissions_token_account: find_bank_emssions_token_account_pda(
-                bank_pk,
-                emission_mint,
-                marginfi::id(),
-            )
-            .0,
-            emissions_funding_account: funding_account_ata,
-            token_program: spl_token::id(),
-        }
-        .to_account_metas(Some(true)),
-        data: marginfi::instruction::LendingPoolUpdateEmissionsParameters {
-            emissions_flags,
-            emissions_rate,
-            additional_emissions,
-        }
-        .data(),
-    };
+    let ix = MarginfiGroupIxBuilder::new(
+        config.program_id,
+        profile.marginfi_group.expect("marginfi group not set"),
+    )
+    .update_emissions_parameters(
+        config.authority(),
+        bank_pk,
+        emission_mint,
+        funding_account_ata,
+        emissions_flags,
+        emissions_rate,
+        additional_emissions,
+    );
 
-    let recent_blockhash = rpc_client.get_latest_blockhash().unwrap();
     let signing_keypairs = config.get_signers(false);
 
-    let message = Message::new(&[ix], Some(&config.authority()));
-    let mut transaction = Transaction::new_unsigned(message);
-    transaction.partial_sign(&signing_keypairs, recent_blockhash);
-
-    match process_transaction(&transaction, &rpc_client, config.get_tx_mode()) {
+    match process_transaction(
+        &[ix],
+        &config.authority(),
+        &rpc_client,
+        config.get_tx_mode(),
+        &signing_keypairs,
+    ) {
         Ok(sig) => println!("Tx succeded (sig: {})", sig),
         Err(err) => println!("Error:\n{:#?}", err),
     };
@@ -1312,53 +1380,464 @@ pub fn bank_configure(
             .push(AccountMeta::new_readonly(oracle.keys[0], false));
     }
 
-    let recent_blockhash = rpc_client.get_latest_blockhash().unwrap();
-    let message = Message::new(&configure_bank_ixs, Some(&config.authority()));
-    let mut transaction = Transaction::new_unsigned(message);
-    transaction.partial_sign(&signing_keypairs, recent_blockhash);
-
-    let sig = process_transaction(&transaction, &rpc_client, config.get_tx_mode())?;
+    let sig = process_transaction(
+        &configure_bank_ixs,
+        &config.authority(),
+        &rpc_client,
+        config.get_tx_mode(),
+        &signing_keypairs,
+    )?;
 
     println!("Transaction signature: {}", sig);
 
     Ok(())
 }
 
-// --------------------------------------------------------------------------------------------------------------------
-// Profile
-// --------------------------------------------------------------------------------------------------------------------
-
-#[allow(clippy::too_many_arguments)]
-pub fn create_profile(
-    name: String,
-    cluster: Cluster,
-    keypair_path: Option<String>,
-    multisig: Option<Pubkey>,
-    rpc_url: String,
-    program_id: Option<Pubkey>,
-    commitment: Option<CommitmentLevel>,
-    marginfi_group: Option<Pubkey>,
-    marginfi_account: Option<Pubkey>,
-) -> Result<()> {
-    let cli_config_dir = get_cli_config_dir();
-    let profile = Profile::new(
-        name,
-        cluster,
-        keypair_path,
-        multisig,
-        rpc_url,
-        program_id,
-        commitment,
-        marginfi_group,
-        marginfi_account,
-    );
-    if !cli_config_dir.exists() {
-        fs::create_dir(&cli_config_dir)?;
+#[cfg(feature = "admin")]
+pub fn bank_reset_outflow(config: Config, profile: Profile, bank_pk: Pubkey) -> Result<()> {
+    let rpc_client = config.mfi_program.rpc();
 
-        let cli_config_file = cli_config_dir.join("config.json");
+    let signing_keypairs = config.get_signers(false);
 
-        fs::write(
-            cli_config_file,
+    let reset_outflow_ixs = config
+        .mfi_program
+        .request()
+        .accounts(marginfi::accounts::LendingPoolResetBankOutflow {
+            marginfi_group: profile.marginfi_group.unwrap(),
+            admin: config.authority(),
+            bank: bank_pk,
+        })
+        .args(marginfi::instruction::LendingPoolResetBankOutflow {})
+        .instructions()?;
+
+    let sig = process_transaction(
+        &reset_outflow_ixs,
+        &config.authority(),
+        &rpc_client,
+        config.get_tx_mode(),
+        &signing_keypairs,
+    )?;
+
+    println!("Transaction signature: {}", sig);
+
+    Ok(())
+}
+
+#[cfg(feature = "admin")]
+pub fn bank_socialize_bad_debt(config: Config, profile: Profile, bank_pk: Pubkey) -> Result<()> {
+    let rpc_client = config.mfi_program.rpc();
+
+    let signing_keypairs = config.get_signers(false);
+
+    let socialize_ixs = config
+        .mfi_program
+        .request()
+        .accounts(marginfi::accounts::LendingPoolConfigureBank {
+            marginfi_group: profile.marginfi_group.unwrap(),
+            admin: config.authority(),
+            bank: bank_pk,
+        })
+        .args(marginfi::instruction::LendingPoolSocializeBadDebt {})
+        .instructions()?;
+
+    let sig = process_transaction(
+        &socialize_ixs,
+        &config.authority(),
+        &rpc_client,
+        config.get_tx_mode(),
+        &signing_keypairs,
+    )?;
+
+    println!("Transaction signature: {}", sig);
+
+    Ok(())
+}
+
+#[cfg(feature = "admin")]
+fn ui_to_borrow_credential_limit(bank: &Bank, borrow_limit_ui: Option<f64>) -> u64 {
+    borrow_limit_ui
+        .map(|ui_amount| spl_token::ui_amount_to_amount(ui_amount, bank.mint_decimals))
+        .unwrap_or(u64::MAX)
+}
+
+#[cfg(feature = "admin")]
+pub fn bank_create_borrow_credential(
+    config: Config,
+    profile: Profile,
+    bank_pk: Pubkey,
+    marginfi_account_pk: Pubkey,
+    borrow_limit_ui: Option<f64>,
+) -> Result<()> {
+    let rpc_client = config.mfi_program.rpc();
+
+    let signing_keypairs = config.get_signers(false);
+
+    let bank = config.mfi_program.account::<Bank>(bank_pk)?;
+    let borrow_limit = ui_to_borrow_credential_limit(&bank, borrow_limit_ui);
+
+    let create_credential_ixs =
+        vec![
+            MarginfiGroupIxBuilder::new(config.program_id, profile.marginfi_group.unwrap())
+                .create_borrow_credential(
+                    config.authority(),
+                    bank_pk,
+                    marginfi_account_pk,
+                    borrow_limit,
+                ),
+        ];
+
+    let sig = process_transaction(
+        &create_credential_ixs,
+        &config.authority(),
+        &rpc_client,
+        config.get_tx_mode(),
+        &signing_keypairs,
+    )?;
+
+    println!("Transaction signature: {}", sig);
+
+    Ok(())
+}
+
+#[cfg(feature = "admin")]
+pub fn bank_update_borrow_credential(
+    config: Config,
+    profile: Profile,
+    bank_pk: Pubkey,
+    marginfi_account_pk: Pubkey,
+    borrow_limit_ui: Option<f64>,
+) -> Result<()> {
+    let rpc_client = config.mfi_program.rpc();
+
+    let signing_keypairs = config.get_signers(false);
+
+    let bank = config.mfi_program.account::<Bank>(bank_pk)?;
+    let borrow_limit = ui_to_borrow_credential_limit(&bank, borrow_limit_ui);
+
+    let update_credential_ixs =
+        vec![
+            MarginfiGroupIxBuilder::new(config.program_id, profile.marginfi_group.unwrap())
+                .update_borrow_credential(
+                    config.authority(),
+                    bank_pk,
+                    marginfi_account_pk,
+                    borrow_limit,
+                ),
+        ];
+
+    let sig = process_transaction(
+        &update_credential_ixs,
+        &config.authority(),
+        &rpc_client,
+        config.get_tx_mode(),
+        &signing_keypairs,
+    )?;
+
+    println!("Transaction signature: {}", sig);
+
+    Ok(())
+}
+
+#[cfg(feature = "admin")]
+pub fn bank_revoke_borrow_credential(
+    config: Config,
+    profile: Profile,
+    bank_pk: Pubkey,
+    marginfi_account_pk: Pubkey,
+) -> Result<()> {
+    let rpc_client = config.mfi_program.rpc();
+
+    let signing_keypairs = config.get_signers(false);
+
+    let revoke_credential_ixs =
+        vec![
+            MarginfiGroupIxBuilder::new(config.program_id, profile.marginfi_group.unwrap())
+                .revoke_borrow_credential(config.authority(), bank_pk, marginfi_account_pk),
+        ];
+
+    let sig = process_transaction(
+        &revoke_credential_ixs,
+        &config.authority(),
+        &rpc_client,
+        config.get_tx_mode(),
+        &signing_keypairs,
+    )?;
+
+    println!("Transaction signature: {}", sig);
+
+    Ok(())
+}
+
+#[cfg(feature = "admin")]
+pub fn bank_setup_insurance_staking(
+    config: Config,
+    profile: Profile,
+    bank_pk: Pubkey,
+    unstake_cooldown: u64,
+) -> Result<()> {
+    let rpc_client = config.mfi_program.rpc();
+
+    let signing_keypairs = config.get_signers(false);
+
+    let setup_ixs =
+        vec![
+            MarginfiGroupIxBuilder::new(config.program_id, profile.marginfi_group.unwrap())
+                .setup_insurance_staking(config.authority(), bank_pk, unstake_cooldown),
+        ];
+
+    let sig = process_transaction(
+        &setup_ixs,
+        &config.authority(),
+        &rpc_client,
+        config.get_tx_mode(),
+        &signing_keypairs,
+    )?;
+
+    println!("Transaction signature: {}", sig);
+
+    Ok(())
+}
+
+#[cfg(feature = "admin")]
+pub fn bank_reset_insurance_staking(
+    config: Config,
+    profile: Profile,
+    bank_pk: Pubkey,
+) -> Result<()> {
+    let rpc_client = config.mfi_program.rpc();
+
+    let bank = config.mfi_program.account::<Bank>(bank_pk)?;
+
+    let signing_keypairs = config.get_signers(false);
+
+    let reset_ixs =
+        vec![
+            MarginfiGroupIxBuilder::new(config.program_id, profile.marginfi_group.unwrap())
+                .reset_insurance_staking(
+                    config.authority(),
+                    bank_pk,
+                    bank.insurance_share_mint,
+                    bank.insurance_share_generation,
+                ),
+        ];
+
+    let sig = process_transaction(
+        &reset_ixs,
+        &config.authority(),
+        &rpc_client,
+        config.get_tx_mode(),
+        &signing_keypairs,
+    )?;
+
+    println!("Transaction signature: {}", sig);
+
+    Ok(())
+}
+
+pub fn bank_insurance_stake(
+    config: &Config,
+    profile: &Profile,
+    bank_pk: Pubkey,
+    ui_amount: f64,
+) -> Result<()> {
+    let signer = config.get_non_ms_authority_keypair()?;
+
+    let rpc_client = config.mfi_program.rpc();
+
+    let bank = config.mfi_program.account::<Bank>(bank_pk)?;
+
+    if !bank.is_insurance_staking_active() {
+        bail!("Insurance staking is not enabled for this bank")
+    }
+
+    let amount = spl_token::ui_amount_to_amount(ui_amount, bank.mint_decimals);
+
+    let share_ata = anchor_spl::associated_token::get_associated_token_address(
+        &signer.pubkey(),
+        &bank.insurance_share_mint,
+    );
+
+    let ix = MarginfiGroupIxBuilder::new(config.program_id, profile.marginfi_group.unwrap())
+        .insurance_stake(
+            signer.pubkey(),
+            bank_pk,
+            bank.insurance_share_mint,
+            anchor_spl::associated_token::get_associated_token_address(
+                &signer.pubkey(),
+                &bank.mint,
+            ),
+            share_ata,
+            amount,
+        );
+
+    let create_ide_ata_ix = create_associated_token_account_idempotent(
+        &signer.pubkey(),
+        &signer.pubkey(),
+        &bank.insurance_share_mint,
+        &spl_token::ID,
+    );
+
+    match process_transaction(
+        &[create_ide_ata_ix, ix],
+        &signer.pubkey(),
+        &rpc_client,
+        config.get_tx_mode(),
+        &[signer],
+    ) {
+        Ok(sig) => println!("Insurance stake successful: {sig}"),
+        Err(err) => println!("Error during insurance stake:\n{err:#?}"),
+    }
+
+    Ok(())
+}
+
+pub fn bank_insurance_request_unstake(
+    config: &Config,
+    profile: &Profile,
+    bank_pk: Pubkey,
+    ui_shares: Option<f64>,
+    request_index: u64,
+) -> Result<()> {
+    let signer = config.get_non_ms_authority_keypair()?;
+
+    let rpc_client = config.mfi_program.rpc();
+
+    let bank = config.mfi_program.account::<Bank>(bank_pk)?;
+
+    if !bank.is_insurance_staking_active() {
+        bail!("Insurance staking is not enabled for this bank")
+    }
+
+    let share_ata = anchor_spl::associated_token::get_associated_token_address(
+        &signer.pubkey(),
+        &bank.insurance_share_mint,
+    );
+
+    let shares = match ui_shares {
+        Some(ui_shares) => spl_token::ui_amount_to_amount(ui_shares, bank.mint_decimals),
+        None => rpc_client
+            .get_token_account_balance(&share_ata)?
+            .amount
+            .parse::<u64>()?,
+    };
+
+    let ix = MarginfiGroupIxBuilder::new(config.program_id, profile.marginfi_group.unwrap())
+        .insurance_request_unstake(
+            signer.pubkey(),
+            bank_pk,
+            bank.insurance_share_mint,
+            share_ata,
+            shares,
+            request_index,
+        );
+
+    match process_transaction(
+        &[ix],
+        &signer.pubkey(),
+        &rpc_client,
+        config.get_tx_mode(),
+        &[signer],
+    ) {
+        Ok(sig) => println!(
+            "Unstake requested, claimable in {}s: {sig}",
+            bank.insurance_unstake_cooldown
+        ),
+        Err(err) => println!("Error during unstake request:\n{err:#?}"),
+    }
+
+    Ok(())
+}
+
+pub fn bank_insurance_unstake(
+    config: &Config,
+    profile: &Profile,
+    bank_pk: Pubkey,
+    request_index: u64,
+) -> Result<()> {
+    let signer = config.get_non_ms_authority_keypair()?;
+
+    let rpc_client = config.mfi_program.rpc();
+
+    let bank = config.mfi_program.account::<Bank>(bank_pk)?;
+
+    let destination_ata =
+        anchor_spl::associated_token::get_associated_token_address(&signer.pubkey(), &bank.mint);
+
+    let ix = MarginfiGroupIxBuilder::new(config.program_id, profile.marginfi_group.unwrap())
+        .insurance_unstake(
+            signer.pubkey(),
+            bank_pk,
+            bank.insurance_share_mint,
+            destination_ata,
+            request_index,
+        );
+
+    let create_ide_ata_ix = create_associated_token_account_idempotent(
+        &signer.pubkey(),
+        &signer.pubkey(),
+        &bank.mint,
+        &spl_token::ID,
+    );
+
+    match process_transaction(
+        &[create_ide_ata_ix, ix],
+        &signer.pubkey(),
+        &rpc_client,
+        config.get_tx_mode(),
+        &[signer],
+    ) {
+        Ok(sig) => println!("Insurance unstake successful: {sig}"),
+        Err(err) => println!("Error during insurance unstake:\n{err:#?}"),
+    }
+
+    Ok(())
+}
+
+// --------------------------------------------------------------------------------------------------------------------
+// Profile
+// --------------------------------------------------------------------------------------------------------------------
+
+#[allow(clippy::too_many_arguments)]
+pub fn create_profile(
+    name: String,
+    cluster: Cluster,
+    keypair_path: Option<String>,
+    multisig: Option<Pubkey>,
+    rpc_url: String,
+    program_id: Option<Pubkey>,
+    commitment: Option<CommitmentLevel>,
+    marginfi_group: Option<Pubkey>,
+    marginfi_account: Option<Pubkey>,
+    squads_multisig: Option<Pubkey>,
+    squads_vault_index: Option<u8>,
+    compute_unit_price: Option<PriorityFee>,
+    compute_unit_limit: Option<u32>,
+    lookup_tables: Vec<Pubkey>,
+) -> Result<()> {
+    let cli_config_dir = get_cli_config_dir();
+    let profile = Profile::new(
+        name,
+        cluster,
+        keypair_path,
+        multisig,
+        rpc_url,
+        program_id,
+        commitment,
+        marginfi_group,
+        marginfi_account,
+        squads_multisig,
+        squads_vault_index,
+        compute_unit_price,
+        compute_unit_limit,
+        lookup_tables,
+    );
+    if !cli_config_dir.exists() {
+        fs::create_dir(&cli_config_dir)?;
+
+        let cli_config_file = cli_config_dir.join("config.json");
+
+        fs::write(
+            cli_config_file,
             serde_json::to_string(&CliConfig {
                 profile_name: profile.name.clone(),
             })?,
@@ -1383,8 +1862,13 @@ pub fn create_profile(
     Ok(())
 }
 
-pub fn show_profile() -> Result<()> {
+pub fn show_profile(output: OutputFormat) -> Result<()> {
     let profile = load_profile()?;
+
+    if output == OutputFormat::Json {
+        return print_json(&profile);
+    }
+
     println!("{profile:?}");
     Ok(())
 }
@@ -1413,7 +1897,7 @@ pub fn set_profile(name: String) -> Result<()> {
     Ok(())
 }
 
-pub fn list_profiles() -> Result<()> {
+pub fn list_profiles(output: OutputFormat) -> Result<()> {
     let cli_config_dir = get_cli_config_dir();
     let cli_profiles_dir = cli_config_dir.join("profiles");
 
@@ -1421,22 +1905,32 @@ pub fn list_profiles() -> Result<()> {
         return Err(anyhow!("Profiles not configured, run `mfi profile create`"));
     }
 
-    let mut profiles = fs::read_dir(&cli_profiles_dir)?
-        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
-        .collect::<Vec<String>>();
+    let mut profiles = fs::read_dir(&cli_profiles_dir)?
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<String>>();
+
+    let cli_config = serde_json::from_str::<CliConfig>(&fs::read_to_string(
+        cli_config_dir.join("config.json"),
+    )?)?;
+
+    profiles.sort();
+
+    if output == OutputFormat::Json {
+        return print_json(&ProfileListView {
+            current: cli_config.profile_name,
+            profiles: profiles
+                .iter()
+                .map(|profile| profile.strip_suffix(".json").unwrap_or(profile).to_string())
+                .collect(),
+        });
+    }
 
     if profiles.is_empty() {
         println!("No profiles exist");
     }
 
-    let cli_config = serde_json::from_str::<CliConfig>(&fs::read_to_string(
-        cli_config_dir.join("config.json"),
-    )?)?;
-
     println!("Current profile: {}", cli_config.profile_name);
 
-    profiles.sort();
-
     println!("Found {} profiles", profiles.len());
     for profile in profiles {
         println!("{profile}");
@@ -1456,6 +1950,11 @@ pub fn configure_profile(
     commitment: Option<CommitmentLevel>,
     group: Option<Pubkey>,
     account: Option<Pubkey>,
+    squads_multisig: Option<Pubkey>,
+    squads_vault_index: Option<u8>,
+    compute_unit_price: Option<PriorityFee>,
+    compute_unit_limit: Option<u32>,
+    lookup_tables: Vec<Pubkey>,
 ) -> Result<()> {
     let mut profile = profile::load_profile_by_name(&name)?;
     profile.config(
@@ -1467,6 +1966,11 @@ pub fn configure_profile(
         commitment,
         group,
         account,
+        squads_multisig,
+        squads_vault_index,
+        compute_unit_price,
+        compute_unit_limit,
+        lookup_tables,
     )?;
 
     Ok(())
@@ -1487,6 +1991,25 @@ pub fn marginfi_account_list(profile: Profile, config: &Config) -> Result<()> {
         RpcFilterType::Memcmp(Memcmp::new_raw_bytes(8 + 32, authority.to_bytes().to_vec())),
     ])?;
 
+    if config.output == OutputFormat::Json {
+        let price_feeds = load_price_feeds(config, &banks)?;
+
+        return print_json(
+            &accounts
+                .iter()
+                .map(|(address, marginfi_account)| {
+                    AccountView::new(
+                        *address,
+                        marginfi_account,
+                        &banks,
+                        &price_feeds,
+                        profile.marginfi_account == Some(*address),
+                    )
+                })
+                .collect::<Result<Vec<_>>>()?,
+        );
+    }
+
     if accounts.is_empty() {
         println!("No marginfi accounts found");
     }
@@ -1624,6 +2147,18 @@ pub fn marginfi_account_get(
 
     let banks = HashMap::from_iter(load_all_banks(config, Some(group))?);
 
+    if config.output == OutputFormat::Json {
+        let price_feeds = load_price_feeds(config, &banks)?;
+
+        return print_json(&AccountView::new(
+            marginfi_account_pk,
+            &marginfi_account,
+            &banks,
+            &price_feeds,
+            false,
+        )?);
+    }
+
     print_account(marginfi_account_pk, marginfi_account, banks, false)?;
 
     Ok(())
@@ -1640,6 +2175,11 @@ pub fn marginfi_account_deposit(
     let marginfi_account_pk = profile.get_marginfi_account();
 
     let bank = config.mfi_program.account::<Bank>(bank_pk)?;
+    let banks = HashMap::from([(bank_pk, bank)]);
+
+    let marginfi_account = config
+        .mfi_program
+        .account::<MarginfiAccount>(marginfi_account_pk)?;
 
     let amount = (I80F48::from_num(ui_amount) * EXP_10_I80F48[bank.mint_decimals as usize])
         .floor()
@@ -1653,30 +2193,21 @@ pub fn marginfi_account_deposit(
     let deposit_ata =
         anchor_spl::associated_token::get_associated_token_address(&signer.pubkey(), &bank.mint);
 
-    let ix = Instruction {
-        program_id: config.program_id,
-        accounts: marginfi::accounts::LendingAccountDeposit {
-            marginfi_group: profile.marginfi_group.unwrap(),
-            marginfi_account: marginfi_account_pk,
-            signer: signer.pubkey(),
-            bank: bank_pk,
-            signer_token_account: deposit_ata,
-            bank_liquidity_vault: bank.liquidity_vault,
-            token_program: token::ID,
-        }
-        .to_account_metas(Some(true)),
-        data: marginfi::instruction::LendingAccountDeposit { amount }.data(),
-    };
+    let ix = MarginfiAccountIxBuilder::new(
+        config.program_id,
+        marginfi_account_pk,
+        &marginfi_account,
+        &banks,
+    )
+    .deposit(signer.pubkey(), bank_pk, deposit_ata, amount)?;
 
-    let recent_blockhash = rpc_client.get_latest_blockhash().unwrap();
-    let tx = Transaction::new_signed_with_payer(
+    match process_transaction(
         &[ix],
-        Some(&signer.pubkey()),
+        &signer.pubkey(),
+        &rpc_client,
+        config.get_tx_mode(),
         &[signer],
-        recent_blockhash,
-    );
-
-    match process_transaction(&tx, &rpc_client, config.get_tx_mode()) {
+    ) {
         Ok(sig) => println!("Deposit successful: {sig}"),
         Err(err) => println!("Error during deposit:\n{err:#?}"),
     }
@@ -1719,37 +2250,13 @@ pub fn marginfi_account_withdraw(
     let withdraw_ata =
         anchor_spl::associated_token::get_associated_token_address(&signer.pubkey(), &bank.mint);
 
-    let mut ix = Instruction {
-        program_id: config.program_id,
-        accounts: marginfi::accounts::LendingAccountWithdraw {
-            marginfi_group: profile.marginfi_group.unwrap(),
-            marginfi_account: marginfi_account_pk,
-            signer: signer.pubkey(),
-            bank: bank_pk,
-            bank_liquidity_vault: bank.liquidity_vault,
-            token_program: token::ID,
-            destination_token_account: withdraw_ata,
-            bank_liquidity_vault_authority: find_bank_vault_authority_pda(
-                &bank_pk,
-                BankVaultType::Liquidity,
-                &config.program_id,
-            )
-            .0,
-        }
-        .to_account_metas(Some(true)),
-        data: marginfi::instruction::LendingAccountWithdraw {
-            amount,
-            withdraw_all: if withdraw_all { Some(true) } else { None },
-        }
-        .data(),
-    };
-
-    ix.accounts.extend(load_observation_account_metas(
+    let ix = MarginfiAccountIxBuilder::new(
+        config.program_id,
+        marginfi_account_pk,
         &marginfi_account,
         &banks,
-        vec![],
-        if withdraw_all { vec![bank_pk] } else { vec![] },
-    ));
+    )
+    .withdraw(signer.pubkey(), bank_pk, withdraw_ata, amount, withdraw_all)?;
 
     let create_ide_ata_ix = create_associated_token_account_idempotent(
         &signer.pubkey(),
@@ -1758,15 +2265,13 @@ pub fn marginfi_account_withdraw(
         &spl_token::ID,
     );
 
-    let recent_blockhash = rpc_client.get_latest_blockhash().unwrap();
-    let tx = Transaction::new_signed_with_payer(
+    match process_transaction(
         &[create_ide_ata_ix, ix],
-        Some(&signer.pubkey()),
+        &signer.pubkey(),
+        &rpc_client,
+        config.get_tx_mode(),
         &[signer],
-        recent_blockhash,
-    );
-
-    match process_transaction(&tx, &rpc_client, config.get_tx_mode()) {
+    ) {
         Ok(sig) => println!("Withdraw successful: {sig}"),
         Err(err) => println!("Error during withdraw:\n{err:#?}"),
     }
@@ -1808,33 +2313,13 @@ pub fn marginfi_account_borrow(
     let withdraw_ata =
         anchor_spl::associated_token::get_associated_token_address(&signer.pubkey(), &bank.mint);
 
-    let mut ix = Instruction {
-        program_id: config.program_id,
-        accounts: marginfi::accounts::LendingAccountBorrow {
-            marginfi_group: profile.marginfi_group.unwrap(),
-            marginfi_account: marginfi_account_pk,
-            signer: signer.pubkey(),
-            bank: bank_pk,
-            bank_liquidity_vault: bank.liquidity_vault,
-            token_program: token::ID,
-            destination_token_account: withdraw_ata,
-            bank_liquidity_vault_authority: find_bank_vault_authority_pda(
-                &bank_pk,
-                BankVaultType::Liquidity,
-                &config.program_id,
-            )
-            .0,
-        }
-        .to_account_metas(Some(true)),
-        data: marginfi::instruction::LendingAccountBorrow { amount }.data(),
-    };
-
-    ix.accounts.extend(load_observation_account_metas(
+    let ix = MarginfiAccountIxBuilder::new(
+        config.program_id,
+        marginfi_account_pk,
         &marginfi_account,
         &banks,
-        vec![bank_pk],
-        vec![],
-    ));
+    )
+    .borrow(signer.pubkey(), bank_pk, withdraw_ata, amount)?;
 
     let create_ide_ata_ix = create_associated_token_account_idempotent(
         &signer.pubkey(),
@@ -1843,15 +2328,13 @@ pub fn marginfi_account_borrow(
         &spl_token::ID,
     );
 
-    let recent_blockhash = rpc_client.get_latest_blockhash().unwrap();
-    let tx = Transaction::new_signed_with_payer(
+    match process_transaction(
         &[create_ide_ata_ix, ix],
-        Some(&signer.pubkey()),
+        &signer.pubkey(),
+        &rpc_client,
+        config.get_tx_mode(),
         &[signer],
-        recent_blockhash,
-    );
-
-    match process_transaction(&tx, &rpc_client, config.get_tx_mode()) {
+    ) {
         Ok(sig) => println!("Borrow successful: {sig}"),
         Err(err) => println!("Error during borrow:\n{err:#?}"),
     }
@@ -1859,6 +2342,77 @@ pub fn marginfi_account_borrow(
     Ok(())
 }
 
+pub fn marginfi_account_transfer_balance(
+    profile: &Profile,
+    config: &Config,
+    bank_pk: Pubkey,
+    destination_marginfi_account_pk: Pubkey,
+    ui_amount: f64,
+    transfer_all: bool,
+) -> Result<()> {
+    let signer = config.get_non_ms_authority_keypair()?;
+
+    let rpc_client = config.mfi_program.rpc();
+
+    let marginfi_account_pk = profile.get_marginfi_account();
+
+    let banks = HashMap::from_iter(load_all_banks(
+        config,
+        Some(profile.marginfi_group.unwrap()),
+    )?);
+    let bank = banks.get(&bank_pk).expect("Bank not found");
+
+    let marginfi_account = config
+        .mfi_program
+        .account::<MarginfiAccount>(marginfi_account_pk)?;
+    let destination_marginfi_account = config
+        .mfi_program
+        .account::<MarginfiAccount>(destination_marginfi_account_pk)?;
+
+    let amount = (I80F48::from_num(ui_amount) * EXP_10_I80F48[bank.mint_decimals as usize])
+        .floor()
+        .to_num::<u64>();
+
+    // Check that bank belongs to the correct group
+    if bank.group != profile.marginfi_group.unwrap() {
+        bail!("Bank does not belong to group")
+    }
+
+    if destination_marginfi_account.authority != marginfi_account.authority {
+        bail!("Destination account has a different authority")
+    }
+
+    let ix = MarginfiAccountIxBuilder::new(
+        config.program_id,
+        marginfi_account_pk,
+        &marginfi_account,
+        &banks,
+    )
+    .transfer_balance(
+        signer.pubkey(),
+        bank_pk,
+        (
+            destination_marginfi_account_pk,
+            &destination_marginfi_account,
+        ),
+        amount,
+        transfer_all,
+    )?;
+
+    match process_transaction(
+        &[ix],
+        &signer.pubkey(),
+        &rpc_client,
+        config.get_tx_mode(),
+        &[signer],
+    ) {
+        Ok(sig) => println!("Balance transfer successful: {sig}"),
+        Err(err) => println!("Error during balance transfer:\n{err:#?}"),
+    }
+
+    Ok(())
+}
+
 pub fn marginfi_account_liquidate(
     profile: &Profile,
     config: &Config,
@@ -1903,63 +2457,29 @@ pub fn marginfi_account_liquidate(
         bail!("Liability bank does not belong to group")
     }
 
-    let mut ix = Instruction {
-        program_id: config.program_id,
-        accounts: marginfi::accounts::LendingAccountLiquidate {
-            marginfi_group: profile.marginfi_group.unwrap(),
-            asset_bank: asset_bank_pk,
-            liab_bank: liability_bank_pk,
-            liquidator_marginfi_account: marginfi_account_pk,
-            signer: signer.pubkey(),
-            liquidatee_marginfi_account: liquidatee_marginfi_account_pk,
-            bank_liquidity_vault_authority: find_bank_vault_authority_pda(
-                &liability_bank_pk,
-                BankVaultType::Liquidity,
-                &config.program_id,
-            )
-            .0,
-            bank_liquidity_vault: liability_bank.liquidity_vault,
-            bank_insurance_vault: liability_bank.insurance_vault,
-            token_program: token::ID,
-        }
-        .to_account_metas(Some(true)),
-        data: marginfi::instruction::LendingAccountLiquidate { asset_amount }.data(),
-    };
-
-    ix.accounts.push(AccountMeta {
-        pubkey: asset_bank.config.oracle_keys[0],
-        is_signer: false,
-        is_writable: false,
-    });
-    ix.accounts.push(AccountMeta {
-        pubkey: liability_bank.config.oracle_keys[0],
-        is_signer: false,
-        is_writable: false,
-    });
-    ix.accounts.extend(load_observation_account_metas(
+    let ix = MarginfiAccountIxBuilder::new(
+        config.program_id,
+        marginfi_account_pk,
         &marginfi_account,
         &banks,
-        vec![liability_bank_pk, asset_bank_pk],
-        vec![],
-    ));
-    ix.accounts.extend(load_observation_account_metas(
-        &liquidatee_marginfi_account,
-        &banks,
-        vec![],
-        vec![],
-    ));
+    )
+    .liquidate(
+        signer.pubkey(),
+        asset_bank_pk,
+        liability_bank_pk,
+        (liquidatee_marginfi_account_pk, &liquidatee_marginfi_account),
+        asset_amount,
+    )?;
 
     let cu_ix = ComputeBudgetInstruction::set_compute_unit_limit(1_400_000);
 
-    let recent_blockhash = rpc_client.get_latest_blockhash().unwrap();
-    let tx = Transaction::new_signed_with_payer(
+    match process_transaction(
         &[ix, cu_ix],
-        Some(&signer.pubkey()),
+        &signer.pubkey(),
+        &rpc_client,
+        config.get_tx_mode(),
         &[signer],
-        recent_blockhash,
-    );
-
-    match process_transaction(&tx, &config.mfi_program.rpc(), config.get_tx_mode()) {
+    ) {
         Ok(sig) => println!("Liquidation successful: {sig}"),
         Err(err) => println!("Error during liquidation:\n{err:#?}"),
     }
@@ -1967,37 +2487,193 @@ pub fn marginfi_account_liquidate(
     Ok(())
 }
 
-pub fn marginfi_account_create(profile: &Profile, config: &Config) -> Result<()> {
+pub fn marginfi_account_create_order(
+    profile: &Profile,
+    config: &Config,
+    asset_bank_pk: Pubkey,
+    liability_bank_pk: Pubkey,
+    trigger: OrderTrigger,
+    trigger_value: f64,
+    keeper_tip: f64,
+) -> Result<()> {
     let signer = config.get_non_ms_authority_keypair()?;
 
     let rpc_client = config.mfi_program.rpc();
 
-    let marginfi_account_key = Keypair::new();
+    let marginfi_account_pk = profile.get_marginfi_account();
 
-    let ix = Instruction {
-        program_id: config.program_id,
-        accounts: marginfi::accounts::MarginfiAccountInitialize {
-            marginfi_group: profile.marginfi_group.unwrap(),
-            marginfi_account: marginfi_account_key.pubkey(),
-            system_program: system_program::ID,
-            authority: signer.pubkey(),
-            fee_payer: signer.pubkey(),
-        }
-        .to_account_metas(Some(true)),
-        data: marginfi::instruction::MarginfiAccountInitialize.data(),
-    };
+    let marginfi_account = config
+        .mfi_program
+        .account::<MarginfiAccount>(marginfi_account_pk)?;
+
+    let order_pk = find_order_pda(
+        &marginfi_account_pk,
+        &asset_bank_pk,
+        &liability_bank_pk,
+        trigger,
+        &config.program_id,
+    )
+    .0;
+
+    let ix = MarginfiAccountIxBuilder::new(
+        config.program_id,
+        marginfi_account_pk,
+        &marginfi_account,
+        &HashMap::new(),
+    )
+    .create_order(
+        signer.pubkey(),
+        asset_bank_pk,
+        liability_bank_pk,
+        trigger,
+        I80F48::from_num(trigger_value),
+        I80F48::from_num(keeper_tip),
+    );
 
-    let recent_blockhash = rpc_client.get_latest_blockhash().unwrap();
-    let tx = Transaction::new_signed_with_payer(
+    match process_transaction(
         &[ix],
-        Some(&signer.pubkey()),
-        &[signer, &marginfi_account_key],
-        recent_blockhash,
+        &signer.pubkey(),
+        &rpc_client,
+        config.get_tx_mode(),
+        &[signer],
+    ) {
+        Ok(sig) => println!("Order {order_pk} created: {sig}"),
+        Err(err) => println!("Error during order creation:\n{err:#?}"),
+    }
+
+    Ok(())
+}
+
+pub fn marginfi_account_close_order(
+    profile: &Profile,
+    config: &Config,
+    order_pk: Pubkey,
+) -> Result<()> {
+    let signer = config.get_non_ms_authority_keypair()?;
+
+    let rpc_client = config.mfi_program.rpc();
+
+    let marginfi_account_pk = profile.get_marginfi_account();
+
+    let marginfi_account = config
+        .mfi_program
+        .account::<MarginfiAccount>(marginfi_account_pk)?;
+
+    let ix = MarginfiAccountIxBuilder::new(
+        config.program_id,
+        marginfi_account_pk,
+        &marginfi_account,
+        &HashMap::new(),
+    )
+    .close_order(signer.pubkey(), order_pk);
+
+    match process_transaction(
+        &[ix],
+        &signer.pubkey(),
+        &rpc_client,
+        config.get_tx_mode(),
+        &[signer],
+    ) {
+        Ok(sig) => println!("Order closed: {sig}"),
+        Err(err) => println!("Error during order close:\n{err:#?}"),
+    }
+
+    Ok(())
+}
+
+/// Execute a triggered order as a keeper, paying the liability from
+/// and receiving the collateral into the signer associated token accounts.
+pub fn marginfi_account_execute_order(
+    profile: &Profile,
+    config: &Config,
+    order_pk: Pubkey,
+) -> Result<()> {
+    let signer = config.get_non_ms_authority_keypair()?;
+
+    let rpc_client = config.mfi_program.rpc();
+
+    let order = config.mfi_program.account::<Order>(order_pk)?;
+
+    let banks = HashMap::from_iter(load_all_banks(
+        config,
+        Some(profile.marginfi_group.unwrap()),
+    )?);
+    let asset_bank = banks.get(&order.asset_bank).expect("Asset bank not found");
+    let liability_bank = banks
+        .get(&order.liab_bank)
+        .expect("Liability bank not found");
+
+    let marginfi_account = config
+        .mfi_program
+        .account::<MarginfiAccount>(order.marginfi_account)?;
+
+    let ix = MarginfiAccountIxBuilder::new(
+        config.program_id,
+        order.marginfi_account,
+        &marginfi_account,
+        &banks,
+    )
+    .execute_order(
+        signer.pubkey(),
+        order_pk,
+        &order,
+        anchor_spl::associated_token::get_associated_token_address(
+            &signer.pubkey(),
+            &liability_bank.mint,
+        ),
+        anchor_spl::associated_token::get_associated_token_address(
+            &signer.pubkey(),
+            &asset_bank.mint,
+        ),
+    )?;
+
+    let create_ide_ata_ix = create_associated_token_account_idempotent(
+        &signer.pubkey(),
+        &signer.pubkey(),
+        &asset_bank.mint,
+        &spl_token::ID,
+    );
+
+    match process_transaction(
+        &[create_ide_ata_ix, ix],
+        &signer.pubkey(),
+        &rpc_client,
+        config.get_tx_mode(),
+        &[signer],
+    ) {
+        Ok(sig) => println!("Order executed: {sig}"),
+        Err(err) => println!("Error during order execution:\n{err:#?}"),
+    }
+
+    Ok(())
+}
+
+pub fn marginfi_account_create(profile: &Profile, config: &Config) -> Result<()> {
+    let signer = config.get_non_ms_authority_keypair()?;
+
+    let rpc_client = config.mfi_program.rpc();
+
+    let marginfi_account_key = Keypair::new();
+
+    let ix = initialize_marginfi_account(
+        config.program_id,
+        profile.marginfi_group.unwrap(),
+        marginfi_account_key.pubkey(),
+        signer.pubkey(),
+        signer.pubkey(),
     );
 
+    let signers = [signer as &dyn Signer, &marginfi_account_key];
+
     let marginfi_account_pk = marginfi_account_key.pubkey();
 
-    match process_transaction(&tx, &config.mfi_program.rpc(), config.get_tx_mode()) {
+    match process_transaction(
+        &[ix],
+        &signer.pubkey(),
+        &rpc_client,
+        config.get_tx_mode(),
+        &signers,
+    ) {
         Ok(_sig) => print!("{marginfi_account_pk}"),
         Err(err) => println!("Error during initialize:\n{err:#?}"),
     }
@@ -2021,8 +2697,20 @@ pub fn marginfi_account_create(profile: &Profile, config: &Config) -> Result<()>
 ///
 
 #[cfg(feature = "lip")]
-pub fn process_list_lip_campaigns(config: &Config) {
-    let campaings = config.lip_program.accounts::<Campaign>(vec![]).unwrap();
+pub fn process_list_lip_campaigns(config: &Config) -> Result<()> {
+    let campaings = config.lip_program.accounts::<Campaign>(vec![])?;
+
+    if config.output == OutputFormat::Json {
+        let mut campaign_views = vec![];
+        for (address, campaign) in campaings.iter() {
+            let bank = config
+                .mfi_program
+                .account::<Bank>(campaign.marginfi_bank_pk)?;
+            campaign_views.push(CampaignView::new(address, campaign, &bank));
+        }
+
+        return print_json(&campaign_views);
+    }
 
     print!("Found {} campaigns", campaings.len());
 
@@ -2051,10 +2739,12 @@ Max Rewards: {}
             campaign.max_rewards as f32 / 10.0_f32.powi(bank.mint_decimals as i32),
         );
     });
+
+    Ok(())
 }
 
 #[cfg(feature = "lip")]
-pub fn process_list_deposits(config: &Config) {
+pub fn process_list_deposits(config: &Config) -> Result<()> {
     use solana_sdk::clock::SECONDS_PER_DAY;
 
     let mut deposits = config.lip_program.accounts::<Deposit>(vec![]).unwrap();
@@ -2066,6 +2756,22 @@ pub fn process_list_deposits(config: &Config) {
 
     deposits.sort_by(|(_, a), (_, b)| a.start_time.cmp(&b.start_time));
 
+    if config.output == OutputFormat::Json {
+        let time_now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+        return print_json(
+            &deposits
+                .iter()
+                .map(|(address, deposit)| {
+                    let campaign = campaings.get(&deposit.campaign).unwrap();
+                    let bank = banks.get(&campaign.marginfi_bank_pk).unwrap();
+
+                    DepositView::new(address, deposit, campaign, bank, time_now)
+                })
+                .collect::<Vec<_>>(),
+        );
+    }
+
     deposits.iter().for_each(|(address, deposit)| {
         let campaign = campaings.get(&deposit.campaign).unwrap();
         let bank = banks.get(&campaign.marginfi_bank_pk).unwrap();
@@ -2103,7 +2809,9 @@ Deposit start {}, end {} ({})
             timestamp_to_string(end_time as i64),
             maturity_string,
         )
-    })
+    });
+
+    Ok(())
 }
 
 #[cfg(feature = "lip")]
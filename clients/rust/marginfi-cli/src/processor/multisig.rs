@@ -0,0 +1,247 @@
+use anyhow::{anyhow, bail, Result};
+use solana_sdk::{pubkey::Pubkey, signer::Signer, transaction::Transaction};
+
+use super::output::{print_json, MultisigMemberView, MultisigView, ProposalView};
+use crate::{
+    config::{Config, OutputFormat},
+    squads::{
+        decompile_instructions, find_proposal_pda, find_transaction_pda, load_multisig,
+        load_proposal, load_vault_transaction, proposal_approve_ix, propose,
+        vault_transaction_execute_ix, ProposalStatus, SquadsConfig, PERMISSION_EXECUTE,
+        PERMISSION_INITIATE, PERMISSION_VOTE,
+    },
+    utils::{load_lookup_tables, send_transaction},
+};
+
+fn squads_config(config: &Config) -> Result<SquadsConfig> {
+    config
+        .squads
+        .ok_or_else(|| anyhow!("Profile has no Squads multisig"))
+}
+
+fn permission_names(permissions: u8) -> Vec<&'static str> {
+    [
+        (PERMISSION_INITIATE, "initiate"),
+        (PERMISSION_VOTE, "vote"),
+        (PERMISSION_EXECUTE, "execute"),
+    ]
+    .iter()
+    .filter(|(mask, _)| permissions & mask != 0)
+    .map(|(_, name)| *name)
+    .collect()
+}
+
+pub fn multisig_status(config: &Config, last: u64) -> Result<()> {
+    let squads = squads_config(config)?;
+    let rpc_client = config.mfi_program.rpc();
+    let multisig = load_multisig(&rpc_client, &squads.multisig)?;
+
+    let first_index = multisig.transaction_index.saturating_sub(last) + 1;
+    let proposals = (first_index..=multisig.transaction_index)
+        .rev()
+        .filter_map(|transaction_index| {
+            let proposal_pk = find_proposal_pda(&squads.multisig, transaction_index).0;
+            // Proposals may have been closed, or never created for a transaction
+            let proposal = load_proposal(&rpc_client, &proposal_pk).ok()?;
+
+            let stale = transaction_index <= multisig.stale_transaction_index
+                && !matches!(
+                    proposal.status,
+                    ProposalStatus::Approved { .. }
+                        | ProposalStatus::Executing
+                        | ProposalStatus::Executed { .. }
+                );
+
+            Some((transaction_index, proposal, stale))
+        })
+        .collect::<Vec<_>>();
+
+    if config.output == OutputFormat::Json {
+        return print_json(&MultisigView {
+            address: squads.multisig.to_string(),
+            vault: squads.vault().to_string(),
+            vault_index: squads.vault_index,
+            threshold: multisig.threshold,
+            time_lock: multisig.time_lock,
+            transaction_index: multisig.transaction_index,
+            stale_transaction_index: multisig.stale_transaction_index,
+            members: multisig
+                .members
+                .iter()
+                .map(|member| MultisigMemberView {
+                    key: member.key.to_string(),
+                    permissions: permission_names(member.permissions),
+                })
+                .collect(),
+            proposals: proposals
+                .iter()
+                .map(|(transaction_index, proposal, stale)| {
+                    let (status, status_timestamp) = proposal.status.name_and_timestamp();
+
+                    ProposalView {
+                        transaction_index: *transaction_index,
+                        status,
+                        status_timestamp,
+                        stale: *stale,
+                        approved: proposal.approved.iter().map(Pubkey::to_string).collect(),
+                        rejected: proposal.rejected.iter().map(Pubkey::to_string).collect(),
+                    }
+                })
+                .collect(),
+        });
+    }
+
+    println!(
+        r#"
+Multisig: {}
+Vault: {} (index: {})
+Threshold: {} of {}
+Time lock: {}s
+Transaction index: {} (stale: {})
+Members:"#,
+        squads.multisig,
+        squads.vault(),
+        squads.vault_index,
+        multisig.threshold,
+        multisig.members.len(),
+        multisig.time_lock,
+        multisig.transaction_index,
+        multisig.stale_transaction_index,
+    );
+
+    for member in multisig.members.iter() {
+        println!(
+            "  {} ({})",
+            member.key,
+            permission_names(member.permissions).join(", ")
+        );
+    }
+
+    println!("Proposals:");
+
+    for (transaction_index, proposal, stale) in proposals.iter() {
+        println!(
+            "  #{}: {:?}{} - {} of {} approvals, {} rejections",
+            transaction_index,
+            proposal.status,
+            if *stale { " (stale)" } else { "" },
+            proposal.approved.len(),
+            multisig.threshold,
+            proposal.rejected.len(),
+        );
+        for approver in proposal.approved.iter() {
+            println!("      approved by {}", approver);
+        }
+    }
+
+    Ok(())
+}
+
+pub fn multisig_approve(config: &Config, transaction_index: u64) -> Result<()> {
+    let squads = squads_config(config)?;
+    let rpc_client = config.mfi_program.rpc();
+
+    let ix = proposal_approve_ix(&squads, transaction_index, &config.fee_payer.pubkey())?;
+
+    let sig = send_transaction(
+        &[ix],
+        &config.fee_payer.pubkey(),
+        &rpc_client,
+        config.compute_budget,
+        config.get_lookup_tables(),
+        &[&config.fee_payer],
+    )?;
+    println!("Proposal #{} approved: {}", transaction_index, sig);
+
+    Ok(())
+}
+
+pub fn multisig_execute(config: &Config, transaction_index: u64) -> Result<()> {
+    let squads = squads_config(config)?;
+    let rpc_client = config.mfi_program.rpc();
+
+    let proposal = load_proposal(
+        &rpc_client,
+        &find_proposal_pda(&squads.multisig, transaction_index).0,
+    )?;
+    if !matches!(proposal.status, ProposalStatus::Approved { .. }) {
+        bail!(
+            "Proposal #{} is not approved: {:?}",
+            transaction_index,
+            proposal.status
+        );
+    }
+
+    let vault_transaction = load_vault_transaction(
+        &rpc_client,
+        &find_transaction_pda(&squads.multisig, transaction_index).0,
+    )?;
+
+    let message = &vault_transaction.message;
+    let message_lookup_tables = load_lookup_tables(
+        &rpc_client,
+        &message
+            .address_table_lookups
+            .iter()
+            .map(|lookup| lookup.account_key)
+            .collect::<Vec<_>>(),
+    )?;
+
+    let ix = vault_transaction_execute_ix(
+        &squads,
+        transaction_index,
+        &config.fee_payer.pubkey(),
+        message,
+        &message_lookup_tables,
+    )?;
+
+    // Every account of the vault transaction is passed to the execution, load them from the
+    // profile lookup tables and from those of the vault transaction
+    let lookup_tables = [config.get_lookup_tables(), &message_lookup_tables].concat();
+
+    let sig = send_transaction(
+        &[ix],
+        &config.fee_payer.pubkey(),
+        &rpc_client,
+        config.compute_budget,
+        &lookup_tables,
+        &[&config.fee_payer],
+    )?;
+    println!("Proposal #{} executed: {}", transaction_index, sig);
+
+    Ok(())
+}
+
+/// Batch the instructions of base58 encoded transactions, as printed by other commands in
+/// multisig mode, into a single proposal.
+pub fn multisig_propose(
+    config: &Config,
+    transactions: Vec<String>,
+    memo: Option<String>,
+) -> Result<()> {
+    let squads = squads_config(config)?;
+    let rpc_client = config.mfi_program.rpc();
+
+    let mut instructions = vec![];
+    for transaction in transactions.iter() {
+        let bytes = bs58::decode(transaction)
+            .into_vec()
+            .map_err(|e| anyhow!("Invalid base58 transaction: {}", e))?;
+        let tx: Transaction = bincode::deserialize(&bytes)
+            .map_err(|e| anyhow!("Invalid serialized transaction: {}", e))?;
+
+        instructions.extend(decompile_instructions(&tx.message));
+    }
+
+    propose(
+        &rpc_client,
+        &squads,
+        &config.fee_payer,
+        &instructions,
+        memo,
+        config.compute_budget,
+        config.get_lookup_tables(),
+    )?;
+
+    Ok(())
+}
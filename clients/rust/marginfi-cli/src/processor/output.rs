@@ -0,0 +1,582 @@
+//! Structured views of on-chain state, printed by read commands with `--output json`.
+//!
+//! Raw values (native amounts, shares) are exact decimal strings, UI amounts and rates are numbers.
+
+use std::{
+    collections::HashMap,
+    ops::Not,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use fixed::types::I80F48;
+use log::warn;
+use marginfi::state::{
+    health::HealthEngine,
+    marginfi_account::{BalanceSide, BankAccountWrapper, MarginfiAccount, RiskRequirementType},
+    marginfi_group::{Bank, InterestRateConfig, MarginfiGroup},
+    price::OraclePriceFeedAdapter,
+};
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::utils::EXP_10_I80F48;
+
+pub fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+fn to_ui(native: I80F48, mint_decimals: u8) -> f64 {
+    (native / EXP_10_I80F48[mint_decimals as usize]).to_num()
+}
+
+/// Native amount with its UI denominated value.
+#[derive(Serialize)]
+pub struct AmountView {
+    pub native: String,
+    pub ui: f64,
+}
+
+impl AmountView {
+    pub fn new(native: I80F48, mint_decimals: u8) -> Self {
+        Self {
+            native: native.to_string(),
+            ui: to_ui(native, mint_decimals),
+        }
+    }
+
+    pub fn from_u64(native: u64, mint_decimals: u8) -> Self {
+        Self::new(I80F48::from_num(native), mint_decimals)
+    }
+}
+
+#[derive(Serialize)]
+pub struct GroupView {
+    pub address: String,
+    pub admin: String,
+    pub flashloan_cpi_programs: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub banks: Option<Vec<BankView>>,
+}
+
+impl GroupView {
+    pub fn new(address: &Pubkey, group: &MarginfiGroup, banks: Option<Vec<BankView>>) -> Self {
+        Self {
+            address: address.to_string(),
+            admin: group.admin.to_string(),
+            flashloan_cpi_programs: group
+                .flashloan_cpi_programs
+                .iter()
+                .filter(|pk| pk.ne(&&Pubkey::default()))
+                .map(Pubkey::to_string)
+                .collect(),
+            banks,
+        }
+    }
+}
+
+/// Rates at the current utilization, as APR.
+#[derive(Serialize)]
+pub struct RatesView {
+    pub utilization_rate: f64,
+    pub lending_apr: f64,
+    pub borrowing_apr: f64,
+    pub group_fee_apr: f64,
+    pub insurance_fee_apr: f64,
+}
+
+impl RatesView {
+    pub fn new(bank: &Bank, total_deposits: I80F48, total_liabilities: I80F48) -> Option<Self> {
+        let utilization_rate = if total_deposits.is_positive() {
+            total_liabilities.checked_div(total_deposits)?
+        } else {
+            I80F48::ZERO
+        };
+        let (lending_apr, borrowing_apr, group_fee_apr, insurance_fee_apr) = bank
+            .config
+            .interest_rate_config
+            .calc_interest_rate(utilization_rate)?;
+
+        Some(Self {
+            utilization_rate: utilization_rate.to_num(),
+            lending_apr: lending_apr.to_num(),
+            borrowing_apr: borrowing_apr.to_num(),
+            group_fee_apr: group_fee_apr.to_num(),
+            insurance_fee_apr: insurance_fee_apr.to_num(),
+        })
+    }
+}
+
+#[derive(Serialize)]
+pub struct InterestRateConfigView {
+    pub optimal_utilization_rate: f64,
+    pub plateau_interest_rate: f64,
+    pub max_interest_rate: f64,
+    pub insurance_fee_fixed_apr: f64,
+    pub insurance_ir_fee: f64,
+    pub protocol_fixed_fee_apr: f64,
+    pub protocol_ir_fee: f64,
+    pub insurance_flashloan_fee: f64,
+    pub protocol_flashloan_fee: f64,
+}
+
+impl InterestRateConfigView {
+    pub fn new(ir_config: &InterestRateConfig) -> Self {
+        Self {
+            optimal_utilization_rate: I80F48::from(ir_config.optimal_utilization_rate).to_num(),
+            plateau_interest_rate: I80F48::from(ir_config.plateau_interest_rate).to_num(),
+            max_interest_rate: I80F48::from(ir_config.max_interest_rate).to_num(),
+            insurance_fee_fixed_apr: I80F48::from(ir_config.insurance_fee_fixed_apr).to_num(),
+            insurance_ir_fee: I80F48::from(ir_config.insurance_ir_fee).to_num(),
+            protocol_fixed_fee_apr: I80F48::from(ir_config.protocol_fixed_fee_apr).to_num(),
+            protocol_ir_fee: I80F48::from(ir_config.protocol_ir_fee).to_num(),
+            insurance_flashloan_fee: I80F48::from(ir_config.insurance_flashloan_fee).to_num(),
+            protocol_flashloan_fee: I80F48::from(ir_config.protocol_flashloan_fee).to_num(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct BankConfigView {
+    pub operational_state: String,
+    pub risk_tier: String,
+    pub borrow_mode: String,
+    pub bad_debt_mode: String,
+    pub asset_weight_init: f64,
+    pub asset_weight_maint: f64,
+    pub liability_weight_init: f64,
+    pub liability_weight_maint: f64,
+    pub deposit_limit: AmountView,
+    pub borrow_limit: AmountView,
+    pub total_asset_value_init_limit: u64,
+    pub total_deposit_value_limit: u64,
+    pub total_borrow_value_limit: u64,
+    pub interest_rate_config: InterestRateConfigView,
+    pub oracle_setup: String,
+    pub oracle_keys: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct OutflowView {
+    pub window: u64,
+    pub limit: AmountView,
+    pub value_limit: u64,
+    pub current: AmountView,
+}
+
+#[derive(Serialize)]
+pub struct InsuranceStakingView {
+    pub share_mint: String,
+    pub protocol_shares: AmountView,
+    pub cooldown_shares: AmountView,
+    pub unstake_cooldown: u64,
+}
+
+#[derive(Serialize)]
+pub struct EmissionsView {
+    pub flags: u64,
+    pub rate: u64,
+    pub mint: String,
+    pub remaining: String,
+}
+
+#[derive(Serialize)]
+pub struct VaultBalancesView {
+    pub liquidity_vault: AmountView,
+    pub fee_vault: AmountView,
+    pub insurance_vault: AmountView,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub emissions_vault: Option<AmountView>,
+}
+
+#[derive(Serialize)]
+pub struct BankView {
+    pub address: String,
+    pub group: String,
+    pub mint: String,
+    pub mint_decimals: u8,
+    pub asset_share_value: f64,
+    pub liability_share_value: f64,
+    pub total_asset_shares: String,
+    pub total_liability_shares: String,
+    pub total_deposits: AmountView,
+    pub total_liabilities: AmountView,
+    pub rates: Option<RatesView>,
+    pub collected_group_fees_outstanding: AmountView,
+    pub collected_insurance_fees_outstanding: AmountView,
+    pub unrecovered_bad_debt: AmountView,
+    pub config: BankConfigView,
+    pub outflow: OutflowView,
+    pub insurance_staking: InsuranceStakingView,
+    pub emissions: EmissionsView,
+    pub last_update: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vault_balances: Option<VaultBalancesView>,
+}
+
+impl BankView {
+    pub fn new(address: &Pubkey, bank: &Bank) -> Result<Self> {
+        let decimals = bank.mint_decimals;
+        let total_deposits = bank.get_asset_amount(bank.total_asset_shares.into())?;
+        let total_liabilities = bank.get_liability_amount(bank.total_liability_shares.into())?;
+
+        Ok(Self {
+            address: address.to_string(),
+            group: bank.group.to_string(),
+            mint: bank.mint.to_string(),
+            mint_decimals: decimals,
+            asset_share_value: I80F48::from(bank.asset_share_value).to_num(),
+            liability_share_value: I80F48::from(bank.liability_share_value).to_num(),
+            total_asset_shares: I80F48::from(bank.total_asset_shares).to_string(),
+            total_liability_shares: I80F48::from(bank.total_liability_shares).to_string(),
+            total_deposits: AmountView::new(total_deposits, decimals),
+            total_liabilities: AmountView::new(total_liabilities, decimals),
+            rates: RatesView::new(bank, total_deposits, total_liabilities),
+            collected_group_fees_outstanding: AmountView::new(
+                bank.collected_group_fees_outstanding.into(),
+                decimals,
+            ),
+            collected_insurance_fees_outstanding: AmountView::new(
+                bank.collected_insurance_fees_outstanding.into(),
+                decimals,
+            ),
+            unrecovered_bad_debt: AmountView::new(bank.unrecovered_bad_debt.into(), decimals),
+            config: BankConfigView {
+                operational_state: format!("{:?}", bank.config.operational_state),
+                risk_tier: format!("{:?}", bank.config.risk_tier),
+                borrow_mode: format!("{:?}", bank.config.borrow_mode),
+                bad_debt_mode: format!("{:?}", bank.config.bad_debt_mode),
+                asset_weight_init: I80F48::from(bank.config.asset_weight_init).to_num(),
+                asset_weight_maint: I80F48::from(bank.config.asset_weight_maint).to_num(),
+                liability_weight_init: I80F48::from(bank.config.liability_weight_init).to_num(),
+                liability_weight_maint: I80F48::from(bank.config.liability_weight_maint).to_num(),
+                deposit_limit: AmountView::from_u64(bank.config.deposit_limit, decimals),
+                borrow_limit: AmountView::from_u64(bank.config.borrow_limit, decimals),
+                total_asset_value_init_limit: bank.config.total_asset_value_init_limit,
+                total_deposit_value_limit: bank.config.total_deposit_value_limit,
+                total_borrow_value_limit: bank.config.total_borrow_value_limit,
+                interest_rate_config: InterestRateConfigView::new(
+                    &bank.config.interest_rate_config,
+                ),
+                oracle_setup: format!("{:?}", bank.config.oracle_setup),
+                oracle_keys: bank
+                    .config
+                    .oracle_keys
+                    .iter()
+                    .filter(|pk| pk.ne(&&Pubkey::default()))
+                    .map(Pubkey::to_string)
+                    .collect(),
+            },
+            outflow: OutflowView {
+                window: bank.outflow_limit_window,
+                limit: AmountView::from_u64(bank.outflow_limit, decimals),
+                value_limit: bank.outflow_value_limit,
+                current: AmountView::new(bank.outflow_window_amount.into(), decimals),
+            },
+            insurance_staking: InsuranceStakingView {
+                share_mint: bank.insurance_share_mint.to_string(),
+                protocol_shares: AmountView::from_u64(bank.insurance_protocol_shares, decimals),
+                cooldown_shares: AmountView::from_u64(bank.insurance_cooldown_shares, decimals),
+                unstake_cooldown: bank.insurance_unstake_cooldown,
+            },
+            emissions: EmissionsView {
+                flags: bank.emissions_flags,
+                rate: bank.emissions_rate,
+                mint: bank.emissions_mint.to_string(),
+                remaining: I80F48::from(bank.emissions_remaining).to_string(),
+            },
+            last_update: bank.last_update,
+            vault_balances: None,
+        })
+    }
+}
+
+#[derive(Serialize)]
+pub struct BalanceView {
+    pub bank: String,
+    pub mint: String,
+    pub side: &'static str,
+    pub asset_shares: String,
+    pub liability_shares: String,
+    /// Deposits are positive, liabilities negative.
+    pub amount: AmountView,
+    pub emissions_outstanding: String,
+}
+
+#[derive(Serialize)]
+pub struct HealthComponentsView {
+    pub assets: f64,
+    pub liabilities: f64,
+    pub health: f64,
+}
+
+#[derive(Serialize)]
+pub struct HealthView {
+    pub initial: HealthComponentsView,
+    pub maintenance: HealthComponentsView,
+}
+
+impl HealthView {
+    fn new(
+        marginfi_account: &MarginfiAccount,
+        banks: &HashMap<Pubkey, Bank>,
+        price_feeds: &HashMap<Pubkey, OraclePriceFeedAdapter>,
+    ) -> Result<Self> {
+        let health_engine = HealthEngine::load(marginfi_account, banks, price_feeds)?;
+        let components = |requirement_type: RiskRequirementType| -> Result<HealthComponentsView> {
+            let (assets, liabilities) =
+                health_engine.get_account_health_components(requirement_type)?;
+
+            Ok(HealthComponentsView {
+                assets: assets.to_num(),
+                liabilities: liabilities.to_num(),
+                health: (assets - liabilities).to_num(),
+            })
+        };
+
+        Ok(Self {
+            initial: components(RiskRequirementType::Initial)?,
+            maintenance: components(RiskRequirementType::Maintenance)?,
+        })
+    }
+}
+
+#[derive(Serialize)]
+pub struct AccountView {
+    pub address: String,
+    pub group: String,
+    pub authority: String,
+    pub default: bool,
+    pub balances: Vec<BalanceView>,
+    /// Missing when an oracle of the account banks can't be loaded.
+    pub health: Option<HealthView>,
+}
+
+impl AccountView {
+    pub fn new(
+        address: Pubkey,
+        marginfi_account: &MarginfiAccount,
+        banks: &HashMap<Pubkey, Bank>,
+        price_feeds: &HashMap<Pubkey, OraclePriceFeedAdapter>,
+        default: bool,
+    ) -> Result<Self> {
+        let current_timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+        let balances = marginfi_account
+            .lending_account
+            .get_active_balances_iter()
+            .map(|balance| {
+                let mut bank = *banks.get(&balance.bank_pk).expect("Bank not found");
+                let (side, amount) = if balance.is_empty(BalanceSide::Assets).not() {
+                    ("asset", bank.get_asset_amount(balance.asset_shares.into())?)
+                } else if balance.is_empty(BalanceSide::Liabilities).not() {
+                    (
+                        "liability",
+                        -bank.get_liability_amount(balance.liability_shares.into())?,
+                    )
+                } else {
+                    ("empty", I80F48::ZERO)
+                };
+
+                let mut balance = *balance;
+                BankAccountWrapper {
+                    bank: &mut bank,
+                    balance: &mut balance,
+                }
+                .claim_emissions(current_timestamp)?;
+
+                Ok(BalanceView {
+                    bank: balance.bank_pk.to_string(),
+                    mint: bank.mint.to_string(),
+                    side,
+                    asset_shares: I80F48::from(balance.asset_shares).to_string(),
+                    liability_shares: I80F48::from(balance.liability_shares).to_string(),
+                    amount: AmountView::new(amount, bank.mint_decimals),
+                    emissions_outstanding: I80F48::from(balance.emissions_outstanding).to_string(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let health = match HealthView::new(marginfi_account, banks, price_feeds) {
+            Ok(health) => Some(health),
+            Err(err) => {
+                warn!("Failed to compute health of {}: {}", address, err);
+                None
+            }
+        };
+
+        Ok(Self {
+            address: address.to_string(),
+            group: marginfi_account.group.to_string(),
+            authority: marginfi_account.authority.to_string(),
+            default,
+            balances,
+            health,
+        })
+    }
+}
+
+/// Health, liquidation prices and max borrows of an account, as printed by `account simulate`.
+#[derive(Serialize)]
+pub struct HealthSummaryView {
+    pub initial: HealthComponentsView,
+    pub maintenance: HealthComponentsView,
+    pub liquidation_prices: Vec<LiquidationPriceView>,
+    pub max_borrows: Vec<MaxBorrowView>,
+}
+
+#[derive(Serialize)]
+pub struct LiquidationPriceView {
+    pub bank: String,
+    pub mint: String,
+    pub side: &'static str,
+    /// Missing when the balance can't be liquidated, or when `error` is set.
+    pub price: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct MaxBorrowView {
+    pub bank: String,
+    pub mint: String,
+    /// Missing when `error` is set.
+    pub amount: Option<AmountView>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct SimulationView {
+    pub address: String,
+    pub current: HealthSummaryView,
+    pub simulated: HealthSummaryView,
+}
+
+#[cfg(feature = "dev")]
+#[derive(Serialize)]
+pub struct OraclePriceView {
+    pub bank: String,
+    pub oracle_setup: String,
+    pub oracle_keys: Vec<String>,
+    pub real_time: f64,
+    pub maint_asset: f64,
+    pub maint_liability: f64,
+    pub init_asset: f64,
+    pub init_liability: f64,
+}
+
+#[derive(Serialize)]
+pub struct MultisigMemberView {
+    pub key: String,
+    pub permissions: Vec<&'static str>,
+}
+
+#[derive(Serialize)]
+pub struct ProposalView {
+    pub transaction_index: u64,
+    pub status: &'static str,
+    /// Time of the last status change, missing while executing.
+    pub status_timestamp: Option<i64>,
+    pub stale: bool,
+    pub approved: Vec<String>,
+    pub rejected: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct MultisigView {
+    pub address: String,
+    pub vault: String,
+    pub vault_index: u8,
+    pub threshold: u16,
+    pub time_lock: u32,
+    pub transaction_index: u64,
+    pub stale_transaction_index: u64,
+    pub members: Vec<MultisigMemberView>,
+    pub proposals: Vec<ProposalView>,
+}
+
+#[derive(Serialize)]
+pub struct ProfileListView {
+    pub current: String,
+    pub profiles: Vec<String>,
+}
+
+#[cfg(feature = "lip")]
+pub use lip::*;
+
+#[cfg(feature = "lip")]
+mod lip {
+    use liquidity_incentive_program::state::{Campaign, Deposit};
+    use marginfi::state::marginfi_group::Bank;
+    use serde::Serialize;
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::AmountView;
+
+    #[derive(Serialize)]
+    pub struct CampaignView {
+        pub address: String,
+        pub bank: String,
+        pub mint: String,
+        pub active: bool,
+        pub total_capacity: AmountView,
+        pub remaining_capacity: AmountView,
+        pub lockup_period: u64,
+        pub max_rewards: AmountView,
+    }
+
+    impl CampaignView {
+        pub fn new(address: &Pubkey, campaign: &Campaign, bank: &Bank) -> Self {
+            Self {
+                address: address.to_string(),
+                bank: campaign.marginfi_bank_pk.to_string(),
+                mint: bank.mint.to_string(),
+                active: campaign.active,
+                total_capacity: AmountView::from_u64(campaign.max_deposits, bank.mint_decimals),
+                remaining_capacity: AmountView::from_u64(
+                    campaign.remaining_capacity,
+                    bank.mint_decimals,
+                ),
+                lockup_period: campaign.lockup_period,
+                max_rewards: AmountView::from_u64(campaign.max_rewards, bank.mint_decimals),
+            }
+        }
+    }
+
+    #[derive(Serialize)]
+    pub struct DepositView {
+        pub address: String,
+        pub campaign: String,
+        pub mint: String,
+        pub owner: String,
+        pub amount: AmountView,
+        pub start_time: i64,
+        pub end_time: i64,
+        pub mature: bool,
+    }
+
+    impl DepositView {
+        pub fn new(
+            address: &Pubkey,
+            deposit: &Deposit,
+            campaign: &Campaign,
+            bank: &Bank,
+            time_now: u64,
+        ) -> Self {
+            let end_time = deposit.start_time as u64 + campaign.lockup_period;
+
+            Self {
+                address: address.to_string(),
+                campaign: deposit.campaign.to_string(),
+                mint: bank.mint.to_string(),
+                owner: deposit.owner.to_string(),
+                amount: AmountView::from_u64(deposit.amount, bank.mint_decimals),
+                start_time: deposit.start_time,
+                end_time: end_time as i64,
+                mature: time_now > end_time,
+            }
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use anyhow::{anyhow, bail, Result};
+use clap::clap_derive::ArgEnum;
+use fixed::types::I80F48;
+use marginfi::state::marginfi_group::{Bank, InterestRateConfig, InterestRateConfigOpt};
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+
+use super::output::{print_json, InterestRateConfigView};
+use crate::config::{Config, OutputFormat};
+
+const CHART_HEIGHT: usize = 16;
+
+#[derive(Clone, Copy, Debug, ArgEnum)]
+pub enum RatesFormat {
+    Table,
+    Chart,
+    Csv,
+}
+
+/// Rates at a utilization rate, as APR.
+#[derive(Serialize)]
+struct RatePoint {
+    utilization_rate: f64,
+    borrowing_apr: f64,
+    lending_apr: f64,
+    protocol_fee_apr: f64,
+    insurance_fee_apr: f64,
+    current: bool,
+}
+
+impl RatePoint {
+    fn new(
+        ir_config: &InterestRateConfig,
+        utilization_rate: I80F48,
+        current: bool,
+    ) -> Result<Self> {
+        let (lending_apr, borrowing_apr, protocol_fee_apr, insurance_fee_apr) = ir_config
+            .calc_interest_rate(utilization_rate)
+            .ok_or_else(|| anyhow!("Bad math at utilization {}", utilization_rate))?;
+
+        Ok(Self {
+            utilization_rate: utilization_rate.to_num(),
+            borrowing_apr: borrowing_apr.to_num(),
+            lending_apr: lending_apr.to_num(),
+            protocol_fee_apr: protocol_fee_apr.to_num(),
+            insurance_fee_apr: insurance_fee_apr.to_num(),
+            current,
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct RatesInspection {
+    bank: String,
+    current_interest_rate_config: InterestRateConfigView,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    proposed_interest_rate_config: Option<InterestRateConfigView>,
+    current_utilization_rate: f64,
+    points: Vec<RatePoint>,
+}
+
+/// Tabulate the rates of a bank across utilization, with the current parameters or the
+/// proposed changes applied.
+pub fn bank_inspect_rates(
+    config: &Config,
+    bank_pk: Pubkey,
+    proposal: InterestRateConfigOpt,
+    steps: u16,
+    format: RatesFormat,
+) -> Result<()> {
+    if steps == 0 {
+        bail!("Steps must be positive");
+    }
+
+    let bank: Bank = config.mfi_program.account(bank_pk)?;
+    let current_ir_config = bank.config.interest_rate_config;
+
+    let is_proposal = proposal != InterestRateConfigOpt::default();
+    let mut ir_config = current_ir_config;
+    ir_config.update(&proposal);
+
+    let optimal_utilization_rate = I80F48::from(ir_config.optimal_utilization_rate);
+    if optimal_utilization_rate <= I80F48::ZERO || optimal_utilization_rate > I80F48::ONE {
+        bail!("Optimal utilization rate must be in (0, 1]");
+    }
+
+    let total_deposits = bank.get_asset_amount(bank.total_asset_shares.into())?;
+    let total_liabilities = bank.get_liability_amount(bank.total_liability_shares.into())?;
+    let current_utilization_rate = if total_deposits.is_positive() {
+        total_liabilities / total_deposits
+    } else {
+        I80F48::ZERO
+    };
+
+    let mut points = (0..=steps)
+        .map(|step| {
+            RatePoint::new(
+                &ir_config,
+                I80F48::from_num(step) / I80F48::from_num(steps),
+                false,
+            )
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let current_point = RatePoint::new(&ir_config, current_utilization_rate, true)?;
+    let current_index = points
+        .iter()
+        .position(|point| point.utilization_rate > current_point.utilization_rate)
+        .unwrap_or(points.len());
+    points.insert(current_index, current_point);
+
+    if config.output == OutputFormat::Json {
+        return print_json(&RatesInspection {
+            bank: bank_pk.to_string(),
+            current_interest_rate_config: InterestRateConfigView::new(&current_ir_config),
+            proposed_interest_rate_config: is_proposal
+                .then(|| InterestRateConfigView::new(&ir_config)),
+            current_utilization_rate: current_utilization_rate.to_num(),
+            points,
+        });
+    }
+
+    match format {
+        RatesFormat::Csv => print_csv(&points),
+        RatesFormat::Table => {
+            print_parameters(&current_ir_config, is_proposal.then_some(&ir_config));
+            print_table(&points);
+        }
+        RatesFormat::Chart => {
+            print_parameters(&current_ir_config, is_proposal.then_some(&ir_config));
+            print_chart(&points);
+        }
+    }
+
+    Ok(())
+}
+
+fn print_parameters(current: &InterestRateConfig, proposed: Option<&InterestRateConfig>) {
+    let parameters: [(&str, fn(&InterestRateConfig) -> I80F48); 7] = [
+        ("Optimal utilization rate", |c| {
+            c.optimal_utilization_rate.into()
+        }),
+        ("Plateau interest rate", |c| c.plateau_interest_rate.into()),
+        ("Max interest rate", |c| c.max_interest_rate.into()),
+        ("Insurance IR fee", |c| c.insurance_ir_fee.into()),
+        ("Insurance fixed fee APR", |c| {
+            c.insurance_fee_fixed_apr.into()
+        }),
+        ("Protocol IR fee", |c| c.protocol_ir_fee.into()),
+        ("Protocol fixed fee APR", |c| {
+            c.protocol_fixed_fee_apr.into()
+        }),
+    ];
+
+    for (label, get) in parameters {
+        match proposed {
+            Some(proposed) if get(proposed) != get(current) => {
+                println!("{}: {:.4} -> {:.4}", label, get(current), get(proposed))
+            }
+            _ => println!("{}: {:.4}", label, get(current)),
+        }
+    }
+    println!();
+}
+
+fn print_table(points: &[RatePoint]) {
+    println!(
+        "{:>11} {:>11} {:>11} {:>13} {:>13}",
+        "Utilization", "Borrow APR", "Lending APR", "Protocol fee", "Insurance fee"
+    );
+
+    for point in points {
+        println!(
+            "{:>10.2}% {:>10.2}% {:>10.2}% {:>12.2}% {:>12.2}%{}",
+            point.utilization_rate * 100.,
+            point.borrowing_apr * 100.,
+            point.lending_apr * 100.,
+            point.protocol_fee_apr * 100.,
+            point.insurance_fee_apr * 100.,
+            if point.current { "  <- current" } else { "" }
+        );
+    }
+}
+
+fn print_csv(points: &[RatePoint]) {
+    println!(
+        "utilization_rate,borrowing_apr,lending_apr,protocol_fee_apr,insurance_fee_apr,current"
+    );
+
+    for point in points {
+        println!(
+            "{},{},{},{},{},{}",
+            point.utilization_rate,
+            point.borrowing_apr,
+            point.lending_apr,
+            point.protocol_fee_apr,
+            point.insurance_fee_apr,
+            point.current
+        );
+    }
+}
+
+/// Plot the borrowing (`B`) and lending (`L`) APR against utilization, `|` marks the current utilization.
+fn print_chart(points: &[RatePoint]) {
+    let max_apr = points
+        .iter()
+        .map(|point| point.borrowing_apr.max(point.lending_apr))
+        .fold(0., f64::max);
+    let scale = if max_apr > 0. {
+        (CHART_HEIGHT - 1) as f64 / max_apr
+    } else {
+        0.
+    };
+    let level = |apr: f64| (apr * scale).round() as usize;
+
+    for row in (0..CHART_HEIGHT).rev() {
+        let axis_label = if row == CHART_HEIGHT - 1 {
+            format!("{:.2}%", max_apr * 100.)
+        } else if row == 0 {
+            "0%".to_string()
+        } else {
+            String::new()
+        };
+
+        let line = points
+            .iter()
+            .map(|point| {
+                match (
+                    level(point.borrowing_apr) == row,
+                    level(point.lending_apr) == row,
+                ) {
+                    (true, true) => '*',
+                    (true, false) => 'B',
+                    (false, true) => 'L',
+                    (false, false) if point.current => '|',
+                    (false, false) => ' ',
+                }
+            })
+            .flat_map(|c| [c, ' '])
+            .collect::<String>();
+
+        println!("{:>8} |{}", axis_label, line.trim_end());
+    }
+
+    println!("{:>8} +{}", "", "-".repeat(points.len() * 2));
+    println!(
+        "{:>8}  0%{:>width$}",
+        "",
+        "100% utilization",
+        width = points.len() * 2 - 2
+    );
+    println!("B: borrow APR, L: lending APR, *: both, |: current utilization");
+}
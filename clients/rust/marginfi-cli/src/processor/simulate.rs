@@ -0,0 +1,452 @@
+use std::{cmp::min, collections::HashMap, ops::Not, str::FromStr};
+
+use anyhow::{anyhow, bail, Result};
+use fixed::types::I80F48;
+use marginfi::{
+    constants::ZERO_AMOUNT_THRESHOLD,
+    state::{
+        borrow_credential::BorrowCredential,
+        health::HealthEngine,
+        marginfi_account::{Balance, BalanceSide, MarginfiAccount, RiskRequirementType},
+        marginfi_group::{Bank, BankOperationalState},
+        price::OraclePriceFeedAdapter,
+    },
+    utils::NumTraitsWithTolerance,
+};
+use marginfi_v2_sdk::pda::find_borrow_credential_pda;
+use solana_sdk::pubkey::Pubkey;
+
+use super::{
+    load_all_banks, load_price_feeds,
+    output::{
+        print_json, AmountView, HealthComponentsView, HealthSummaryView, LiquidationPriceView,
+        MaxBorrowView, SimulationView,
+    },
+};
+use crate::{
+    config::{Config, OutputFormat},
+    profile::Profile,
+    utils::EXP_10_I80F48,
+};
+
+/// Hypothetical `<BANK>=<UI_AMOUNT>` action.
+#[derive(Clone, Debug)]
+pub struct BankAmountArg {
+    pub bank: Pubkey,
+    pub ui_amount: f64,
+}
+
+impl FromStr for BankAmountArg {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (bank, ui_amount) = split_key_value(s)?;
+
+        Ok(Self { bank, ui_amount })
+    }
+}
+
+/// Hypothetical `<BANK_OR_MINT>=<PERCENT>` price move, e.g. `-20` for a 20% drop.
+#[derive(Clone, Debug)]
+pub struct PriceShockArg {
+    pub target: Pubkey,
+    pub change_pct: f64,
+}
+
+impl FromStr for PriceShockArg {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (target, change_pct) = split_key_value(s)?;
+
+        if change_pct <= -100. {
+            bail!("Price change must be above -100%");
+        }
+
+        Ok(Self { target, change_pct })
+    }
+}
+
+fn split_key_value(s: &str) -> Result<(Pubkey, f64)> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Expected <ADDRESS>=<VALUE>, got {}", s))?;
+
+    Ok((Pubkey::from_str(key)?, value.parse()?))
+}
+
+#[derive(Debug, Default)]
+pub struct SimulationScenario {
+    pub deposits: Vec<BankAmountArg>,
+    pub repays: Vec<BankAmountArg>,
+    pub withdrawals: Vec<BankAmountArg>,
+    pub borrows: Vec<BankAmountArg>,
+    pub price_shocks: Vec<PriceShockArg>,
+}
+
+#[derive(Clone, Copy)]
+enum SimulatedAction {
+    Deposit,
+    Repay,
+    Withdraw,
+    Borrow,
+}
+
+/// Print the health of a marginfi account before and after applying hypothetical actions and price moves.
+///
+/// Actions are applied in order: deposits, repays, withdrawals, borrows.
+pub fn marginfi_account_simulate(
+    profile: Profile,
+    config: &Config,
+    marginfi_account_pk: Option<Pubkey>,
+    scenario: SimulationScenario,
+) -> Result<()> {
+    let marginfi_account_pk =
+        marginfi_account_pk.unwrap_or_else(|| profile.marginfi_account.unwrap());
+
+    let marginfi_account = config
+        .mfi_program
+        .account::<MarginfiAccount>(marginfi_account_pk)?;
+
+    let banks = HashMap::from_iter(load_all_banks(config, Some(marginfi_account.group))?);
+    let price_feeds = load_price_feeds(config, &banks)?;
+    let borrow_credentials = load_borrow_credentials(config, marginfi_account_pk, &banks);
+
+    let current = health_summary(&marginfi_account, &banks, &price_feeds, &borrow_credentials)?;
+    if config.output == OutputFormat::Text {
+        println!("Address: {marginfi_account_pk}");
+        println!("Current:");
+        print_health_summary(&current);
+    }
+
+    let mut simulated_account = marginfi_account;
+    let mut simulated_banks = banks.clone();
+
+    for (actions, action_type) in [
+        (&scenario.deposits, SimulatedAction::Deposit),
+        (&scenario.repays, SimulatedAction::Repay),
+        (&scenario.withdrawals, SimulatedAction::Withdraw),
+        (&scenario.borrows, SimulatedAction::Borrow),
+    ] {
+        for action in actions {
+            apply_action(
+                &mut simulated_account,
+                &mut simulated_banks,
+                &price_feeds,
+                &borrow_credentials,
+                action,
+                action_type,
+            )?;
+        }
+    }
+
+    let mut price_factors: HashMap<Pubkey, f64> = HashMap::new();
+    for shock in &scenario.price_shocks {
+        let oracles = banks
+            .iter()
+            .filter(|(bank_pk, bank)| **bank_pk == shock.target || bank.mint == shock.target)
+            .map(|(_, bank)| bank.config.oracle_keys[0])
+            .collect::<Vec<_>>();
+
+        if oracles.is_empty() {
+            bail!("No bank or mint {} in group", shock.target);
+        }
+
+        for oracle in oracles {
+            price_factors.insert(oracle, 1. + shock.change_pct / 100.);
+        }
+    }
+
+    let simulated_price_feeds = price_feeds
+        .iter()
+        .map(
+            |(oracle_pk, price_feed)| match price_factors.get(oracle_pk) {
+                Some(factor) => (*oracle_pk, price_feed.with_price_factor(*factor)),
+                None => (*oracle_pk, price_feed.clone()),
+            },
+        )
+        .collect::<HashMap<_, _>>();
+
+    let simulated = health_summary(
+        &simulated_account,
+        &simulated_banks,
+        &simulated_price_feeds,
+        &borrow_credentials,
+    )?;
+
+    if config.output == OutputFormat::Json {
+        return print_json(&SimulationView {
+            address: marginfi_account_pk.to_string(),
+            current,
+            simulated,
+        });
+    }
+
+    println!("Simulated:");
+    print_health_summary(&simulated);
+
+    Ok(())
+}
+
+/// Borrow credentials of the account in the permissioned banks of the group, keyed by bank.
+fn load_borrow_credentials(
+    config: &Config,
+    marginfi_account_pk: Pubkey,
+    banks: &HashMap<Pubkey, Bank>,
+) -> HashMap<Pubkey, BorrowCredential> {
+    banks
+        .iter()
+        .filter(|(_, bank)| bank.config.is_borrow_permissioned())
+        .filter_map(|(bank_pk, _)| {
+            let borrow_credential_pk =
+                find_borrow_credential_pda(bank_pk, &marginfi_account_pk, &config.program_id).0;
+
+            config
+                .mfi_program
+                .account::<BorrowCredential>(borrow_credential_pk)
+                .ok()
+                .map(|borrow_credential| (*bank_pk, borrow_credential))
+        })
+        .collect()
+}
+
+/// Apply `action` to the account and bank the way the program would, rejecting it where the program would.
+///
+/// Borrows are checked against [`HealthEngine::borrow_capacity`], covering the isolated risk tier rules,
+/// the bank borrow and borrow value limits and the borrow credential of permissioned banks.
+fn apply_action(
+    marginfi_account: &mut MarginfiAccount,
+    banks: &mut HashMap<Pubkey, Bank>,
+    price_feeds: &HashMap<Pubkey, OraclePriceFeedAdapter>,
+    borrow_credentials: &HashMap<Pubkey, BorrowCredential>,
+    action: &BankAmountArg,
+    action_type: SimulatedAction,
+) -> Result<()> {
+    let borrow_capacity = match action_type {
+        SimulatedAction::Borrow => {
+            let borrow_credential = borrow_credentials.get(&action.bank);
+
+            if banks
+                .get(&action.bank)
+                .map_or(false, |bank| bank.config.is_borrow_permissioned())
+                && borrow_credential.is_none()
+            {
+                bail!(
+                    "Account has no borrow credential for permissioned bank {}",
+                    action.bank
+                );
+            }
+
+            Some(
+                HealthEngine::load(marginfi_account, banks, price_feeds)?
+                    .borrow_capacity(&action.bank, borrow_credential)?,
+            )
+        }
+        _ => None,
+    };
+
+    let bank = banks
+        .get_mut(&action.bank)
+        .ok_or_else(|| anyhow!("Bank {} not found in group", action.bank))?;
+    let amount = I80F48::from_num(action.ui_amount) * EXP_10_I80F48[bank.mint_decimals as usize];
+
+    let lending_account = &mut marginfi_account.lending_account;
+    let balance_index = match lending_account
+        .balances
+        .iter()
+        .position(|balance| balance.active && balance.bank_pk == action.bank)
+    {
+        Some(balance_index) => balance_index,
+        None => {
+            let empty_index = lending_account
+                .get_first_empty_balance()
+                .ok_or_else(|| anyhow!("No free balance slots left in the account"))?;
+
+            lending_account.balances[empty_index] = Balance {
+                active: true,
+                bank_pk: action.bank,
+                ..Balance::empty_deactivated()
+            };
+
+            empty_index
+        }
+    };
+    let balance = &mut lending_account.balances[balance_index];
+
+    match action_type {
+        SimulatedAction::Deposit | SimulatedAction::Repay => {
+            let current_liability_amount =
+                bank.get_liability_amount(balance.liability_shares.into())?;
+            let repaid_amount = min(current_liability_amount, amount);
+            let deposited_amount = amount - repaid_amount;
+
+            if matches!(action_type, SimulatedAction::Repay)
+                && deposited_amount.is_positive_with_tolerance(ZERO_AMOUNT_THRESHOLD)
+            {
+                bail!("Repay exceeds the liability in bank {}", action.bank);
+            }
+
+            let liability_shares = bank.get_liability_shares(repaid_amount)?;
+            balance.change_liability_shares(-liability_shares)?;
+            bank.change_liability_shares(-liability_shares, true)?;
+
+            let asset_shares = bank.get_asset_shares(deposited_amount)?;
+            balance.change_asset_shares(asset_shares)?;
+            bank.change_asset_shares(asset_shares, false)?;
+        }
+        SimulatedAction::Withdraw | SimulatedAction::Borrow => {
+            let current_asset_amount = bank.get_asset_amount(balance.asset_shares.into())?;
+            let withdrawn_amount = min(current_asset_amount, amount);
+            let borrowed_amount = amount - withdrawn_amount;
+
+            if matches!(action_type, SimulatedAction::Withdraw)
+                && borrowed_amount.is_positive_with_tolerance(ZERO_AMOUNT_THRESHOLD)
+            {
+                bail!("Withdrawal exceeds the deposit in bank {}", action.bank);
+            }
+
+            if let Some(borrow_capacity) = borrow_capacity {
+                if borrowed_amount > borrow_capacity {
+                    bail!(
+                        "Borrow exceeds the borrow limits of bank {} (max {:.3})",
+                        action.bank,
+                        borrow_capacity / EXP_10_I80F48[bank.mint_decimals as usize]
+                    );
+                }
+            }
+
+            let asset_shares = bank.get_asset_shares(withdrawn_amount)?;
+            balance.change_asset_shares(-asset_shares)?;
+            bank.change_asset_shares(-asset_shares, false)?;
+
+            let liability_shares = bank.get_liability_shares(borrowed_amount)?;
+            balance.change_liability_shares(liability_shares)?;
+            bank.change_liability_shares(liability_shares, false)?;
+
+            bank.check_utilization_ratio()?;
+        }
+    }
+
+    if balance.is_empty(BalanceSide::Assets) && balance.is_empty(BalanceSide::Liabilities) {
+        *balance = Balance::empty_deactivated();
+    }
+
+    Ok(())
+}
+
+fn health_summary(
+    marginfi_account: &MarginfiAccount,
+    banks: &HashMap<Pubkey, Bank>,
+    price_feeds: &HashMap<Pubkey, OraclePriceFeedAdapter>,
+    borrow_credentials: &HashMap<Pubkey, BorrowCredential>,
+) -> Result<HealthSummaryView> {
+    let health_engine = HealthEngine::load(marginfi_account, banks, price_feeds)?;
+
+    let components = |requirement_type: RiskRequirementType| -> Result<HealthComponentsView> {
+        let (assets, liabilities) =
+            health_engine.get_account_health_components(requirement_type)?;
+
+        Ok(HealthComponentsView {
+            assets: assets.to_num(),
+            liabilities: liabilities.to_num(),
+            health: (assets - liabilities).to_num(),
+        })
+    };
+
+    let liquidation_prices = marginfi_account
+        .lending_account
+        .balances
+        .iter()
+        .filter(|balance| balance.active)
+        .map(|balance| {
+            let bank = banks.get(&balance.bank_pk).unwrap();
+            let (price, error) = match health_engine.liquidation_price(&balance.bank_pk) {
+                Ok(price) => (price.map(|price| price.to_num::<f64>()), None),
+                Err(err) => (None, Some(err.to_string())),
+            };
+
+            LiquidationPriceView {
+                bank: balance.bank_pk.to_string(),
+                mint: bank.mint.to_string(),
+                side: if balance.is_empty(BalanceSide::Assets).not() {
+                    "asset"
+                } else {
+                    "liability"
+                },
+                price,
+                error,
+            }
+        })
+        .collect();
+
+    let mut operational_banks = banks
+        .iter()
+        .filter(|(_, bank)| bank.config.operational_state == BankOperationalState::Operational)
+        .collect::<Vec<_>>();
+    operational_banks.sort_by_key(|(bank_pk, _)| **bank_pk);
+
+    let max_borrows = operational_banks
+        .into_iter()
+        .map(|(bank_pk, bank)| {
+            let (amount, error) =
+                match health_engine.max_borrow(bank_pk, borrow_credentials.get(bank_pk)) {
+                    Ok(amount) => (Some(AmountView::new(amount, bank.mint_decimals)), None),
+                    Err(err) => (None, Some(err.to_string())),
+                };
+
+            MaxBorrowView {
+                bank: bank_pk.to_string(),
+                mint: bank.mint.to_string(),
+                amount,
+                error,
+            }
+        })
+        .collect();
+
+    Ok(HealthSummaryView {
+        initial: components(RiskRequirementType::Initial)?,
+        maintenance: components(RiskRequirementType::Maintenance)?,
+        liquidation_prices,
+        max_borrows,
+    })
+}
+
+fn print_health_summary(summary: &HealthSummaryView) {
+    for (label, components) in [
+        ("Initial", &summary.initial),
+        ("Maintenance", &summary.maintenance),
+    ] {
+        println!(
+            "\t{} health: {:.2} (assets: {:.2}, liabilities: {:.2})",
+            label, components.health, components.assets, components.liabilities
+        );
+    }
+
+    println!("\tLiquidation prices:");
+    for liquidation_price in summary.liquidation_prices.iter() {
+        let price = match (&liquidation_price.price, &liquidation_price.error) {
+            (_, Some(err)) => format!("unavailable ({})", err),
+            (Some(price), None) => format!("{:.6}", price),
+            (None, None) => "none".to_string(),
+        };
+
+        println!(
+            "\t\tBank: {} (mint: {}, {}): {}",
+            liquidation_price.bank, liquidation_price.mint, liquidation_price.side, price
+        );
+    }
+
+    println!("\tMax borrow:");
+    for max_borrow in summary.max_borrows.iter() {
+        let amount = match (&max_borrow.amount, &max_borrow.error) {
+            (_, Some(err)) => format!("unavailable ({})", err),
+            (Some(amount), None) => format!("{:.3}", amount.ui),
+            (None, None) => "none".to_string(),
+        };
+
+        println!(
+            "\t\tBank: {} (mint: {}): {}",
+            max_borrow.bank, max_borrow.mint, amount
+        );
+    }
+}
@@ -1,7 +1,7 @@
-use crate::config::CliSigner;
+use crate::{config::CliSigner, signer::load_signer, squads::SquadsConfig};
 
 use {
-    crate::config::{Config, GlobalOptions},
+    crate::config::{ComputeBudgetOptions, Config, GlobalOptions, PriorityFee},
     anchor_client::{Client, Cluster},
     anyhow::{anyhow, bail, Result},
     dirs::home_dir,
@@ -10,9 +10,9 @@ use {
         commitment_config::{CommitmentConfig, CommitmentLevel},
         pubkey,
         pubkey::Pubkey,
-        signature::{read_keypair_file, Keypair},
+        signature::Keypair,
     },
-    std::{fs, path::PathBuf},
+    std::{cell::OnceCell, fs, path::PathBuf, rc::Rc},
 };
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -26,6 +26,19 @@ pub struct Profile {
     pub commitment: Option<CommitmentLevel>,
     pub marginfi_group: Option<Pubkey>,
     pub marginfi_account: Option<Pubkey>,
+    /// Squads multisig admin transactions are proposed to, `keypair_path` must be a member.
+    #[serde(default)]
+    pub squads_multisig: Option<Pubkey>,
+    #[serde(default)]
+    pub squads_vault_index: Option<u8>,
+    #[serde(default)]
+    pub compute_unit_price: Option<PriorityFee>,
+    /// Compute unit limit of transactions, simulated when unset.
+    #[serde(default)]
+    pub compute_unit_limit: Option<u32>,
+    /// Lookup tables of transactions, those of the group admin are used when empty.
+    #[serde(default)]
+    pub lookup_tables: Vec<Pubkey>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -45,6 +58,11 @@ impl Profile {
         commitment: Option<CommitmentLevel>,
         marginfi_group: Option<Pubkey>,
         marginfi_account: Option<Pubkey>,
+        squads_multisig: Option<Pubkey>,
+        squads_vault_index: Option<u8>,
+        compute_unit_price: Option<PriorityFee>,
+        compute_unit_limit: Option<u32>,
+        lookup_tables: Vec<Pubkey>,
     ) -> Self {
         if keypair_path.is_none() && multisig.is_none() {
             panic!("Either keypair_path or multisig must be set");
@@ -54,6 +72,10 @@ impl Profile {
             panic!("Only one of keypair_path or multisig can be set");
         }
 
+        if squads_multisig.is_some() && keypair_path.is_none() {
+            panic!("keypair_path of a member must be set with squads_multisig");
+        }
+
         Profile {
             name,
             cluster,
@@ -64,23 +86,46 @@ impl Profile {
             commitment,
             marginfi_group,
             marginfi_account,
+            squads_multisig,
+            squads_vault_index,
+            compute_unit_price,
+            compute_unit_limit,
+            lookup_tables,
         }
     }
 
     pub fn get_config(&self, global_options: Option<&GlobalOptions>) -> Result<Config> {
-        let fee_payer = if let Some(keypair_path) = self.keypair_path.clone() {
-            read_keypair_file(&*shellexpand::tilde(&keypair_path))
-                .expect("Example requires a keypair file")
+        let fee_payer = if let Some(keypair_path) = &self.keypair_path {
+            load_signer(keypair_path)?
         } else {
-            Keypair::new()
+            CliSigner::Keypair(Keypair::new())
         };
 
-        let multisig = self.multisig;
+        let squads = self.squads_multisig.map(|multisig| SquadsConfig {
+            multisig,
+            vault_index: self.squads_vault_index.unwrap_or_default(),
+        });
+        // The Squads vault is the authority, proposals are created by the keypair
+        let multisig = match squads {
+            Some(squads) => Some(squads.vault()),
+            None => self.multisig,
+        };
 
         let dry_run = match global_options {
             Some(options) => options.dry_run,
             None => false,
         };
+        let output = global_options
+            .map(|options| options.output)
+            .unwrap_or_default();
+        let compute_budget = ComputeBudgetOptions {
+            compute_unit_price: global_options
+                .and_then(|options| options.compute_unit_price)
+                .or(self.compute_unit_price),
+            compute_unit_limit: global_options
+                .and_then(|options| options.compute_unit_limit)
+                .or(self.compute_unit_limit),
+        };
         let cluster = self.cluster.clone();
         let program_id = match self.program_id {
         Some(pid) => pid,
@@ -98,7 +143,7 @@ impl Profile {
         };
         let client = Client::new_with_options(
             Cluster::Custom(self.rpc_url.clone(), "https://dontcare.com:123".to_string()),
-            CliSigner::Keypair(Keypair::new()),
+            Rc::new(Keypair::new()),
             commitment,
         );
         let program = client.program(program_id).unwrap();
@@ -117,9 +162,15 @@ impl Profile {
             cluster,
             fee_payer,
             multisig,
+            squads,
             program_id,
             commitment,
             dry_run,
+            output,
+            compute_budget,
+            lookup_tables: self.lookup_tables.clone(),
+            marginfi_group: self.marginfi_group,
+            lookup_table_accounts: OnceCell::new(),
             client,
             mfi_program: program,
             lip_program,
@@ -137,11 +188,20 @@ impl Profile {
         commitment: Option<CommitmentLevel>,
         group: Option<Pubkey>,
         account: Option<Pubkey>,
+        squads_multisig: Option<Pubkey>,
+        squads_vault_index: Option<u8>,
+        compute_unit_price: Option<PriorityFee>,
+        compute_unit_limit: Option<u32>,
+        lookup_tables: Vec<Pubkey>,
     ) -> Result<()> {
         if keypair_path.is_some() && multisig.is_some() {
             panic!("Only one of keypair_path or multisig can be set");
         }
 
+        if squads_multisig.is_some() && multisig.is_some() {
+            panic!("Only one of multisig or squads_multisig can be set");
+        }
+
         if let Some(cluster) = cluster {
             self.cluster = cluster;
         }
@@ -154,6 +214,31 @@ impl Profile {
         if let Some(multisig) = multisig {
             self.multisig = Some(multisig);
             self.keypair_path = None;
+            self.squads_multisig = None;
+        }
+
+        if let Some(squads_multisig) = squads_multisig {
+            if self.keypair_path.is_none() {
+                bail!("keypair_path of a member must be set with squads_multisig");
+            }
+
+            self.squads_multisig = Some(squads_multisig);
+        }
+
+        if let Some(squads_vault_index) = squads_vault_index {
+            self.squads_vault_index = Some(squads_vault_index);
+        }
+
+        if let Some(compute_unit_price) = compute_unit_price {
+            self.compute_unit_price = Some(compute_unit_price);
+        }
+
+        if let Some(compute_unit_limit) = compute_unit_limit {
+            self.compute_unit_limit = Some(compute_unit_limit);
+        }
+
+        if !lookup_tables.is_empty() {
+            self.lookup_tables = lookup_tables;
         }
 
         if let Some(rpc_url) = rpc_url {
@@ -271,6 +356,10 @@ Profile:
     Authority: {}
     Keypair: {}
     Multisig: {}
+    Squads Multisig: {}
+    Compute Unit Price: {}
+    Compute Unit Limit: {}
+    Lookup Tables: {}
         "#,
             self.name,
             config.program_id,
@@ -290,6 +379,28 @@ Profile:
             self.multisig
                 .map(|x| x.to_string())
                 .unwrap_or_else(|| "None".to_owned()),
+            self.squads_multisig
+                .map(|x| format!(
+                    "{} (vault index: {})",
+                    x,
+                    self.squads_vault_index.unwrap_or_default()
+                ))
+                .unwrap_or_else(|| "None".to_owned()),
+            self.compute_unit_price
+                .map(|x| x.to_string())
+                .unwrap_or_else(|| "None".to_owned()),
+            self.compute_unit_limit
+                .map(|x| x.to_string())
+                .unwrap_or_else(|| "simulated".to_owned()),
+            if self.lookup_tables.is_empty() {
+                "group admin tables".to_owned()
+            } else {
+                self.lookup_tables
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            },
         )?;
 
         Ok(())
@@ -0,0 +1,160 @@
+//! Signers that keep keys off disk: hardware wallets and external signer processes.
+//!
+//! The profile `keypair_path` selects the signer:
+//! - `usb://ledger?key=0`: Ledger hardware wallet, at an optional derivation path
+//! - `unix:///run/mfi-signer.sock`: external signer listening on a unix socket
+//! - anything else: keypair file
+//!
+//! External signers serve one newline delimited JSON request per connection:
+//! - `{"method":"get_pubkey"}` -> `{"pubkey":"<base58>"}`
+//! - `{"method":"sign_message","message":"<base58>"}` -> `{"signature":"<base58>"}`
+//!
+//! and reply `{"error":"<reason>"}` on failure, e.g. when the operator rejects the request.
+
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use solana_clap_v3_utils::keypair::{parse_signer_source, SignerSourceKind};
+use solana_remote_wallet::{
+    remote_keypair::generate_remote_keypair, remote_wallet::maybe_wallet_manager,
+};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Signature},
+    signer::{Signer, SignerError},
+};
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::config::CliSigner;
+
+const SOCKET_SCHEME: &str = "unix://";
+
+/// Load the signer of a profile `keypair_path`.
+pub fn load_signer(path: &str) -> Result<CliSigner> {
+    if let Some(socket_path) = path.strip_prefix(SOCKET_SCHEME) {
+        return Ok(CliSigner::Socket(SocketSigner::connect(socket_path)?));
+    }
+
+    let path = shellexpand::tilde(path);
+    let source = parse_signer_source(&*path)?;
+
+    match source.kind {
+        SignerSourceKind::Filepath(path) => Ok(CliSigner::Keypair(
+            read_keypair_file(&path)
+                .map_err(|e| anyhow!("Failed to read keypair {}: {}", path, e))?,
+        )),
+        SignerSourceKind::Usb(locator) => {
+            let wallet_manager =
+                maybe_wallet_manager()?.ok_or_else(|| anyhow!("No hardware wallet found"))?;
+            let keypair = generate_remote_keypair(
+                locator,
+                source.derivation_path.unwrap_or_default(),
+                &wallet_manager,
+                false,
+                "keypair",
+            )?;
+
+            Ok(CliSigner::RemoteWallet(keypair))
+        }
+        _ => bail!(
+            "Unsupported signer {}, use a keypair file, usb:// or unix:// path",
+            path
+        ),
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+enum SocketRequest {
+    GetPubkey,
+    SignMessage { message: String },
+}
+
+#[derive(Deserialize)]
+struct SocketResponse {
+    pubkey: Option<String>,
+    signature: Option<String>,
+    error: Option<String>,
+}
+
+/// Signer delegating to an external process over a unix socket.
+#[derive(Debug)]
+pub struct SocketSigner {
+    path: PathBuf,
+    pubkey: Pubkey,
+}
+
+impl SocketSigner {
+    pub fn connect(path: &str) -> Result<Self> {
+        let path = PathBuf::from(path);
+        let response = request(&path, &SocketRequest::GetPubkey)
+            .map_err(|e| anyhow!("Failed to reach signer at {}: {}", path.display(), e))?;
+        let pubkey = response
+            .pubkey
+            .ok_or_else(|| anyhow!("Signer at {} returned no pubkey", path.display()))?;
+
+        Ok(Self {
+            pubkey: Pubkey::from_str(&pubkey)?,
+            path,
+        })
+    }
+}
+
+fn request(path: &Path, request: &SocketRequest) -> Result<SocketResponse, SignerError> {
+    let connection_error = |e: std::io::Error| SignerError::Connection(e.to_string());
+
+    let mut stream = UnixStream::connect(path).map_err(connection_error)?;
+    let mut payload =
+        serde_json::to_vec(request).map_err(|e| SignerError::InvalidInput(e.to_string()))?;
+    payload.push(b'\n');
+    stream.write_all(&payload).map_err(connection_error)?;
+
+    let mut line = String::new();
+    BufReader::new(stream)
+        .read_line(&mut line)
+        .map_err(connection_error)?;
+
+    let response: SocketResponse =
+        serde_json::from_str(&line).map_err(|e| SignerError::Protocol(e.to_string()))?;
+
+    match response.error {
+        Some(error) => Err(SignerError::Custom(error)),
+        None => Ok(response),
+    }
+}
+
+impl Signer for SocketSigner {
+    fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+        Ok(self.pubkey)
+    }
+
+    fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        let response = request(
+            &self.path,
+            &SocketRequest::SignMessage {
+                message: bs58::encode(message).into_string(),
+            },
+        )?;
+
+        let signature = response
+            .signature
+            .ok_or_else(|| SignerError::Protocol("no signature returned".to_string()))?;
+        let signature =
+            Signature::from_str(&signature).map_err(|e| SignerError::Protocol(e.to_string()))?;
+
+        // Don't trust the external signer to sign with the advertised key
+        if !signature.verify(self.pubkey.as_ref(), message) {
+            return Err(SignerError::KeypairPubkeyMismatch);
+        }
+
+        Ok(signature)
+    }
+
+    fn is_interactive(&self) -> bool {
+        true
+    }
+}
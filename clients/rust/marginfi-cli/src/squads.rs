@@ -0,0 +1,957 @@
+//! Minimal client for the Squads v4 multisig program: vault transactions, proposals, votes and execution.
+
+use crate::{config::ComputeBudgetOptions, utils::send_transaction};
+use anchor_client::anchor_lang::{AnchorDeserialize, AnchorSerialize};
+use anyhow::{anyhow, bail, Result};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{
+    address_lookup_table_account::AddressLookupTableAccount,
+    compute_budget,
+    hash::{hash, Hash},
+    instruction::{AccountMeta, Instruction},
+    message::{v0, Message},
+    pubkey,
+    pubkey::Pubkey,
+    signature::Signature,
+    signer::Signer,
+    system_program,
+};
+
+pub const SQUADS_PROGRAM_ID: Pubkey = pubkey!("SQDS4ep65T869zMMBKyuUq6aD6EgTu8psMjkvj52pCf");
+
+const SEED_PREFIX: &[u8] = b"multisig";
+const SEED_VAULT: &[u8] = b"vault";
+const SEED_TRANSACTION: &[u8] = b"transaction";
+const SEED_PROPOSAL: &[u8] = b"proposal";
+
+pub const PERMISSION_INITIATE: u8 = 1 << 0;
+pub const PERMISSION_VOTE: u8 = 1 << 1;
+pub const PERMISSION_EXECUTE: u8 = 1 << 2;
+
+/// Squads multisig and vault the CLI proposes admin transactions to.
+#[derive(Copy, Clone, Debug)]
+pub struct SquadsConfig {
+    pub multisig: Pubkey,
+    pub vault_index: u8,
+}
+
+impl SquadsConfig {
+    pub fn vault(&self) -> Pubkey {
+        find_vault_pda(&self.multisig, self.vault_index).0
+    }
+}
+
+pub fn find_vault_pda(multisig: &Pubkey, vault_index: u8) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[SEED_PREFIX, multisig.as_ref(), SEED_VAULT, &[vault_index]],
+        &SQUADS_PROGRAM_ID,
+    )
+}
+
+pub fn find_transaction_pda(multisig: &Pubkey, transaction_index: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            SEED_PREFIX,
+            multisig.as_ref(),
+            SEED_TRANSACTION,
+            &transaction_index.to_le_bytes(),
+        ],
+        &SQUADS_PROGRAM_ID,
+    )
+}
+
+pub fn find_proposal_pda(multisig: &Pubkey, transaction_index: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            SEED_PREFIX,
+            multisig.as_ref(),
+            SEED_TRANSACTION,
+            &transaction_index.to_le_bytes(),
+            SEED_PROPOSAL,
+        ],
+        &SQUADS_PROGRAM_ID,
+    )
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug)]
+pub struct Member {
+    pub key: Pubkey,
+    pub permissions: u8,
+}
+
+// Account layout, not every field is used
+#[allow(dead_code)]
+#[derive(AnchorSerialize, AnchorDeserialize, Debug)]
+pub struct Multisig {
+    pub create_key: Pubkey,
+    pub config_authority: Pubkey,
+    pub threshold: u16,
+    pub time_lock: u32,
+    /// Index of the last created transaction.
+    pub transaction_index: u64,
+    /// Transactions up to this index can't be approved or executed anymore, after a config change.
+    pub stale_transaction_index: u64,
+    pub rent_collector: Option<Pubkey>,
+    pub bump: u8,
+    pub members: Vec<Member>,
+}
+
+impl Multisig {
+    pub fn member(&self, key: &Pubkey) -> Option<&Member> {
+        self.members.iter().find(|member| member.key == *key)
+    }
+}
+
+#[derive(AnchorDeserialize, Debug)]
+pub enum ProposalStatus {
+    Draft { timestamp: i64 },
+    Active { timestamp: i64 },
+    Rejected { timestamp: i64 },
+    Approved { timestamp: i64 },
+    Executing,
+    Executed { timestamp: i64 },
+    Cancelled { timestamp: i64 },
+}
+
+impl ProposalStatus {
+    /// Name of the status and the time it was set at, if recorded.
+    pub fn name_and_timestamp(&self) -> (&'static str, Option<i64>) {
+        match *self {
+            ProposalStatus::Draft { timestamp } => ("Draft", Some(timestamp)),
+            ProposalStatus::Active { timestamp } => ("Active", Some(timestamp)),
+            ProposalStatus::Rejected { timestamp } => ("Rejected", Some(timestamp)),
+            ProposalStatus::Approved { timestamp } => ("Approved", Some(timestamp)),
+            ProposalStatus::Executing => ("Executing", None),
+            ProposalStatus::Executed { timestamp } => ("Executed", Some(timestamp)),
+            ProposalStatus::Cancelled { timestamp } => ("Cancelled", Some(timestamp)),
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(AnchorDeserialize, Debug)]
+pub struct Proposal {
+    pub multisig: Pubkey,
+    pub transaction_index: u64,
+    pub status: ProposalStatus,
+    pub bump: u8,
+    pub approved: Vec<Pubkey>,
+    pub rejected: Vec<Pubkey>,
+    pub cancelled: Vec<Pubkey>,
+}
+
+/// Vault transaction message, as stored in the vault transaction account.
+#[derive(AnchorDeserialize, Debug, PartialEq)]
+pub struct VaultTransactionMessage {
+    pub num_signers: u8,
+    pub num_writable_signers: u8,
+    pub num_writable_non_signers: u8,
+    pub account_keys: Vec<Pubkey>,
+    pub instructions: Vec<VaultCompiledInstruction>,
+    pub address_table_lookups: Vec<VaultMessageAddressTableLookup>,
+}
+
+#[derive(AnchorDeserialize, Debug, PartialEq)]
+pub struct VaultCompiledInstruction {
+    pub program_id_index: u8,
+    pub account_indexes: Vec<u8>,
+    pub data: Vec<u8>,
+}
+
+#[derive(AnchorDeserialize, Debug, PartialEq)]
+pub struct VaultMessageAddressTableLookup {
+    pub account_key: Pubkey,
+    pub writable_indexes: Vec<u8>,
+    pub readonly_indexes: Vec<u8>,
+}
+
+impl VaultTransactionMessage {
+    /// Compile `instructions` to be signed by `vault` only, loading accounts from `lookup_tables`
+    /// where possible.
+    pub fn compile(
+        vault: &Pubkey,
+        instructions: &[Instruction],
+        lookup_tables: &[AddressLookupTableAccount],
+    ) -> Result<Self> {
+        let message =
+            v0::Message::try_compile(vault, instructions, lookup_tables, Hash::default())?;
+        let header = message.header;
+
+        if header.num_required_signatures != 1 {
+            bail!(
+                "Instructions require signers other than the vault {}: {:?}",
+                vault,
+                &message.account_keys[1..header.num_required_signatures as usize]
+            );
+        }
+
+        let num_signers = header.num_required_signatures;
+        let num_writable_signers = num_signers - header.num_readonly_signed_accounts;
+        let num_writable_non_signers = u8::try_from(message.account_keys.len())?
+            - num_signers
+            - header.num_readonly_unsigned_accounts;
+
+        Ok(Self {
+            num_signers,
+            num_writable_signers,
+            num_writable_non_signers,
+            account_keys: message.account_keys,
+            instructions: message
+                .instructions
+                .into_iter()
+                .map(|ix| VaultCompiledInstruction {
+                    program_id_index: ix.program_id_index,
+                    account_indexes: ix.accounts,
+                    data: ix.data,
+                })
+                .collect(),
+            address_table_lookups: message
+                .address_table_lookups
+                .into_iter()
+                .map(|lookup| VaultMessageAddressTableLookup {
+                    account_key: lookup.account_key,
+                    writable_indexes: lookup.writable_indexes,
+                    readonly_indexes: lookup.readonly_indexes,
+                })
+                .collect(),
+        })
+    }
+
+    pub fn is_writable(&self, index: usize) -> bool {
+        let num_signers = self.num_signers as usize;
+
+        if index < num_signers {
+            index < self.num_writable_signers as usize
+        } else {
+            index - num_signers < self.num_writable_non_signers as usize
+        }
+    }
+
+    /// Squads `TransactionMessage` serialization, with `u8` length prefixes (`u16` for instruction data),
+    /// as expected by `vault_transaction_create`.
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        let mut data = vec![
+            self.num_signers,
+            self.num_writable_signers,
+            self.num_writable_non_signers,
+        ];
+
+        data.push(u8::try_from(self.account_keys.len())?);
+        for key in &self.account_keys {
+            data.extend_from_slice(key.as_ref());
+        }
+
+        data.push(u8::try_from(self.instructions.len())?);
+        for ix in &self.instructions {
+            data.push(ix.program_id_index);
+            data.push(u8::try_from(ix.account_indexes.len())?);
+            data.extend_from_slice(&ix.account_indexes);
+            data.extend_from_slice(&u16::try_from(ix.data.len())?.to_le_bytes());
+            data.extend_from_slice(&ix.data);
+        }
+
+        data.push(u8::try_from(self.address_table_lookups.len())?);
+        for lookup in &self.address_table_lookups {
+            data.extend_from_slice(lookup.account_key.as_ref());
+            data.push(u8::try_from(lookup.writable_indexes.len())?);
+            data.extend_from_slice(&lookup.writable_indexes);
+            data.push(u8::try_from(lookup.readonly_indexes.len())?);
+            data.extend_from_slice(&lookup.readonly_indexes);
+        }
+
+        Ok(data)
+    }
+}
+
+// Account layout, not every field is used
+#[allow(dead_code)]
+#[derive(AnchorDeserialize, Debug)]
+pub struct VaultTransaction {
+    pub multisig: Pubkey,
+    pub creator: Pubkey,
+    pub index: u64,
+    pub bump: u8,
+    pub vault_index: u8,
+    pub vault_bump: u8,
+    pub ephemeral_signer_bumps: Vec<u8>,
+    pub message: VaultTransactionMessage,
+}
+
+fn sighash(namespace: &str, name: &str) -> [u8; 8] {
+    let mut discriminator = [0u8; 8];
+    discriminator
+        .copy_from_slice(&hash(format!("{}:{}", namespace, name).as_bytes()).to_bytes()[..8]);
+    discriminator
+}
+
+fn load_account_data(rpc_client: &RpcClient, address: &Pubkey, name: &str) -> Result<Vec<u8>> {
+    let account = rpc_client
+        .get_account(address)
+        .map_err(|err| anyhow!("Failed to load {} {}: {}", name, address, err))?;
+
+    if account.owner != SQUADS_PROGRAM_ID {
+        bail!("{} {} is not owned by the Squads program", name, address);
+    }
+
+    if account.data.len() < 8 || account.data[..8] != sighash("account", name) {
+        bail!("{} is not a Squads {} account", address, name);
+    }
+
+    Ok(account.data[8..].to_vec())
+}
+
+pub fn load_multisig(rpc_client: &RpcClient, address: &Pubkey) -> Result<Multisig> {
+    let data = load_account_data(rpc_client, address, "Multisig")?;
+
+    Ok(Multisig::deserialize(&mut data.as_slice())?)
+}
+
+pub fn load_proposal(rpc_client: &RpcClient, address: &Pubkey) -> Result<Proposal> {
+    let data = load_account_data(rpc_client, address, "Proposal")?;
+
+    Ok(Proposal::deserialize(&mut data.as_slice())?)
+}
+
+pub fn load_vault_transaction(
+    rpc_client: &RpcClient,
+    address: &Pubkey,
+) -> Result<VaultTransaction> {
+    let data = load_account_data(rpc_client, address, "VaultTransaction")?;
+
+    Ok(VaultTransaction::deserialize(&mut data.as_slice())?)
+}
+
+fn build_ix<T: AnchorSerialize>(
+    name: &str,
+    accounts: Vec<AccountMeta>,
+    args: T,
+) -> Result<Instruction> {
+    let mut data = sighash("global", name).to_vec();
+    args.serialize(&mut data)?;
+
+    Ok(Instruction {
+        program_id: SQUADS_PROGRAM_ID,
+        accounts,
+        data,
+    })
+}
+
+#[derive(AnchorSerialize)]
+struct VaultTransactionCreateArgs {
+    vault_index: u8,
+    ephemeral_signers: u8,
+    transaction_message: Vec<u8>,
+    memo: Option<String>,
+}
+
+#[derive(AnchorSerialize)]
+struct ProposalCreateArgs {
+    transaction_index: u64,
+    draft: bool,
+}
+
+#[derive(AnchorSerialize)]
+struct ProposalVoteArgs {
+    memo: Option<String>,
+}
+
+pub fn vault_transaction_create_ix(
+    squads: &SquadsConfig,
+    transaction_index: u64,
+    creator: &Pubkey,
+    message: &VaultTransactionMessage,
+    memo: Option<String>,
+) -> Result<Instruction> {
+    build_ix(
+        "vault_transaction_create",
+        vec![
+            AccountMeta::new(squads.multisig, false),
+            AccountMeta::new(
+                find_transaction_pda(&squads.multisig, transaction_index).0,
+                false,
+            ),
+            AccountMeta::new_readonly(*creator, true),
+            AccountMeta::new(*creator, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        VaultTransactionCreateArgs {
+            vault_index: squads.vault_index,
+            ephemeral_signers: 0,
+            transaction_message: message.serialize()?,
+            memo,
+        },
+    )
+}
+
+pub fn proposal_create_ix(
+    squads: &SquadsConfig,
+    transaction_index: u64,
+    creator: &Pubkey,
+) -> Result<Instruction> {
+    build_ix(
+        "proposal_create",
+        vec![
+            AccountMeta::new_readonly(squads.multisig, false),
+            AccountMeta::new(
+                find_proposal_pda(&squads.multisig, transaction_index).0,
+                false,
+            ),
+            AccountMeta::new_readonly(*creator, true),
+            AccountMeta::new(*creator, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        ProposalCreateArgs {
+            transaction_index,
+            draft: false,
+        },
+    )
+}
+
+pub fn proposal_approve_ix(
+    squads: &SquadsConfig,
+    transaction_index: u64,
+    member: &Pubkey,
+) -> Result<Instruction> {
+    build_ix(
+        "proposal_approve",
+        vec![
+            AccountMeta::new_readonly(squads.multisig, false),
+            AccountMeta::new(*member, true),
+            AccountMeta::new(
+                find_proposal_pda(&squads.multisig, transaction_index).0,
+                false,
+            ),
+        ],
+        ProposalVoteArgs { memo: None },
+    )
+}
+
+/// Execute an approved vault transaction, its accounts are passed as remaining accounts.
+///
+/// `lookup_tables` must contain the address lookup tables of `message`.
+pub fn vault_transaction_execute_ix(
+    squads: &SquadsConfig,
+    transaction_index: u64,
+    member: &Pubkey,
+    message: &VaultTransactionMessage,
+    lookup_tables: &[AddressLookupTableAccount],
+) -> Result<Instruction> {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(squads.multisig, false),
+        AccountMeta::new(
+            find_proposal_pda(&squads.multisig, transaction_index).0,
+            false,
+        ),
+        AccountMeta::new_readonly(
+            find_transaction_pda(&squads.multisig, transaction_index).0,
+            false,
+        ),
+        AccountMeta::new_readonly(*member, true),
+    ];
+
+    // Lookup tables come first, then the static accounts, then the loaded writable and readonly accounts
+    let (mut loaded_writable, mut loaded_readonly) = (vec![], vec![]);
+    for lookup in message.address_table_lookups.iter() {
+        let lookup_table = lookup_tables
+            .iter()
+            .find(|lookup_table| lookup_table.key == lookup.account_key)
+            .ok_or_else(|| anyhow!("Lookup table {} is not active", lookup.account_key))?;
+        let load = |indexes: &[u8]| {
+            indexes
+                .iter()
+                .map(|index| {
+                    lookup_table
+                        .addresses
+                        .get(*index as usize)
+                        .copied()
+                        .ok_or_else(|| {
+                            anyhow!(
+                                "Index {} is out of lookup table {}",
+                                index,
+                                lookup_table.key
+                            )
+                        })
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+
+        accounts.push(AccountMeta::new_readonly(lookup.account_key, false));
+        loaded_writable.extend(load(&lookup.writable_indexes)?);
+        loaded_readonly.extend(load(&lookup.readonly_indexes)?);
+    }
+
+    accounts.extend(
+        message
+            .account_keys
+            .iter()
+            .enumerate()
+            .map(|(index, key)| AccountMeta {
+                pubkey: *key,
+                is_signer: false,
+                is_writable: message.is_writable(index),
+            }),
+    );
+    accounts.extend(
+        loaded_writable
+            .into_iter()
+            .map(|key| AccountMeta::new(key, false)),
+    );
+    accounts.extend(
+        loaded_readonly
+            .into_iter()
+            .map(|key| AccountMeta::new_readonly(key, false)),
+    );
+
+    build_ix("vault_transaction_execute", accounts, ())
+}
+
+/// Create a vault transaction executing `instructions` and its proposal, approved by `member` if it can vote.
+///
+/// Returns the transaction index of the proposal.
+pub fn propose(
+    rpc_client: &RpcClient,
+    squads: &SquadsConfig,
+    member: &impl Signer,
+    instructions: &[Instruction],
+    memo: Option<String>,
+    compute_budget: ComputeBudgetOptions,
+    lookup_tables: &[AddressLookupTableAccount],
+) -> Result<(u64, Signature)> {
+    let multisig = load_multisig(rpc_client, &squads.multisig)?;
+    let permissions = multisig
+        .member(&member.pubkey())
+        .ok_or_else(|| {
+            anyhow!(
+                "{} is not a member of multisig {}",
+                member.pubkey(),
+                squads.multisig
+            )
+        })?
+        .permissions;
+
+    if permissions & PERMISSION_INITIATE == 0 {
+        bail!("{} can't initiate transactions", member.pubkey());
+    }
+
+    // Compute budget instructions only apply to the outer transaction, not to the vault execution
+    let instructions = instructions
+        .iter()
+        .filter(|ix| ix.program_id != compute_budget::id())
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let transaction_index = multisig.transaction_index + 1;
+    let message = VaultTransactionMessage::compile(&squads.vault(), &instructions, lookup_tables)?;
+
+    let mut ixs = vec![
+        vault_transaction_create_ix(squads, transaction_index, &member.pubkey(), &message, memo)?,
+        proposal_create_ix(squads, transaction_index, &member.pubkey())?,
+    ];
+
+    let can_vote = permissions & PERMISSION_VOTE != 0;
+    if can_vote {
+        ixs.push(proposal_approve_ix(
+            squads,
+            transaction_index,
+            &member.pubkey(),
+        )?);
+    }
+
+    let sig = send_transaction(
+        &ixs,
+        &member.pubkey(),
+        rpc_client,
+        compute_budget,
+        lookup_tables,
+        &[member],
+    )?;
+
+    println!(
+        "Proposal #{} created with {} instructions ({} of {} approvals): {}",
+        transaction_index,
+        instructions.len(),
+        u8::from(can_vote),
+        multisig.threshold,
+        sig
+    );
+
+    Ok((transaction_index, sig))
+}
+
+/// Instructions of a legacy transaction message.
+pub fn decompile_instructions(message: &Message) -> Vec<Instruction> {
+    message
+        .instructions
+        .iter()
+        .map(|ix| Instruction {
+            program_id: message.account_keys[ix.program_id_index as usize],
+            accounts: ix
+                .accounts
+                .iter()
+                .map(|index| {
+                    let index = *index as usize;
+
+                    AccountMeta {
+                        pubkey: message.account_keys[index],
+                        is_signer: message.is_signer(index),
+                        is_writable: message.is_writable(index),
+                    }
+                })
+                .collect(),
+            data: ix.data.clone(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program_test::ProgramTest;
+    use solana_sdk::{
+        account::Account, native_token::LAMPORTS_PER_SOL, rent::Rent, signature::Keypair,
+        system_instruction, transaction::Transaction,
+    };
+
+    const SEED_MULTISIG: &[u8] = b"multisig";
+
+    fn take<'a>(data: &mut &'a [u8], len: usize) -> &'a [u8] {
+        let (head, tail) = data.split_at(len);
+        *data = tail;
+        head
+    }
+
+    /// Re-encode a compact `TransactionMessage` with borsh `u32` length prefixes,
+    /// the way Squads stores it in the vault transaction account.
+    fn compact_to_borsh(mut data: &[u8]) -> Vec<u8> {
+        fn vec_u8_prefix(data: &mut &[u8], out: &mut Vec<u8>, item_len: usize) -> u8 {
+            let len = take(data, 1)[0];
+            out.extend_from_slice(&(len as u32).to_le_bytes());
+            out.extend_from_slice(take(data, len as usize * item_len));
+            len
+        }
+
+        let data = &mut data;
+        let mut out = take(data, 3).to_vec();
+
+        vec_u8_prefix(data, &mut out, 32);
+
+        let num_instructions = take(data, 1)[0];
+        out.extend_from_slice(&(num_instructions as u32).to_le_bytes());
+        for _ in 0..num_instructions {
+            out.push(take(data, 1)[0]);
+            vec_u8_prefix(data, &mut out, 1);
+
+            let data_len = u16::from_le_bytes(take(data, 2).try_into().unwrap());
+            out.extend_from_slice(&(data_len as u32).to_le_bytes());
+            out.extend_from_slice(take(data, data_len as usize));
+        }
+
+        let num_lookups = take(data, 1)[0];
+        out.extend_from_slice(&(num_lookups as u32).to_le_bytes());
+        for _ in 0..num_lookups {
+            out.extend_from_slice(take(data, 32));
+            vec_u8_prefix(data, &mut out, 1);
+            vec_u8_prefix(data, &mut out, 1);
+        }
+
+        assert!(data.is_empty(), "trailing bytes in transaction message");
+
+        out
+    }
+
+    #[test]
+    fn vault_transaction_message_round_trip() {
+        let vault = Pubkey::new_unique();
+        let (writable, readonly, program_id) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let instructions = vec![
+            Instruction {
+                program_id,
+                accounts: vec![
+                    AccountMeta::new_readonly(readonly, false),
+                    AccountMeta::new(vault, true),
+                    AccountMeta::new(writable, false),
+                ],
+                // Longer than a u8 length prefix allows
+                data: (0..300).map(|i| i as u8).collect(),
+            },
+            Instruction {
+                program_id: system_program::ID,
+                accounts: vec![AccountMeta::new(vault, true)],
+                data: vec![],
+            },
+        ];
+
+        let message = VaultTransactionMessage::compile(&vault, &instructions, &[]).unwrap();
+
+        let stored = compact_to_borsh(&message.serialize().unwrap());
+        let decoded = VaultTransactionMessage::deserialize(&mut stored.as_slice()).unwrap();
+
+        assert_eq!(decoded, message);
+        assert_eq!(decoded.account_keys[0], vault);
+        assert_eq!(decoded.num_signers, 1);
+        assert_eq!(decoded.num_writable_signers, 1);
+
+        // Every account keeps the writability it had in the instructions
+        let compiled = Message::new(&instructions, Some(&vault));
+        for (index, key) in decoded.account_keys.iter().enumerate() {
+            assert_eq!(
+                decoded.is_writable(index),
+                compiled.is_writable(index),
+                "account {}",
+                key
+            );
+        }
+        assert!(decoded.is_writable(
+            decoded
+                .account_keys
+                .iter()
+                .position(|key| *key == writable)
+                .unwrap()
+        ));
+        assert!(!decoded.is_writable(
+            decoded
+                .account_keys
+                .iter()
+                .position(|key| *key == readonly)
+                .unwrap()
+        ));
+
+        // Instructions point at the same programs, accounts and data
+        for (compiled_ix, ix) in decoded.instructions.iter().zip(&instructions) {
+            assert_eq!(
+                decoded.account_keys[compiled_ix.program_id_index as usize],
+                ix.program_id
+            );
+            assert_eq!(
+                compiled_ix
+                    .account_indexes
+                    .iter()
+                    .map(|index| decoded.account_keys[*index as usize])
+                    .collect::<Vec<_>>(),
+                ix.accounts
+                    .iter()
+                    .map(|meta| meta.pubkey)
+                    .collect::<Vec<_>>()
+            );
+            assert_eq!(compiled_ix.data, ix.data);
+        }
+    }
+
+    #[test]
+    fn vault_transaction_message_rejects_other_signers() {
+        let vault = Pubkey::new_unique();
+        let instructions = vec![Instruction {
+            program_id: system_program::ID,
+            accounts: vec![
+                AccountMeta::new(vault, true),
+                AccountMeta::new(Pubkey::new_unique(), true),
+            ],
+            data: vec![],
+        }];
+
+        assert!(VaultTransactionMessage::compile(&vault, &instructions, &[]).is_err());
+    }
+
+    #[test]
+    fn vault_transaction_message_loads_accounts_from_lookup_tables() {
+        let squads = SquadsConfig {
+            multisig: Pubkey::new_unique(),
+            vault_index: 0,
+        };
+        let vault = squads.vault();
+        let (writable, readonly, program_id) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let lookup_table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![Pubkey::new_unique(), writable, readonly],
+        };
+        let instructions = vec![Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(readonly, false),
+                AccountMeta::new(vault, true),
+                AccountMeta::new(writable, false),
+            ],
+            data: vec![],
+        }];
+
+        let message = VaultTransactionMessage::compile(
+            &vault,
+            &instructions,
+            std::slice::from_ref(&lookup_table),
+        )
+        .unwrap();
+
+        assert_eq!(message.account_keys, vec![vault, program_id]);
+        assert_eq!(
+            message.address_table_lookups,
+            vec![VaultMessageAddressTableLookup {
+                account_key: lookup_table.key,
+                writable_indexes: vec![1],
+                readonly_indexes: vec![2],
+            }]
+        );
+
+        let stored = compact_to_borsh(&message.serialize().unwrap());
+        let decoded = VaultTransactionMessage::deserialize(&mut stored.as_slice()).unwrap();
+        assert_eq!(decoded, message);
+
+        let member = Pubkey::new_unique();
+        let ix = vault_transaction_execute_ix(
+            &squads,
+            1,
+            &member,
+            &decoded,
+            std::slice::from_ref(&lookup_table),
+        )
+        .unwrap();
+
+        assert_eq!(
+            ix.accounts[4..],
+            [
+                AccountMeta::new_readonly(lookup_table.key, false),
+                AccountMeta::new(vault, false),
+                AccountMeta::new_readonly(program_id, false),
+                AccountMeta::new(writable, false),
+                AccountMeta::new_readonly(readonly, false),
+            ]
+        );
+        assert!(vault_transaction_execute_ix(&squads, 1, &member, &decoded, &[]).is_err());
+    }
+
+    /// Run a vault transaction through the Squads program: create it with its proposal,
+    /// approve and execute it, moving lamports out of the vault.
+    ///
+    /// Loads `tests/fixtures/squads_multisig_program.so`, dumped by `scripts/dump-squads-program.sh`.
+    #[tokio::test]
+    async fn vault_transaction_executes_against_squads_program() {
+        assert!(
+            std::path::Path::new("tests/fixtures/squads_multisig_program.so").exists(),
+            "Squads program binary missing, run scripts/dump-squads-program.sh"
+        );
+
+        let member = Keypair::new();
+        let create_key = Pubkey::new_unique();
+        let (multisig_pk, multisig_bump) = Pubkey::find_program_address(
+            &[SEED_PREFIX, SEED_MULTISIG, create_key.as_ref()],
+            &SQUADS_PROGRAM_ID,
+        );
+        let squads = SquadsConfig {
+            multisig: multisig_pk,
+            vault_index: 0,
+        };
+        let recipient = Pubkey::new_unique();
+
+        let multisig = Multisig {
+            create_key,
+            config_authority: Pubkey::default(),
+            threshold: 1,
+            time_lock: 0,
+            transaction_index: 0,
+            stale_transaction_index: 0,
+            rent_collector: None,
+            bump: multisig_bump,
+            members: vec![Member {
+                key: member.pubkey(),
+                permissions: PERMISSION_INITIATE | PERMISSION_VOTE | PERMISSION_EXECUTE,
+            }],
+        };
+        let mut multisig_data = sighash("account", "Multisig").to_vec();
+        multisig.serialize(&mut multisig_data).unwrap();
+
+        let mut program_test = ProgramTest::default();
+        program_test.prefer_bpf(true);
+        program_test.add_program("squads_multisig_program", SQUADS_PROGRAM_ID, None);
+        program_test.add_account(
+            multisig_pk,
+            Account {
+                lamports: Rent::default().minimum_balance(multisig_data.len()),
+                data: multisig_data,
+                owner: SQUADS_PROGRAM_ID,
+                ..Account::default()
+            },
+        );
+        program_test.add_account(
+            member.pubkey(),
+            Account::new(LAMPORTS_PER_SOL, 0, &system_program::ID),
+        );
+        program_test.add_account(
+            squads.vault(),
+            Account::new(LAMPORTS_PER_SOL, 0, &system_program::ID),
+        );
+
+        let mut ctx = program_test.start_with_context().await;
+
+        let instructions = vec![system_instruction::transfer(
+            &squads.vault(),
+            &recipient,
+            LAMPORTS_PER_SOL / 2,
+        )];
+        let message =
+            VaultTransactionMessage::compile(&squads.vault(), &instructions, &[]).unwrap();
+
+        let propose_tx = Transaction::new_signed_with_payer(
+            &[
+                vault_transaction_create_ix(&squads, 1, &member.pubkey(), &message, None).unwrap(),
+                proposal_create_ix(&squads, 1, &member.pubkey()).unwrap(),
+                proposal_approve_ix(&squads, 1, &member.pubkey()).unwrap(),
+            ],
+            Some(&member.pubkey()),
+            &[&member],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client
+            .process_transaction(propose_tx)
+            .await
+            .unwrap();
+
+        // The stored message is the one executed, with its accounts passed in order
+        let vault_transaction_account = ctx
+            .banks_client
+            .get_account(find_transaction_pda(&multisig_pk, 1).0)
+            .await
+            .unwrap()
+            .unwrap();
+        let vault_transaction =
+            VaultTransaction::deserialize(&mut &vault_transaction_account.data[8..]).unwrap();
+        assert_eq!(vault_transaction.message, message);
+
+        let execute_tx = Transaction::new_signed_with_payer(
+            &[vault_transaction_execute_ix(
+                &squads,
+                1,
+                &member.pubkey(),
+                &vault_transaction.message,
+                &[],
+            )
+            .unwrap()],
+            Some(&member.pubkey()),
+            &[&member],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client
+            .process_transaction(execute_tx)
+            .await
+            .unwrap();
+
+        let proposal_account = ctx
+            .banks_client
+            .get_account(find_proposal_pda(&multisig_pk, 1).0)
+            .await
+            .unwrap()
+            .unwrap();
+        let proposal = Proposal::deserialize(&mut &proposal_account.data[8..]).unwrap();
+        assert!(matches!(proposal.status, ProposalStatus::Executed { .. }));
+        assert_eq!(proposal.approved, vec![member.pubkey()]);
+
+        let recipient_balance = ctx.banks_client.get_balance(recipient).await.unwrap();
+        assert_eq!(recipient_balance, LAMPORTS_PER_SOL / 2);
+    }
+}
@@ -1,49 +1,87 @@
-use crate::config::TxMode;
-use marginfi::bank_seed;
-use marginfi::constants::EMISSIONS_TOKEN_ACCOUNT_SEED;
+use crate::config::{ComputeBudgetOptions, PriorityFee, TxMode};
+use crate::squads::propose;
 #[cfg(feature = "admin")]
-use marginfi::constants::{EMISSIONS_AUTH_SEED, MAX_ORACLE_KEYS};
+use marginfi::constants::MAX_ORACLE_KEYS;
 use {
-    anyhow::{bail, Result},
+    anyhow::{anyhow, bail, Result},
     fixed::types::I80F48,
     fixed_macro::types::I80F48,
-    log::error,
-    marginfi::{
-        bank_authority_seed,
-        state::{
-            marginfi_account::MarginfiAccount,
-            marginfi_group::{Bank, BankVaultType},
+    log::{error, info, warn},
+    solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig},
+    solana_address_lookup_table_program::state::AddressLookupTable,
+    solana_client::{
+        rpc_client::RpcClient,
+        rpc_config::{
+            RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcSendTransactionConfig,
+            RpcSimulateTransactionConfig,
         },
+        rpc_filter::{Memcmp, RpcFilterType},
+        rpc_request::MAX_MULTIPLE_ACCOUNTS,
     },
-    solana_client::rpc_client::RpcClient,
     solana_sdk::{
-        instruction::AccountMeta, pubkey::Pubkey, signature::Signature, transaction::Transaction,
+        address_lookup_table_account::AddressLookupTableAccount,
+        clock::Slot,
+        compute_budget::{self, ComputeBudgetInstruction},
+        hash::Hash,
+        instruction::Instruction,
+        message::{v0, Message, VersionedMessage},
+        pubkey::Pubkey,
+        signature::Signature,
+        signers::Signers,
+        transaction::{Transaction, VersionedTransaction},
     },
-    std::collections::HashMap,
+    std::{cmp::min, thread, time::Duration},
 };
 
-pub fn process_transaction(
-    tx: &Transaction,
+/// Upper bound of the compute unit limit, used to simulate transactions.
+const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+/// Share of the simulated compute units added to the limit, as state can change before landing.
+const COMPUTE_UNIT_LIMIT_MARGIN: f64 = 0.1;
+const RESEND_INTERVAL: Duration = Duration::from_secs(2);
+/// Offset of the authority in lookup table accounts, after the type index, deactivation slot,
+/// last extended slot and start index, and the authority option tag.
+const LOOKUP_TABLE_AUTHORITY_OFFSET: usize = 4 + 8 + 8 + 1;
+
+/// Send, simulate or propose `instructions` paid by `payer`, depending on `tx_mode`.
+///
+/// Sent and simulated transactions are compiled to v0 transactions using the lookup tables of
+/// `tx_mode`, see [`send_transaction`] for sent transactions.
+pub fn process_transaction<T: Signers>(
+    instructions: &[Instruction],
+    payer: &Pubkey,
     rpc_client: &RpcClient,
     tx_mode: TxMode,
+    signers: &T,
 ) -> Result<Signature> {
     match tx_mode {
-        TxMode::DryRun => match rpc_client.simulate_transaction(tx) {
-            Ok(response) => {
-                println!("------- program logs -------");
-                response
-                    .value
-                    .logs
-                    .unwrap()
-                    .into_iter()
-                    .for_each(|line| println!("{line}"));
-                println!("----------------------------");
-                Ok(Signature::default())
+        TxMode::DryRun { lookup_tables } => {
+            let simulated_tx = compile_unsigned_transaction(
+                payer,
+                instructions,
+                lookup_tables,
+                rpc_client.get_latest_blockhash()?,
+            )?;
+
+            match rpc_client.simulate_transaction(&simulated_tx) {
+                Ok(response) => {
+                    println!("------- program logs -------");
+                    response
+                        .value
+                        .logs
+                        .unwrap()
+                        .into_iter()
+                        .for_each(|line| println!("{line}"));
+                    println!("----------------------------");
+                    Ok(Signature::default())
+                }
+                Err(err) => bail!(err),
             }
-            Err(err) => bail!(err),
-        },
+        }
         TxMode::Multisig => {
-            let bytes = bincode::serialize(tx)?;
+            let mut tx = Transaction::new_with_payer(instructions, Some(payer));
+            tx.partial_sign(signers, rpc_client.get_latest_blockhash()?);
+
+            let bytes = bincode::serialize(&tx)?;
             let tx_size = bytes.len();
             let tx_serialized = bs58::encode(bytes).into_string();
 
@@ -54,61 +92,312 @@ pub fn process_transaction(
 
             Ok(Signature::default())
         }
-        TxMode::Normal => match rpc_client.send_and_confirm_transaction_with_spinner(tx) {
-            Ok(sig) => Ok(sig),
-            Err(err) => {
-                error!("transaction failed: {:?}", err);
-                bail!(err);
-            }
-        },
+        TxMode::Squads {
+            squads,
+            member,
+            compute_budget,
+            lookup_tables,
+        } => {
+            let (_, sig) = propose(
+                rpc_client,
+                &squads,
+                member,
+                instructions,
+                None,
+                compute_budget,
+                lookup_tables,
+            )?;
+
+            Ok(sig)
+        }
+        TxMode::Normal {
+            compute_budget,
+            lookup_tables,
+        } => send_transaction(
+            instructions,
+            payer,
+            rpc_client,
+            compute_budget,
+            lookup_tables,
+            signers,
+        ),
     }
 }
 
-pub fn find_bank_vault_pda(
-    bank_pk: &Pubkey,
-    vault_type: BankVaultType,
-    program_id: &Pubkey,
-) -> (Pubkey, u8) {
-    Pubkey::find_program_address(bank_seed!(vault_type, bank_pk), program_id)
+/// Send `instructions` paid by `payer` as a v0 transaction using `lookup_tables`.
+///
+/// The transaction gets the compute budget of `options`, replacing the compute budget
+/// instructions of `instructions`, is signed by `signers` and is resent until it lands or its
+/// blockhash expires.
+pub fn send_transaction<T: Signers>(
+    instructions: &[Instruction],
+    payer: &Pubkey,
+    rpc_client: &RpcClient,
+    options: ComputeBudgetOptions,
+    lookup_tables: &[AddressLookupTableAccount],
+    signers: &T,
+) -> Result<Signature> {
+    let result = build_transaction(
+        instructions,
+        payer,
+        rpc_client,
+        options,
+        lookup_tables,
+        signers,
+    )
+    .and_then(|(tx, last_valid_block_height)| {
+        send_until_expired(rpc_client, &tx, last_valid_block_height)
+    });
+
+    if let Err(err) = &result {
+        error!("transaction failed: {:?}", err);
+    }
+
+    result
 }
 
-pub fn find_bank_vault_authority_pda(
-    bank_pk: &Pubkey,
-    vault_type: BankVaultType,
-    program_id: &Pubkey,
-) -> (Pubkey, u8) {
-    Pubkey::find_program_address(bank_authority_seed!(vault_type, bank_pk), program_id)
+/// Build a v0 transaction with compute budget instructions, replacing those of `instructions`,
+/// and sign it with a fresh blockhash.
+///
+/// Returns the signed transaction and the last block height its blockhash is valid at.
+fn build_transaction<T: Signers>(
+    instructions: &[Instruction],
+    payer: &Pubkey,
+    rpc_client: &RpcClient,
+    options: ComputeBudgetOptions,
+    lookup_tables: &[AddressLookupTableAccount],
+    signers: &T,
+) -> Result<(VersionedTransaction, u64)> {
+    let instructions = instructions
+        .iter()
+        .filter(|ix| ix.program_id != compute_budget::id())
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let mut budget_ixs = vec![];
+
+    let compute_unit_price = match options.compute_unit_price {
+        Some(PriorityFee::Fixed(price)) => price,
+        Some(PriorityFee::Estimated { percentile }) => estimate_compute_unit_price(
+            rpc_client,
+            &Message::new(&instructions, Some(payer)),
+            percentile,
+        )?,
+        None => 0,
+    };
+    if compute_unit_price > 0 {
+        budget_ixs.push(ComputeBudgetInstruction::set_compute_unit_price(
+            compute_unit_price,
+        ));
+    }
+
+    let compute_unit_limit = match options.compute_unit_limit {
+        Some(limit) => limit,
+        None => simulate_compute_unit_limit(
+            rpc_client,
+            payer,
+            &budget_ixs,
+            &instructions,
+            lookup_tables,
+        )?,
+    };
+    budget_ixs.push(ComputeBudgetInstruction::set_compute_unit_limit(
+        compute_unit_limit,
+    ));
+
+    info!(
+        "compute unit price: {} micro-lamports, limit: {}",
+        compute_unit_price, compute_unit_limit
+    );
+
+    let (recent_blockhash, last_valid_block_height) =
+        rpc_client.get_latest_blockhash_with_commitment(rpc_client.commitment())?;
+    let message = v0::Message::try_compile(
+        payer,
+        &[budget_ixs, instructions].concat(),
+        lookup_tables,
+        recent_blockhash,
+    )?;
+    let tx = VersionedTransaction::try_new(VersionedMessage::V0(message), signers)?;
+
+    Ok((tx, last_valid_block_height))
 }
 
-#[cfg(feature = "admin")]
-pub fn find_bank_emssions_auth_pda(
-    bank: Pubkey,
-    emissions_mint: Pubkey,
-    program_id: Pubkey,
-) -> (Pubkey, u8) {
-    Pubkey::find_program_address(
-        &[
-            EMISSIONS_AUTH_SEED.as_bytes(),
-            bank.as_ref(),
-            emissions_mint.as_ref(),
-        ],
-        &program_id,
-    )
+/// v0 transaction without signatures, for simulations.
+fn compile_unsigned_transaction(
+    payer: &Pubkey,
+    instructions: &[Instruction],
+    lookup_tables: &[AddressLookupTableAccount],
+    recent_blockhash: Hash,
+) -> Result<VersionedTransaction> {
+    let message = VersionedMessage::V0(v0::Message::try_compile(
+        payer,
+        instructions,
+        lookup_tables,
+        recent_blockhash,
+    )?);
+
+    Ok(VersionedTransaction {
+        signatures: vec![Signature::default(); message.header().num_required_signatures as usize],
+        message,
+    })
 }
 
-pub fn find_bank_emssions_token_account_pda(
-    bank: Pubkey,
-    emissions_mint: Pubkey,
-    program_id: Pubkey,
-) -> (Pubkey, u8) {
-    Pubkey::find_program_address(
-        &[
-            EMISSIONS_TOKEN_ACCOUNT_SEED.as_bytes(),
-            bank.as_ref(),
-            emissions_mint.as_ref(),
-        ],
-        &program_id,
-    )
+/// Percentile of the prioritization fees recently paid to write the accounts of `message`.
+fn estimate_compute_unit_price(
+    rpc_client: &RpcClient,
+    message: &Message,
+    percentile: u8,
+) -> Result<u64> {
+    let writable_accounts = message
+        .account_keys
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| message.is_writable(*index))
+        .map(|(_, key)| *key)
+        .collect::<Vec<_>>();
+
+    let mut fees = rpc_client
+        .get_recent_prioritization_fees(&writable_accounts)?
+        .into_iter()
+        .map(|fee| fee.prioritization_fee)
+        .collect::<Vec<_>>();
+
+    if fees.is_empty() {
+        return Ok(0);
+    }
+
+    fees.sort_unstable();
+
+    Ok(fees[(fees.len() - 1) * percentile as usize / 100])
+}
+
+fn simulate_compute_unit_limit(
+    rpc_client: &RpcClient,
+    payer: &Pubkey,
+    budget_ixs: &[Instruction],
+    instructions: &[Instruction],
+    lookup_tables: &[AddressLookupTableAccount],
+) -> Result<u32> {
+    let mut ixs = budget_ixs.to_vec();
+    ixs.push(ComputeBudgetInstruction::set_compute_unit_limit(
+        MAX_COMPUTE_UNIT_LIMIT,
+    ));
+    ixs.extend_from_slice(instructions);
+
+    let tx = compile_unsigned_transaction(payer, &ixs, lookup_tables, Hash::default())?;
+    let simulation = rpc_client
+        .simulate_transaction_with_config(
+            &tx,
+            RpcSimulateTransactionConfig {
+                sig_verify: false,
+                replace_recent_blockhash: true,
+                commitment: Some(rpc_client.commitment()),
+                ..Default::default()
+            },
+        )?
+        .value;
+
+    if let Some(err) = simulation.err {
+        bail!(
+            "simulation failed: {}\n{}",
+            err,
+            simulation.logs.unwrap_or_default().join("\n")
+        );
+    }
+
+    let units_consumed = simulation
+        .units_consumed
+        .ok_or_else(|| anyhow!("simulation returned no compute units"))?;
+
+    Ok(min(
+        (units_consumed as f64 * (1. + COMPUTE_UNIT_LIMIT_MARGIN)) as u32,
+        MAX_COMPUTE_UNIT_LIMIT,
+    ))
+}
+
+/// Send `tx`, then resend it until it lands or its blockhash expires.
+fn send_until_expired(
+    rpc_client: &RpcClient,
+    tx: &VersionedTransaction,
+    last_valid_block_height: u64,
+) -> Result<Signature> {
+    // Preflight checks run on the first send only
+    let sig = rpc_client.send_transaction(tx)?;
+    let resend_config = RpcSendTransactionConfig {
+        skip_preflight: true,
+        ..Default::default()
+    };
+
+    loop {
+        thread::sleep(RESEND_INTERVAL);
+
+        if let Some(result) =
+            rpc_client.get_signature_status_with_commitment(&sig, rpc_client.commitment())?
+        {
+            result?;
+            return Ok(sig);
+        }
+
+        if rpc_client.get_block_height()? > last_valid_block_height {
+            bail!("transaction {} expired before landing", sig);
+        }
+
+        info!("resending transaction {}", sig);
+        if let Err(err) = rpc_client.send_transaction_with_config(tx, resend_config) {
+            warn!("failed to resend transaction {}: {:?}", sig, err);
+        }
+    }
+}
+
+/// Lookup tables owned by `authority`.
+pub fn find_lookup_tables(rpc_client: &RpcClient, authority: &Pubkey) -> Result<Vec<Pubkey>> {
+    let accounts = rpc_client.get_program_accounts_with_config(
+        &solana_address_lookup_table_program::id(),
+        RpcProgramAccountsConfig {
+            filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                LOOKUP_TABLE_AUTHORITY_OFFSET,
+                [&[1], authority.as_ref()].concat(),
+            ))]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                data_slice: Some(UiDataSliceConfig {
+                    offset: 0,
+                    length: 0,
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    )?;
+
+    Ok(accounts.into_iter().map(|(address, _)| address).collect())
+}
+
+/// Load the active lookup tables of `addresses`.
+pub fn load_lookup_tables(
+    rpc_client: &RpcClient,
+    addresses: &[Pubkey],
+) -> Result<Vec<AddressLookupTableAccount>> {
+    let mut lookup_tables = vec![];
+
+    for chunk in addresses.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        for (address, account) in chunk.iter().zip(rpc_client.get_multiple_accounts(chunk)?) {
+            let account = account.ok_or_else(|| anyhow!("Lookup table {} not found", address))?;
+            let lookup_table = AddressLookupTable::deserialize(&account.data)?;
+
+            if lookup_table.meta.deactivation_slot != Slot::MAX {
+                continue;
+            }
+
+            lookup_tables.push(AddressLookupTableAccount {
+                key: *address,
+                addresses: lookup_table.addresses.to_vec(),
+            });
+        }
+    }
+
+    Ok(lookup_tables)
 }
 
 #[cfg(feature = "admin")]
@@ -136,54 +425,6 @@ pub const EXP_10_I80F48: [I80F48; 15] = [
     I80F48!(100_000_000_000_000),
 ];
 
-pub fn load_observation_account_metas(
-    marginfi_account: &MarginfiAccount,
-    banks_map: &HashMap<Pubkey, Bank>,
-    include_banks: Vec<Pubkey>,
-    exclude_banks: Vec<Pubkey>,
-) -> Vec<AccountMeta> {
-    let mut bank_pks = marginfi_account
-        .lending_account
-        .balances
-        .iter()
-        .filter_map(|balance| balance.active.then_some(balance.bank_pk))
-        .collect::<Vec<_>>();
-
-    for bank_pk in include_banks {
-        if !bank_pks.contains(&bank_pk) {
-            bank_pks.push(bank_pk);
-        }
-    }
-
-    bank_pks.retain(|bank_pk| !exclude_banks.contains(bank_pk));
-
-    let mut banks = vec![];
-    for bank_pk in bank_pks.clone() {
-        let bank = banks_map.get(&bank_pk).unwrap();
-        banks.push(bank);
-    }
-
-    let account_metas = banks
-        .iter()
-        .zip(bank_pks.iter())
-        .flat_map(|(bank, bank_pk)| {
-            vec![
-                AccountMeta {
-                    pubkey: *bank_pk,
-                    is_signer: false,
-                    is_writable: false,
-                },
-                AccountMeta {
-                    pubkey: bank.config.oracle_keys[0],
-                    is_signer: false,
-                    is_writable: false,
-                },
-            ]
-        })
-        .collect::<Vec<_>>();
-    account_metas
-}
-
 #[cfg(feature = "admin")]
 pub fn calc_emissions_rate(ui_rate: f64, emissions_mint_decimals: u8) -> u64 {
     (ui_rate * 10u64.pow(emissions_mint_decimals as u32) as f64) as u64
@@ -0,0 +1,274 @@
+use anchor_lang::{prelude::Pubkey, AnchorDeserialize, Discriminator};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use marginfi::events::*;
+use thiserror::Error;
+
+const PROGRAM_DATA_LOG_PREFIX: &str = "Program data: ";
+const PROGRAM_LOG_PREFIX: &str = "Program ";
+
+#[derive(Debug, Error)]
+pub enum DecodeError {
+    #[error("event data is shorter than the event discriminator")]
+    MissingDiscriminator,
+    #[error("invalid base64 event data: {0}")]
+    InvalidBase64(#[from] base64::DecodeError),
+    #[error("failed to deserialize {event}: {source}")]
+    InvalidEventData {
+        event: &'static str,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+macro_rules! marginfi_events {
+    ($($event:ident),* $(,)?) => {
+        /// Any event emitted by the marginfi program, one variant per event type and version.
+        #[allow(clippy::large_enum_variant)]
+        pub enum MarginfiEvent {
+            $($event($event),)*
+        }
+
+        impl MarginfiEvent {
+            /// Decode event data (discriminator followed by the borsh serialized event).
+            ///
+            /// Returns `None` if the discriminator does not match any known event.
+            pub fn decode(data: &[u8]) -> Result<Option<Self>, DecodeError> {
+                if data.len() < 8 {
+                    return Err(DecodeError::MissingDiscriminator);
+                }
+
+                let (discriminator, mut event_data) = data.split_at(8);
+
+                $(
+                    if discriminator == $event::DISCRIMINATOR {
+                        return $event::deserialize(&mut event_data)
+                            .map(|event| Some(MarginfiEvent::$event(event)))
+                            .map_err(|source| DecodeError::InvalidEventData {
+                                event: stringify!($event),
+                                source,
+                            });
+                    }
+                )*
+
+                Ok(None)
+            }
+
+            /// Name of the on-chain event type.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(MarginfiEvent::$event(_) => stringify!($event),)*
+                }
+            }
+        }
+    };
+}
+
+marginfi_events!(
+    // marginfi group events
+    MarginfiGroupCreateEvent,
+    MarginfiGroupConfigureEvent,
+    MarginfiGroupConfigureFlashloanCpiProgramEvent,
+    LendingPoolBankCreateEvent,
+    LendingPoolBankConfigureEvent,
+    LendingPoolBankResetOutflowEvent,
+    LendingPoolBankSetBorrowCredentialEvent,
+    LendingPoolBankSetupInsuranceStakingEvent,
+    LendingPoolBankInsuranceStakeEvent,
+    LendingPoolBankInsuranceRequestUnstakeEvent,
+    LendingPoolBankInsuranceUnstakeEvent,
+    LendingPoolBankResetInsuranceStakingEvent,
+    LendingPoolBankSetupEmissionsEvent,
+    LendingPoolBankUpdateEmissionsEvent,
+    LendingPoolBankAccrueInterestEvent,
+    LendingPoolBankAccrueInterestEventV2,
+    LendingPoolBankCollectFeesEvent,
+    LendingPoolBankHandleBankruptcyEvent,
+    LendingPoolBankDeferBadDebtEvent,
+    LendingPoolBankRecoverBadDebtEvent,
+    LendingPoolBankBadDebtAuctionEvent,
+    // marginfi account events
+    MarginfiAccountCreateEvent,
+    LendingAccountDepositEvent,
+    LendingAccountDepositEventV2,
+    LendingAccountRepayEvent,
+    LendingAccountRepayEventV2,
+    LendingAccountBorrowEvent,
+    LendingAccountBorrowEventV2,
+    LendingAccountTransferBalanceEvent,
+    LendingAccountFlashloanFeeEvent,
+    LendingAccountWithdrawEvent,
+    LendingAccountWithdrawEventV2,
+    LendingAccountLiquidateEvent,
+    LendingAccountLiquidateEventV2,
+    MarginfiAccountTransferAccountAuthorityEvent,
+    MarginfiAccountSetFlagEvent,
+    MarginfiAccountUnsetFlagEvent,
+    LendingAccountStartFlashloanEvent,
+    LendingAccountEndFlashloanEvent,
+    LendingAccountSettleEmissionsEvent,
+    LendingAccountWithdrawEmissionsEvent,
+    MarginfiAccountCreateOrderEvent,
+    MarginfiAccountCloseOrderEvent,
+    LendingAccountExecuteOrderEvent,
+);
+
+impl MarginfiEvent {
+    /// Banks and their post-operation state, for the event versions that carry it.
+    pub fn bank_states(&self) -> Vec<(&Pubkey, &BankStateSnapshot)> {
+        match self {
+            MarginfiEvent::LendingPoolBankAccrueInterestEventV2(event) => {
+                vec![(&event.bank, &event.bank_state)]
+            }
+            MarginfiEvent::LendingAccountDepositEventV2(event) => {
+                vec![(&event.bank, &event.bank_state)]
+            }
+            MarginfiEvent::LendingAccountRepayEventV2(event) => {
+                vec![(&event.bank, &event.bank_state)]
+            }
+            MarginfiEvent::LendingAccountBorrowEventV2(event) => {
+                vec![(&event.bank, &event.bank_state)]
+            }
+            MarginfiEvent::LendingAccountWithdrawEventV2(event) => {
+                vec![(&event.bank, &event.bank_state)]
+            }
+            MarginfiEvent::LendingAccountLiquidateEventV2(event) => vec![
+                (&event.asset_bank, &event.asset_bank_state),
+                (&event.liability_bank, &event.liability_bank_state),
+            ],
+            _ => vec![],
+        }
+    }
+}
+
+/// Decode the events emitted by `program_id` from a transaction's log messages.
+///
+/// Only `Program data:` entries logged while `program_id` is the innermost invoked program
+/// are decoded, so data logged by other programs, including those invoked through CPI, is ignored.
+/// Entries with an unknown discriminator are skipped.
+pub fn decode_logs(
+    log_messages: &[String],
+    program_id: &Pubkey,
+) -> Result<Vec<MarginfiEvent>, DecodeError> {
+    let program_id = program_id.to_string();
+    let mut invocation_stack: Vec<&str> = vec![];
+    let mut events = vec![];
+
+    for log in log_messages {
+        if let Some(data) = log.strip_prefix(PROGRAM_DATA_LOG_PREFIX) {
+            if invocation_stack.last() != Some(&program_id.as_str()) {
+                continue;
+            }
+
+            for chunk in data.split(' ') {
+                if let Some(event) = MarginfiEvent::decode(&STANDARD.decode(chunk)?)? {
+                    events.push(event);
+                }
+            }
+        } else if let Some(log) = log.strip_prefix(PROGRAM_LOG_PREFIX) {
+            let mut words = log.split(' ');
+
+            match (words.next(), words.next()) {
+                // `Program log:`, `Program return:`, ...
+                (Some(id), _) if id.ends_with(':') => {}
+                (Some(id), Some("invoke")) => invocation_stack.push(id),
+                (Some(_), Some(status)) if status == "success" || status.starts_with("failed") => {
+                    invocation_stack.pop();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::Event;
+    use fixed::types::I80F48;
+
+    fn program_data_log(event: &impl Event) -> String {
+        format!(
+            "{}{}",
+            PROGRAM_DATA_LOG_PREFIX,
+            STANDARD.encode(event.data())
+        )
+    }
+
+    fn account_event_header() -> AccountEventHeader {
+        AccountEventHeader {
+            signer: Some(Pubkey::new_unique()),
+            marginfi_account: Pubkey::new_unique(),
+            marginfi_account_authority: Pubkey::new_unique(),
+            marginfi_group: Pubkey::new_unique(),
+        }
+    }
+
+    #[test]
+    fn decode_logs_all_versions() {
+        let program_id = Pubkey::new_unique();
+        let other_program_id = Pubkey::new_unique();
+        let bank = Pubkey::new_unique();
+
+        let v1 = LendingAccountDepositEvent {
+            header: account_event_header(),
+            bank,
+            mint: Pubkey::new_unique(),
+            amount: 100,
+        };
+        let v2 = LendingAccountDepositEventV2 {
+            header: account_event_header(),
+            bank,
+            mint: Pubkey::new_unique(),
+            amount: 200,
+            bank_state: BankStateSnapshot {
+                asset_share_value: I80F48::from_num(1.5).into(),
+                liability_share_value: I80F48::from_num(1.25).into(),
+                total_asset_shares: I80F48::from_num(1_000).into(),
+                total_liability_shares: I80F48::from_num(500).into(),
+                lending_rate: Some(0.05),
+                borrowing_rate: None,
+            },
+        };
+
+        let log_messages = vec![
+            format!("Program {} invoke [1]", program_id),
+            "Program log: Instruction: LendingAccountDeposit".to_string(),
+            program_data_log(&v1),
+            format!("Program {} invoke [2]", other_program_id),
+            program_data_log(&v1),
+            format!("Program {} success", other_program_id),
+            program_data_log(&v2),
+            format!(
+                "Program {} consumed 10000 of 200000 compute units",
+                program_id
+            ),
+            format!("Program {} success", program_id),
+        ];
+
+        let events = decode_logs(&log_messages, &program_id).unwrap();
+
+        assert_eq!(events.len(), 2);
+
+        assert_eq!(events[0].name(), "LendingAccountDepositEvent");
+        assert!(events[0].bank_states().is_empty());
+
+        assert_eq!(events[1].name(), "LendingAccountDepositEventV2");
+        let (event_bank, bank_state) = events[1].bank_states()[0];
+        assert_eq!(event_bank, &bank);
+        assert_eq!(I80F48::from(bank_state.asset_share_value), 1.5);
+        assert_eq!(I80F48::from(bank_state.total_liability_shares), 500);
+        assert_eq!(bank_state.lending_rate, Some(0.05));
+        assert!(bank_state.borrowing_rate.is_none());
+    }
+
+    #[test]
+    fn decode_unknown_event() {
+        assert!(MarginfiEvent::decode(&[0; 16]).unwrap().is_none());
+        assert!(matches!(
+            MarginfiEvent::decode(&[0; 4]),
+            Err(DecodeError::MissingDiscriminator)
+        ));
+    }
+}
@@ -0,0 +1,78 @@
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use fixed::types::I80F48;
+use marginfi_v2_liquidator::{
+    liquidator::{Liquidator, LiquidatorConfig},
+    state::load_lookup_tables,
+};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig, pubkey::Pubkey, signature::read_keypair_file,
+};
+use std::time::Duration;
+
+#[derive(Debug, Parser)]
+#[clap(version = env!("CARGO_PKG_VERSION"))]
+pub struct Opts {
+    #[clap(long, default_value = "https://api.mainnet-beta.solana.com")]
+    pub rpc_url: String,
+    /// Keypair of the liquidator account authority, paying for transactions
+    #[clap(long, default_value = "~/.config/solana/id.json")]
+    pub keypair_path: String,
+    #[clap(long, default_value_t = marginfi::id())]
+    pub program_id: Pubkey,
+    #[clap(long)]
+    pub marginfi_group: Pubkey,
+    #[clap(long)]
+    pub liquidator_account: Pubkey,
+    /// Minimum liquidator fee, in USD, for a liquidation to be submitted
+    #[clap(long, default_value_t = 1.)]
+    pub min_profit: f64,
+    #[clap(long, default_value_t = 10)]
+    pub poll_interval_sec: u64,
+    /// Simulate liquidation transactions instead of sending them
+    #[clap(long)]
+    pub dry_run: bool,
+    /// Run a single pass instead of polling
+    #[clap(long)]
+    pub once: bool,
+    /// Lookup tables liquidation transactions are compiled with
+    #[clap(long)]
+    pub lookup_tables: Vec<Pubkey>,
+    /// Compute unit price of liquidation transactions, in micro-lamports
+    #[clap(long, default_value_t = 0)]
+    pub compute_unit_price: u64,
+}
+
+fn main() -> Result<()> {
+    env_logger::init();
+
+    let opts = Opts::parse();
+
+    let signer = read_keypair_file(&*shellexpand::tilde(&opts.keypair_path))
+        .map_err(|err| anyhow!("Failed to read keypair {}: {}", opts.keypair_path, err))?;
+    let rpc = RpcClient::new_with_commitment(opts.rpc_url, CommitmentConfig::confirmed());
+    let lookup_tables = load_lookup_tables(&rpc, &opts.lookup_tables)?;
+
+    let liquidator = Liquidator::new(
+        rpc,
+        signer,
+        LiquidatorConfig {
+            program_id: opts.program_id,
+            marginfi_group: opts.marginfi_group,
+            liquidator_account: opts.liquidator_account,
+            min_profit: I80F48::from_num(opts.min_profit),
+            poll_interval: Duration::from_secs(opts.poll_interval_sec),
+            dry_run: opts.dry_run,
+            lookup_tables,
+            compute_unit_price: opts.compute_unit_price,
+        },
+    );
+
+    if opts.once {
+        liquidator.run_once()?;
+        Ok(())
+    } else {
+        liquidator.run()
+    }
+}
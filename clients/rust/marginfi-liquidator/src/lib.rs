@@ -0,0 +1,9 @@
+//! Liquidator bot for marginfi v2 groups.
+//!
+//! - [`state`]: group snapshot loading over RPC, with interest accrued and oracles parsed
+//! - [`strategy`]: liquidation candidate selection and sizing under the program constraints
+//! - [`liquidator`]: polling loop building, simulating and sending liquidation transactions
+
+pub mod liquidator;
+pub mod state;
+pub mod strategy;
@@ -0,0 +1,283 @@
+use anyhow::{anyhow, Result};
+use fixed::types::I80F48;
+use log::{error, info, warn};
+use marginfi_v2_sdk::instructions::MarginfiAccountIxBuilder;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{
+    address_lookup_table_account::AddressLookupTableAccount,
+    compute_budget::ComputeBudgetInstruction,
+    hash::Hash,
+    instruction::Instruction,
+    message::{v0, VersionedMessage},
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    transaction::VersionedTransaction,
+};
+use std::{cmp::min, collections::HashSet, thread, time::Duration};
+
+use crate::{
+    state::GroupState,
+    strategy::{find_liquidations, LiquidationPlan},
+};
+
+/// Upper bound of the compute unit limit, used to simulate liquidations.
+const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+/// Share of the simulated compute units added to the limit, as state can change before landing.
+const COMPUTE_UNIT_LIMIT_MARGIN: f64 = 0.1;
+
+pub struct LiquidatorConfig {
+    pub program_id: Pubkey,
+    pub marginfi_group: Pubkey,
+    /// Marginfi account of the liquidator, owned by the signer.
+    pub liquidator_account: Pubkey,
+    /// Minimum liquidator fee, in USD, for a liquidation to be submitted.
+    pub min_profit: I80F48,
+    pub poll_interval: Duration,
+    /// Simulate liquidation transactions instead of sending them.
+    pub dry_run: bool,
+    /// Lookup tables liquidation transactions are compiled with.
+    pub lookup_tables: Vec<AddressLookupTableAccount>,
+    /// Compute unit price of liquidation transactions, in micro-lamports.
+    pub compute_unit_price: u64,
+}
+
+/// Chain access of the [`Liquidator`], implemented over RPC by [`RpcClient`].
+pub trait Transport {
+    /// Load a snapshot of `marginfi_group`.
+    fn load_group_state(&self, program_id: &Pubkey, marginfi_group: &Pubkey) -> Result<GroupState>;
+
+    /// Reload `banks` and `marginfi_accounts` of `state`, with the bank oracles.
+    fn refresh_group_state(
+        &self,
+        state: &mut GroupState,
+        banks: &[Pubkey],
+        marginfi_accounts: &[Pubkey],
+    ) -> Result<()>;
+
+    fn get_latest_blockhash(&self) -> Result<Hash>;
+
+    /// Simulate `tx`, returning the compute units consumed.
+    fn simulate_transaction(&self, tx: &VersionedTransaction) -> Result<u64>;
+
+    fn send_and_confirm_transaction(&self, tx: &VersionedTransaction) -> Result<Signature>;
+}
+
+impl Transport for RpcClient {
+    fn load_group_state(&self, program_id: &Pubkey, marginfi_group: &Pubkey) -> Result<GroupState> {
+        GroupState::load(self, program_id, marginfi_group)
+    }
+
+    fn refresh_group_state(
+        &self,
+        state: &mut GroupState,
+        banks: &[Pubkey],
+        marginfi_accounts: &[Pubkey],
+    ) -> Result<()> {
+        state.refresh(self, banks, marginfi_accounts)
+    }
+
+    fn get_latest_blockhash(&self) -> Result<Hash> {
+        Ok(RpcClient::get_latest_blockhash(self)?)
+    }
+
+    fn simulate_transaction(&self, tx: &VersionedTransaction) -> Result<u64> {
+        let simulation = RpcClient::simulate_transaction(self, tx)?.value;
+
+        match simulation.err {
+            None => Ok(simulation.units_consumed.unwrap_or_default()),
+            Some(err) => Err(anyhow!(
+                "Simulation failed: {}, logs: {:#?}",
+                err,
+                simulation.logs.unwrap_or_default()
+            )),
+        }
+    }
+
+    fn send_and_confirm_transaction(&self, tx: &VersionedTransaction) -> Result<Signature> {
+        Ok(RpcClient::send_and_confirm_transaction(self, tx)?)
+    }
+}
+
+/// Polls a marginfi group and liquidates unhealthy accounts.
+pub struct Liquidator<T: Transport = RpcClient> {
+    transport: T,
+    signer: Keypair,
+    config: LiquidatorConfig,
+}
+
+impl<T: Transport> Liquidator<T> {
+    pub fn new(transport: T, signer: Keypair, config: LiquidatorConfig) -> Self {
+        Self {
+            transport,
+            signer,
+            config,
+        }
+    }
+
+    pub fn run(&self) -> ! {
+        loop {
+            if let Err(err) = self.run_once() {
+                error!("Liquidation pass failed: {:?}", err);
+            }
+
+            thread::sleep(self.config.poll_interval);
+        }
+    }
+
+    /// Load the group, then submit the liquidation of every unhealthy account, most profitable first.
+    ///
+    /// Each account is liquidated at most once per pass. After every landed liquidation the liquidator,
+    /// the liquidatee and both banks are reloaded and the remaining liquidations planned again, as the
+    /// liquidator free health changed.
+    ///
+    /// Returns the number of liquidations that succeeded.
+    pub fn run_once(&self) -> Result<usize> {
+        let mut state = self.load_group_state()?;
+        let mut plans = self.find_liquidations(&state)?;
+
+        info!(
+            "Loaded {} accounts, {} liquidatable",
+            state.marginfi_accounts.len(),
+            plans.len()
+        );
+
+        let mut attempted = HashSet::new();
+        let mut liquidated = 0;
+        while let Some(plan) = plans
+            .iter()
+            .find(|plan| !attempted.contains(&plan.liquidatee))
+            .cloned()
+        {
+            attempted.insert(plan.liquidatee);
+
+            match self.liquidate(&state, &plan) {
+                Ok(()) => {
+                    liquidated += 1;
+
+                    if !self.config.dry_run {
+                        self.transport.refresh_group_state(
+                            &mut state,
+                            &[plan.asset_bank, plan.liab_bank],
+                            &[self.config.liquidator_account, plan.liquidatee],
+                        )?;
+                        plans = self.find_liquidations(&state)?;
+                    }
+                }
+                Err(err) => warn!("Liquidation of {} failed: {:?}", plan.liquidatee, err),
+            }
+        }
+
+        Ok(liquidated)
+    }
+
+    fn load_group_state(&self) -> Result<GroupState> {
+        self.transport
+            .load_group_state(&self.config.program_id, &self.config.marginfi_group)
+    }
+
+    fn find_liquidations(&self, state: &GroupState) -> Result<Vec<LiquidationPlan>> {
+        find_liquidations(
+            state,
+            &self.config.liquidator_account,
+            self.config.min_profit,
+        )
+    }
+
+    fn liquidate(&self, state: &GroupState, plan: &LiquidationPlan) -> Result<()> {
+        info!(
+            "Liquidating {} of bank {} for bank {} liability on {} (value: ${:.2}, profit: ${:.2})",
+            plan.asset_amount,
+            plan.asset_bank,
+            plan.liab_bank,
+            plan.liquidatee,
+            plan.asset_value,
+            plan.profit
+        );
+
+        let ixs = build_liquidation_ixs(
+            state,
+            &self.config.program_id,
+            &self.config.liquidator_account,
+            &self.signer.pubkey(),
+            plan,
+        )?;
+
+        // Simulate at the max limit to size the compute budget of the sent transaction
+        let units_consumed = self
+            .transport
+            .simulate_transaction(&self.build_transaction(&ixs, MAX_COMPUTE_UNIT_LIMIT)?)?;
+
+        if self.config.dry_run {
+            info!("Dry run succeeded, {} compute units", units_consumed);
+        } else {
+            let compute_unit_limit = min(
+                (units_consumed as f64 * (1. + COMPUTE_UNIT_LIMIT_MARGIN)) as u32,
+                MAX_COMPUTE_UNIT_LIMIT,
+            );
+            let tx = self.build_transaction(&ixs, compute_unit_limit)?;
+            let signature = self.transport.send_and_confirm_transaction(&tx)?;
+            info!("Liquidated {}: {}", plan.liquidatee, signature);
+        }
+
+        Ok(())
+    }
+
+    /// Signed v0 transaction of `ixs` with the configured lookup tables, compute unit price and
+    /// `compute_unit_limit`.
+    fn build_transaction(
+        &self,
+        ixs: &[Instruction],
+        compute_unit_limit: u32,
+    ) -> Result<VersionedTransaction> {
+        let mut budget_ixs = vec![ComputeBudgetInstruction::set_compute_unit_limit(
+            compute_unit_limit,
+        )];
+        if self.config.compute_unit_price > 0 {
+            budget_ixs.push(ComputeBudgetInstruction::set_compute_unit_price(
+                self.config.compute_unit_price,
+            ));
+        }
+
+        let message = v0::Message::try_compile(
+            &self.signer.pubkey(),
+            &[budget_ixs, ixs.to_vec()].concat(),
+            &self.config.lookup_tables,
+            self.transport.get_latest_blockhash()?,
+        )?;
+
+        Ok(VersionedTransaction::try_new(
+            VersionedMessage::V0(message),
+            &[&self.signer],
+        )?)
+    }
+}
+
+/// Instructions executing `plan` from `liquidator_account`, signed by its authority.
+pub fn build_liquidation_ixs(
+    state: &GroupState,
+    program_id: &Pubkey,
+    liquidator_account: &Pubkey,
+    signer: &Pubkey,
+    plan: &LiquidationPlan,
+) -> Result<Vec<Instruction>> {
+    let liquidator = state
+        .marginfi_accounts
+        .get(liquidator_account)
+        .ok_or_else(|| anyhow!("Liquidator account {} not found", liquidator_account))?;
+    let liquidatee = state
+        .marginfi_accounts
+        .get(&plan.liquidatee)
+        .ok_or_else(|| anyhow!("Liquidatee account {} not found", plan.liquidatee))?;
+
+    let liquidate_ix =
+        MarginfiAccountIxBuilder::new(*program_id, *liquidator_account, liquidator, &state.banks)
+            .liquidate(
+            *signer,
+            plan.asset_bank,
+            plan.liab_bank,
+            (plan.liquidatee, liquidatee),
+            plan.asset_amount,
+        )?;
+
+    Ok(vec![liquidate_ix])
+}
@@ -0,0 +1,258 @@
+use anchor_lang::Discriminator;
+use anyhow::{anyhow, Result};
+use log::{debug, warn};
+use marginfi::{
+    constants::MAX_PRICE_AGE_SEC,
+    state::{
+        marginfi_account::MarginfiAccount, marginfi_group::Bank, price::OraclePriceFeedAdapter,
+    },
+};
+use marginfi_v2_sdk::accounts::decode_account;
+use solana_account_decoder::UiAccountEncoding;
+use solana_address_lookup_table_program::state::AddressLookupTable;
+use solana_client::{
+    rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+    rpc_request::MAX_MULTIPLE_ACCOUNTS,
+};
+use solana_sdk::{
+    account::Account,
+    account_info::IntoAccountInfo,
+    address_lookup_table_account::AddressLookupTableAccount,
+    clock::{Clock, Slot},
+    pubkey::Pubkey,
+    sysvar::{self, Sysvar},
+};
+use std::collections::HashMap;
+
+/// Offset of `Bank::group`, after the discriminator, `mint` and `mint_decimals`.
+const BANK_GROUP_OFFSET: usize = 8 + 32 + 1;
+/// Offset of `MarginfiAccount::group`, after the discriminator.
+const MARGINFI_ACCOUNT_GROUP_OFFSET: usize = 8;
+
+/// Snapshot of a marginfi group, everything needed to evaluate and liquidate its accounts.
+pub struct GroupState {
+    pub banks: HashMap<Pubkey, Bank>,
+    /// Price feeds keyed by oracle address, stale or invalid oracles are left out.
+    pub price_feeds: HashMap<Pubkey, OraclePriceFeedAdapter>,
+    pub marginfi_accounts: HashMap<Pubkey, MarginfiAccount>,
+}
+
+impl GroupState {
+    /// Build a snapshot from loaded accounts.
+    ///
+    /// Banks accrue interest up to `current_timestamp`, as the program does before liquidating,
+    /// and oracles older than the program max price age are dropped.
+    pub fn new(
+        banks: HashMap<Pubkey, Bank>,
+        marginfi_accounts: HashMap<Pubkey, MarginfiAccount>,
+        oracle_accounts: Vec<(Pubkey, Account)>,
+        current_timestamp: i64,
+    ) -> Result<Self> {
+        let mut state = Self {
+            banks: HashMap::new(),
+            price_feeds: HashMap::new(),
+            marginfi_accounts: HashMap::new(),
+        };
+        state.update(banks, marginfi_accounts, oracle_accounts, current_timestamp)?;
+
+        Ok(state)
+    }
+
+    /// Replace the snapshot of reloaded accounts, the same way [`GroupState::new`] builds it.
+    ///
+    /// Oracles are parsed against the config of the banks in the snapshot after the update.
+    pub fn update(
+        &mut self,
+        mut banks: HashMap<Pubkey, Bank>,
+        marginfi_accounts: HashMap<Pubkey, MarginfiAccount>,
+        oracle_accounts: Vec<(Pubkey, Account)>,
+        current_timestamp: i64,
+    ) -> Result<()> {
+        for bank in banks.values_mut() {
+            bank.accrue_interest(current_timestamp)?;
+        }
+
+        self.banks.extend(banks);
+        self.marginfi_accounts.extend(marginfi_accounts);
+
+        let oracle_banks = self
+            .banks
+            .values()
+            .map(|bank| (bank.config.oracle_keys[0], bank))
+            .collect::<HashMap<_, _>>();
+
+        for (oracle_pk, mut account) in oracle_accounts {
+            let bank = match oracle_banks.get(&oracle_pk) {
+                Some(bank) => bank,
+                None => continue,
+            };
+            let oracle_ai = (&oracle_pk, &mut account).into_account_info();
+
+            match OraclePriceFeedAdapter::try_from_bank_config(
+                &bank.config,
+                &[oracle_ai],
+                current_timestamp,
+                MAX_PRICE_AGE_SEC,
+            ) {
+                Ok(price_feed) => {
+                    self.price_feeds.insert(oracle_pk, price_feed);
+                }
+                Err(err) => {
+                    self.price_feeds.remove(&oracle_pk);
+                    debug!("Skipping oracle {}: {}", oracle_pk, err)
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Load a snapshot of `marginfi_group` over RPC.
+    pub fn load(rpc: &RpcClient, program_id: &Pubkey, marginfi_group: &Pubkey) -> Result<Self> {
+        let banks = get_group_accounts::<Bank>(rpc, program_id, marginfi_group, BANK_GROUP_OFFSET)?;
+        let marginfi_accounts = get_group_accounts::<MarginfiAccount>(
+            rpc,
+            program_id,
+            marginfi_group,
+            MARGINFI_ACCOUNT_GROUP_OFFSET,
+        )?;
+
+        let oracle_accounts = get_oracle_accounts(rpc, &banks)?;
+        let current_timestamp = get_current_timestamp(rpc)?;
+
+        Self::new(banks, marginfi_accounts, oracle_accounts, current_timestamp)
+    }
+
+    /// Reload `bank_pks` and `marginfi_account_pks` over RPC, with the bank oracles.
+    pub fn refresh(
+        &mut self,
+        rpc: &RpcClient,
+        bank_pks: &[Pubkey],
+        marginfi_account_pks: &[Pubkey],
+    ) -> Result<()> {
+        let banks = get_accounts::<Bank>(rpc, bank_pks)?;
+        let marginfi_accounts = get_accounts::<MarginfiAccount>(rpc, marginfi_account_pks)?;
+        let oracle_accounts = get_oracle_accounts(rpc, &banks)?;
+        let current_timestamp = get_current_timestamp(rpc)?;
+
+        self.update(banks, marginfi_accounts, oracle_accounts, current_timestamp)
+    }
+}
+
+/// Load the active lookup tables of `addresses`.
+pub fn load_lookup_tables(
+    rpc: &RpcClient,
+    addresses: &[Pubkey],
+) -> Result<Vec<AddressLookupTableAccount>> {
+    let mut lookup_tables = vec![];
+
+    for chunk in addresses.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        for (address, account) in chunk.iter().zip(rpc.get_multiple_accounts(chunk)?) {
+            let account = account.ok_or_else(|| anyhow!("Lookup table {} not found", address))?;
+            let lookup_table = AddressLookupTable::deserialize(&account.data)?;
+
+            if lookup_table.meta.deactivation_slot != Slot::MAX {
+                warn!("Skipping deactivated lookup table {}", address);
+                continue;
+            }
+
+            lookup_tables.push(AddressLookupTableAccount {
+                key: *address,
+                addresses: lookup_table.addresses.to_vec(),
+            });
+        }
+    }
+
+    Ok(lookup_tables)
+}
+
+fn get_oracle_accounts(
+    rpc: &RpcClient,
+    banks: &HashMap<Pubkey, Bank>,
+) -> Result<Vec<(Pubkey, Account)>> {
+    let mut oracle_pks = banks
+        .values()
+        .map(|bank| bank.config.oracle_keys[0])
+        .collect::<Vec<_>>();
+    oracle_pks.sort();
+    oracle_pks.dedup();
+
+    let mut oracle_accounts = Vec::with_capacity(oracle_pks.len());
+    for chunk in oracle_pks.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        for (oracle_pk, account) in chunk.iter().zip(rpc.get_multiple_accounts(chunk)?) {
+            match account {
+                Some(account) => oracle_accounts.push((*oracle_pk, account)),
+                None => warn!("Oracle {} not found", oracle_pk),
+            }
+        }
+    }
+
+    Ok(oracle_accounts)
+}
+
+fn get_current_timestamp(rpc: &RpcClient) -> Result<i64> {
+    let mut clock_account = rpc.get_account(&sysvar::clock::ID)?;
+    let clock =
+        Clock::from_account_info(&(&sysvar::clock::ID, &mut clock_account).into_account_info())?;
+
+    Ok(clock.unix_timestamp)
+}
+
+fn get_accounts<T: anchor_lang::AccountDeserialize>(
+    rpc: &RpcClient,
+    addresses: &[Pubkey],
+) -> Result<HashMap<Pubkey, T>> {
+    let mut accounts = HashMap::with_capacity(addresses.len());
+    for chunk in addresses.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        for (address, account) in chunk.iter().zip(rpc.get_multiple_accounts(chunk)?) {
+            match account {
+                Some(account) => {
+                    accounts.insert(*address, decode_account::<T>(&account.data)?);
+                }
+                None => warn!("Account {} not found", address),
+            }
+        }
+    }
+
+    Ok(accounts)
+}
+
+fn get_group_accounts<T: anchor_lang::AccountDeserialize + Discriminator>(
+    rpc: &RpcClient,
+    program_id: &Pubkey,
+    marginfi_group: &Pubkey,
+    group_offset: usize,
+) -> Result<HashMap<Pubkey, T>> {
+    let accounts = rpc.get_program_accounts_with_config(
+        program_id,
+        RpcProgramAccountsConfig {
+            filters: Some(vec![
+                RpcFilterType::Memcmp(Memcmp::new_raw_bytes(0, T::DISCRIMINATOR.to_vec())),
+                RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                    group_offset,
+                    marginfi_group.to_bytes().to_vec(),
+                )),
+            ]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    )?;
+
+    Ok(accounts
+        .into_iter()
+        .filter_map(
+            |(address, account)| match decode_account::<T>(&account.data) {
+                Ok(decoded) => Some((address, decoded)),
+                Err(err) => {
+                    warn!("Failed to decode account {}: {}", address, err);
+                    None
+                }
+            },
+        )
+        .collect())
+}
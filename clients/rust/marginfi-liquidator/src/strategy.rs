@@ -0,0 +1,227 @@
+use anyhow::{anyhow, Result};
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use log::debug;
+use marginfi::{
+    constants::{LIQUIDATION_INSURANCE_FEE, LIQUIDATION_LIQUIDATOR_FEE},
+    state::{
+        health::{BalanceWithPriceFeed, HealthEngine},
+        marginfi_account::{
+            calc_amount, calc_value, BalanceSide, MarginfiAccount, RequirementType,
+            RiskRequirementType, IN_FLASHLOAN_FLAG,
+        },
+        marginfi_group::{Bank, RiskTier},
+    },
+};
+use solana_sdk::pubkey::Pubkey;
+use std::{cmp::min, ops::Not};
+
+use crate::state::GroupState;
+
+/// Share of the computed maximum that is liquidated, leaving room for price
+/// and interest moves between planning and execution.
+const SIZE_SAFETY_FACTOR: I80F48 = I80F48!(0.98);
+
+/// A liquidation of `asset_amount` native tokens of `asset_bank` collateral,
+/// paid for with `liab_bank` liability.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiquidationPlan {
+    pub liquidatee: Pubkey,
+    pub asset_bank: Pubkey,
+    pub liab_bank: Pubkey,
+    pub asset_amount: u64,
+    /// USD value of the collateral bought by the liquidator.
+    pub asset_value: I80F48,
+    /// USD value of the liquidator fee earned.
+    pub profit: I80F48,
+}
+
+/// Find every liquidatable account in the group and its most profitable liquidation,
+/// sorted by profit, best first.
+///
+/// `liquidator` is excluded, and sizes are capped by its free initial health.
+pub fn find_liquidations(
+    state: &GroupState,
+    liquidator: &Pubkey,
+    min_profit: I80F48,
+) -> Result<Vec<LiquidationPlan>> {
+    let liquidator_account = state
+        .marginfi_accounts
+        .get(liquidator)
+        .ok_or_else(|| anyhow!("Liquidator account {} not found", liquidator))?;
+
+    let mut plans = state
+        .marginfi_accounts
+        .iter()
+        .filter(|(address, _)| *address != liquidator)
+        .filter_map(|(address, marginfi_account)| {
+            match plan_liquidation(state, (address, marginfi_account), liquidator_account) {
+                Ok(plan) => plan,
+                Err(err) => {
+                    debug!("Skipping account {}: {}", address, err);
+                    None
+                }
+            }
+        })
+        .filter(|plan| plan.profit >= min_profit)
+        .collect::<Vec<_>>();
+
+    plans.sort_by(|a, b| b.profit.cmp(&a.profit));
+
+    Ok(plans)
+}
+
+/// Pick the asset/liability pair and size that liquidates the most collateral
+/// of an unhealthy account, under the program post-liquidation constraints:
+///
+/// 1. The liquidatee must stay at or below the maintenance requirement.
+/// 2. The liquidatee liability can't be fully repaid, and collateral can't be oversold.
+/// 3. The liquidator must stay above the initial requirement.
+///
+/// Returns `None` if the account is healthy or nothing can be liquidated.
+pub fn plan_liquidation(
+    state: &GroupState,
+    liquidatee: (&Pubkey, &MarginfiAccount),
+    liquidator_account: &MarginfiAccount,
+) -> Result<Option<LiquidationPlan>> {
+    let (liquidatee_pk, liquidatee_account) = liquidatee;
+
+    if liquidatee_account.get_flag(IN_FLASHLOAN_FLAG) {
+        return Ok(None);
+    }
+
+    let liquidatee_health =
+        HealthEngine::load(liquidatee_account, &state.banks, &state.price_feeds)?;
+    if !liquidatee_health.is_liquidatable()? {
+        return Ok(None);
+    }
+    let maintenance_health =
+        liquidatee_health.get_account_health(RiskRequirementType::Maintenance)?;
+
+    let liquidator_free_health =
+        HealthEngine::load(liquidator_account, &state.banks, &state.price_feeds)?
+            .get_account_health(RiskRequirementType::Initial)?;
+    if liquidator_free_health <= I80F48::ZERO {
+        return Ok(None);
+    }
+
+    let balances = liquidatee_account
+        .lending_account
+        .balances
+        .iter()
+        .filter(|balance| balance.active)
+        .map(|balance| {
+            let bank = state
+                .banks
+                .get(&balance.bank_pk)
+                .ok_or_else(|| anyhow!("Bank {} not found", balance.bank_pk))?;
+
+            Ok(BalanceWithPriceFeed {
+                bank,
+                balance,
+                price_feed: state.price_feeds.get(&bank.config.oracle_keys[0]),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let assets = balances
+        .iter()
+        .filter(|a| a.balance.is_empty(BalanceSide::Assets).not());
+    let liabilities = balances.iter().filter(|a| {
+        a.balance.is_empty(BalanceSide::Liabilities).not()
+            && a.balance.is_empty(BalanceSide::Assets)
+    });
+
+    let final_discount = I80F48::ONE - (LIQUIDATION_INSURANCE_FEE + LIQUIDATION_LIQUIDATOR_FEE);
+    let liquidator_discount = I80F48::ONE - LIQUIDATION_LIQUIDATOR_FEE;
+
+    let mut best: Option<LiquidationPlan> = None;
+
+    for asset in assets {
+        // Collateral is valued at the real time price biased low, as in the liquidation instruction
+        let asset_price = match asset.get_price(RequirementType::Maintenance, BalanceSide::Assets) {
+            Ok(price) => price,
+            Err(_) => continue,
+        };
+        let asset_amount = asset
+            .bank
+            .get_asset_amount(asset.balance.asset_shares.into())?;
+        let asset_value = calc_value(asset_amount, asset_price, asset.bank.mint_decimals, None)?;
+
+        for liability in liabilities.clone() {
+            let liab_price =
+                match liability.get_price(RequirementType::Maintenance, BalanceSide::Liabilities) {
+                    Ok(price) => price,
+                    Err(_) => continue,
+                };
+            let liab_amount = liability
+                .bank
+                .get_liability_amount(liability.balance.liability_shares.into())?;
+            let liab_value =
+                calc_value(liab_amount, liab_price, liability.bank.mint_decimals, None)?;
+
+            // Maintenance health gained by the liquidatee per USD of collateral liquidated
+            let liquidatee_health_gain = final_discount
+                * liability
+                    .bank
+                    .config
+                    .get_weight(RequirementType::Maintenance, BalanceSide::Liabilities)
+                - collateral_weight(asset.bank, RequirementType::Maintenance);
+            if liquidatee_health_gain <= I80F48::ZERO {
+                continue;
+            }
+
+            // Initial health spent by the liquidator per USD of collateral bought
+            let liquidator_health_cost = liquidator_discount
+                * liability
+                    .bank
+                    .config
+                    .get_weight(RequirementType::Initial, BalanceSide::Liabilities)
+                - collateral_weight(asset.bank, RequirementType::Initial);
+
+            let mut max_value = min(
+                min(-maintenance_health / liquidatee_health_gain, asset_value),
+                liab_value / final_discount,
+            );
+            if liquidator_health_cost > I80F48::ZERO {
+                max_value = min(max_value, liquidator_free_health / liquidator_health_cost);
+            }
+
+            let liquidated_value = max_value * SIZE_SAFETY_FACTOR;
+            let liquidated_amount =
+                calc_amount(liquidated_value, asset_price, asset.bank.mint_decimals)?
+                    .floor()
+                    .checked_to_num::<u64>()
+                    .unwrap_or(0);
+
+            if liquidated_amount == 0 {
+                continue;
+            }
+
+            let plan = LiquidationPlan {
+                liquidatee: *liquidatee_pk,
+                asset_bank: asset.balance.bank_pk,
+                liab_bank: liability.balance.bank_pk,
+                asset_amount: liquidated_amount,
+                asset_value: liquidated_value,
+                profit: liquidated_value * LIQUIDATION_LIQUIDATOR_FEE,
+            };
+
+            if best.as_ref().map_or(true, |best| plan.profit > best.profit) {
+                best = Some(plan);
+            }
+        }
+    }
+
+    Ok(best)
+}
+
+/// Weight of collateral in the health calculation, isolated risk tier collateral is not counted.
+fn collateral_weight(bank: &Bank, requirement_type: RequirementType) -> I80F48 {
+    match bank.config.risk_tier {
+        RiskTier::Collateral => bank
+            .config
+            .get_weight(requirement_type, BalanceSide::Assets),
+        RiskTier::Isolated => I80F48::ZERO,
+    }
+}
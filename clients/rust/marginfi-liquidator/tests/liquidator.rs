@@ -0,0 +1,442 @@
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use fixtures::native;
+use fixtures::prelude::*;
+use marginfi::state::{
+    marginfi_account::MarginfiAccount,
+    marginfi_group::{Bank, BankConfig, BankConfigOpt},
+};
+use marginfi_v2_liquidator::{
+    liquidator::{build_liquidation_ixs, Liquidator, LiquidatorConfig, Transport},
+    state::GroupState,
+    strategy::find_liquidations,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    compute_budget::ComputeBudgetInstruction,
+    hash::Hash,
+    pubkey::Pubkey,
+    signature::Signature,
+    signer::Signer,
+    transaction::{Transaction, VersionedTransaction},
+};
+use std::{collections::HashMap, time::Duration};
+use tokio::runtime::Runtime;
+
+type LoadedAccounts = (
+    HashMap<Pubkey, Bank>,
+    HashMap<Pubkey, MarginfiAccount>,
+    Vec<(Pubkey, Account)>,
+    i64,
+);
+
+async fn load_accounts(
+    test_f: &TestFixture,
+    banks: &[&BankFixture],
+    marginfi_accounts: &[&MarginfiAccountFixture],
+) -> LoadedAccounts {
+    let mut bank_accounts = HashMap::new();
+    let mut oracle_accounts = vec![];
+    for bank_f in banks {
+        let bank = bank_f.load().await;
+        let oracle_pk = bank.config.oracle_keys[0];
+        let oracle_account = test_f
+            .context
+            .borrow_mut()
+            .banks_client
+            .get_account(oracle_pk)
+            .await
+            .unwrap()
+            .unwrap();
+
+        oracle_accounts.push((oracle_pk, oracle_account));
+        bank_accounts.insert(bank_f.key, bank);
+    }
+
+    let mut accounts = HashMap::new();
+    for account_f in marginfi_accounts {
+        accounts.insert(account_f.key, account_f.load().await);
+    }
+
+    let clock = test_f.get_clock().await;
+
+    (
+        bank_accounts,
+        accounts,
+        oracle_accounts,
+        clock.unix_timestamp,
+    )
+}
+
+async fn load_group_state(
+    test_f: &TestFixture,
+    banks: &[&BankFixture],
+    marginfi_accounts: &[&MarginfiAccountFixture],
+) -> GroupState {
+    let (banks, marginfi_accounts, oracle_accounts, current_timestamp) =
+        load_accounts(test_f, banks, marginfi_accounts).await;
+
+    GroupState::new(banks, marginfi_accounts, oracle_accounts, current_timestamp).unwrap()
+}
+
+/// [`Transport`] over the program test banks client, driving its runtime from the synchronous liquidator.
+struct BanksTransport<'a> {
+    runtime: &'a Runtime,
+    test_f: &'a TestFixture,
+    banks: Vec<&'a BankFixture>,
+    marginfi_accounts: Vec<&'a MarginfiAccountFixture>,
+}
+
+impl Transport for BanksTransport<'_> {
+    fn load_group_state(
+        &self,
+        _program_id: &Pubkey,
+        _marginfi_group: &Pubkey,
+    ) -> anyhow::Result<GroupState> {
+        Ok(self.runtime.block_on(load_group_state(
+            self.test_f,
+            &self.banks,
+            &self.marginfi_accounts,
+        )))
+    }
+
+    fn refresh_group_state(
+        &self,
+        state: &mut GroupState,
+        banks: &[Pubkey],
+        marginfi_accounts: &[Pubkey],
+    ) -> anyhow::Result<()> {
+        let bank_fs = self
+            .banks
+            .iter()
+            .filter(|bank_f| banks.contains(&bank_f.key))
+            .copied()
+            .collect::<Vec<_>>();
+        let marginfi_account_fs = self
+            .marginfi_accounts
+            .iter()
+            .filter(|account_f| marginfi_accounts.contains(&account_f.key))
+            .copied()
+            .collect::<Vec<_>>();
+
+        let (banks, marginfi_accounts, oracle_accounts, current_timestamp) = self
+            .runtime
+            .block_on(load_accounts(self.test_f, &bank_fs, &marginfi_account_fs));
+
+        state.update(banks, marginfi_accounts, oracle_accounts, current_timestamp)
+    }
+
+    fn get_latest_blockhash(&self) -> anyhow::Result<Hash> {
+        let mut ctx = self.test_f.context.borrow_mut();
+
+        Ok(self
+            .runtime
+            .block_on(ctx.banks_client.get_latest_blockhash())?)
+    }
+
+    fn simulate_transaction(&self, tx: &VersionedTransaction) -> anyhow::Result<u64> {
+        let mut ctx = self.test_f.context.borrow_mut();
+        let simulation = self
+            .runtime
+            .block_on(ctx.banks_client.simulate_transaction(tx.clone()))?;
+
+        if let Some(Err(err)) = simulation.result {
+            anyhow::bail!("Simulation failed: {}", err);
+        }
+
+        Ok(simulation
+            .simulation_details
+            .map_or(0, |details| details.units_consumed))
+    }
+
+    fn send_and_confirm_transaction(&self, tx: &VersionedTransaction) -> anyhow::Result<Signature> {
+        let mut ctx = self.test_f.context.borrow_mut();
+        self.runtime
+            .block_on(ctx.banks_client.process_transaction(tx.clone()))?;
+
+        Ok(tx.signatures[0])
+    }
+}
+
+#[tokio::test]
+async fn liquidator_skips_healthy_accounts() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
+    let sol_bank_f = test_f.get_bank(&BankMint::SOL);
+
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(2_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank_f, 2_000)
+        .await?;
+
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let borrower_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_sol.key, sol_bank_f, 100)
+        .await?;
+    borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_usdc.key, usdc_bank_f, 100)
+        .await?;
+
+    let state = load_group_state(
+        &test_f,
+        &[usdc_bank_f, sol_bank_f],
+        &[&lender_mfi_account_f, &borrower_mfi_account_f],
+    )
+    .await;
+
+    let plans = find_liquidations(&state, &lender_mfi_account_f.key, I80F48::ZERO)?;
+
+    assert!(plans.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn liquidator_plans_executable_liquidation() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings {
+        banks: vec![
+            TestBankSetting {
+                mint: BankMint::USDC,
+                ..TestBankSetting::default()
+            },
+            TestBankSetting {
+                mint: BankMint::SOL,
+                config: Some(BankConfig {
+                    asset_weight_init: I80F48!(1).into(),
+                    asset_weight_maint: I80F48!(1).into(),
+                    ..*DEFAULT_SOL_TEST_BANK_CONFIG
+                }),
+            },
+        ],
+        group_config: Some(GroupConfig { admin: None }),
+    }))
+    .await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
+    let sol_bank_f = test_f.get_bank(&BankMint::SOL);
+
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(2_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank_f, 2_000)
+        .await?;
+
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let borrower_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+
+    // Borrower deposits 100 SOL worth of $1000 and borrows $999
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_sol.key, sol_bank_f, 100)
+        .await?;
+    borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_usdc.key, usdc_bank_f, 999)
+        .await?;
+
+    // Synthetically bring down the borrower account health by reducing the asset weights of the SOL bank
+    sol_bank_f
+        .update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.25).into()),
+            asset_weight_maint: Some(I80F48!(0.5).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    let state = load_group_state(
+        &test_f,
+        &[usdc_bank_f, sol_bank_f],
+        &[&lender_mfi_account_f, &borrower_mfi_account_f],
+    )
+    .await;
+
+    let plans = find_liquidations(&state, &lender_mfi_account_f.key, I80F48::ZERO)?;
+
+    assert_eq!(plans.len(), 1);
+    let plan = &plans[0];
+    assert_eq!(plan.liquidatee, borrower_mfi_account_f.key);
+    assert_eq!(plan.asset_bank, sol_bank_f.key);
+    assert_eq!(plan.liab_bank, usdc_bank_f.key);
+    assert!(plan.profit > I80F48::ZERO);
+
+    // Execute the planned liquidation, the program enforces every post-liquidation constraint
+    let ixs = build_liquidation_ixs(
+        &state,
+        &marginfi::id(),
+        &lender_mfi_account_f.key,
+        &test_f.payer(),
+        plan,
+    )?;
+
+    let res = {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[
+                vec![ComputeBudgetInstruction::set_compute_unit_limit(1_400_000)],
+                ixs,
+            ]
+            .concat(),
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    };
+
+    assert!(res.is_ok());
+
+    // Borrower lost exactly the planned collateral
+    let state = load_group_state(
+        &test_f,
+        &[usdc_bank_f, sol_bank_f],
+        &[&lender_mfi_account_f, &borrower_mfi_account_f],
+    )
+    .await;
+
+    let borrower_sol_amount = state.banks[&sol_bank_f.key].get_asset_amount(
+        state.marginfi_accounts[&borrower_mfi_account_f.key]
+            .lending_account
+            .balances
+            .iter()
+            .find(|balance| balance.active && balance.bank_pk == sol_bank_f.key)
+            .unwrap()
+            .asset_shares
+            .into(),
+    )?;
+
+    assert_eq!(
+        borrower_sol_amount,
+        I80F48::from_num(native!(100, "SOL") - plan.asset_amount)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn liquidator_run_once_liquidates_every_unhealthy_account() -> anyhow::Result<()> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+
+    let test_f = runtime.block_on(TestFixture::new(Some(TestSettings {
+        banks: vec![
+            TestBankSetting {
+                mint: BankMint::USDC,
+                ..TestBankSetting::default()
+            },
+            TestBankSetting {
+                mint: BankMint::SOL,
+                config: Some(BankConfig {
+                    asset_weight_init: I80F48!(1).into(),
+                    asset_weight_maint: I80F48!(1).into(),
+                    ..*DEFAULT_SOL_TEST_BANK_CONFIG
+                }),
+            },
+        ],
+        group_config: Some(GroupConfig { admin: None }),
+    })));
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
+    let sol_bank_f = test_f.get_bank(&BankMint::SOL);
+
+    let (lender_mfi_account_f, borrower_mfi_account_fs) = runtime.block_on(async {
+        let lender_mfi_account_f = test_f.create_marginfi_account().await;
+        let lender_token_account_usdc = test_f
+            .usdc_mint
+            .create_token_account_and_mint_to(3_000)
+            .await;
+        lender_mfi_account_f
+            .try_bank_deposit(lender_token_account_usdc.key, usdc_bank_f, 3_000)
+            .await?;
+
+        // Two borrowers deposit 100 SOL worth of $1000 and borrow $999
+        let mut borrower_mfi_account_fs = vec![];
+        for _ in 0..2 {
+            let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+            let borrower_token_account_sol =
+                test_f.sol_mint.create_token_account_and_mint_to(100).await;
+            let borrower_token_account_usdc =
+                test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+
+            borrower_mfi_account_f
+                .try_bank_deposit(borrower_token_account_sol.key, sol_bank_f, 100)
+                .await?;
+            borrower_mfi_account_f
+                .try_bank_borrow(borrower_token_account_usdc.key, usdc_bank_f, 999)
+                .await?;
+
+            borrower_mfi_account_fs.push(borrower_mfi_account_f);
+        }
+
+        // Synthetically bring down the borrower account health by reducing the asset weights of the SOL bank
+        sol_bank_f
+            .update_config(BankConfigOpt {
+                asset_weight_init: Some(I80F48!(0.25).into()),
+                asset_weight_maint: Some(I80F48!(0.5).into()),
+                ..Default::default()
+            })
+            .await?;
+
+        anyhow::Ok((lender_mfi_account_f, borrower_mfi_account_fs))
+    })?;
+
+    let mut marginfi_accounts = vec![&lender_mfi_account_f];
+    marginfi_accounts.extend(borrower_mfi_account_fs.iter());
+
+    let liquidator = Liquidator::new(
+        BanksTransport {
+            runtime: &runtime,
+            test_f: &test_f,
+            banks: vec![usdc_bank_f, sol_bank_f],
+            marginfi_accounts: marginfi_accounts.clone(),
+        },
+        test_f.payer_keypair(),
+        LiquidatorConfig {
+            program_id: marginfi::id(),
+            marginfi_group: test_f.marginfi_group.key,
+            liquidator_account: lender_mfi_account_f.key,
+            min_profit: I80F48::ZERO,
+            poll_interval: Duration::ZERO,
+            dry_run: false,
+            lookup_tables: vec![],
+            compute_unit_price: 0,
+        },
+    );
+
+    assert_eq!(liquidator.run_once()?, 2);
+
+    // Both borrowers lost collateral to the liquidator, which now holds it
+    let state = runtime.block_on(load_group_state(
+        &test_f,
+        &[usdc_bank_f, sol_bank_f],
+        &marginfi_accounts,
+    ));
+    let sol_amount = |marginfi_account_pk: &Pubkey| {
+        let asset_shares = state.marginfi_accounts[marginfi_account_pk]
+            .lending_account
+            .balances
+            .iter()
+            .find(|balance| balance.active && balance.bank_pk == sol_bank_f.key)
+            .map_or(I80F48::ZERO, |balance| balance.asset_shares.into());
+
+        state.banks[&sol_bank_f.key].get_asset_amount(asset_shares)
+    };
+
+    for borrower_mfi_account_f in &borrower_mfi_account_fs {
+        assert!(sol_amount(&borrower_mfi_account_f.key)? < I80F48::from_num(native!(100, "SOL")));
+    }
+    assert!(sol_amount(&lender_mfi_account_f.key)? > I80F48::ZERO);
+
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use crate::{SdkError, SdkResult};
+use anchor_lang::{AccountDeserialize, Discriminator};
+use marginfi::state::{
+    borrow_credential::BorrowCredential,
+    insurance_unstake_request::InsuranceUnstakeRequest,
+    marginfi_account::MarginfiAccount,
+    marginfi_group::{Bank, MarginfiGroup},
+    order::Order,
+};
+
+/// Any account owned by the marginfi program.
+pub enum MarginfiProgramAccount {
+    MarginfiGroup(Box<MarginfiGroup>),
+    MarginfiAccount(Box<MarginfiAccount>),
+    Bank(Box<Bank>),
+    BorrowCredential(Box<BorrowCredential>),
+    InsuranceUnstakeRequest(Box<InsuranceUnstakeRequest>),
+    Order(Box<Order>),
+}
+
+impl MarginfiProgramAccount {
+    /// Decode raw account data, dispatching on the account discriminator.
+    pub fn decode(data: &[u8]) -> SdkResult<Self> {
+        let discriminator = data.get(..8).ok_or(SdkError::UnknownAccount)?;
+
+        Ok(match discriminator {
+            d if d == MarginfiGroup::DISCRIMINATOR => {
+                Self::MarginfiGroup(Box::new(decode_account(data)?))
+            }
+            d if d == MarginfiAccount::DISCRIMINATOR => {
+                Self::MarginfiAccount(Box::new(decode_account(data)?))
+            }
+            d if d == Bank::DISCRIMINATOR => Self::Bank(Box::new(decode_account(data)?)),
+            d if d == BorrowCredential::DISCRIMINATOR => {
+                Self::BorrowCredential(Box::new(decode_account(data)?))
+            }
+            d if d == InsuranceUnstakeRequest::DISCRIMINATOR => {
+                Self::InsuranceUnstakeRequest(Box::new(decode_account(data)?))
+            }
+            d if d == Order::DISCRIMINATOR => Self::Order(Box::new(decode_account(data)?)),
+            _ => return Err(SdkError::UnknownAccount),
+        })
+    }
+}
+
+/// Decode raw account data into a known account type, checking the account discriminator.
+pub fn decode_account<T: AccountDeserialize>(data: &[u8]) -> SdkResult<T> {
+    Ok(T::try_deserialize(&mut &data[..])?)
+}
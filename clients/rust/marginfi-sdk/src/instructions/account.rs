@@ -0,0 +1,844 @@
+use super::{build_ix, get_bank};
+use crate::{
+    pda::{
+        find_bank_emissions_auth_pda, find_bank_emissions_token_account_pda,
+        find_bank_vault_authority_pda, find_borrow_credential_pda, find_order_pda,
+    },
+    remaining_accounts::{bank_oracle, observation_account_metas},
+    SdkResult,
+};
+use anchor_lang::{
+    prelude::{AccountMeta, Pubkey},
+    solana_program::{instruction::Instruction, sysvar},
+    system_program,
+};
+use anchor_spl::token;
+use fixed::types::I80F48;
+use marginfi::state::{
+    marginfi_account::{BalanceSide, MarginfiAccount},
+    marginfi_group::{Bank, BankVaultType},
+    order::{Order, OrderTrigger},
+};
+use std::collections::HashMap;
+
+/// Create a marginfi account in `marginfi_group`, `marginfi_account` must sign as a new account.
+pub fn initialize_marginfi_account(
+    program_id: Pubkey,
+    marginfi_group: Pubkey,
+    marginfi_account: Pubkey,
+    authority: Pubkey,
+    fee_payer: Pubkey,
+) -> Instruction {
+    build_ix(
+        program_id,
+        marginfi::accounts::MarginfiAccountInitialize {
+            marginfi_group,
+            marginfi_account,
+            authority,
+            fee_payer,
+            system_program: system_program::ID,
+        },
+        marginfi::instruction::MarginfiAccountInitialize {},
+        vec![],
+    )
+}
+
+/// Builds the instructions of a loaded marginfi account.
+///
+/// `banks` must hold every bank the account has an active balance in,
+/// and every bank passed to the builder methods.
+pub struct MarginfiAccountIxBuilder<'a> {
+    pub program_id: Pubkey,
+    pub address: Pubkey,
+    pub marginfi_account: &'a MarginfiAccount,
+    pub banks: &'a HashMap<Pubkey, Bank>,
+}
+
+impl<'a> MarginfiAccountIxBuilder<'a> {
+    pub fn new(
+        program_id: Pubkey,
+        address: Pubkey,
+        marginfi_account: &'a MarginfiAccount,
+        banks: &'a HashMap<Pubkey, Bank>,
+    ) -> Self {
+        Self {
+            program_id,
+            address,
+            marginfi_account,
+            banks,
+        }
+    }
+
+    fn observation_account_metas(
+        &self,
+        include_banks: &[Pubkey],
+        exclude_banks: &[Pubkey],
+    ) -> SdkResult<Vec<AccountMeta>> {
+        observation_account_metas(
+            self.marginfi_account,
+            self.banks,
+            include_banks,
+            exclude_banks,
+        )
+    }
+
+    fn liquidity_vault_authority(&self, bank_pk: &Pubkey) -> Pubkey {
+        find_bank_vault_authority_pda(bank_pk, BankVaultType::Liquidity, &self.program_id).0
+    }
+
+    pub fn deposit(
+        &self,
+        signer: Pubkey,
+        bank_pk: Pubkey,
+        signer_token_account: Pubkey,
+        amount: u64,
+    ) -> SdkResult<Instruction> {
+        let bank = get_bank(self.banks, &bank_pk)?;
+
+        let mut remaining_accounts = vec![];
+        if bank.config.is_deposit_value_limit_active() {
+            remaining_accounts.extend([
+                AccountMeta::new_readonly(bank_pk, false),
+                AccountMeta::new_readonly(bank_oracle(&bank_pk, self.banks)?, false),
+            ]);
+        }
+
+        Ok(build_ix(
+            self.program_id,
+            marginfi::accounts::LendingAccountDeposit {
+                marginfi_group: self.marginfi_account.group,
+                marginfi_account: self.address,
+                signer,
+                bank: bank_pk,
+                signer_token_account,
+                bank_liquidity_vault: bank.liquidity_vault,
+                token_program: token::ID,
+            },
+            marginfi::instruction::LendingAccountDeposit { amount },
+            remaining_accounts,
+        ))
+    }
+
+    pub fn repay(
+        &self,
+        signer: Pubkey,
+        bank_pk: Pubkey,
+        signer_token_account: Pubkey,
+        amount: u64,
+        repay_all: bool,
+    ) -> SdkResult<Instruction> {
+        let bank = get_bank(self.banks, &bank_pk)?;
+
+        Ok(build_ix(
+            self.program_id,
+            marginfi::accounts::LendingAccountRepay {
+                marginfi_group: self.marginfi_account.group,
+                marginfi_account: self.address,
+                signer,
+                bank: bank_pk,
+                signer_token_account,
+                bank_liquidity_vault: bank.liquidity_vault,
+                token_program: token::ID,
+            },
+            marginfi::instruction::LendingAccountRepay {
+                amount,
+                repay_all: repay_all.then_some(true),
+            },
+            vec![],
+        ))
+    }
+
+    pub fn withdraw(
+        &self,
+        signer: Pubkey,
+        bank_pk: Pubkey,
+        destination_token_account: Pubkey,
+        amount: u64,
+        withdraw_all: bool,
+    ) -> SdkResult<Instruction> {
+        let bank = get_bank(self.banks, &bank_pk)?;
+
+        // The pair of a fully withdrawn balance is kept for the USD outflow limit
+        let remaining_accounts = if withdraw_all && !bank.is_outflow_value_limit_active() {
+            self.observation_account_metas(&[], &[bank_pk])?
+        } else {
+            self.observation_account_metas(&[], &[])?
+        };
+
+        Ok(build_ix(
+            self.program_id,
+            marginfi::accounts::LendingAccountWithdraw {
+                marginfi_group: self.marginfi_account.group,
+                marginfi_account: self.address,
+                signer,
+                bank: bank_pk,
+                destination_token_account,
+                bank_liquidity_vault_authority: self.liquidity_vault_authority(&bank_pk),
+                bank_liquidity_vault: bank.liquidity_vault,
+                token_program: token::ID,
+            },
+            marginfi::instruction::LendingAccountWithdraw {
+                amount,
+                withdraw_all: withdraw_all.then_some(true),
+            },
+            remaining_accounts,
+        ))
+    }
+
+    pub fn borrow(
+        &self,
+        signer: Pubkey,
+        bank_pk: Pubkey,
+        destination_token_account: Pubkey,
+        amount: u64,
+    ) -> SdkResult<Instruction> {
+        let bank = get_bank(self.banks, &bank_pk)?;
+
+        let mut remaining_accounts = self.observation_account_metas(&[bank_pk], &[])?;
+
+        if bank.config.is_borrow_permissioned() {
+            remaining_accounts.push(AccountMeta::new_readonly(
+                find_borrow_credential_pda(&bank_pk, &self.address, &self.program_id).0,
+                false,
+            ));
+        }
+
+        Ok(build_ix(
+            self.program_id,
+            marginfi::accounts::LendingAccountBorrow {
+                marginfi_group: self.marginfi_account.group,
+                marginfi_account: self.address,
+                signer,
+                bank: bank_pk,
+                destination_token_account,
+                bank_liquidity_vault_authority: self.liquidity_vault_authority(&bank_pk),
+                bank_liquidity_vault: bank.liquidity_vault,
+                token_program: token::ID,
+            },
+            marginfi::instruction::LendingAccountBorrow { amount },
+            remaining_accounts,
+        ))
+    }
+
+    pub fn close_balance(&self, signer: Pubkey, bank_pk: Pubkey) -> Instruction {
+        build_ix(
+            self.program_id,
+            marginfi::accounts::LendingAccountCloseBalance {
+                marginfi_group: self.marginfi_account.group,
+                marginfi_account: self.address,
+                signer,
+                bank: bank_pk,
+            },
+            marginfi::instruction::LendingAccountCloseBalance {},
+            vec![],
+        )
+    }
+
+    /// Transfer a balance to `destination`, a marginfi account of the same authority.
+    ///
+    /// The source balance is closed if `transfer_all` is set or `amount` covers the entire balance.
+    /// Moving a liability in permissioned borrowing mode requires the destination borrow credential.
+    pub fn transfer_balance(
+        &self,
+        signer: Pubkey,
+        bank_pk: Pubkey,
+        destination: (Pubkey, &MarginfiAccount),
+        amount: u64,
+        transfer_all: bool,
+    ) -> SdkResult<Instruction> {
+        let (destination_pk, destination_marginfi_account) = destination;
+        let bank = get_bank(self.banks, &bank_pk)?;
+
+        // Invalid transfers are left for the program to reject
+        let (moves_liability, closes_balance) = self
+            .marginfi_account
+            .lending_account
+            .balances
+            .iter()
+            .find(|balance| balance.active && balance.bank_pk == bank_pk)
+            .and_then(|balance| {
+                balance
+                    .get_transfer_shares(bank, I80F48::from(amount), transfer_all)
+                    .ok()
+            })
+            .map(|(side, _, closes_balance)| {
+                (matches!(side, BalanceSide::Liabilities), closes_balance)
+            })
+            .unwrap_or((false, transfer_all));
+
+        let mut remaining_accounts = if closes_balance {
+            self.observation_account_metas(&[], &[bank_pk])?
+        } else {
+            self.observation_account_metas(&[], &[])?
+        };
+        remaining_accounts.extend(observation_account_metas(
+            destination_marginfi_account,
+            self.banks,
+            &[bank_pk],
+            &[],
+        )?);
+
+        if moves_liability && bank.config.is_borrow_permissioned() {
+            remaining_accounts.push(AccountMeta::new_readonly(
+                find_borrow_credential_pda(&bank_pk, &destination_pk, &self.program_id).0,
+                false,
+            ));
+        }
+
+        Ok(build_ix(
+            self.program_id,
+            marginfi::accounts::LendingAccountTransferBalance {
+                marginfi_group: self.marginfi_account.group,
+                source_marginfi_account: self.address,
+                destination_marginfi_account: destination_pk,
+                signer,
+                bank: bank_pk,
+            },
+            marginfi::instruction::LendingAccountTransferBalance {
+                amount,
+                transfer_all: transfer_all.then_some(true),
+            },
+            remaining_accounts,
+        ))
+    }
+
+    pub fn withdraw_emissions(
+        &self,
+        signer: Pubkey,
+        bank_pk: Pubkey,
+        destination_account: Pubkey,
+    ) -> SdkResult<Instruction> {
+        let bank = get_bank(self.banks, &bank_pk)?;
+
+        Ok(build_ix(
+            self.program_id,
+            marginfi::accounts::LendingAccountWithdrawEmissions {
+                marginfi_group: self.marginfi_account.group,
+                marginfi_account: self.address,
+                signer,
+                bank: bank_pk,
+                emissions_mint: bank.emissions_mint,
+                emissions_auth: find_bank_emissions_auth_pda(
+                    &bank_pk,
+                    &bank.emissions_mint,
+                    &self.program_id,
+                )
+                .0,
+                emissions_vault: find_bank_emissions_token_account_pda(
+                    &bank_pk,
+                    &bank.emissions_mint,
+                    &self.program_id,
+                )
+                .0,
+                destination_account,
+                token_program: token::ID,
+            },
+            marginfi::instruction::LendingAccountWithdrawEmissions {},
+            vec![],
+        ))
+    }
+
+    pub fn settle_emissions(&self, bank_pk: Pubkey) -> Instruction {
+        build_ix(
+            self.program_id,
+            marginfi::accounts::LendingAccountSettleEmissions {
+                marginfi_account: self.address,
+                bank: bank_pk,
+            },
+            marginfi::instruction::LendingAccountSettleEmissions {},
+            vec![],
+        )
+    }
+
+    /// Liquidate `liquidatee`, with this account as the liquidator.
+    pub fn liquidate(
+        &self,
+        signer: Pubkey,
+        asset_bank_pk: Pubkey,
+        liab_bank_pk: Pubkey,
+        liquidatee: (Pubkey, &MarginfiAccount),
+        asset_amount: u64,
+    ) -> SdkResult<Instruction> {
+        let (liquidatee_pk, liquidatee_marginfi_account) = liquidatee;
+        let liab_bank = get_bank(self.banks, &liab_bank_pk)?;
+
+        let mut remaining_accounts = vec![
+            AccountMeta::new_readonly(bank_oracle(&asset_bank_pk, self.banks)?, false),
+            AccountMeta::new_readonly(bank_oracle(&liab_bank_pk, self.banks)?, false),
+        ];
+        remaining_accounts
+            .extend(self.observation_account_metas(&[liab_bank_pk, asset_bank_pk], &[])?);
+        remaining_accounts.extend(observation_account_metas(
+            liquidatee_marginfi_account,
+            self.banks,
+            &[],
+            &[],
+        )?);
+        if liab_bank.config.is_borrow_permissioned() {
+            remaining_accounts.push(AccountMeta::new_readonly(
+                find_borrow_credential_pda(&liab_bank_pk, &self.address, &self.program_id).0,
+                false,
+            ));
+        }
+
+        Ok(build_ix(
+            self.program_id,
+            marginfi::accounts::LendingAccountLiquidate {
+                marginfi_group: self.marginfi_account.group,
+                asset_bank: asset_bank_pk,
+                liab_bank: liab_bank_pk,
+                liquidator_marginfi_account: self.address,
+                signer,
+                liquidatee_marginfi_account: liquidatee_pk,
+                bank_liquidity_vault_authority: self.liquidity_vault_authority(&liab_bank_pk),
+                bank_liquidity_vault: liab_bank.liquidity_vault,
+                bank_insurance_vault: liab_bank.insurance_vault,
+                token_program: token::ID,
+            },
+            marginfi::instruction::LendingAccountLiquidate { asset_amount },
+            remaining_accounts,
+        ))
+    }
+
+    pub fn create_order(
+        &self,
+        signer: Pubkey,
+        asset_bank_pk: Pubkey,
+        liab_bank_pk: Pubkey,
+        trigger: OrderTrigger,
+        trigger_value: I80F48,
+        keeper_tip: I80F48,
+    ) -> Instruction {
+        build_ix(
+            self.program_id,
+            marginfi::accounts::MarginfiAccountCreateOrder {
+                marginfi_group: self.marginfi_account.group,
+                marginfi_account: self.address,
+                signer,
+                asset_bank: asset_bank_pk,
+                liab_bank: liab_bank_pk,
+                order: find_order_pda(
+                    &self.address,
+                    &asset_bank_pk,
+                    &liab_bank_pk,
+                    trigger,
+                    &self.program_id,
+                )
+                .0,
+                system_program: system_program::ID,
+            },
+            marginfi::instruction::MarginfiAccountCreateOrder {
+                trigger,
+                trigger_value: trigger_value.into(),
+                keeper_tip: keeper_tip.into(),
+            },
+            vec![],
+        )
+    }
+
+    pub fn close_order(&self, signer: Pubkey, order: Pubkey) -> Instruction {
+        build_ix(
+            self.program_id,
+            marginfi::accounts::MarginfiAccountCloseOrder {
+                marginfi_account: self.address,
+                signer,
+                order,
+            },
+            marginfi::instruction::MarginfiAccountCloseOrder {},
+            vec![],
+        )
+    }
+
+    /// Execute a triggered order of this account, with `signer` as the keeper
+    /// repaying the liability from `signer_token_account` and receiving the collateral
+    /// in `destination_token_account`.
+    pub fn execute_order(
+        &self,
+        signer: Pubkey,
+        order_pk: Pubkey,
+        order: &Order,
+        signer_token_account: Pubkey,
+        destination_token_account: Pubkey,
+    ) -> SdkResult<Instruction> {
+        let asset_bank = get_bank(self.banks, &order.asset_bank)?;
+        let liab_bank = get_bank(self.banks, &order.liab_bank)?;
+
+        let mut remaining_accounts = vec![
+            AccountMeta::new_readonly(bank_oracle(&order.asset_bank, self.banks)?, false),
+            AccountMeta::new_readonly(bank_oracle(&order.liab_bank, self.banks)?, false),
+        ];
+        remaining_accounts.extend(self.observation_account_metas(&[], &[])?);
+
+        Ok(build_ix(
+            self.program_id,
+            marginfi::accounts::LendingAccountExecuteOrder {
+                marginfi_group: self.marginfi_account.group,
+                marginfi_account: self.address,
+                marginfi_account_authority: self.marginfi_account.authority,
+                order: order_pk,
+                asset_bank: order.asset_bank,
+                liab_bank: order.liab_bank,
+                signer,
+                signer_token_account,
+                destination_token_account,
+                asset_bank_liquidity_vault_authority: self
+                    .liquidity_vault_authority(&order.asset_bank),
+                asset_bank_liquidity_vault: asset_bank.liquidity_vault,
+                liab_bank_liquidity_vault: liab_bank.liquidity_vault,
+                token_program: token::ID,
+            },
+            marginfi::instruction::LendingAccountExecuteOrder {},
+            remaining_accounts,
+        ))
+    }
+
+    /// Start a flashloan ending at the top-level instruction `end_index`.
+    pub fn start_flashloan(&self, signer: Pubkey, end_index: u64) -> Instruction {
+        build_ix(
+            self.program_id,
+            marginfi::accounts::LendingAccountStartFlashloan {
+                marginfi_account: self.address,
+                signer,
+                ixs_sysvar: sysvar::instructions::ID,
+            },
+            marginfi::instruction::LendingAccountStartFlashloan { end_index },
+            vec![],
+        )
+    }
+
+    /// End a flashloan, the health check observes the account balances as they will be
+    /// at the end of the flashloan: `include_banks` are opened and `exclude_banks` closed within it.
+    pub fn end_flashloan(
+        &self,
+        signer: Pubkey,
+        include_banks: &[Pubkey],
+        exclude_banks: &[Pubkey],
+    ) -> SdkResult<Instruction> {
+        let mut observation_account_metas =
+            self.observation_account_metas(include_banks, exclude_banks)?;
+
+        // Banks are charged the flashloan fee when the flashloan ends
+        observation_account_metas
+            .iter_mut()
+            .step_by(2)
+            .for_each(|bank_meta| bank_meta.is_writable = true);
+
+        Ok(build_ix(
+            self.program_id,
+            marginfi::accounts::LendingAccountEndFlashloan {
+                marginfi_account: self.address,
+                signer,
+            },
+            marginfi::instruction::LendingAccountEndFlashloan {},
+            observation_account_metas,
+        ))
+    }
+
+    pub fn set_new_account_authority(
+        &self,
+        signer: Pubkey,
+        new_authority: Pubkey,
+        fee_payer: Pubkey,
+    ) -> Instruction {
+        build_ix(
+            self.program_id,
+            marginfi::accounts::MarginfiAccountSetAccountAuthority {
+                marginfi_account: self.address,
+                marginfi_group: self.marginfi_account.group,
+                signer,
+                new_authority,
+                fee_payer,
+            },
+            marginfi::instruction::SetNewAccountAuthority {},
+            vec![],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::{InstructionData, ToAccountMetas};
+    use bytemuck::Zeroable;
+    use marginfi::state::{marginfi_account::Balance, marginfi_group::BankBorrowMode};
+
+    struct TestBank {
+        pk: Pubkey,
+        oracle: Pubkey,
+    }
+
+    fn test_bank(banks: &mut HashMap<Pubkey, Bank>) -> TestBank {
+        let test_bank = TestBank {
+            pk: Pubkey::new_unique(),
+            oracle: Pubkey::new_unique(),
+        };
+
+        let mut bank = Bank {
+            mint: Pubkey::new_unique(),
+            liquidity_vault: Pubkey::new_unique(),
+            insurance_vault: Pubkey::new_unique(),
+            asset_share_value: I80F48::ONE.into(),
+            liability_share_value: I80F48::ONE.into(),
+            ..Default::default()
+        };
+        bank.config.oracle_keys[0] = test_bank.oracle;
+        banks.insert(test_bank.pk, bank);
+
+        test_bank
+    }
+
+    fn balance(bank_pk: Pubkey, asset_shares: u64, liability_shares: u64) -> Balance {
+        Balance {
+            active: true,
+            bank_pk,
+            asset_shares: I80F48::from_num(asset_shares).into(),
+            liability_shares: I80F48::from_num(liability_shares).into(),
+            emissions_outstanding: I80F48::ZERO.into(),
+            last_update: 0,
+            deferred_bad_debt: 0,
+        }
+    }
+
+    fn observation_pairs(banks: &[&TestBank]) -> Vec<AccountMeta> {
+        banks
+            .iter()
+            .flat_map(|bank| {
+                [
+                    AccountMeta::new_readonly(bank.pk, false),
+                    AccountMeta::new_readonly(bank.oracle, false),
+                ]
+            })
+            .collect()
+    }
+
+    /// Account with a 100 deposit in `sol` and a 50 liability in `usdc`.
+    fn setup() -> (
+        MarginfiAccount,
+        HashMap<Pubkey, Bank>,
+        TestBank,
+        TestBank,
+        TestBank,
+    ) {
+        let mut banks = HashMap::new();
+        let sol = test_bank(&mut banks);
+        let usdc = test_bank(&mut banks);
+        let other = test_bank(&mut banks);
+
+        let mut marginfi_account: MarginfiAccount = Zeroable::zeroed();
+        marginfi_account.group = Pubkey::new_unique();
+        marginfi_account.authority = Pubkey::new_unique();
+        marginfi_account.lending_account.balances[0] = balance(sol.pk, 100, 0);
+        marginfi_account.lending_account.balances[1] = balance(usdc.pk, 0, 50);
+
+        (marginfi_account, banks, sol, usdc, other)
+    }
+
+    #[test]
+    fn borrow_account_metas() {
+        let (marginfi_account, mut banks, sol, usdc, other) = setup();
+        banks.get_mut(&other.pk).unwrap().config.borrow_mode = BankBorrowMode::Permissioned;
+
+        let program_id = Pubkey::new_unique();
+        let address = Pubkey::new_unique();
+        let (signer, destination) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let builder = MarginfiAccountIxBuilder::new(program_id, address, &marginfi_account, &banks);
+
+        let ix = builder.borrow(signer, other.pk, destination, 10).unwrap();
+
+        let mut expected = marginfi::accounts::LendingAccountBorrow {
+            marginfi_group: marginfi_account.group,
+            marginfi_account: address,
+            signer,
+            bank: other.pk,
+            destination_token_account: destination,
+            bank_liquidity_vault_authority: find_bank_vault_authority_pda(
+                &other.pk,
+                BankVaultType::Liquidity,
+                &program_id,
+            )
+            .0,
+            bank_liquidity_vault: banks[&other.pk].liquidity_vault,
+            token_program: token::ID,
+        }
+        .to_account_metas(None);
+        // Active balances in order, then the new balance, then the borrow credential
+        expected.extend(observation_pairs(&[&sol, &usdc, &other]));
+        expected.push(AccountMeta::new_readonly(
+            find_borrow_credential_pda(&other.pk, &address, &program_id).0,
+            false,
+        ));
+
+        assert_eq!(ix.program_id, program_id);
+        assert_eq!(ix.accounts, expected);
+        assert_eq!(
+            ix.data,
+            marginfi::instruction::LendingAccountBorrow { amount: 10 }.data()
+        );
+    }
+
+    #[test]
+    fn withdraw_all_account_metas() {
+        let (marginfi_account, mut banks, sol, usdc, _) = setup();
+        {
+            let sol_bank = banks.get_mut(&sol.pk).unwrap();
+            sol_bank.outflow_limit_window = 60;
+            sol_bank.outflow_value_limit = 1_000;
+        }
+
+        let program_id = Pubkey::new_unique();
+        let address = Pubkey::new_unique();
+        let (signer, destination) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let builder = MarginfiAccountIxBuilder::new(program_id, address, &marginfi_account, &banks);
+
+        let ix = builder
+            .withdraw(signer, sol.pk, destination, 0, true)
+            .unwrap();
+
+        let mut expected = marginfi::accounts::LendingAccountWithdraw {
+            marginfi_group: marginfi_account.group,
+            marginfi_account: address,
+            signer,
+            bank: sol.pk,
+            destination_token_account: destination,
+            bank_liquidity_vault_authority: find_bank_vault_authority_pda(
+                &sol.pk,
+                BankVaultType::Liquidity,
+                &program_id,
+            )
+            .0,
+            bank_liquidity_vault: banks[&sol.pk].liquidity_vault,
+            token_program: token::ID,
+        }
+        .to_account_metas(None);
+        // The closed balance pair is kept in place for the outflow limit
+        expected.extend(observation_pairs(&[&sol, &usdc]));
+
+        assert_eq!(ix.accounts, expected);
+        assert_eq!(
+            ix.data,
+            marginfi::instruction::LendingAccountWithdraw {
+                amount: 0,
+                withdraw_all: Some(true),
+            }
+            .data()
+        );
+    }
+
+    #[test]
+    fn liquidate_account_metas() {
+        let (liquidatee, mut banks, sol, usdc, other) = setup();
+        banks.get_mut(&usdc.pk).unwrap().config.borrow_mode = BankBorrowMode::Permissioned;
+
+        let mut liquidator: MarginfiAccount = Zeroable::zeroed();
+        liquidator.group = liquidatee.group;
+        liquidator.lending_account.balances[0] = balance(other.pk, 1_000, 0);
+
+        let program_id = Pubkey::new_unique();
+        let (liquidator_pk, liquidatee_pk) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let signer = Pubkey::new_unique();
+        let builder = MarginfiAccountIxBuilder::new(program_id, liquidator_pk, &liquidator, &banks);
+
+        let ix = builder
+            .liquidate(signer, sol.pk, usdc.pk, (liquidatee_pk, &liquidatee), 10)
+            .unwrap();
+
+        let mut expected = marginfi::accounts::LendingAccountLiquidate {
+            marginfi_group: liquidatee.group,
+            asset_bank: sol.pk,
+            liab_bank: usdc.pk,
+            liquidator_marginfi_account: liquidator_pk,
+            signer,
+            liquidatee_marginfi_account: liquidatee_pk,
+            bank_liquidity_vault_authority: find_bank_vault_authority_pda(
+                &usdc.pk,
+                BankVaultType::Liquidity,
+                &program_id,
+            )
+            .0,
+            bank_liquidity_vault: banks[&usdc.pk].liquidity_vault,
+            bank_insurance_vault: banks[&usdc.pk].insurance_vault,
+            token_program: token::ID,
+        }
+        .to_account_metas(None);
+        // Asset and liability oracles, the liquidator observation accounts with the new
+        // liability and asset balances, the liquidatee's, then the liquidator credential
+        expected.push(AccountMeta::new_readonly(sol.oracle, false));
+        expected.push(AccountMeta::new_readonly(usdc.oracle, false));
+        expected.extend(observation_pairs(&[&other, &usdc, &sol]));
+        expected.extend(observation_pairs(&[&sol, &usdc]));
+        expected.push(AccountMeta::new_readonly(
+            find_borrow_credential_pda(&usdc.pk, &liquidator_pk, &program_id).0,
+            false,
+        ));
+
+        assert_eq!(ix.accounts, expected);
+        assert_eq!(
+            ix.data,
+            marginfi::instruction::LendingAccountLiquidate { asset_amount: 10 }.data()
+        );
+    }
+
+    #[test]
+    fn transfer_balance_account_metas() {
+        let (source, banks, sol, usdc, other) = setup();
+
+        let mut destination: MarginfiAccount = Zeroable::zeroed();
+        destination.group = source.group;
+        destination.lending_account.balances[0] = balance(other.pk, 10, 0);
+
+        let program_id = Pubkey::new_unique();
+        let (source_pk, destination_pk) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let signer = Pubkey::new_unique();
+        let builder = MarginfiAccountIxBuilder::new(program_id, source_pk, &source, &banks);
+
+        let expected_accounts = marginfi::accounts::LendingAccountTransferBalance {
+            marginfi_group: source.group,
+            source_marginfi_account: source_pk,
+            destination_marginfi_account: destination_pk,
+            signer,
+            bank: sol.pk,
+        }
+        .to_account_metas(None);
+
+        // Partial transfer: the source keeps the balance
+        let ix = builder
+            .transfer_balance(signer, sol.pk, (destination_pk, &destination), 40, false)
+            .unwrap();
+
+        let mut expected = expected_accounts.clone();
+        expected.extend(observation_pairs(&[&sol, &usdc]));
+        expected.extend(observation_pairs(&[&other, &sol]));
+
+        assert_eq!(ix.accounts, expected);
+
+        // Transferring the entire balance by amount closes it
+        let ix = builder
+            .transfer_balance(signer, sol.pk, (destination_pk, &destination), 100, false)
+            .unwrap();
+
+        let mut expected = expected_accounts;
+        expected.extend(observation_pairs(&[&usdc]));
+        expected.extend(observation_pairs(&[&other, &sol]));
+
+        assert_eq!(ix.accounts, expected);
+    }
+
+    #[test]
+    fn bank_not_loaded() {
+        let (marginfi_account, mut banks, _, usdc, other) = setup();
+        banks.remove(&usdc.pk);
+
+        let builder = MarginfiAccountIxBuilder::new(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            &marginfi_account,
+            &banks,
+        );
+
+        // The observation accounts of the active USDC balance can't be built
+        assert!(matches!(
+            builder.borrow(Pubkey::new_unique(), other.pk, Pubkey::new_unique(), 10),
+            Err(crate::SdkError::BankNotLoaded(bank_pk)) if bank_pk == usdc.pk
+        ));
+    }
+}
@@ -0,0 +1,655 @@
+use super::{build_ix, get_bank, oracle_account_metas};
+use crate::{
+    pda::{
+        find_bank_emissions_auth_pda, find_bank_emissions_token_account_pda,
+        find_bank_vault_authority_pda, find_bank_vault_pda, find_bank_with_seed_pda,
+        find_borrow_credential_pda, find_insurance_share_mint_pda,
+        find_insurance_unstake_request_pda,
+    },
+    remaining_accounts::{bank_oracle, observation_account_metas},
+    SdkResult,
+};
+use anchor_lang::{
+    prelude::{AccountMeta, Pubkey},
+    solana_program::{instruction::Instruction, sysvar},
+    system_program,
+};
+use anchor_spl::token;
+use marginfi::state::{
+    marginfi_account::MarginfiAccount,
+    marginfi_group::{Bank, BankConfigCompact, BankConfigOpt, BankVaultType, GroupConfig},
+};
+use std::collections::HashMap;
+
+/// Builds the admin and permissionless instructions of a marginfi group.
+pub struct MarginfiGroupIxBuilder {
+    pub program_id: Pubkey,
+    pub marginfi_group: Pubkey,
+}
+
+impl MarginfiGroupIxBuilder {
+    pub fn new(program_id: Pubkey, marginfi_group: Pubkey) -> Self {
+        Self {
+            program_id,
+            marginfi_group,
+        }
+    }
+
+    fn vault(&self, bank_pk: &Pubkey, vault_type: BankVaultType) -> Pubkey {
+        find_bank_vault_pda(bank_pk, vault_type, &self.program_id).0
+    }
+
+    fn vault_authority(&self, bank_pk: &Pubkey, vault_type: BankVaultType) -> Pubkey {
+        find_bank_vault_authority_pda(bank_pk, vault_type, &self.program_id).0
+    }
+
+    /// Create the group, `marginfi_group` must sign as a new account.
+    pub fn initialize(&self, admin: Pubkey) -> Instruction {
+        build_ix(
+            self.program_id,
+            marginfi::accounts::MarginfiGroupInitialize {
+                marginfi_group: self.marginfi_group,
+                admin,
+                system_program: system_program::ID,
+            },
+            marginfi::instruction::MarginfiGroupInitialize {},
+            vec![],
+        )
+    }
+
+    pub fn configure(&self, admin: Pubkey, config: GroupConfig) -> Instruction {
+        build_ix(
+            self.program_id,
+            marginfi::accounts::MarginfiGroupConfigure {
+                marginfi_group: self.marginfi_group,
+                admin,
+            },
+            marginfi::instruction::MarginfiGroupConfigure { config },
+            vec![],
+        )
+    }
+
+    pub fn configure_flashloan_cpi_program(
+        &self,
+        admin: Pubkey,
+        program_id: Pubkey,
+        allowed: bool,
+    ) -> Instruction {
+        build_ix(
+            self.program_id,
+            marginfi::accounts::MarginfiGroupConfigure {
+                marginfi_group: self.marginfi_group,
+                admin,
+            },
+            marginfi::instruction::MarginfiGroupConfigureFlashloanCpiProgram {
+                program_id,
+                allowed,
+            },
+            vec![],
+        )
+    }
+
+    /// Add a bank, `bank` must sign as a new account.
+    pub fn add_bank(
+        &self,
+        admin: Pubkey,
+        fee_payer: Pubkey,
+        bank: Pubkey,
+        bank_mint: Pubkey,
+        bank_config: BankConfigCompact,
+    ) -> Instruction {
+        let oracle_accounts = oracle_account_metas(&[bank_config.oracle_key]);
+
+        build_ix(
+            self.program_id,
+            marginfi::accounts::LendingPoolAddBank {
+                marginfi_group: self.marginfi_group,
+                admin,
+                fee_payer,
+                bank_mint,
+                bank,
+                liquidity_vault_authority: self.vault_authority(&bank, BankVaultType::Liquidity),
+                liquidity_vault: self.vault(&bank, BankVaultType::Liquidity),
+                insurance_vault_authority: self.vault_authority(&bank, BankVaultType::Insurance),
+                insurance_vault: self.vault(&bank, BankVaultType::Insurance),
+                fee_vault_authority: self.vault_authority(&bank, BankVaultType::Fee),
+                fee_vault: self.vault(&bank, BankVaultType::Fee),
+                rent: sysvar::rent::ID,
+                token_program: token::ID,
+                system_program: system_program::ID,
+            },
+            marginfi::instruction::LendingPoolAddBank { bank_config },
+            oracle_accounts,
+        )
+    }
+
+    /// Add a bank at the PDA derived from `bank_seed`, see [`find_bank_with_seed_pda`].
+    pub fn add_bank_with_seed(
+        &self,
+        admin: Pubkey,
+        fee_payer: Pubkey,
+        bank_mint: Pubkey,
+        bank_seed: u64,
+        bank_config: BankConfigCompact,
+    ) -> Instruction {
+        let bank = find_bank_with_seed_pda(
+            &self.marginfi_group,
+            &bank_mint,
+            bank_seed,
+            &self.program_id,
+        )
+        .0;
+        let oracle_accounts = oracle_account_metas(&[bank_config.oracle_key]);
+
+        build_ix(
+            self.program_id,
+            marginfi::accounts::LendingPoolAddBankWithSeed {
+                marginfi_group: self.marginfi_group,
+                admin,
+                fee_payer,
+                bank_mint,
+                bank,
+                liquidity_vault_authority: self.vault_authority(&bank, BankVaultType::Liquidity),
+                liquidity_vault: self.vault(&bank, BankVaultType::Liquidity),
+                insurance_vault_authority: self.vault_authority(&bank, BankVaultType::Insurance),
+                insurance_vault: self.vault(&bank, BankVaultType::Insurance),
+                fee_vault_authority: self.vault_authority(&bank, BankVaultType::Fee),
+                fee_vault: self.vault(&bank, BankVaultType::Fee),
+                rent: sysvar::rent::ID,
+                token_program: token::ID,
+                system_program: system_program::ID,
+            },
+            marginfi::instruction::LendingPoolAddBankWithSeed {
+                bank_config,
+                bank_seed,
+            },
+            oracle_accounts,
+        )
+    }
+
+    pub fn configure_bank(
+        &self,
+        admin: Pubkey,
+        bank: Pubkey,
+        bank_config_opt: BankConfigOpt,
+    ) -> Instruction {
+        let oracle_accounts = bank_config_opt
+            .oracle
+            .as_ref()
+            .map(|oracle| oracle_account_metas(&oracle.keys))
+            .unwrap_or_default();
+
+        build_ix(
+            self.program_id,
+            marginfi::accounts::LendingPoolConfigureBank {
+                marginfi_group: self.marginfi_group,
+                admin,
+                bank,
+            },
+            marginfi::instruction::LendingPoolConfigureBank { bank_config_opt },
+            oracle_accounts,
+        )
+    }
+
+    pub fn reset_bank_outflow(&self, admin: Pubkey, bank: Pubkey) -> Instruction {
+        build_ix(
+            self.program_id,
+            marginfi::accounts::LendingPoolResetBankOutflow {
+                marginfi_group: self.marginfi_group,
+                admin,
+                bank,
+            },
+            marginfi::instruction::LendingPoolResetBankOutflow {},
+            vec![],
+        )
+    }
+
+    pub fn socialize_bad_debt(&self, admin: Pubkey, bank: Pubkey) -> Instruction {
+        build_ix(
+            self.program_id,
+            marginfi::accounts::LendingPoolConfigureBank {
+                marginfi_group: self.marginfi_group,
+                admin,
+                bank,
+            },
+            marginfi::instruction::LendingPoolSocializeBadDebt {},
+            vec![],
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn setup_emissions(
+        &self,
+        admin: Pubkey,
+        bank: Pubkey,
+        emissions_mint: Pubkey,
+        emissions_funding_account: Pubkey,
+        flags: u64,
+        rate: u64,
+        total_emissions: u64,
+    ) -> Instruction {
+        build_ix(
+            self.program_id,
+            marginfi::accounts::LendingPoolSetupEmissions {
+                marginfi_group: self.marginfi_group,
+                admin,
+                bank,
+                emissions_mint,
+                emissions_auth: find_bank_emissions_auth_pda(
+                    &bank,
+                    &emissions_mint,
+                    &self.program_id,
+                )
+                .0,
+                emissions_token_account: find_bank_emissions_token_account_pda(
+                    &bank,
+                    &emissions_mint,
+                    &self.program_id,
+                )
+                .0,
+                emissions_funding_account,
+                token_program: token::ID,
+                system_program: system_program::ID,
+            },
+            marginfi::instruction::LendingPoolSetupEmissions {
+                flags,
+                rate,
+                total_emissions,
+            },
+            vec![],
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_emissions_parameters(
+        &self,
+        admin: Pubkey,
+        bank: Pubkey,
+        emissions_mint: Pubkey,
+        emissions_funding_account: Pubkey,
+        emissions_flags: Option<u64>,
+        emissions_rate: Option<u64>,
+        additional_emissions: Option<u64>,
+    ) -> Instruction {
+        build_ix(
+            self.program_id,
+            marginfi::accounts::LendingPoolUpdateEmissionsParameters {
+                marginfi_group: self.marginfi_group,
+                admin,
+                bank,
+                emissions_mint,
+                emissions_token_account: find_bank_emissions_token_account_pda(
+                    &bank,
+                    &emissions_mint,
+                    &self.program_id,
+                )
+                .0,
+                emissions_funding_account,
+                token_program: token::ID,
+            },
+            marginfi::instruction::LendingPoolUpdateEmissionsParameters {
+                emissions_flags,
+                emissions_rate,
+                additional_emissions,
+            },
+            vec![],
+        )
+    }
+
+    pub fn create_borrow_credential(
+        &self,
+        admin: Pubkey,
+        bank: Pubkey,
+        marginfi_account: Pubkey,
+        borrow_limit: u64,
+    ) -> Instruction {
+        build_ix(
+            self.program_id,
+            marginfi::accounts::LendingPoolCreateBorrowCredential {
+                marginfi_group: self.marginfi_group,
+                admin,
+                bank,
+                marginfi_account,
+                borrow_credential: find_borrow_credential_pda(
+                    &bank,
+                    &marginfi_account,
+                    &self.program_id,
+                )
+                .0,
+                system_program: system_program::ID,
+            },
+            marginfi::instruction::LendingPoolCreateBorrowCredential { borrow_limit },
+            vec![],
+        )
+    }
+
+    pub fn update_borrow_credential(
+        &self,
+        admin: Pubkey,
+        bank: Pubkey,
+        marginfi_account: Pubkey,
+        borrow_limit: u64,
+    ) -> Instruction {
+        build_ix(
+            self.program_id,
+            marginfi::accounts::LendingPoolUpdateBorrowCredential {
+                marginfi_group: self.marginfi_group,
+                admin,
+                borrow_credential: find_borrow_credential_pda(
+                    &bank,
+                    &marginfi_account,
+                    &self.program_id,
+                )
+                .0,
+            },
+            marginfi::instruction::LendingPoolUpdateBorrowCredential { borrow_limit },
+            vec![],
+        )
+    }
+
+    pub fn revoke_borrow_credential(
+        &self,
+        admin: Pubkey,
+        bank: Pubkey,
+        marginfi_account: Pubkey,
+    ) -> Instruction {
+        build_ix(
+            self.program_id,
+            marginfi::accounts::LendingPoolRevokeBorrowCredential {
+                marginfi_group: self.marginfi_group,
+                admin,
+                borrow_credential: find_borrow_credential_pda(
+                    &bank,
+                    &marginfi_account,
+                    &self.program_id,
+                )
+                .0,
+            },
+            marginfi::instruction::LendingPoolRevokeBorrowCredential {},
+            vec![],
+        )
+    }
+
+    pub fn setup_insurance_staking(
+        &self,
+        admin: Pubkey,
+        bank: Pubkey,
+        unstake_cooldown: u64,
+    ) -> Instruction {
+        build_ix(
+            self.program_id,
+            marginfi::accounts::LendingPoolSetupInsuranceStaking {
+                marginfi_group: self.marginfi_group,
+                admin,
+                bank,
+                insurance_vault: self.vault(&bank, BankVaultType::Insurance),
+                insurance_vault_authority: self.vault_authority(&bank, BankVaultType::Insurance),
+                insurance_share_mint: find_insurance_share_mint_pda(&bank, 0, &self.program_id).0,
+                rent: sysvar::rent::ID,
+                token_program: token::ID,
+                system_program: system_program::ID,
+            },
+            marginfi::instruction::LendingPoolSetupInsuranceStaking { unstake_cooldown },
+            vec![],
+        )
+    }
+
+    pub fn insurance_stake(
+        &self,
+        signer: Pubkey,
+        bank: Pubkey,
+        insurance_share_mint: Pubkey,
+        signer_token_account: Pubkey,
+        destination_share_account: Pubkey,
+        amount: u64,
+    ) -> Instruction {
+        build_ix(
+            self.program_id,
+            marginfi::accounts::LendingPoolInsuranceStake {
+                marginfi_group: self.marginfi_group,
+                bank,
+                signer,
+                signer_token_account,
+                insurance_vault: self.vault(&bank, BankVaultType::Insurance),
+                insurance_vault_authority: self.vault_authority(&bank, BankVaultType::Insurance),
+                insurance_share_mint,
+                destination_share_account,
+                token_program: token::ID,
+            },
+            marginfi::instruction::LendingPoolInsuranceStake { amount },
+            vec![],
+        )
+    }
+
+    pub fn insurance_request_unstake(
+        &self,
+        signer: Pubkey,
+        bank: Pubkey,
+        insurance_share_mint: Pubkey,
+        signer_share_account: Pubkey,
+        shares: u64,
+        request_index: u64,
+    ) -> Instruction {
+        build_ix(
+            self.program_id,
+            marginfi::accounts::LendingPoolInsuranceRequestUnstake {
+                marginfi_group: self.marginfi_group,
+                bank,
+                signer,
+                insurance_share_mint,
+                signer_share_account,
+                unstake_request: find_insurance_unstake_request_pda(
+                    &bank,
+                    &signer,
+                    request_index,
+                    &self.program_id,
+                )
+                .0,
+                token_program: token::ID,
+                system_program: system_program::ID,
+            },
+            marginfi::instruction::LendingPoolInsuranceRequestUnstake {
+                shares,
+                request_index,
+            },
+            vec![],
+        )
+    }
+
+    pub fn insurance_unstake(
+        &self,
+        signer: Pubkey,
+        bank: Pubkey,
+        insurance_share_mint: Pubkey,
+        destination_token_account: Pubkey,
+        request_index: u64,
+    ) -> Instruction {
+        build_ix(
+            self.program_id,
+            marginfi::accounts::LendingPoolInsuranceUnstake {
+                marginfi_group: self.marginfi_group,
+                bank,
+                signer,
+                destination_token_account,
+                insurance_vault: self.vault(&bank, BankVaultType::Insurance),
+                insurance_vault_authority: self.vault_authority(&bank, BankVaultType::Insurance),
+                insurance_share_mint,
+                unstake_request: find_insurance_unstake_request_pda(
+                    &bank,
+                    &signer,
+                    request_index,
+                    &self.program_id,
+                )
+                .0,
+                token_program: token::ID,
+            },
+            marginfi::instruction::LendingPoolInsuranceUnstake { request_index },
+            vec![],
+        )
+    }
+
+    /// Move insurance staking of a depleted insurance fund to the share mint of the next generation,
+    /// `insurance_share_generation` being the current generation of the bank.
+    pub fn reset_insurance_staking(
+        &self,
+        admin: Pubkey,
+        bank: Pubkey,
+        insurance_share_mint: Pubkey,
+        insurance_share_generation: u8,
+    ) -> Instruction {
+        build_ix(
+            self.program_id,
+            marginfi::accounts::LendingPoolResetInsuranceStaking {
+                marginfi_group: self.marginfi_group,
+                admin,
+                bank,
+                insurance_vault: self.vault(&bank, BankVaultType::Insurance),
+                insurance_vault_authority: self.vault_authority(&bank, BankVaultType::Insurance),
+                insurance_share_mint,
+                new_insurance_share_mint: find_insurance_share_mint_pda(
+                    &bank,
+                    insurance_share_generation.wrapping_add(1),
+                    &self.program_id,
+                )
+                .0,
+                rent: sysvar::rent::ID,
+                token_program: token::ID,
+                system_program: system_program::ID,
+            },
+            marginfi::instruction::LendingPoolResetInsuranceStaking {},
+            vec![],
+        )
+    }
+
+    /// Handle the bad debt of the bankrupt `marginfi_account` in `bank`.
+    pub fn handle_bankruptcy(
+        &self,
+        admin: Pubkey,
+        bank: Pubkey,
+        marginfi_account: (Pubkey, &MarginfiAccount),
+        banks: &HashMap<Pubkey, Bank>,
+    ) -> SdkResult<Instruction> {
+        let (marginfi_account_pk, marginfi_account) = marginfi_account;
+
+        Ok(build_ix(
+            self.program_id,
+            marginfi::accounts::LendingPoolHandleBankruptcy {
+                marginfi_group: self.marginfi_group,
+                admin,
+                bank,
+                marginfi_account: marginfi_account_pk,
+                liquidity_vault: self.vault(&bank, BankVaultType::Liquidity),
+                insurance_vault: self.vault(&bank, BankVaultType::Insurance),
+                insurance_vault_authority: self.vault_authority(&bank, BankVaultType::Insurance),
+                token_program: token::ID,
+            },
+            marginfi::instruction::LendingPoolHandleBankruptcy {},
+            observation_account_metas(marginfi_account, banks, &[bank], &[])?,
+        ))
+    }
+
+    /// Buy the `asset_bank` collateral left in the bankrupt `marginfi_account`,
+    /// paying in `debt_bank` tokens towards its deferred bad debt.
+    #[allow(clippy::too_many_arguments)]
+    pub fn auction_bad_debt_collateral(
+        &self,
+        signer: Pubkey,
+        asset_bank: Pubkey,
+        debt_bank: Pubkey,
+        marginfi_account: Pubkey,
+        signer_token_account: Pubkey,
+        destination_token_account: Pubkey,
+        banks: &HashMap<Pubkey, Bank>,
+    ) -> SdkResult<Instruction> {
+        let asset_bank_liquidity_vault = get_bank(banks, &asset_bank)?.liquidity_vault;
+        let debt_bank_liquidity_vault = get_bank(banks, &debt_bank)?.liquidity_vault;
+
+        Ok(build_ix(
+            self.program_id,
+            marginfi::accounts::LendingPoolAuctionBadDebtCollateral {
+                marginfi_group: self.marginfi_group,
+                asset_bank,
+                debt_bank,
+                marginfi_account,
+                signer,
+                signer_token_account,
+                destination_token_account,
+                asset_bank_liquidity_vault_authority: self
+                    .vault_authority(&asset_bank, BankVaultType::Liquidity),
+                asset_bank_liquidity_vault,
+                debt_bank_liquidity_vault,
+                token_program: token::ID,
+            },
+            marginfi::instruction::LendingPoolAuctionBadDebtCollateral {},
+            vec![
+                AccountMeta::new_readonly(bank_oracle(&asset_bank, banks)?, false),
+                AccountMeta::new_readonly(bank_oracle(&debt_bank, banks)?, false),
+            ],
+        ))
+    }
+
+    pub fn accrue_bank_interest(&self, bank: Pubkey) -> Instruction {
+        build_ix(
+            self.program_id,
+            marginfi::accounts::LendingPoolAccrueBankInterest {
+                marginfi_group: self.marginfi_group,
+                bank,
+            },
+            marginfi::instruction::LendingPoolAccrueBankInterest {},
+            vec![],
+        )
+    }
+
+    pub fn collect_bank_fees(&self, bank: Pubkey) -> Instruction {
+        build_ix(
+            self.program_id,
+            marginfi::accounts::LendingPoolCollectBankFees {
+                marginfi_group: self.marginfi_group,
+                bank,
+                liquidity_vault_authority: self.vault_authority(&bank, BankVaultType::Liquidity),
+                liquidity_vault: self.vault(&bank, BankVaultType::Liquidity),
+                insurance_vault: self.vault(&bank, BankVaultType::Insurance),
+                fee_vault: self.vault(&bank, BankVaultType::Fee),
+                token_program: token::ID,
+            },
+            marginfi::instruction::LendingPoolCollectBankFees {},
+            vec![],
+        )
+    }
+
+    pub fn set_account_flag(
+        &self,
+        admin: Pubkey,
+        marginfi_account: Pubkey,
+        flag: u64,
+    ) -> Instruction {
+        build_ix(
+            self.program_id,
+            marginfi::accounts::SetAccountFlag {
+                marginfi_group: self.marginfi_group,
+                marginfi_account,
+                admin,
+            },
+            marginfi::instruction::SetAccountFlag { flag },
+            vec![],
+        )
+    }
+
+    pub fn unset_account_flag(
+        &self,
+        admin: Pubkey,
+        marginfi_account: Pubkey,
+        flag: u64,
+    ) -> Instruction {
+        build_ix(
+            self.program_id,
+            marginfi::accounts::UnsetAccountFlag {
+                marginfi_group: self.marginfi_group,
+                marginfi_account,
+                admin,
+            },
+            marginfi::instruction::UnsetAccountFlag { flag },
+            vec![],
+        )
+    }
+}
@@ -0,0 +1,50 @@
+//! Instruction builders.
+//!
+//! [`MarginfiAccountIxBuilder`] builds the instructions of a loaded marginfi account,
+//! [`MarginfiGroupIxBuilder`] the admin and permissionless instructions of a group.
+//! Banks touched by an instruction are looked up in the loaded banks to derive vaults,
+//! oracles and health check observation accounts.
+
+mod account;
+mod group;
+
+pub use account::{initialize_marginfi_account, MarginfiAccountIxBuilder};
+pub use group::MarginfiGroupIxBuilder;
+
+use crate::{SdkError, SdkResult};
+use anchor_lang::{
+    prelude::{AccountMeta, Pubkey},
+    solana_program::instruction::Instruction,
+    InstructionData, ToAccountMetas,
+};
+use marginfi::state::marginfi_group::Bank;
+use std::collections::HashMap;
+
+fn build_ix(
+    program_id: Pubkey,
+    accounts: impl ToAccountMetas,
+    data: impl InstructionData,
+    remaining_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let mut accounts = accounts.to_account_metas(Some(true));
+    accounts.extend(remaining_accounts);
+
+    Instruction {
+        program_id,
+        accounts,
+        data: data.data(),
+    }
+}
+
+fn get_bank<'a>(banks: &'a HashMap<Pubkey, Bank>, bank_pk: &Pubkey) -> SdkResult<&'a Bank> {
+    banks.get(bank_pk).ok_or(SdkError::BankNotLoaded(*bank_pk))
+}
+
+/// Oracle accounts of a bank config, as expected by the program oracle setup validation.
+fn oracle_account_metas(oracle_keys: &[Pubkey]) -> Vec<AccountMeta> {
+    oracle_keys
+        .iter()
+        .filter(|key| **key != Pubkey::default())
+        .map(|key| AccountMeta::new_readonly(*key, false))
+        .collect()
+}
@@ -0,0 +1,35 @@
+//! Rust SDK for the marginfi v2 program.
+//!
+//! - [`instructions`]: instruction builders for every program instruction, deriving PDAs
+//!   and assembling remaining accounts from loaded account state
+//! - [`pda`]: program derived addresses
+//! - [`accounts`]: program account decoders
+//! - [`events`]: program event decoders
+//! - [`remaining_accounts`]: health check observation accounts of a marginfi account
+//! - [`health`]: account health, max borrow/withdraw and liquidation price, using the program's own risk math
+
+pub mod accounts;
+pub mod instructions;
+pub mod pda;
+pub mod remaining_accounts;
+
+pub use marginfi;
+pub use marginfi::state::health;
+pub use marginfi_v2_events as events;
+
+use anchor_lang::prelude::Pubkey;
+use thiserror::Error;
+
+pub type SdkResult<T> = Result<T, SdkError>;
+
+#[derive(Debug, Error)]
+pub enum SdkError {
+    #[error("bank {0} is not loaded")]
+    BankNotLoaded(Pubkey),
+    #[error("bank {0} has no oracle configured")]
+    MissingBankOracle(Pubkey),
+    #[error("account data does not match any marginfi account type")]
+    UnknownAccount,
+    #[error("failed to deserialize account: {0}")]
+    InvalidAccountData(#[from] anchor_lang::error::Error),
+}
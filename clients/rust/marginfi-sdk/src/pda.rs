@@ -0,0 +1,138 @@
+use anchor_lang::prelude::Pubkey;
+use marginfi::{
+    bank_authority_seed, bank_seed,
+    constants::{
+        BORROW_CREDENTIAL_SEED, EMISSIONS_AUTH_SEED, EMISSIONS_TOKEN_ACCOUNT_SEED,
+        INSURANCE_SHARE_MINT_SEED, INSURANCE_UNSTAKE_REQUEST_SEED, ORDER_SEED,
+    },
+    state::{marginfi_group::BankVaultType, order::OrderTrigger},
+};
+
+pub fn find_bank_vault_pda(
+    bank: &Pubkey,
+    vault_type: BankVaultType,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(bank_seed!(vault_type, bank), program_id)
+}
+
+pub fn find_bank_vault_authority_pda(
+    bank: &Pubkey,
+    vault_type: BankVaultType,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(bank_authority_seed!(vault_type, bank), program_id)
+}
+
+/// Bank created with `lending_pool_add_bank_with_seed`.
+pub fn find_bank_with_seed_pda(
+    marginfi_group: &Pubkey,
+    bank_mint: &Pubkey,
+    bank_seed: u64,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            marginfi_group.as_ref(),
+            bank_mint.as_ref(),
+            &bank_seed.to_le_bytes(),
+        ],
+        program_id,
+    )
+}
+
+pub fn find_bank_emissions_auth_pda(
+    bank: &Pubkey,
+    emissions_mint: &Pubkey,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            EMISSIONS_AUTH_SEED.as_bytes(),
+            bank.as_ref(),
+            emissions_mint.as_ref(),
+        ],
+        program_id,
+    )
+}
+
+pub fn find_bank_emissions_token_account_pda(
+    bank: &Pubkey,
+    emissions_mint: &Pubkey,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            EMISSIONS_TOKEN_ACCOUNT_SEED.as_bytes(),
+            bank.as_ref(),
+            emissions_mint.as_ref(),
+        ],
+        program_id,
+    )
+}
+
+pub fn find_borrow_credential_pda(
+    bank: &Pubkey,
+    marginfi_account: &Pubkey,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            BORROW_CREDENTIAL_SEED.as_bytes(),
+            bank.as_ref(),
+            marginfi_account.as_ref(),
+        ],
+        program_id,
+    )
+}
+
+pub fn find_insurance_share_mint_pda(
+    bank: &Pubkey,
+    generation: u8,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            INSURANCE_SHARE_MINT_SEED.as_bytes(),
+            bank.as_ref(),
+            &[generation],
+        ],
+        program_id,
+    )
+}
+
+pub fn find_insurance_unstake_request_pda(
+    bank: &Pubkey,
+    authority: &Pubkey,
+    request_index: u64,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            INSURANCE_UNSTAKE_REQUEST_SEED.as_bytes(),
+            bank.as_ref(),
+            authority.as_ref(),
+            &request_index.to_le_bytes(),
+        ],
+        program_id,
+    )
+}
+
+pub fn find_order_pda(
+    marginfi_account: &Pubkey,
+    asset_bank: &Pubkey,
+    liab_bank: &Pubkey,
+    trigger: OrderTrigger,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            ORDER_SEED.as_bytes(),
+            marginfi_account.as_ref(),
+            asset_bank.as_ref(),
+            liab_bank.as_ref(),
+            &[trigger as u8],
+        ],
+        program_id,
+    )
+}
@@ -0,0 +1,58 @@
+use crate::{SdkError, SdkResult};
+use anchor_lang::prelude::{AccountMeta, Pubkey};
+use marginfi::state::{marginfi_account::MarginfiAccount, marginfi_group::Bank};
+use std::collections::HashMap;
+
+/// Health check observation accounts of `marginfi_account`, laid out as (bank, oracle) pairs
+/// in the order of the account balances, as expected by the program risk engine.
+///
+/// `include_banks` are appended when not already active, for balances created by the instruction,
+/// `exclude_banks` are removed, for balances closed by the instruction.
+pub fn observation_account_metas(
+    marginfi_account: &MarginfiAccount,
+    banks: &HashMap<Pubkey, Bank>,
+    include_banks: &[Pubkey],
+    exclude_banks: &[Pubkey],
+) -> SdkResult<Vec<AccountMeta>> {
+    let mut bank_pks = marginfi_account
+        .lending_account
+        .balances
+        .iter()
+        .filter_map(|balance| balance.active.then_some(balance.bank_pk))
+        .collect::<Vec<_>>();
+
+    for bank_pk in include_banks {
+        if !bank_pks.contains(bank_pk) {
+            bank_pks.push(*bank_pk);
+        }
+    }
+
+    bank_pks.retain(|bank_pk| !exclude_banks.contains(bank_pk));
+
+    let mut account_metas = Vec::with_capacity(bank_pks.len() * 2);
+
+    for bank_pk in bank_pks {
+        account_metas.push(AccountMeta::new_readonly(bank_pk, false));
+        account_metas.push(AccountMeta::new_readonly(
+            bank_oracle(&bank_pk, banks)?,
+            false,
+        ));
+    }
+
+    Ok(account_metas)
+}
+
+/// Primary oracle account of a loaded bank.
+pub fn bank_oracle(bank_pk: &Pubkey, banks: &HashMap<Pubkey, Bank>) -> SdkResult<Pubkey> {
+    let bank = banks
+        .get(bank_pk)
+        .ok_or(SdkError::BankNotLoaded(*bank_pk))?;
+
+    let oracle = bank.config.oracle_keys[0];
+
+    if oracle == Pubkey::default() {
+        return Err(SdkError::MissingBankOracle(*bank_pk));
+    }
+
+    Ok(oracle)
+}
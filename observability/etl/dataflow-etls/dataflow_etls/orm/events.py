@@ -14,14 +14,29 @@ MARGINFI_GROUP_CONFIGURE_EVENT_NAME = 'MarginfiGroupConfigureEvent'
 LENDING_POOL_BANK_CREATE_EVENT_NAME = 'LendingPoolBankCreateEvent'
 LENDING_POOL_BANK_CONFIGURE_EVENT_NAME = 'LendingPoolBankConfigureEvent'
 LENDING_POOL_BANK_ACCRUE_INTEREST_EVENT_NAME = 'LendingPoolBankAccrueInterestEvent'
+LENDING_POOL_BANK_ACCRUE_INTEREST_EVENT_V2_NAME = 'LendingPoolBankAccrueInterestEventV2'
 LENDING_POOL_BANK_COLLECT_FEES_EVENT_NAME = 'LendingPoolBankCollectFeesEvent'
 LENDING_POOL_BANK_HANDLE_BANKRUPTCY_EVENT_NAME = 'LendingPoolBankHandleBankruptcyEvent'
+LENDING_POOL_BANK_SETUP_EMISSIONS_EVENT_NAME = 'LendingPoolBankSetupEmissionsEvent'
+LENDING_POOL_BANK_UPDATE_EMISSIONS_EVENT_NAME = 'LendingPoolBankUpdateEmissionsEvent'
 MARGINFI_ACCOUNT_CREATE_EVENT_NAME = 'MarginfiAccountCreateEvent'
 LENDING_ACCOUNT_DEPOSIT_EVENT_NAME = 'LendingAccountDepositEvent'
 LENDING_ACCOUNT_WITHDRAW_EVENT_NAME = 'LendingAccountWithdrawEvent'
 LENDING_ACCOUNT_BORROW_EVENT_NAME = 'LendingAccountBorrowEvent'
 LENDING_ACCOUNT_REPAY_EVENT_NAME = 'LendingAccountRepayEvent'
+LENDING_ACCOUNT_DEPOSIT_EVENT_V2_NAME = 'LendingAccountDepositEventV2'
+LENDING_ACCOUNT_WITHDRAW_EVENT_V2_NAME = 'LendingAccountWithdrawEventV2'
+LENDING_ACCOUNT_BORROW_EVENT_V2_NAME = 'LendingAccountBorrowEventV2'
+LENDING_ACCOUNT_REPAY_EVENT_V2_NAME = 'LendingAccountRepayEventV2'
 LENDING_ACCOUNT_LIQUIDATE_EVENT_NAME = 'LendingAccountLiquidateEvent'
+LENDING_ACCOUNT_LIQUIDATE_EVENT_V2_NAME = 'LendingAccountLiquidateEventV2'
+MARGINFI_ACCOUNT_SET_FLAG_EVENT_NAME = 'MarginfiAccountSetFlagEvent'
+MARGINFI_ACCOUNT_UNSET_FLAG_EVENT_NAME = 'MarginfiAccountUnsetFlagEvent'
+MARGINFI_ACCOUNT_TRANSFER_ACCOUNT_AUTHORITY_EVENT_NAME = 'MarginfiAccountTransferAccountAuthorityEvent'
+LENDING_ACCOUNT_START_FLASHLOAN_EVENT_NAME = 'LendingAccountStartFlashloanEvent'
+LENDING_ACCOUNT_END_FLASHLOAN_EVENT_NAME = 'LendingAccountEndFlashloanEvent'
+LENDING_ACCOUNT_SETTLE_EMISSIONS_EVENT_NAME = 'LendingAccountSettleEmissionsEvent'
+LENDING_ACCOUNT_WITHDRAW_EMISSIONS_EVENT_NAME = 'LendingAccountWithdrawEmissionsEvent'
 
 
 @dataclass
@@ -244,8 +259,41 @@ class LendingPoolBankConfigureRecord(GroupRecordBase):
             event.data.config.interest_rate_config.protocol_ir_fee, wrapped_i80f48_to_float)
 
 
+# Post-operation bank state, only carried by V2 events
+BANK_STATE_SCHEMA = ",".join(
+    [
+        "asset_share_value:BIGNUMERIC",
+        "liability_share_value:BIGNUMERIC",
+        "total_asset_shares:BIGNUMERIC",
+        "total_liability_shares:BIGNUMERIC",
+        "lending_rate:BIGNUMERIC",
+        "borrowing_rate:BIGNUMERIC",
+    ]
+)
+
+
+@dataclass
+class BankStateRecordMixin:
+    asset_share_value: Optional[float]
+    liability_share_value: Optional[float]
+    total_asset_shares: Optional[float]
+    total_liability_shares: Optional[float]
+    lending_rate: Optional[float]
+    borrowing_rate: Optional[float]
+
+    def _set_bank_state(self, event: Event):
+        bank_state = getattr(event.data, "bank_state", None)
+
+        self.asset_share_value = map_optional(bank_state, lambda state: wrapped_i80f48_to_float(state.asset_share_value))
+        self.liability_share_value = map_optional(bank_state, lambda state: wrapped_i80f48_to_float(state.liability_share_value))
+        self.total_asset_shares = map_optional(bank_state, lambda state: wrapped_i80f48_to_float(state.total_asset_shares))
+        self.total_liability_shares = map_optional(bank_state, lambda state: wrapped_i80f48_to_float(state.total_liability_shares))
+        self.lending_rate = map_optional(bank_state, lambda state: state.lending_rate)
+        self.borrowing_rate = map_optional(bank_state, lambda state: state.borrowing_rate)
+
+
 @dataclass
-class LendingPoolBankAccrueInterestRecord(GroupRecordBase):
+class LendingPoolBankAccrueInterestRecord(GroupRecordBase, BankStateRecordMixin):
     SCHEMA = GroupRecordBase.SCHEMA + "," + ",".join(
         [
             "bank:STRING",
@@ -254,7 +302,7 @@ class LendingPoolBankAccrueInterestRecord(GroupRecordBase):
             "fees_collected:BIGNUMERIC",
             "insurance_collected:BIGNUMERIC",
         ]
-    )
+    ) + "," + BANK_STATE_SCHEMA
 
     bank: str
     mint: str
@@ -270,6 +318,7 @@ class LendingPoolBankAccrueInterestRecord(GroupRecordBase):
         self.delta = event.data.delta
         self.fees_collected = event.data.fees_collected
         self.insurance_collected = event.data.insurance_collected
+        self._set_bank_state(event)
 
 
 @dataclass
@@ -331,6 +380,65 @@ class LendingPoolBankHandleBankruptcyRecord(GroupRecordBase):
         self.socialized_amount = event.data.socialized_amount
 
 
+@dataclass
+class LendingPoolBankSetupEmissionsRecord(GroupRecordBase):
+    SCHEMA = GroupRecordBase.SCHEMA + "," + ",".join(
+        [
+            "bank:STRING",
+            "emissions_mint:STRING",
+            "emissions_flags:BIGNUMERIC",
+            "emissions_rate:BIGNUMERIC",
+            "total_emissions:BIGNUMERIC",
+        ]
+    )
+
+    bank: str
+    emissions_mint: str
+    emissions_flags: int
+    emissions_rate: int
+    total_emissions: int
+
+    def __init__(self, event: Event, instruction: "InstructionWithLogs", instruction_args: NamedInstruction):
+        super().__init__(event, instruction, instruction_args)
+
+        self.bank = str(event.data.bank)
+        self.emissions_mint = str(event.data.emissions_mint)
+        self.emissions_flags = event.data.emissions_flags
+        self.emissions_rate = event.data.emissions_rate
+        self.total_emissions = event.data.total_emissions
+
+
+@dataclass
+class LendingPoolBankUpdateEmissionsRecord(GroupRecordBase):
+    SCHEMA = GroupRecordBase.SCHEMA + "," + ",".join(
+        [
+            "bank:STRING",
+            "emissions_mint:STRING",
+            "emissions_flags:BIGNUMERIC",
+            "emissions_rate:BIGNUMERIC",
+            "additional_emissions:BIGNUMERIC",
+            "emissions_remaining:BIGNUMERIC",
+        ]
+    )
+
+    bank: str
+    emissions_mint: str
+    emissions_flags: int
+    emissions_rate: int
+    additional_emissions: int
+    emissions_remaining: float
+
+    def __init__(self, event: Event, instruction: "InstructionWithLogs", instruction_args: NamedInstruction):
+        super().__init__(event, instruction, instruction_args)
+
+        self.bank = str(event.data.bank)
+        self.emissions_mint = str(event.data.emissions_mint)
+        self.emissions_flags = event.data.emissions_flags
+        self.emissions_rate = event.data.emissions_rate
+        self.additional_emissions = event.data.additional_emissions
+        self.emissions_remaining = event.data.emissions_remaining
+
+
 # Account events
 
 @dataclass
@@ -342,7 +450,7 @@ class MarginfiAccountCreateRecord(AccountRecordBase):
 
 
 @dataclass
-class LendingAccountChangeLiquidityRecord(AccountRecordBase):
+class LendingAccountChangeLiquidityRecord(AccountRecordBase, BankStateRecordMixin):
     SCHEMA = AccountRecordBase.SCHEMA + "," + ",".join(
         [
             "operation:STRING",
@@ -351,7 +459,7 @@ class LendingAccountChangeLiquidityRecord(AccountRecordBase):
             "amount:BIGNUMERIC",
             "balance_closed:BOOLEAN"
         ]
-    )
+    ) + "," + BANK_STATE_SCHEMA
 
     operation: str
     bank: str
@@ -362,13 +470,15 @@ class LendingAccountChangeLiquidityRecord(AccountRecordBase):
     def __init__(self, event: Event, instruction: "InstructionWithLogs", instruction_args: NamedInstruction):
         super().__init__(event, instruction, instruction_args)
 
-        self.operation = event.name.removeprefix("LendingAccount").removesuffix("Event").lower()
+        self.operation = event.name.removeprefix("LendingAccount").removesuffix("V2").removesuffix("Event").lower()
         self.bank = str(event.data.bank)
         self.mint = str(event.data.mint)
         self.amount = event.data.amount
         self.balance_closed = False
-        if event.name == LENDING_ACCOUNT_REPAY_EVENT_NAME or event.name == LENDING_ACCOUNT_WITHDRAW_EVENT_NAME:
+        if event.name in [LENDING_ACCOUNT_REPAY_EVENT_NAME, LENDING_ACCOUNT_WITHDRAW_EVENT_NAME,
+                          LENDING_ACCOUNT_REPAY_EVENT_V2_NAME, LENDING_ACCOUNT_WITHDRAW_EVENT_V2_NAME]:
             self.balance_closed = event.data.close_balance
+        self._set_bank_state(event)
 
 
 @dataclass
@@ -391,6 +501,10 @@ class LendingAccountLiquidateRecord(AccountRecordBase):
             "liquidatee_liability_post_balance:BIGNUMERIC",
             "liquidator_asset_post_balance:BIGNUMERIC",
             "liquidator_liability_post_balance:BIGNUMERIC",
+            "asset_bank_asset_share_value:BIGNUMERIC",
+            "asset_bank_liability_share_value:BIGNUMERIC",
+            "liability_bank_asset_share_value:BIGNUMERIC",
+            "liability_bank_liability_share_value:BIGNUMERIC",
         ]
     )
 
@@ -410,6 +524,10 @@ class LendingAccountLiquidateRecord(AccountRecordBase):
     liquidatee_liability_post_balance: float
     liquidator_asset_post_balance: float
     liquidator_liability_post_balance: float
+    asset_bank_asset_share_value: Optional[float]
+    asset_bank_liability_share_value: Optional[float]
+    liability_bank_asset_share_value: Optional[float]
+    liability_bank_liability_share_value: Optional[float]
 
     def __init__(self, event: Event, instruction: "InstructionWithLogs", instruction_args: NamedInstruction):
         super().__init__(event, instruction, instruction_args)
@@ -431,6 +549,127 @@ class LendingAccountLiquidateRecord(AccountRecordBase):
         self.liquidator_asset_post_balance = event.data.post_balances.liquidator_asset_balance
         self.liquidator_liability_post_balance = event.data.post_balances.liquidator_liability_balance
 
+        # Post-liquidation bank share values, only carried by V2 events
+        asset_bank_state = getattr(event.data, "asset_bank_state", None)
+        liability_bank_state = getattr(event.data, "liability_bank_state", None)
+        self.asset_bank_asset_share_value = map_optional(
+            asset_bank_state, lambda state: wrapped_i80f48_to_float(state.asset_share_value))
+        self.asset_bank_liability_share_value = map_optional(
+            asset_bank_state, lambda state: wrapped_i80f48_to_float(state.liability_share_value))
+        self.liability_bank_asset_share_value = map_optional(
+            liability_bank_state, lambda state: wrapped_i80f48_to_float(state.asset_share_value))
+        self.liability_bank_liability_share_value = map_optional(
+            liability_bank_state, lambda state: wrapped_i80f48_to_float(state.liability_share_value))
+
+
+@dataclass
+class MarginfiAccountChangeFlagRecord(AccountRecordBase):
+    SCHEMA = AccountRecordBase.SCHEMA + "," + ",".join(
+        [
+            "operation:STRING",
+            "flag:BIGNUMERIC",
+            "account_flags:BIGNUMERIC",
+        ]
+    )
+
+    operation: str
+    flag: int
+    account_flags: int
+
+    def __init__(self, event: Event, instruction: "InstructionWithLogs", instruction_args: NamedInstruction):
+        super().__init__(event, instruction, instruction_args)
+
+        self.operation = event.name.removeprefix("MarginfiAccount").removesuffix("FlagEvent").lower()
+        self.flag = event.data.flag
+        self.account_flags = event.data.account_flags
+
+
+@dataclass
+class MarginfiAccountTransferAccountAuthorityRecord(AccountRecordBase):
+    SCHEMA = AccountRecordBase.SCHEMA + "," + ",".join(
+        [
+            "old_account_authority:STRING",
+            "new_account_authority:STRING",
+        ]
+    )
+
+    old_account_authority: str
+    new_account_authority: str
+
+    def __init__(self, event: Event, instruction: "InstructionWithLogs", instruction_args: NamedInstruction):
+        super().__init__(event, instruction, instruction_args)
+
+        self.old_account_authority = str(event.data.old_account_authority)
+        self.new_account_authority = str(event.data.new_account_authority)
+
+
+@dataclass
+class LendingAccountFlashloanRecord(AccountRecordBase):
+    SCHEMA = AccountRecordBase.SCHEMA + "," + ",".join(
+        [
+            "operation:STRING",
+            "end_index:INTEGER",
+        ]
+    )
+
+    operation: str
+    end_index: Optional[int]
+
+    def __init__(self, event: Event, instruction: "InstructionWithLogs", instruction_args: NamedInstruction):
+        super().__init__(event, instruction, instruction_args)
+
+        self.operation = event.name.removeprefix("LendingAccount").removesuffix("FlashloanEvent").lower()
+        self.end_index = None
+        if event.name == LENDING_ACCOUNT_START_FLASHLOAN_EVENT_NAME:
+            self.end_index = event.data.end_index
+
+
+@dataclass
+class LendingAccountSettleEmissionsRecord(AccountRecordBase):
+    SCHEMA = AccountRecordBase.SCHEMA + "," + ",".join(
+        [
+            "bank:STRING",
+            "emissions_mint:STRING",
+            "amount:BIGNUMERIC",
+        ]
+    )
+
+    bank: str
+    emissions_mint: str
+    amount: float
+
+    def __init__(self, event: Event, instruction: "InstructionWithLogs", instruction_args: NamedInstruction):
+        super().__init__(event, instruction, instruction_args)
+
+        self.bank = str(event.data.bank)
+        self.emissions_mint = str(event.data.emissions_mint)
+        self.amount = event.data.amount
+
+
+@dataclass
+class LendingAccountWithdrawEmissionsRecord(AccountRecordBase):
+    SCHEMA = AccountRecordBase.SCHEMA + "," + ",".join(
+        [
+            "bank:STRING",
+            "emissions_mint:STRING",
+            "destination_account:STRING",
+            "amount:BIGNUMERIC",
+        ]
+    )
+
+    bank: str
+    emissions_mint: str
+    destination_account: str
+    amount: int
+
+    def __init__(self, event: Event, instruction: "InstructionWithLogs", instruction_args: NamedInstruction):
+        super().__init__(event, instruction, instruction_args)
+
+        self.bank = str(event.data.bank)
+        self.emissions_mint = str(event.data.emissions_mint)
+        self.destination_account = str(event.data.destination_account)
+        self.amount = event.data.amount
+
 
 EventRecordTypes = [MarginfiGroupCreateRecord,
                     MarginfiGroupConfigureRecord,
@@ -439,9 +678,16 @@ EventRecordTypes = [MarginfiGroupCreateRecord,
                     LendingPoolBankAccrueInterestRecord,
                     LendingPoolBankCollectFeesRecord,
                     LendingPoolBankHandleBankruptcyRecord,
+                    LendingPoolBankSetupEmissionsRecord,
+                    LendingPoolBankUpdateEmissionsRecord,
                     MarginfiAccountCreateRecord,
                     LendingAccountChangeLiquidityRecord,
-                    LendingAccountLiquidateRecord]
+                    LendingAccountLiquidateRecord,
+                    MarginfiAccountChangeFlagRecord,
+                    MarginfiAccountTransferAccountAuthorityRecord,
+                    LendingAccountFlashloanRecord,
+                    LendingAccountSettleEmissionsRecord,
+                    LendingAccountWithdrawEmissionsRecord]
 
 EventRecord = Union[
     MarginfiGroupCreateRecord,
@@ -451,9 +697,16 @@ EventRecord = Union[
     LendingPoolBankAccrueInterestRecord,
     LendingPoolBankCollectFeesRecord,
     LendingPoolBankHandleBankruptcyRecord,
+    LendingPoolBankSetupEmissionsRecord,
+    LendingPoolBankUpdateEmissionsRecord,
     MarginfiAccountCreateRecord,
     LendingAccountChangeLiquidityRecord,
-    LendingAccountLiquidateRecord
+    LendingAccountLiquidateRecord,
+    MarginfiAccountChangeFlagRecord,
+    MarginfiAccountTransferAccountAuthorityRecord,
+    LendingAccountFlashloanRecord,
+    LendingAccountSettleEmissionsRecord,
+    LendingAccountWithdrawEmissionsRecord
 ]
 
 EVENT_TO_RECORD_TYPE: Dict[str, Type[EventRecord]] = {
@@ -462,6 +715,7 @@ EVENT_TO_RECORD_TYPE: Dict[str, Type[EventRecord]] = {
     f"{LENDING_POOL_BANK_CREATE_EVENT_NAME}": LendingPoolBankCreateRecord,
     f"{LENDING_POOL_BANK_CONFIGURE_EVENT_NAME}": LendingPoolBankConfigureRecord,
     f"{LENDING_POOL_BANK_ACCRUE_INTEREST_EVENT_NAME}": LendingPoolBankAccrueInterestRecord,
+    f"{LENDING_POOL_BANK_ACCRUE_INTEREST_EVENT_V2_NAME}": LendingPoolBankAccrueInterestRecord,
     f"{LENDING_POOL_BANK_COLLECT_FEES_EVENT_NAME}": LendingPoolBankCollectFeesRecord,
     f"{LENDING_POOL_BANK_HANDLE_BANKRUPTCY_EVENT_NAME}": LendingPoolBankHandleBankruptcyRecord,
     f"{MARGINFI_ACCOUNT_CREATE_EVENT_NAME}": MarginfiAccountCreateRecord,
@@ -469,5 +723,19 @@ EVENT_TO_RECORD_TYPE: Dict[str, Type[EventRecord]] = {
     f"{LENDING_ACCOUNT_WITHDRAW_EVENT_NAME}": LendingAccountChangeLiquidityRecord,
     f"{LENDING_ACCOUNT_BORROW_EVENT_NAME}": LendingAccountChangeLiquidityRecord,
     f"{LENDING_ACCOUNT_REPAY_EVENT_NAME}": LendingAccountChangeLiquidityRecord,
+    f"{LENDING_ACCOUNT_DEPOSIT_EVENT_V2_NAME}": LendingAccountChangeLiquidityRecord,
+    f"{LENDING_ACCOUNT_WITHDRAW_EVENT_V2_NAME}": LendingAccountChangeLiquidityRecord,
+    f"{LENDING_ACCOUNT_BORROW_EVENT_V2_NAME}": LendingAccountChangeLiquidityRecord,
+    f"{LENDING_ACCOUNT_REPAY_EVENT_V2_NAME}": LendingAccountChangeLiquidityRecord,
+    f"{LENDING_POOL_BANK_SETUP_EMISSIONS_EVENT_NAME}": LendingPoolBankSetupEmissionsRecord,
+    f"{LENDING_POOL_BANK_UPDATE_EMISSIONS_EVENT_NAME}": LendingPoolBankUpdateEmissionsRecord,
     f"{LENDING_ACCOUNT_LIQUIDATE_EVENT_NAME}": LendingAccountLiquidateRecord,
+    f"{LENDING_ACCOUNT_LIQUIDATE_EVENT_V2_NAME}": LendingAccountLiquidateRecord,
+    f"{MARGINFI_ACCOUNT_SET_FLAG_EVENT_NAME}": MarginfiAccountChangeFlagRecord,
+    f"{MARGINFI_ACCOUNT_UNSET_FLAG_EVENT_NAME}": MarginfiAccountChangeFlagRecord,
+    f"{MARGINFI_ACCOUNT_TRANSFER_ACCOUNT_AUTHORITY_EVENT_NAME}": MarginfiAccountTransferAccountAuthorityRecord,
+    f"{LENDING_ACCOUNT_START_FLASHLOAN_EVENT_NAME}": LendingAccountFlashloanRecord,
+    f"{LENDING_ACCOUNT_END_FLASHLOAN_EVENT_NAME}": LendingAccountFlashloanRecord,
+    f"{LENDING_ACCOUNT_SETTLE_EMISSIONS_EVENT_NAME}": LendingAccountSettleEmissionsRecord,
+    f"{LENDING_ACCOUNT_WITHDRAW_EMISSIONS_EVENT_NAME}": LendingAccountWithdrawEmissionsRecord,
 }
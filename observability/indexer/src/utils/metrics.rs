@@ -1,4 +1,3 @@
-use super::marginfi_account_dup::RiskEngine2;
 use crate::utils::big_query::DATE_FORMAT_STR;
 use crate::utils::snapshot::{BankAccounts, OracleData, Snapshot};
 use anyhow::anyhow;
@@ -8,6 +7,7 @@ use fixed_macro::types::I80F48;
 use itertools::Itertools;
 use marginfi::constants::ZERO_AMOUNT_THRESHOLD;
 use marginfi::prelude::MarginfiGroup;
+use marginfi::state::health::HealthEngine;
 use marginfi::state::marginfi_account::{
     calc_value, MarginfiAccount, RequirementType, RiskRequirementType,
 };
@@ -473,9 +473,10 @@ impl MarginfiAccountMetrics {
                 }
             }));
 
-        let risk_engine = RiskEngine2::load(marginfi_account, &banks, &price_feeds)?;
+        let risk_engine = HealthEngine::load(marginfi_account, &banks, &price_feeds)?;
 
-        let (total_assets_usd, total_liabilities_usd) = risk_engine.get_equity_components()?;
+        let (total_assets_usd, total_liabilities_usd) =
+            risk_engine.get_account_health_components(RiskRequirementType::Equity)?;
         let (total_assets_usd, total_liabilities_usd) = (
             total_assets_usd.to_num::<f64>(),
             total_liabilities_usd.to_num::<f64>(),
@@ -14,6 +14,13 @@ pub const FEE_VAULT_SEED: &str = "fee_vault";
 pub const EMISSIONS_AUTH_SEED: &str = "emissions_auth_seed";
 pub const EMISSIONS_TOKEN_ACCOUNT_SEED: &str = "emissions_token_account_seed";
 
+pub const BORROW_CREDENTIAL_SEED: &str = "borrow_credential";
+
+pub const INSURANCE_SHARE_MINT_SEED: &str = "insurance_share_mint";
+pub const INSURANCE_UNSTAKE_REQUEST_SEED: &str = "insurance_unstake_request";
+
+pub const ORDER_SEED: &str = "order";
+
 cfg_if::cfg_if! {
     if #[cfg(feature = "devnet")] {
         pub const PYTH_ID: Pubkey = pubkey!("gSbePebfvPy7tRqimPoVecS2UsBvYv46ynrzWocc92s");
@@ -28,6 +35,13 @@ cfg_if::cfg_if! {
 pub const LIQUIDATION_LIQUIDATOR_FEE: I80F48 = I80F48!(0.025);
 pub const LIQUIDATION_INSURANCE_FEE: I80F48 = I80F48!(0.025);
 
+/// Bad debt collateral auction discount grows linearly from 0 to the max discount over the auction duration.
+pub const BAD_DEBT_AUCTION_MAX_DISCOUNT: I80F48 = I80F48!(0.2);
+pub const BAD_DEBT_AUCTION_DURATION_SEC: i64 = 6 * 60 * 60;
+
+/// Max keeper tip of an order, as a share of the repaid liability value.
+pub const MAX_ORDER_KEEPER_TIP: I80F48 = I80F48!(0.05);
+
 pub const SECONDS_PER_YEAR: I80F48 = I80F48!(31_536_000);
 
 pub const MAX_PRICE_AGE_SEC: u64 = 60;
@@ -42,6 +56,9 @@ pub const USDC_EXPONENT: i32 = 6;
 
 pub const MAX_ORACLE_KEYS: usize = 5;
 
+/// Maximum number of programs a group can allow to start and end flashloans via CPI.
+pub const MAX_FLASHLOAN_CPI_PROGRAMS: usize = 4;
+
 /// Any balance below 1 SPL token amount is treated as none,
 /// this is to account for any artifacts resulting from binary fraction arithemtic.
 pub const EMPTY_BALANCE_THRESHOLD: I80F48 = I80F48!(1);
@@ -117,3 +134,16 @@ pub const EXP_10: [i128; MAX_EXP_10] = [
 
 /// Value where total_asset_value_init_limit is considered inactive
 pub const TOTAL_ASSET_VALUE_INIT_LIMIT_INACTIVE: u64 = 0;
+
+/// Value where total_deposit_value_limit is considered inactive
+pub const TOTAL_DEPOSIT_VALUE_LIMIT_INACTIVE: u64 = 0;
+
+/// Value where total_borrow_value_limit is considered inactive
+pub const TOTAL_BORROW_VALUE_LIMIT_INACTIVE: u64 = 0;
+
+/// Value where bank outflow_limit and outflow_value_limit are considered inactive
+pub const OUTFLOW_LIMIT_INACTIVE: u64 = 0;
+
+/// Insurance fund shares credited to the protocol out of the first stake into a fund without shares,
+/// so that the share value can't be inflated by donating to the insurance vault.
+pub const INSURANCE_DEAD_SHARES: u64 = 1_000;
@@ -91,6 +91,38 @@ pub enum MarginfiError {
     IllegalBalanceState,
     #[msg("Illegal account authority transfer")] // 6044
     IllegalAccountAuthorityTransfer,
+    #[msg("Bank deposit value capacity exceeded")] // 6045
+    BankAssetValueCapacityExceeded,
+    #[msg("Bank borrow value capacity exceeded")] // 6046
+    BankLiabilityValueCapacityExceeded,
+    #[msg("Bank outflow limit exceeded")] // 6047
+    BankOutflowLimitExceeded,
+    #[msg("Bank outflow value limit exceeded")] // 6048
+    BankOutflowValueLimitExceeded,
+    #[msg("Invalid borrow credential")] // 6049
+    InvalidBorrowCredential,
+    #[msg("Account borrow limit exceeded")] // 6050
+    AccountBorrowLimitExceeded,
+    #[msg("Insurance staking is not enabled for this bank")] // 6051
+    InsuranceStakingNotEnabled,
+    #[msg("Insurance unstake cooldown has not elapsed")] // 6052
+    InsuranceUnstakeCooldownActive,
+    #[msg("Insurance fund is depleted")] // 6053
+    InsuranceFundDepleted,
+    #[msg("Bad debt auction not available")] // 6054
+    BadDebtAuctionNotAvailable,
+    #[msg("Flashloan CPI caller program is not allowed")] // 6055
+    FlashloanCpiProgramNotAllowed,
+    #[msg("Invalid order")] // 6056
+    InvalidOrder,
+    #[msg("Order trigger condition not met")] // 6057
+    OrderTriggerNotMet,
+    #[msg("Insurance stake too small to mint shares")] // 6058
+    InsuranceStakeTooSmall,
+    #[msg("Insurance fund is not depleted")] // 6059
+    InsuranceFundNotDepleted,
+    #[msg("Order was created by a previous account authority")] // 6060
+    OrderAuthorityChanged,
 }
 
 impl From<MarginfiError> for ProgramError {
@@ -1,4 +1,10 @@
-use crate::{prelude::*, state::marginfi_group::BankConfigOpt};
+use crate::{
+    prelude::*,
+    state::{
+        marginfi_group::{BankConfigOpt, WrappedI80F48},
+        order::OrderTrigger,
+    },
+};
 use anchor_lang::prelude::*;
 
 // Event headers
@@ -17,6 +23,20 @@ pub struct AccountEventHeader {
     pub marginfi_group: Pubkey,
 }
 
+/// Post-operation bank state carried by versioned (`V2`) events.
+///
+/// Share values and totals are emitted at full precision, the interest rates are informational
+/// and `None` if they can't be computed.
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct BankStateSnapshot {
+    pub asset_share_value: WrappedI80F48,
+    pub liability_share_value: WrappedI80F48,
+    pub total_asset_shares: WrappedI80F48,
+    pub total_liability_shares: WrappedI80F48,
+    pub lending_rate: Option<f64>,
+    pub borrowing_rate: Option<f64>,
+}
+
 // marginfi group events
 
 #[event]
@@ -30,6 +50,13 @@ pub struct MarginfiGroupConfigureEvent {
     pub config: GroupConfig,
 }
 
+#[event]
+pub struct MarginfiGroupConfigureFlashloanCpiProgramEvent {
+    pub header: GroupEventHeader,
+    pub program_id: Pubkey,
+    pub allowed: bool,
+}
+
 #[event]
 pub struct LendingPoolBankCreateEvent {
     pub header: GroupEventHeader,
@@ -45,6 +72,88 @@ pub struct LendingPoolBankConfigureEvent {
     pub config: BankConfigOpt,
 }
 
+#[event]
+pub struct LendingPoolBankResetOutflowEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub outflow_reset: f64,
+}
+
+#[event]
+pub struct LendingPoolBankSetBorrowCredentialEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub marginfi_account: Pubkey,
+    pub borrow_limit: u64,
+    pub revoked: bool,
+}
+
+#[event]
+pub struct LendingPoolBankSetupInsuranceStakingEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub share_mint: Pubkey,
+    pub protocol_shares: u64,
+    pub unstake_cooldown: u64,
+}
+
+#[event]
+pub struct LendingPoolBankInsuranceStakeEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
+}
+
+#[event]
+pub struct LendingPoolBankInsuranceRequestUnstakeEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub shares: u64,
+    pub cooldown_shares: u64,
+}
+
+#[event]
+pub struct LendingPoolBankInsuranceUnstakeEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
+}
+
+#[event]
+pub struct LendingPoolBankResetInsuranceStakingEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub share_mint: Pubkey,
+    pub written_off_shares: u64,
+}
+
+#[event]
+pub struct LendingPoolBankSetupEmissionsEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub emissions_mint: Pubkey,
+    pub emissions_flags: u64,
+    pub emissions_rate: u64,
+    pub total_emissions: u64,
+}
+
+#[event]
+pub struct LendingPoolBankUpdateEmissionsEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub emissions_mint: Pubkey,
+    pub emissions_flags: u64,
+    pub emissions_rate: u64,
+    pub additional_emissions: u64,
+    pub emissions_remaining: f64,
+}
+
+/// Superseded by [`LendingPoolBankAccrueInterestEventV2`], kept for decoding historical logs.
 #[event]
 pub struct LendingPoolBankAccrueInterestEvent {
     pub header: GroupEventHeader,
@@ -55,6 +164,17 @@ pub struct LendingPoolBankAccrueInterestEvent {
     pub insurance_collected: f64,
 }
 
+#[event]
+pub struct LendingPoolBankAccrueInterestEventV2 {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub delta: u64,
+    pub fees_collected: f64,
+    pub insurance_collected: f64,
+    pub bank_state: BankStateSnapshot,
+}
+
 #[event]
 pub struct LendingPoolBankCollectFeesEvent {
     pub header: GroupEventHeader,
@@ -76,6 +196,36 @@ pub struct LendingPoolBankHandleBankruptcyEvent {
     pub socialized_amount: f64,
 }
 
+#[event]
+pub struct LendingPoolBankDeferBadDebtEvent {
+    pub header: AccountEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub deferred_amount: f64,
+    pub unrecovered_bad_debt: f64,
+}
+
+#[event]
+pub struct LendingPoolBankRecoverBadDebtEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub recovered_amount: f64,
+    pub socialized_amount: f64,
+    pub unrecovered_bad_debt: f64,
+}
+
+#[event]
+pub struct LendingPoolBankBadDebtAuctionEvent {
+    pub header: AccountEventHeader,
+    pub asset_bank: Pubkey,
+    pub debt_bank: Pubkey,
+    pub asset_amount: f64,
+    pub payment_amount: f64,
+    pub discount: f64,
+    pub unrecovered_bad_debt: f64,
+}
+
 // marginfi account events
 
 #[event]
@@ -83,6 +233,7 @@ pub struct MarginfiAccountCreateEvent {
     pub header: AccountEventHeader,
 }
 
+/// Superseded by [`LendingAccountDepositEventV2`], kept for decoding historical logs.
 #[event]
 pub struct LendingAccountDepositEvent {
     pub header: AccountEventHeader,
@@ -91,6 +242,16 @@ pub struct LendingAccountDepositEvent {
     pub amount: u64,
 }
 
+#[event]
+pub struct LendingAccountDepositEventV2 {
+    pub header: AccountEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub bank_state: BankStateSnapshot,
+}
+
+/// Superseded by [`LendingAccountRepayEventV2`], kept for decoding historical logs.
 #[event]
 pub struct LendingAccountRepayEvent {
     pub header: AccountEventHeader,
@@ -100,6 +261,17 @@ pub struct LendingAccountRepayEvent {
     pub close_balance: bool,
 }
 
+#[event]
+pub struct LendingAccountRepayEventV2 {
+    pub header: AccountEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub close_balance: bool,
+    pub bank_state: BankStateSnapshot,
+}
+
+/// Superseded by [`LendingAccountBorrowEventV2`], kept for decoding historical logs.
 #[event]
 pub struct LendingAccountBorrowEvent {
     pub header: AccountEventHeader,
@@ -108,6 +280,35 @@ pub struct LendingAccountBorrowEvent {
     pub amount: u64,
 }
 
+#[event]
+pub struct LendingAccountBorrowEventV2 {
+    pub header: AccountEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub bank_state: BankStateSnapshot,
+}
+
+#[event]
+pub struct LendingAccountTransferBalanceEvent {
+    pub header: AccountEventHeader,
+    pub destination_marginfi_account: Pubkey,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub asset_amount: f64,
+    pub liability_amount: f64,
+}
+
+#[event]
+pub struct LendingAccountFlashloanFeeEvent {
+    pub header: AccountEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub borrowed_amount: f64,
+    pub fee: f64,
+}
+
+/// Superseded by [`LendingAccountWithdrawEventV2`], kept for decoding historical logs.
 #[event]
 pub struct LendingAccountWithdrawEvent {
     pub header: AccountEventHeader,
@@ -117,6 +318,16 @@ pub struct LendingAccountWithdrawEvent {
     pub close_balance: bool,
 }
 
+#[event]
+pub struct LendingAccountWithdrawEventV2 {
+    pub header: AccountEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub close_balance: bool,
+    pub bank_state: BankStateSnapshot,
+}
+
 #[derive(AnchorSerialize, AnchorDeserialize)]
 pub struct LiquidationBalances {
     pub liquidatee_asset_balance: f64,
@@ -125,6 +336,7 @@ pub struct LiquidationBalances {
     pub liquidator_liability_balance: f64,
 }
 
+/// Superseded by [`LendingAccountLiquidateEventV2`], kept for decoding historical logs.
 #[event]
 pub struct LendingAccountLiquidateEvent {
     pub header: AccountEventHeader,
@@ -140,9 +352,100 @@ pub struct LendingAccountLiquidateEvent {
     pub post_balances: LiquidationBalances,
 }
 
+#[event]
+pub struct LendingAccountLiquidateEventV2 {
+    pub header: AccountEventHeader,
+    pub liquidatee_marginfi_account: Pubkey,
+    pub liquidatee_marginfi_account_authority: Pubkey,
+    pub asset_bank: Pubkey,
+    pub asset_mint: Pubkey,
+    pub liability_bank: Pubkey,
+    pub liability_mint: Pubkey,
+    pub liquidatee_pre_health: f64,
+    pub liquidatee_post_health: f64,
+    pub pre_balances: LiquidationBalances,
+    pub post_balances: LiquidationBalances,
+    pub asset_bank_state: BankStateSnapshot,
+    pub liability_bank_state: BankStateSnapshot,
+}
+
 #[event]
 pub struct MarginfiAccountTransferAccountAuthorityEvent {
     pub header: AccountEventHeader,
     pub old_account_authority: Pubkey,
     pub new_account_authority: Pubkey,
 }
+
+#[event]
+pub struct MarginfiAccountSetFlagEvent {
+    pub header: AccountEventHeader,
+    pub flag: u64,
+    pub account_flags: u64,
+}
+
+#[event]
+pub struct MarginfiAccountUnsetFlagEvent {
+    pub header: AccountEventHeader,
+    pub flag: u64,
+    pub account_flags: u64,
+}
+
+#[event]
+pub struct LendingAccountStartFlashloanEvent {
+    pub header: AccountEventHeader,
+    pub end_index: u64,
+}
+
+#[event]
+pub struct LendingAccountEndFlashloanEvent {
+    pub header: AccountEventHeader,
+}
+
+#[event]
+pub struct LendingAccountSettleEmissionsEvent {
+    pub header: AccountEventHeader,
+    pub bank: Pubkey,
+    pub emissions_mint: Pubkey,
+    pub amount: f64,
+}
+
+#[event]
+pub struct LendingAccountWithdrawEmissionsEvent {
+    pub header: AccountEventHeader,
+    pub bank: Pubkey,
+    pub emissions_mint: Pubkey,
+    pub destination_account: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct MarginfiAccountCreateOrderEvent {
+    pub header: AccountEventHeader,
+    pub order: Pubkey,
+    pub asset_bank: Pubkey,
+    pub liab_bank: Pubkey,
+    pub trigger: OrderTrigger,
+    pub trigger_value: f64,
+    pub keeper_tip: f64,
+}
+
+#[event]
+pub struct MarginfiAccountCloseOrderEvent {
+    pub header: AccountEventHeader,
+    pub order: Pubkey,
+}
+
+#[event]
+pub struct LendingAccountExecuteOrderEvent {
+    pub header: AccountEventHeader,
+    pub order: Pubkey,
+    pub asset_bank: Pubkey,
+    pub asset_mint: Pubkey,
+    pub liab_bank: Pubkey,
+    pub liab_mint: Pubkey,
+    pub asset_amount: u64,
+    pub liab_amount: u64,
+    pub trigger_observed_value: f64,
+    pub pre_health: f64,
+    pub post_health: f64,
+}
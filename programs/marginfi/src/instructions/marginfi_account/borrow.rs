@@ -1,12 +1,14 @@
 use crate::{
     bank_signer, check,
     constants::{LIQUIDITY_VAULT_AUTHORITY_SEED, LIQUIDITY_VAULT_SEED},
-    events::{AccountEventHeader, LendingAccountBorrowEvent},
+    events::{AccountEventHeader, LendingAccountBorrowEventV2},
     prelude::{MarginfiError, MarginfiGroup, MarginfiResult},
     state::{
+        borrow_credential::BorrowCredential,
         marginfi_account::{BankAccountWrapper, MarginfiAccount, RiskEngine, DISABLED_FLAG},
         marginfi_group::{Bank, BankVaultType},
     },
+    utils::find_bank_oracle_ais,
 };
 use anchor_lang::prelude::*;
 use anchor_spl::token::{Token, TokenAccount, Transfer};
@@ -17,9 +19,13 @@ use solana_program::{clock::Clock, sysvar::Sysvar};
 /// 2. Create the user's bank account for the asset borrowed if it does not exist yet
 /// 3. Record liability increase in the bank account
 /// 4. Transfer funds from the bank's liquidity vault to the signer's token account
-/// 5. Verify that the user account is in a healthy state
+/// 5. Verify the borrow credential, if the bank is in permissioned borrowing mode
+/// 6. Verify the bank USD borrow cap and outflow limit, if active
+/// 7. Verify that the user account is in a healthy state
 ///
 /// Will error if there is an existing asset <=> withdrawing is not allowed.
+///
+/// In permissioned borrowing mode the borrow credential is expected as the last remaining account.
 pub fn lending_account_borrow(ctx: Context<LendingAccountBorrow>, amount: u64) -> MarginfiResult {
     let LendingAccountBorrow {
         marginfi_account: marginfi_account_loader,
@@ -32,14 +38,27 @@ pub fn lending_account_borrow(ctx: Context<LendingAccountBorrow>, amount: u64) -
     } = ctx.accounts;
 
     let mut marginfi_account = marginfi_account_loader.load_mut()?;
+    let clock = Clock::get()?;
 
     check!(
         !marginfi_account.get_flag(DISABLED_FLAG),
         MarginfiError::AccountDisabled
     );
 
+    let (borrow_credential_ai, health_check_ais) =
+        if bank_loader.load()?.config.is_borrow_permissioned() {
+            let (borrow_credential_ai, health_check_ais) = ctx
+                .remaining_accounts
+                .split_last()
+                .ok_or(MarginfiError::InvalidBorrowCredential)?;
+
+            (Some(borrow_credential_ai), health_check_ais)
+        } else {
+            (None, ctx.remaining_accounts)
+        };
+
     bank_loader.load_mut()?.accrue_interest(
-        Clock::get()?.unix_timestamp,
+        clock.unix_timestamp,
         #[cfg(not(feature = "client"))]
         bank_loader.key(),
     )?;
@@ -47,6 +66,8 @@ pub fn lending_account_borrow(ctx: Context<LendingAccountBorrow>, amount: u64) -
     {
         let mut bank = bank_loader.load_mut()?;
         let liquidity_vault_authority_bump = bank.liquidity_vault_authority_bump;
+        let pre_total_asset_shares = I80F48::from(bank.total_asset_shares);
+        let pre_total_liability_shares = I80F48::from(bank.total_liability_shares);
 
         let mut bank_account = BankAccountWrapper::find_or_create(
             &bank_loader.key(),
@@ -70,7 +91,32 @@ pub fn lending_account_borrow(ctx: Context<LendingAccountBorrow>, amount: u64) -
             ),
         )?;
 
-        emit!(LendingAccountBorrowEvent {
+        if let Some(borrow_credential_ai) = borrow_credential_ai {
+            BorrowCredential::check_liability(
+                borrow_credential_ai,
+                &bank_loader.key(),
+                &marginfi_account_loader.key(),
+                bank_account
+                    .bank
+                    .get_liability_amount(bank_account.balance.liability_shares.into())?,
+            )?;
+        }
+
+        bank.check_value_limits(
+            pre_total_asset_shares,
+            pre_total_liability_shares,
+            find_bank_oracle_ais(&bank_loader.key(), health_check_ais),
+            clock.unix_timestamp,
+        )?;
+
+        if bank.is_outflow_value_limit_active() {
+            let oracle_ais = find_bank_oracle_ais(&bank_loader.key(), health_check_ais)?;
+            let price = bank.get_limit_price(oracle_ais, clock.unix_timestamp)?;
+
+            bank.check_outflow_value_limit(price)?;
+        }
+
+        emit!(LendingAccountBorrowEventV2 {
             header: AccountEventHeader {
                 signer: Some(ctx.accounts.signer.key()),
                 marginfi_account: marginfi_account_loader.key(),
@@ -80,12 +126,13 @@ pub fn lending_account_borrow(ctx: Context<LendingAccountBorrow>, amount: u64) -
             bank: bank_loader.key(),
             mint: bank.mint,
             amount,
+            bank_state: bank.get_state_snapshot(),
         });
     }
 
     // Check account health, if below threshold fail transaction
-    // Assuming `ctx.remaining_accounts` holds only oracle accounts
-    RiskEngine::check_account_init_health(&marginfi_account, ctx.remaining_accounts)?;
+    // Assuming `health_check_ais` holds only oracle accounts
+    RiskEngine::check_account_init_health(&marginfi_account, health_check_ais)?;
 
     Ok(())
 }
@@ -1,12 +1,13 @@
 use crate::{
     check,
     constants::LIQUIDITY_VAULT_SEED,
-    events::{AccountEventHeader, LendingAccountDepositEvent},
+    events::{AccountEventHeader, LendingAccountDepositEventV2},
     prelude::*,
     state::{
         marginfi_account::{BankAccountWrapper, MarginfiAccount, DISABLED_FLAG},
         marginfi_group::Bank,
     },
+    utils::find_bank_oracle_ais,
 };
 use anchor_lang::prelude::*;
 use anchor_spl::token::{Token, Transfer};
@@ -18,8 +19,12 @@ use solana_program::sysvar::Sysvar;
 /// 2. Create the user's bank account for the asset deposited if it does not exist yet
 /// 3. Record asset increase in the bank account
 /// 4. Transfer funds from the signer's token account to the bank's liquidity vault
+/// 5. Verify the bank USD deposit cap, if active
 ///
 /// Will error if there is an existing liability <=> repaying is not allowed.
+///
+/// If the bank has a USD deposit cap, `ctx.remaining_accounts` must hold the bank
+/// followed by its oracle, the same (bank, oracle) pair used for the account health check.
 pub fn lending_account_deposit(ctx: Context<LendingAccountDeposit>, amount: u64) -> MarginfiResult {
     let LendingAccountDeposit {
         marginfi_account: marginfi_account_loader,
@@ -33,6 +38,7 @@ pub fn lending_account_deposit(ctx: Context<LendingAccountDeposit>, amount: u64)
 
     let mut bank = bank_loader.load_mut()?;
     let mut marginfi_account = marginfi_account_loader.load_mut()?;
+    let clock = Clock::get()?;
 
     check!(
         !marginfi_account.get_flag(DISABLED_FLAG),
@@ -40,11 +46,14 @@ pub fn lending_account_deposit(ctx: Context<LendingAccountDeposit>, amount: u64)
     );
 
     bank.accrue_interest(
-        Clock::get()?.unix_timestamp,
+        clock.unix_timestamp,
         #[cfg(not(feature = "client"))]
         bank_loader.key(),
     )?;
 
+    let pre_total_asset_shares = I80F48::from(bank.total_asset_shares);
+    let pre_total_liability_shares = I80F48::from(bank.total_liability_shares);
+
     let mut bank_account = BankAccountWrapper::find_or_create(
         &bank_loader.key(),
         &mut bank,
@@ -62,7 +71,14 @@ pub fn lending_account_deposit(ctx: Context<LendingAccountDeposit>, amount: u64)
         token_program.to_account_info(),
     )?;
 
-    emit!(LendingAccountDepositEvent {
+    bank.check_value_limits(
+        pre_total_asset_shares,
+        pre_total_liability_shares,
+        find_bank_oracle_ais(&bank_loader.key(), ctx.remaining_accounts),
+        clock.unix_timestamp,
+    )?;
+
+    emit!(LendingAccountDepositEventV2 {
         header: AccountEventHeader {
             signer: Some(signer.key()),
             marginfi_account: marginfi_account_loader.key(),
@@ -72,6 +88,7 @@ pub fn lending_account_deposit(ctx: Context<LendingAccountDeposit>, amount: u64)
         bank: bank_loader.key(),
         mint: bank.mint,
         amount,
+        bank_state: bank.get_state_snapshot(),
     });
 
     Ok(())
@@ -1,9 +1,15 @@
 use anchor_lang::{prelude::*, Accounts, ToAccountInfo};
 use anchor_spl::token::{transfer, Mint, Token, TokenAccount, Transfer};
+use fixed::types::I80F48;
 
 use crate::{
     check,
     constants::{EMISSIONS_AUTH_SEED, EMISSIONS_TOKEN_ACCOUNT_SEED},
+    events::{
+        AccountEventHeader, LendingAccountSettleEmissionsEvent,
+        LendingAccountWithdrawEmissionsEvent,
+    },
+    math_error,
     prelude::{MarginfiError, MarginfiResult},
     state::{
         marginfi_account::{BankAccountWrapper, MarginfiAccount, DISABLED_FLAG},
@@ -54,6 +60,19 @@ pub fn lending_account_withdraw_emissions(
         )?;
     }
 
+    emit!(LendingAccountWithdrawEmissionsEvent {
+        header: AccountEventHeader {
+            signer: Some(ctx.accounts.signer.key()),
+            marginfi_account: ctx.accounts.marginfi_account.key(),
+            marginfi_account_authority: marginfi_account.authority,
+            marginfi_group: marginfi_account.group,
+        },
+        bank: ctx.accounts.bank.key(),
+        emissions_mint: ctx.accounts.emissions_mint.key(),
+        destination_account: ctx.accounts.destination_account.key(),
+        amount: emissions_settle_amount,
+    });
+
     Ok(())
 }
 
@@ -123,8 +142,26 @@ pub fn lending_account_settle_emissions(
         &mut marginfi_account.lending_account,
     )?;
 
+    let pre_emissions_outstanding = I80F48::from(balance.balance.emissions_outstanding);
+
     balance.claim_emissions(Clock::get()?.unix_timestamp.try_into().unwrap())?;
 
+    let settled_amount = I80F48::from(balance.balance.emissions_outstanding)
+        .checked_sub(pre_emissions_outstanding)
+        .ok_or_else(math_error!())?;
+
+    emit!(LendingAccountSettleEmissionsEvent {
+        header: AccountEventHeader {
+            signer: None,
+            marginfi_account: ctx.accounts.marginfi_account.key(),
+            marginfi_account_authority: marginfi_account.authority,
+            marginfi_group: marginfi_account.group,
+        },
+        bank: ctx.accounts.bank.key(),
+        emissions_mint: bank.emissions_mint,
+        amount: settled_amount.to_num::<f64>(),
+    });
+
     Ok(())
 }
 
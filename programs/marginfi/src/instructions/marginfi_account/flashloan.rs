@@ -6,22 +6,75 @@ use solana_program::{
 
 use crate::{
     check,
+    events::{
+        AccountEventHeader, LendingAccountEndFlashloanEvent, LendingAccountFlashloanFeeEvent,
+        LendingAccountStartFlashloanEvent,
+    },
     prelude::*,
-    state::marginfi_account::{MarginfiAccount, RiskEngine, DISABLED_FLAG, IN_FLASHLOAN_FLAG},
+    state::{
+        borrow_credential::BorrowCredential,
+        marginfi_account::{
+            BankAccountWrapper, MarginfiAccount, RiskEngine, DISABLED_FLAG, IN_FLASHLOAN_CPI_FLAG,
+            IN_FLASHLOAN_FLAG,
+        },
+        marginfi_group::{Bank, MarginfiGroup},
+    },
+    utils::find_bank_oracle_ais,
 };
+use fixed::types::I80F48;
 
+/// Start a flashloan.
+///
+/// `end_index` is the index of the matching top-level `end_flashloan` ix.
+///
+/// When invoked via CPI from a program allowed by the group, the marginfi group must be passed
+/// as the only remaining account. The top-level ix invoking the CPI and its program are recorded
+/// on the account, and borrows and withdrawals made within the flashloan must be invoked via CPI
+/// from that same ix, with the instructions sysvar passed in their remaining accounts.
+/// The caller can end the flashloan via CPI within that ix, the top-level `end_flashloan` ix
+/// is still required so that the flashloan can't outlive the transaction.
 pub fn lending_account_start_flashloan(
     ctx: Context<LendingAccountStartFlashloan>,
     end_index: u64,
 ) -> MarginfiResult<()> {
-    check_flashloan_can_start(
-        &ctx.accounts.marginfi_account,
-        &ctx.accounts.ixs_sysvar,
-        end_index as usize,
-    )?;
+    let is_cpi = get_stack_height() > TRANSACTION_LEVEL_STACK_HEIGHT;
+
+    let cpi_caller = if is_cpi {
+        Some(check_flashloan_can_start_cpi(
+            &ctx.accounts.marginfi_account,
+            &ctx.accounts.ixs_sysvar,
+            ctx.remaining_accounts,
+            end_index as usize,
+        )?)
+    } else {
+        check_flashloan_can_start(
+            &ctx.accounts.marginfi_account,
+            &ctx.accounts.ixs_sysvar,
+            end_index as usize,
+        )?;
+
+        None
+    };
 
     let mut marginfi_account = ctx.accounts.marginfi_account.load_mut()?;
     marginfi_account.set_flag(IN_FLASHLOAN_FLAG);
+    marginfi_account.start_flashloan_fee_tracking();
+
+    if let Some((caller_ix_index, caller_program)) = cpi_caller {
+        marginfi_account.set_flag(IN_FLASHLOAN_CPI_FLAG);
+        marginfi_account.flashloan_ix_index = caller_ix_index as u64;
+        marginfi_account.flashloan_cpi_program = caller_program;
+    }
+
+    emit!(LendingAccountStartFlashloanEvent {
+        header: AccountEventHeader {
+            signer: Some(ctx.accounts.signer.key()),
+            marginfi_account: ctx.accounts.marginfi_account.key(),
+            marginfi_account_authority: marginfi_account.authority,
+            marginfi_group: marginfi_account.group,
+        },
+        end_index,
+    });
 
     Ok(())
 }
@@ -79,6 +132,29 @@ pub fn check_flashloan_can_start(
         "Start flashloan ix should not be in CPI"
     );
 
+    check_end_flashloan_ix(&marginfi_account.key(), sysvar_ixs, end_fl_idx)?;
+
+    let marginf_account = marginfi_account.load()?;
+
+    check!(
+        !marginf_account.get_flag(DISABLED_FLAG),
+        MarginfiError::AccountDisabled
+    );
+
+    check!(
+        !marginf_account.get_flag(IN_FLASHLOAN_FLAG),
+        MarginfiError::IllegalFlashloan
+    );
+
+    Ok(())
+}
+
+/// Checks that the top-level ix at `end_fl_idx` is an `end_flashloan` ix for `marginfi_account`.
+fn check_end_flashloan_ix(
+    marginfi_account: &Pubkey,
+    sysvar_ixs: &AccountInfo,
+    end_fl_idx: usize,
+) -> MarginfiResult<()> {
     // Will error if ix doesn't exist
     let unchecked_end_fl_ix = instructions::load_instruction_at_checked(end_fl_idx, sysvar_ixs)?;
 
@@ -101,39 +177,222 @@ pub fn check_flashloan_can_start(
         .ok_or(MarginfiError::IllegalFlashloan)?;
 
     check!(
-        end_fl_marginfi_account.pubkey.eq(&marginfi_account.key()),
+        end_fl_marginfi_account.pubkey.eq(marginfi_account),
         MarginfiError::IllegalFlashloan
     );
 
-    let marginf_account = marginfi_account.load()?;
+    Ok(())
+}
 
+/// Checklist
+/// 1. Start flashloan ix is a direct CPI from a top-level ix
+/// 2. `end_flashloan` ix index is after the current top-level ix index
+/// 3. Ixs has a top-level `end_flashloan` ix for the same marginfi account at `end_index`
+/// 4. Marginfi group matches the account group
+/// 5. Top-level ix program is allowed to CPI flashloans by the group
+/// 6. Account is not disabled
+/// 7. Account is not already in a flashloan
+///
+/// Returns the index and the program of the top-level ix invoking the CPI.
+pub fn check_flashloan_can_start_cpi(
+    marginfi_account_loader: &AccountLoader<MarginfiAccount>,
+    sysvar_ixs: &AccountInfo,
+    remaining_ais: &[AccountInfo],
+    end_fl_idx: usize,
+) -> MarginfiResult<(usize, Pubkey)> {
     check!(
-        !marginf_account.get_flag(DISABLED_FLAG),
+        get_stack_height() == TRANSACTION_LEVEL_STACK_HEIGHT + 1,
+        MarginfiError::IllegalFlashloan,
+        "Start flashloan ix should be a direct CPI"
+    );
+
+    let current_ix_idx: usize = instructions::load_current_index_checked(sysvar_ixs)?.into();
+
+    check!(current_ix_idx < end_fl_idx, MarginfiError::IllegalFlashloan);
+
+    check_end_flashloan_ix(&marginfi_account_loader.key(), sysvar_ixs, end_fl_idx)?;
+
+    let marginfi_account = marginfi_account_loader.load()?;
+
+    let marginfi_group_ai = remaining_ais
+        .first()
+        .ok_or(MarginfiError::IllegalFlashloan)?;
+
+    check!(
+        marginfi_group_ai.key.eq(&marginfi_account.group),
+        MarginfiError::IllegalFlashloan
+    );
+
+    let marginfi_group_loader = AccountLoader::<MarginfiGroup>::try_from(marginfi_group_ai)?;
+    let marginfi_group = marginfi_group_loader.load()?;
+
+    let current_ix = instructions::load_instruction_at_checked(current_ix_idx, sysvar_ixs)?;
+
+    check!(
+        marginfi_group.is_flashloan_cpi_program_allowed(&current_ix.program_id),
+        MarginfiError::FlashloanCpiProgramNotAllowed
+    );
+
+    check!(
+        !marginfi_account.get_flag(DISABLED_FLAG),
         MarginfiError::AccountDisabled
     );
 
     check!(
-        !marginf_account.get_flag(IN_FLASHLOAN_FLAG),
+        !marginfi_account.get_flag(IN_FLASHLOAN_FLAG),
         MarginfiError::IllegalFlashloan
     );
 
-    Ok(())
+    Ok((current_ix_idx, current_ix.program_id))
 }
 
+/// End a flashloan.
+///
+/// 1. Charge the bank flashloan fee on the net liability incurred in each bank within the flashloan
+/// 2. Verify the bank borrow limit, USD borrow cap and borrow credential for the fee charged
+/// 3. Verify that the user account is in a healthy state
+///
+/// Banks charged a flashloan fee must be writable in the health check accounts.
+/// The borrow credentials of the charged permissioned banks are expected after the health check accounts,
+/// in balance order.
 pub fn lending_account_end_flashloan(
     ctx: Context<LendingAccountEndFlashloan>,
 ) -> MarginfiResult<()> {
+    let marginfi_account_pk = ctx.accounts.marginfi_account.key();
+    let mut marginfi_account = ctx.accounts.marginfi_account.load_mut()?;
+
+    // Only flashloans started via CPI can be ended via CPI
     check!(
-        get_stack_height() == TRANSACTION_LEVEL_STACK_HEIGHT,
+        get_stack_height() == TRANSACTION_LEVEL_STACK_HEIGHT
+            || marginfi_account.get_flag(IN_FLASHLOAN_CPI_FLAG),
         MarginfiError::IllegalFlashloan,
         "End flashloan ix should not be in CPI"
     );
 
-    let mut marginfi_account = ctx.accounts.marginfi_account.load_mut()?;
+    let (health_check_ais, borrow_credential_ais) = ctx.remaining_accounts.split_at(
+        marginfi_account
+            .get_remaining_accounts_len()
+            .min(ctx.remaining_accounts.len()),
+    );
+
+    // A top-level end ix following a CPI flashloan already ended via CPI is not charged again
+    if marginfi_account.get_flag(IN_FLASHLOAN_FLAG) {
+        charge_flashloan_fees(
+            &ctx.accounts.signer.key(),
+            &marginfi_account_pk,
+            &mut marginfi_account,
+            health_check_ais,
+            borrow_credential_ais,
+        )?;
+    }
 
     marginfi_account.unset_flag(IN_FLASHLOAN_FLAG);
+    marginfi_account.unset_flag(IN_FLASHLOAN_CPI_FLAG);
+    marginfi_account.flashloan_ix_index = 0;
+    marginfi_account.flashloan_cpi_program = Pubkey::default();
+    marginfi_account.stop_flashloan_fee_tracking();
+
+    RiskEngine::check_account_init_health(&marginfi_account, health_check_ais)?;
+
+    emit!(LendingAccountEndFlashloanEvent {
+        header: AccountEventHeader {
+            signer: Some(ctx.accounts.signer.key()),
+            marginfi_account: ctx.accounts.marginfi_account.key(),
+            marginfi_account_authority: marginfi_account.authority,
+            marginfi_group: marginfi_account.group,
+        },
+    });
+
+    Ok(())
+}
+
+fn charge_flashloan_fees<'info>(
+    signer: &Pubkey,
+    marginfi_account_pk: &Pubkey,
+    marginfi_account: &mut MarginfiAccount,
+    health_check_ais: &[AccountInfo<'info>],
+    borrow_credential_ais: &[AccountInfo<'info>],
+) -> MarginfiResult {
+    let current_timestamp = Clock::get()?.unix_timestamp;
+    let mut borrow_credential_ais = borrow_credential_ais.iter();
+
+    for balance_index in 0..marginfi_account.lending_account.balances.len() {
+        let liability_shares_increase =
+            marginfi_account.get_flashloan_liability_shares_increase(balance_index);
+
+        if liability_shares_increase <= I80F48::ZERO {
+            continue;
+        }
+
+        let bank_pk = marginfi_account.lending_account.balances[balance_index].bank_pk;
+        let bank_ai = health_check_ais
+            .iter()
+            .find(|ai| ai.key == &bank_pk)
+            .ok_or(MarginfiError::MissingPythOrBankAccount)?;
+        let bank_loader = AccountLoader::<Bank>::try_from(bank_ai)?;
+
+        if !bank_loader
+            .load()?
+            .config
+            .interest_rate_config
+            .is_flashloan_fee_active()
+        {
+            continue;
+        }
+
+        let mut bank = bank_loader.load_mut()?;
+        let pre_total_asset_shares = I80F48::from(bank.total_asset_shares);
+        let pre_total_liability_shares = I80F48::from(bank.total_liability_shares);
+
+        let borrowed_amount = bank.get_liability_amount(liability_shares_increase)?;
+
+        let (fee, liability_amount) = {
+            let mut bank_account = BankAccountWrapper {
+                balance: &mut marginfi_account.lending_account.balances[balance_index],
+                bank: &mut bank,
+            };
+
+            let fee = bank_account.charge_flashloan_fee(borrowed_amount)?;
+            let liability_amount = bank_account
+                .bank
+                .get_liability_amount(bank_account.balance.liability_shares.into())?;
+
+            (fee, liability_amount)
+        };
+
+        if bank.config.is_borrow_permissioned() {
+            let borrow_credential_ai = borrow_credential_ais
+                .next()
+                .ok_or(MarginfiError::InvalidBorrowCredential)?;
+
+            BorrowCredential::check_liability(
+                borrow_credential_ai,
+                &bank_pk,
+                marginfi_account_pk,
+                liability_amount,
+            )?;
+        }
+
+        bank.check_value_limits(
+            pre_total_asset_shares,
+            pre_total_liability_shares,
+            find_bank_oracle_ais(&bank_pk, health_check_ais),
+            current_timestamp,
+        )?;
 
-    RiskEngine::check_account_init_health(&marginfi_account, ctx.remaining_accounts)?;
+        emit!(LendingAccountFlashloanFeeEvent {
+            header: AccountEventHeader {
+                signer: Some(*signer),
+                marginfi_account: *marginfi_account_pk,
+                marginfi_account_authority: marginfi_account.authority,
+                marginfi_group: marginfi_account.group,
+            },
+            bank: bank_pk,
+            mint: bank.mint,
+            borrowed_amount: borrowed_amount.to_num::<f64>(),
+            fee: fee.to_num::<f64>(),
+        });
+    }
 
     Ok(())
 }
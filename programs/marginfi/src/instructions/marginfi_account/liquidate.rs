@@ -1,7 +1,8 @@
 use crate::constants::{
     INSURANCE_VAULT_SEED, LIQUIDATION_INSURANCE_FEE, LIQUIDATION_LIQUIDATOR_FEE, MAX_PRICE_AGE_SEC,
 };
-use crate::events::{AccountEventHeader, LendingAccountLiquidateEvent, LiquidationBalances};
+use crate::events::{AccountEventHeader, LendingAccountLiquidateEventV2, LiquidationBalances};
+use crate::state::borrow_credential::BorrowCredential;
 use crate::state::marginfi_account::{calc_amount, calc_value, RiskEngine};
 use crate::state::marginfi_group::{Bank, BankVaultType};
 use crate::state::price::{OraclePriceFeedAdapter, OraclePriceType, PriceAdapter, PriceBias};
@@ -65,6 +66,14 @@ use solana_program::sysvar::Sysvar;
 /// assuming that the liquidatee liability token balance doesn't become positive (doesn't become counted as collateral),
 /// and that the liquidatee collateral token balance doesn't become negative (doesn't become counted as liability).
 ///
+/// The bank USD deposit and borrow caps are checked on the bank totals grown by the liquidation,
+/// the bank outflow limits don't apply to liquidations.
+///
+/// Remaining accounts:
+/// `[asset_oracle, liab_oracle, ...liquidator_accounts, ...liquidatee_accounts, (liquidator_borrow_credential)]`
+///
+/// The liquidator borrow credential is expected as the last remaining account when the liability bank
+/// is in permissioned borrowing mode, as paying off the liability can open a liability in the liquidator account.
 pub fn lending_account_liquidate(
     ctx: Context<LendingAccountLiquidate>,
     asset_amount: u64,
@@ -91,6 +100,32 @@ pub fn lending_account_liquidate(
     let mut liquidatee_marginfi_account = liquidatee_marginfi_account_loader.load_mut()?;
     let current_timestamp = Clock::get()?.unix_timestamp;
 
+    let (liquidator_borrow_credential_ai, remaining_ais) = if ctx
+        .accounts
+        .liab_bank
+        .load()?
+        .config
+        .is_borrow_permissioned()
+    {
+        let (borrow_credential_ai, remaining_ais) = ctx
+            .remaining_accounts
+            .split_last()
+            .ok_or(MarginfiError::InvalidBorrowCredential)?;
+
+        (Some(borrow_credential_ai), remaining_ais)
+    } else {
+        (None, ctx.remaining_accounts)
+    };
+    let liquidator_accounts_starting_pos = 2;
+
+    check!(
+        remaining_ais.len()
+            >= liquidator_accounts_starting_pos
+                + liquidator_marginfi_account.get_remaining_accounts_len()
+                + liquidatee_marginfi_account.get_remaining_accounts_len(),
+        MarginfiError::MissingPythOrBankAccount
+    );
+
     {
         ctx.accounts.asset_bank.load_mut()?.accrue_interest(
             current_timestamp,
@@ -106,9 +141,8 @@ pub fn lending_account_liquidate(
 
     let pre_liquidation_health = {
         let liquidatee_accounts_starting_pos =
-            ctx.remaining_accounts.len() - liquidatee_marginfi_account.get_remaining_accounts_len();
-        let liquidatee_remaining_accounts =
-            &ctx.remaining_accounts[liquidatee_accounts_starting_pos..];
+            remaining_ais.len() - liquidatee_marginfi_account.get_remaining_accounts_len();
+        let liquidatee_remaining_accounts = &remaining_ais[liquidatee_accounts_starting_pos..];
 
         RiskEngine::new(&liquidatee_marginfi_account, liquidatee_remaining_accounts)?
             .check_pre_liquidation_condition_and_get_account_health(&ctx.accounts.liab_bank.key())?
@@ -143,6 +177,15 @@ pub fn lending_account_liquidate(
             liab_pf.get_price_of_type(OraclePriceType::RealTime, Some(PriceBias::High))?
         };
 
+        let asset_bank_pre_total_shares = (
+            I80F48::from(asset_bank.total_asset_shares),
+            I80F48::from(asset_bank.total_liability_shares),
+        );
+        let liab_bank_pre_total_shares = (
+            I80F48::from(liab_bank.total_asset_shares),
+            I80F48::from(liab_bank.total_liability_shares),
+        );
+
         let final_discount = I80F48::ONE - (LIQUIDATION_INSURANCE_FEE + LIQUIDATION_LIQUIDATOR_FEE);
         let liquidator_discount = I80F48::ONE - LIQUIDATION_LIQUIDATOR_FEE;
 
@@ -204,6 +247,17 @@ pub fn lending_account_liquidate(
                 .bank
                 .get_liability_amount(bank_account.balance.liability_shares.into())?;
 
+            if let Some(borrow_credential_ai) = liquidator_borrow_credential_ai {
+                if post_balance > pre_balance {
+                    BorrowCredential::check_liability(
+                        borrow_credential_ai,
+                        &ctx.accounts.liab_bank.key(),
+                        &liquidator_marginfi_account_loader.key(),
+                        post_balance,
+                    )?;
+                }
+            }
+
             (pre_balance, post_balance)
         };
 
@@ -220,7 +274,7 @@ pub fn lending_account_liquidate(
                 .get_asset_amount(bank_account.balance.asset_shares.into())?;
 
             bank_account
-                .withdraw(asset_amount)
+                .withdraw_in_liquidation(asset_amount)
                 .map_err(|_| MarginfiError::IllegalLiquidation)?;
 
             let post_balance = bank_account
@@ -273,7 +327,7 @@ pub fn lending_account_liquidate(
                     liquidatee_liab_bank_account.balance.liability_shares.into(),
                 )?;
 
-            liquidatee_liab_bank_account.increase_balance(liab_amount_final)?;
+            liquidatee_liab_bank_account.increase_balance_in_liquidation(liab_amount_final)?;
 
             let liquidatee_liability_post_balance =
                 liquidatee_liab_bank_account.bank.get_liability_amount(
@@ -312,6 +366,19 @@ pub fn lending_account_liquidate(
                 .ok_or(MarginfiError::MathError)?
                 .into();
 
+        asset_bank.check_value_limits(
+            asset_bank_pre_total_shares.0,
+            asset_bank_pre_total_shares.1,
+            Ok(&ctx.remaining_accounts[0..1]),
+            current_timestamp,
+        )?;
+        liab_bank.check_value_limits(
+            liab_bank_pre_total_shares.0,
+            liab_bank_pre_total_shares.1,
+            Ok(&ctx.remaining_accounts[1..2]),
+            current_timestamp,
+        )?;
+
         (
             LiquidationBalances {
                 liquidatee_asset_balance: liquidatee_asset_pre_balance.to_num::<f64>(),
@@ -330,8 +397,16 @@ pub fn lending_account_liquidate(
 
     // ## Risk checks ##
 
-    let (liquidator_remaining_accounts, liquidatee_remaining_accounts) = ctx.remaining_accounts
-        [2..]
+    // The liquidator account can gain balances in the liquidation
+    check!(
+        remaining_ais.len()
+            >= liquidator_accounts_starting_pos
+                + liquidator_marginfi_account.get_remaining_accounts_len(),
+        MarginfiError::MissingPythOrBankAccount
+    );
+
+    let (liquidator_remaining_accounts, liquidatee_remaining_accounts) = remaining_ais
+        [liquidator_accounts_starting_pos..]
         .split_at(liquidator_marginfi_account.get_remaining_accounts_len());
 
     // Verify liquidatee liquidation post health
@@ -348,7 +423,10 @@ pub fn lending_account_liquidate(
         liquidator_remaining_accounts,
     )?;
 
-    emit!(LendingAccountLiquidateEvent {
+    let asset_bank_state = ctx.accounts.asset_bank.load()?.get_state_snapshot();
+    let liability_bank_state = ctx.accounts.liab_bank.load()?.get_state_snapshot();
+
+    emit!(LendingAccountLiquidateEventV2 {
         header: AccountEventHeader {
             signer: Some(ctx.accounts.signer.key()),
             marginfi_account: liquidator_marginfi_account_loader.key(),
@@ -365,6 +443,8 @@ pub fn lending_account_liquidate(
         liquidatee_post_health: post_liquidation_health.to_num::<f64>(),
         pre_balances,
         post_balances,
+        asset_bank_state,
+        liability_bank_state,
     });
 
     Ok(())
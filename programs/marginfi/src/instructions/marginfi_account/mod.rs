@@ -5,8 +5,10 @@ mod emissions;
 mod flashloan;
 mod initialize;
 mod liquidate;
+mod order;
 mod repay;
 mod transfer_authority;
+mod transfer_balance;
 mod withdraw;
 
 pub use borrow::*;
@@ -16,6 +18,8 @@ pub use emissions::*;
 pub use flashloan::*;
 pub use initialize::*;
 pub use liquidate::*;
+pub use order::*;
 pub use repay::*;
 pub use transfer_authority::*;
+pub use transfer_balance::*;
 pub use withdraw::*;
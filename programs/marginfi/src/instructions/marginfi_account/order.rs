@@ -0,0 +1,503 @@
+use crate::{
+    bank_signer, check,
+    constants::{
+        LIQUIDITY_VAULT_AUTHORITY_SEED, LIQUIDITY_VAULT_SEED, MAX_ORDER_KEEPER_TIP,
+        MAX_PRICE_AGE_SEC, ORDER_SEED,
+    },
+    debug,
+    events::{
+        AccountEventHeader, LendingAccountExecuteOrderEvent, MarginfiAccountCloseOrderEvent,
+        MarginfiAccountCreateOrderEvent,
+    },
+    math_error,
+    prelude::*,
+    state::{
+        marginfi_account::{
+            calc_amount, calc_value, BankAccountWrapper, MarginfiAccount, RiskEngine,
+            RiskRequirementType, DISABLED_FLAG,
+        },
+        marginfi_group::{Bank, BankVaultType, WrappedI80F48},
+        order::{Order, OrderTrigger},
+        price::{OraclePriceFeedAdapter, OraclePriceType, PriceAdapter, PriceBias},
+    },
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Token, TokenAccount, Transfer};
+use fixed::types::I80F48;
+
+/// Create an order repaying the `liab_bank` liability of the marginfi account in full
+/// with its `asset_bank` collateral once the trigger is met.
+///
+/// - `OrderTrigger::HealthBelow`: `trigger_value` is a maintenance health ratio in (0, 1).
+/// - `OrderTrigger::PriceBelow` / `OrderTrigger::PriceAbove`: `trigger_value` is the asset price
+///   denominated in the liability token.
+///
+/// `keeper_tip` is paid to the executing keeper in additional collateral,
+/// as a share of the repaid liability value, and is capped at `MAX_ORDER_KEEPER_TIP`.
+pub fn marginfi_account_create_order(
+    ctx: Context<MarginfiAccountCreateOrder>,
+    trigger: OrderTrigger,
+    trigger_value: WrappedI80F48,
+    keeper_tip: WrappedI80F48,
+) -> MarginfiResult {
+    let MarginfiAccountCreateOrder {
+        marginfi_account: marginfi_account_loader,
+        asset_bank,
+        liab_bank,
+        order: order_loader,
+        signer,
+        ..
+    } = ctx.accounts;
+
+    check!(
+        asset_bank.key() != liab_bank.key(),
+        MarginfiError::InvalidOrder,
+        "Asset and liability bank cannot be the same"
+    );
+
+    let trigger_value_num: I80F48 = trigger_value.into();
+    let keeper_tip_num: I80F48 = keeper_tip.into();
+
+    check!(
+        keeper_tip_num >= I80F48::ZERO && keeper_tip_num <= MAX_ORDER_KEEPER_TIP,
+        MarginfiError::InvalidOrder,
+        "Keeper tip out of range"
+    );
+
+    let is_trigger_value_valid = match trigger {
+        OrderTrigger::HealthBelow => {
+            trigger_value_num > I80F48::ZERO && trigger_value_num < I80F48::ONE
+        }
+        OrderTrigger::PriceBelow | OrderTrigger::PriceAbove => trigger_value_num > I80F48::ZERO,
+    };
+
+    check!(
+        is_trigger_value_valid,
+        MarginfiError::InvalidOrder,
+        "Trigger value out of range"
+    );
+
+    let marginfi_account = marginfi_account_loader.load()?;
+
+    check!(
+        !marginfi_account.get_flag(DISABLED_FLAG),
+        MarginfiError::AccountDisabled
+    );
+
+    order_loader.load_init()?.initialize(
+        marginfi_account_loader.key(),
+        marginfi_account.authority,
+        asset_bank.key(),
+        liab_bank.key(),
+        trigger,
+        trigger_value,
+        keeper_tip,
+    );
+
+    emit!(MarginfiAccountCreateOrderEvent {
+        header: AccountEventHeader {
+            signer: Some(signer.key()),
+            marginfi_account: marginfi_account_loader.key(),
+            marginfi_account_authority: marginfi_account.authority,
+            marginfi_group: marginfi_account.group,
+        },
+        order: order_loader.key(),
+        asset_bank: asset_bank.key(),
+        liab_bank: liab_bank.key(),
+        trigger,
+        trigger_value: trigger_value_num.to_num::<f64>(),
+        keeper_tip: keeper_tip_num.to_num::<f64>(),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(trigger: OrderTrigger)]
+pub struct MarginfiAccountCreateOrder<'info> {
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+
+    #[account(
+        constraint = marginfi_account.load()?.group == marginfi_group.key(),
+    )]
+    pub marginfi_account: AccountLoader<'info, MarginfiAccount>,
+
+    #[account(
+        mut,
+        address = marginfi_account.load()?.authority,
+    )]
+    pub signer: Signer<'info>,
+
+    #[account(
+        constraint = asset_bank.load()?.group == marginfi_group.key(),
+    )]
+    pub asset_bank: AccountLoader<'info, Bank>,
+
+    #[account(
+        constraint = liab_bank.load()?.group == marginfi_group.key(),
+    )]
+    pub liab_bank: AccountLoader<'info, Bank>,
+
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + std::mem::size_of::<Order>(),
+        seeds = [
+            ORDER_SEED.as_bytes(),
+            marginfi_account.key().as_ref(),
+            asset_bank.key().as_ref(),
+            liab_bank.key().as_ref(),
+            &[trigger as u8],
+        ],
+        bump,
+    )]
+    pub order: AccountLoader<'info, Order>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Cancel an order, returning its rent to the account authority.
+///
+/// Orders created by a previous account authority can only be cancelled.
+pub fn marginfi_account_close_order(ctx: Context<MarginfiAccountCloseOrder>) -> MarginfiResult {
+    let marginfi_account = ctx.accounts.marginfi_account.load()?;
+
+    emit!(MarginfiAccountCloseOrderEvent {
+        header: AccountEventHeader {
+            signer: Some(ctx.accounts.signer.key()),
+            marginfi_account: ctx.accounts.marginfi_account.key(),
+            marginfi_account_authority: marginfi_account.authority,
+            marginfi_group: marginfi_account.group,
+        },
+        order: ctx.accounts.order.key(),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct MarginfiAccountCloseOrder<'info> {
+    pub marginfi_account: AccountLoader<'info, MarginfiAccount>,
+
+    #[account(
+        mut,
+        address = marginfi_account.load()?.authority,
+    )]
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        close = signer,
+        constraint = order.load()?.marginfi_account == marginfi_account.key(),
+    )]
+    pub order: AccountLoader<'info, Order>,
+}
+
+/// Execute a triggered order on behalf of the marginfi account, by any keeper.
+///
+/// 1. Accrue interest in both banks
+/// 2. Verify the order trigger, using the maintenance health of the `RiskEngine` or the oracle prices
+/// 3. Repay the liability in full with the keeper's liability tokens
+/// 4. Withdraw collateral worth the repaid liability plus the keeper tip to the keeper,
+///    verifying the asset bank USD outflow limit, if active
+/// 5. Verify that the execution didn't make the account liquidatable, or less healthy if it already was
+/// 6. Close the order, returning its rent to the account authority
+///
+/// Orders created by a previous account authority are rejected.
+///
+/// The keeper is expected to source the liability tokens themselves, e.g. by swapping the received
+/// collateral in the same transaction.
+///
+/// Collateral paid to the keeper: `q_a = q_l * p_l * (1 + tip) / p_a`,
+/// with `p_l` biased low and `p_a` biased high in favour of the marginfi account.
+///
+/// Remaining accounts are the asset bank oracle, the liability bank oracle
+/// and the marginfi account observation accounts before the execution.
+pub fn lending_account_execute_order(ctx: Context<LendingAccountExecuteOrder>) -> MarginfiResult {
+    let LendingAccountExecuteOrder {
+        marginfi_account: marginfi_account_loader,
+        asset_bank: asset_bank_loader,
+        liab_bank: liab_bank_loader,
+        order: order_loader,
+        signer,
+        signer_token_account,
+        destination_token_account,
+        asset_bank_liquidity_vault,
+        asset_bank_liquidity_vault_authority,
+        liab_bank_liquidity_vault,
+        token_program,
+        ..
+    } = ctx.accounts;
+
+    let mut marginfi_account = marginfi_account_loader.load_mut()?;
+
+    check!(
+        !marginfi_account.get_flag(DISABLED_FLAG),
+        MarginfiError::AccountDisabled
+    );
+
+    check!(
+        order_loader.load()?.authority == marginfi_account.authority,
+        MarginfiError::OrderAuthorityChanged
+    );
+
+    let current_timestamp = Clock::get()?.unix_timestamp;
+
+    asset_bank_loader.load_mut()?.accrue_interest(
+        current_timestamp,
+        #[cfg(not(feature = "client"))]
+        asset_bank_loader.key(),
+    )?;
+    liab_bank_loader.load_mut()?.accrue_interest(
+        current_timestamp,
+        #[cfg(not(feature = "client"))]
+        liab_bank_loader.key(),
+    )?;
+
+    check!(
+        ctx.remaining_accounts.len() >= 2,
+        MarginfiError::MissingPythAccount
+    );
+
+    let (oracle_ais, observation_ais) = ctx.remaining_accounts.split_at(2);
+    let asset_oracle_ais = &oracle_ais[0..1];
+
+    let asset_pf = OraclePriceFeedAdapter::try_from_bank_config(
+        &asset_bank_loader.load()?.config,
+        asset_oracle_ais,
+        current_timestamp,
+        MAX_PRICE_AGE_SEC,
+    )?;
+    let liab_pf = OraclePriceFeedAdapter::try_from_bank_config(
+        &liab_bank_loader.load()?.config,
+        &oracle_ais[1..2],
+        current_timestamp,
+        MAX_PRICE_AGE_SEC,
+    )?;
+
+    let (pre_health, trigger_observed_value, keeper_tip) = {
+        let (assets, liabs) = RiskEngine::new(&marginfi_account, observation_ais)?
+            .get_account_health_components(RiskRequirementType::Maintenance)?;
+        let health = assets.checked_sub(liabs).ok_or_else(math_error!())?;
+
+        let order = order_loader.load()?;
+
+        let trigger_observed_value = match order.trigger {
+            OrderTrigger::HealthBelow => {
+                if assets.is_zero() {
+                    I80F48::ZERO
+                } else {
+                    health.checked_div(assets).ok_or_else(math_error!())?
+                }
+            }
+            OrderTrigger::PriceBelow | OrderTrigger::PriceAbove => asset_pf
+                .get_price_of_type(OraclePriceType::RealTime, None)?
+                .checked_div(liab_pf.get_price_of_type(OraclePriceType::RealTime, None)?)
+                .ok_or_else(math_error!())?,
+        };
+
+        debug!(
+            "order trigger: {:?} {}, observed: {}",
+            order.trigger,
+            I80F48::from(order.trigger_value),
+            trigger_observed_value
+        );
+
+        check!(
+            order.is_triggered(trigger_observed_value),
+            MarginfiError::OrderTriggerNotMet
+        );
+
+        (
+            health,
+            trigger_observed_value,
+            I80F48::from(order.keeper_tip),
+        )
+    };
+
+    let (asset_amount, liab_amount) = {
+        let mut asset_bank = asset_bank_loader.load_mut()?;
+        let mut liab_bank = liab_bank_loader.load_mut()?;
+
+        let liab_amount = BankAccountWrapper::find(
+            &liab_bank_loader.key(),
+            &mut liab_bank,
+            &mut marginfi_account.lending_account,
+        )?
+        .repay_all()?;
+
+        let asset_amount: u64 = calc_amount(
+            calc_value(
+                I80F48::from_num(liab_amount),
+                liab_pf.get_price_of_type(OraclePriceType::RealTime, Some(PriceBias::Low))?,
+                liab_bank.mint_decimals,
+                Some(I80F48::ONE + keeper_tip),
+            )?,
+            asset_pf.get_price_of_type(OraclePriceType::RealTime, Some(PriceBias::High))?,
+            asset_bank.mint_decimals,
+        )?
+        .checked_floor()
+        .ok_or_else(math_error!())?
+        .checked_to_num()
+        .ok_or_else(math_error!())?;
+
+        BankAccountWrapper::find(
+            &asset_bank_loader.key(),
+            &mut asset_bank,
+            &mut marginfi_account.lending_account,
+        )?
+        .withdraw(I80F48::from_num(asset_amount))?;
+
+        if asset_bank.is_outflow_value_limit_active() {
+            let price = asset_bank.get_limit_price(asset_oracle_ais, current_timestamp)?;
+
+            asset_bank.check_outflow_value_limit(price)?;
+        }
+
+        liab_bank.deposit_spl_transfer(
+            liab_amount,
+            Transfer {
+                from: signer_token_account.to_account_info(),
+                to: liab_bank_liquidity_vault.to_account_info(),
+                authority: signer.to_account_info(),
+            },
+            token_program.to_account_info(),
+        )?;
+
+        asset_bank.withdraw_spl_transfer(
+            asset_amount,
+            Transfer {
+                from: asset_bank_liquidity_vault.to_account_info(),
+                to: destination_token_account.to_account_info(),
+                authority: asset_bank_liquidity_vault_authority.to_account_info(),
+            },
+            token_program.to_account_info(),
+            bank_signer!(
+                BankVaultType::Liquidity,
+                asset_bank_loader.key(),
+                asset_bank.liquidity_vault_authority_bump
+            ),
+        )?;
+
+        (asset_amount, liab_amount)
+    };
+
+    // The repaid liability balance is closed, so its observation accounts are dropped.
+    let post_observation_ais = observation_ais
+        .chunks(2)
+        .filter(|ais| *ais[0].key != liab_bank_loader.key())
+        .flatten()
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let post_health = RiskEngine::new(&marginfi_account, &post_observation_ais)?
+        .get_account_health(RiskRequirementType::Maintenance)?;
+
+    check!(
+        post_health >= pre_health.min(I80F48::ZERO),
+        MarginfiError::BadAccountHealth,
+        "Order execution cannot decrease the health of the account below the maintenance requirement"
+    );
+
+    emit!(LendingAccountExecuteOrderEvent {
+        header: AccountEventHeader {
+            signer: Some(signer.key()),
+            marginfi_account: marginfi_account_loader.key(),
+            marginfi_account_authority: marginfi_account.authority,
+            marginfi_group: marginfi_account.group,
+        },
+        order: order_loader.key(),
+        asset_bank: asset_bank_loader.key(),
+        asset_mint: asset_bank_loader.load()?.mint,
+        liab_bank: liab_bank_loader.key(),
+        liab_mint: liab_bank_loader.load()?.mint,
+        asset_amount,
+        liab_amount,
+        trigger_observed_value: trigger_observed_value.to_num::<f64>(),
+        pre_health: pre_health.to_num::<f64>(),
+        post_health: post_health.to_num::<f64>(),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingAccountExecuteOrder<'info> {
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+
+    #[account(
+        mut,
+        constraint = marginfi_account.load()?.group == marginfi_group.key(),
+    )]
+    pub marginfi_account: AccountLoader<'info, MarginfiAccount>,
+
+    /// CHECK: Receives the order rent, address constraint
+    #[account(
+        mut,
+        address = marginfi_account.load()?.authority,
+    )]
+    pub marginfi_account_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        close = marginfi_account_authority,
+        constraint = order.load()?.marginfi_account == marginfi_account.key(),
+        constraint = order.load()?.asset_bank == asset_bank.key(),
+        constraint = order.load()?.liab_bank == liab_bank.key(),
+    )]
+    pub order: AccountLoader<'info, Order>,
+
+    #[account(
+        mut,
+        constraint = asset_bank.load()?.group == marginfi_group.key(),
+    )]
+    pub asset_bank: AccountLoader<'info, Bank>,
+
+    #[account(
+        mut,
+        constraint = liab_bank.load()?.group == marginfi_group.key(),
+    )]
+    pub liab_bank: AccountLoader<'info, Bank>,
+
+    pub signer: Signer<'info>,
+
+    /// CHECK: Token mint/authority are checked at transfer
+    #[account(mut)]
+    pub signer_token_account: AccountInfo<'info>,
+
+    /// CHECK: Token mint is checked at transfer
+    #[account(mut)]
+    pub destination_token_account: AccountInfo<'info>,
+
+    /// CHECK: Seed constraint
+    #[account(
+        seeds = [
+            LIQUIDITY_VAULT_AUTHORITY_SEED.as_bytes(),
+            asset_bank.key().as_ref(),
+        ],
+        bump = asset_bank.load()?.liquidity_vault_authority_bump
+    )]
+    pub asset_bank_liquidity_vault_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_SEED.as_bytes(),
+            asset_bank.key().as_ref(),
+        ],
+        bump = asset_bank.load()?.liquidity_vault_bump
+    )]
+    pub asset_bank_liquidity_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_SEED.as_bytes(),
+            liab_bank.key().as_ref(),
+        ],
+        bump = liab_bank.load()?.liquidity_vault_bump
+    )]
+    pub liab_bank_liquidity_vault: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+}
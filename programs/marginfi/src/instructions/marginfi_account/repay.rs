@@ -1,7 +1,7 @@
 use crate::{
     check,
     constants::LIQUIDITY_VAULT_SEED,
-    events::{AccountEventHeader, LendingAccountRepayEvent},
+    events::{AccountEventHeader, LendingAccountRepayEventV2},
     prelude::{MarginfiError, MarginfiGroup, MarginfiResult},
     state::{
         marginfi_account::{BankAccountWrapper, MarginfiAccount, DISABLED_FLAG},
@@ -73,7 +73,7 @@ pub fn lending_account_repay(
         token_program.to_account_info(),
     )?;
 
-    emit!(LendingAccountRepayEvent {
+    emit!(LendingAccountRepayEventV2 {
         header: AccountEventHeader {
             signer: Some(ctx.accounts.signer.key()),
             marginfi_account: marginfi_account_loader.key(),
@@ -84,6 +84,7 @@ pub fn lending_account_repay(
         mint: bank.mint,
         amount: spl_deposit_amount,
         close_balance: repay_all,
+        bank_state: bank.get_state_snapshot(),
     });
 
     Ok(())
@@ -1,17 +1,34 @@
-use crate::{prelude::*, state::marginfi_account::MarginfiAccount};
+use crate::{
+    events::{AccountEventHeader, MarginfiAccountTransferAccountAuthorityEvent},
+    prelude::*,
+    state::marginfi_account::MarginfiAccount,
+};
 use anchor_lang::prelude::*;
 
 pub fn set_account_transfer_authority(
     ctx: Context<MarginfiAccountSetAccountAuthority>,
 ) -> MarginfiResult {
+    let new_account_authority = ctx.accounts.new_authority.key();
+
     // Ensure marginfi_account is dropped out of scope to not exceed stack frame limits
-    {
+    let (old_account_authority, marginfi_group) = {
         let mut marginfi_account = ctx.accounts.marginfi_account.load_mut()?;
-        let new_account_authority = ctx.accounts.new_authority.key();
+        let old_account_authority = marginfi_account.authority;
         marginfi_account.set_new_account_authority_checked(new_account_authority)?;
-    }
 
-    // TODO: add back event (dropped for memory reasons)
+        (old_account_authority, marginfi_account.group)
+    };
+
+    emit!(MarginfiAccountTransferAccountAuthorityEvent {
+        header: AccountEventHeader {
+            signer: Some(ctx.accounts.signer.key()),
+            marginfi_account: ctx.accounts.marginfi_account.key(),
+            marginfi_account_authority: old_account_authority,
+            marginfi_group,
+        },
+        old_account_authority,
+        new_account_authority,
+    });
 
     Ok(())
 }
@@ -0,0 +1,185 @@
+use anchor_lang::prelude::*;
+use fixed::types::I80F48;
+
+use crate::{
+    check,
+    events::{AccountEventHeader, LendingAccountTransferBalanceEvent},
+    prelude::*,
+    state::{
+        borrow_credential::BorrowCredential,
+        marginfi_account::{BankAccountWrapper, MarginfiAccount, RiskEngine, DISABLED_FLAG},
+        marginfi_group::Bank,
+    },
+    utils::find_bank_oracle_ais,
+};
+
+/// Move a balance, or part of it, between two marginfi accounts of the same authority.
+///
+/// 1. Accrue interest
+/// 2. Remove the asset or liability shares from the source account balance,
+///    closing it if the whole balance is moved
+/// 3. Add the shares to the destination account balance, creating it if it does not exist yet
+/// 4. Verify the bank USD deposit and borrow caps, if the bank totals grew
+/// 5. Verify the destination borrow credential, if moving a liability in permissioned borrowing mode
+/// 6. Verify that both accounts are in a healthy state
+///
+/// Remaining accounts are the source account observation accounts
+/// followed by the destination account observation accounts, both after the transfer.
+/// When moving a liability in permissioned borrowing mode, the destination account borrow credential
+/// is expected as the last remaining account.
+pub fn lending_account_transfer_balance(
+    ctx: Context<LendingAccountTransferBalance>,
+    amount: u64,
+    transfer_all: Option<bool>,
+) -> MarginfiResult {
+    let LendingAccountTransferBalance {
+        source_marginfi_account: source_marginfi_account_loader,
+        destination_marginfi_account: destination_marginfi_account_loader,
+        bank: bank_loader,
+        ..
+    } = ctx.accounts;
+
+    check!(
+        source_marginfi_account_loader.key() != destination_marginfi_account_loader.key(),
+        MarginfiError::InvalidTransfer
+    );
+
+    let mut source_marginfi_account = source_marginfi_account_loader.load_mut()?;
+    let mut destination_marginfi_account = destination_marginfi_account_loader.load_mut()?;
+
+    check!(
+        !source_marginfi_account.get_flag(DISABLED_FLAG)
+            && !destination_marginfi_account.get_flag(DISABLED_FLAG),
+        MarginfiError::AccountDisabled
+    );
+
+    let borrow_credential_required = {
+        let mut bank = bank_loader.load_mut()?;
+
+        bank.accrue_interest(
+            Clock::get()?.unix_timestamp,
+            #[cfg(not(feature = "client"))]
+            bank_loader.key(),
+        )?;
+
+        let pre_total_asset_shares = I80F48::from(bank.total_asset_shares);
+        let pre_total_liability_shares = I80F48::from(bank.total_liability_shares);
+
+        let (asset_shares, liability_shares, emissions_outstanding) = BankAccountWrapper::find(
+            &bank_loader.key(),
+            &mut bank,
+            &mut source_marginfi_account.lending_account,
+        )?
+        .transfer_out(I80F48::from_num(amount), transfer_all.unwrap_or(false))?;
+
+        let mut destination_bank_account = BankAccountWrapper::find_or_create(
+            &bank_loader.key(),
+            &mut bank,
+            &mut destination_marginfi_account.lending_account,
+        )?;
+
+        destination_bank_account.transfer_in(
+            asset_shares,
+            liability_shares,
+            emissions_outstanding,
+        )?;
+
+        destination_bank_account.bank.check_value_limits(
+            pre_total_asset_shares,
+            pre_total_liability_shares,
+            find_bank_oracle_ais(&bank_loader.key(), ctx.remaining_accounts),
+            Clock::get()?.unix_timestamp,
+        )?;
+
+        let borrow_credential_required = liability_shares.is_positive()
+            && destination_bank_account
+                .bank
+                .config
+                .is_borrow_permissioned();
+
+        if borrow_credential_required {
+            let borrow_credential_ai = ctx
+                .remaining_accounts
+                .last()
+                .ok_or(MarginfiError::InvalidBorrowCredential)?;
+
+            BorrowCredential::check_liability(
+                borrow_credential_ai,
+                &bank_loader.key(),
+                &destination_marginfi_account_loader.key(),
+                destination_bank_account.bank.get_liability_amount(
+                    destination_bank_account.balance.liability_shares.into(),
+                )?,
+            )?;
+        }
+
+        emit!(LendingAccountTransferBalanceEvent {
+            header: AccountEventHeader {
+                signer: Some(ctx.accounts.signer.key()),
+                marginfi_account: source_marginfi_account_loader.key(),
+                marginfi_account_authority: source_marginfi_account.authority,
+                marginfi_group: source_marginfi_account.group,
+            },
+            destination_marginfi_account: destination_marginfi_account_loader.key(),
+            bank: bank_loader.key(),
+            mint: bank.mint,
+            asset_amount: bank.get_asset_amount(asset_shares)?.to_num::<f64>(),
+            liability_amount: bank.get_liability_amount(liability_shares)?.to_num::<f64>(),
+        });
+
+        borrow_credential_required
+    };
+
+    let health_check_ais = if borrow_credential_required {
+        &ctx.remaining_accounts[..ctx.remaining_accounts.len() - 1]
+    } else {
+        ctx.remaining_accounts
+    };
+
+    let source_remaining_ais_len = source_marginfi_account.get_remaining_accounts_len();
+
+    check!(
+        health_check_ais.len() >= source_remaining_ais_len,
+        MarginfiError::MissingPythOrBankAccount
+    );
+
+    let (source_remaining_ais, destination_remaining_ais) =
+        health_check_ais.split_at(source_remaining_ais_len);
+
+    RiskEngine::check_account_init_health(&source_marginfi_account, source_remaining_ais)?;
+    RiskEngine::check_account_init_health(
+        &destination_marginfi_account,
+        destination_remaining_ais,
+    )?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingAccountTransferBalance<'info> {
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+
+    #[account(
+        mut,
+        constraint = source_marginfi_account.load()?.group == marginfi_group.key(),
+    )]
+    pub source_marginfi_account: AccountLoader<'info, MarginfiAccount>,
+
+    #[account(
+        mut,
+        constraint = destination_marginfi_account.load()?.group == marginfi_group.key(),
+        constraint = destination_marginfi_account.load()?.authority == source_marginfi_account.load()?.authority,
+    )]
+    pub destination_marginfi_account: AccountLoader<'info, MarginfiAccount>,
+
+    #[account(
+        address = source_marginfi_account.load()?.authority,
+    )]
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == marginfi_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+}
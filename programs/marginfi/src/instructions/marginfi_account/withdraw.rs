@@ -1,12 +1,13 @@
 use crate::{
     bank_signer, check,
     constants::{LIQUIDITY_VAULT_AUTHORITY_SEED, LIQUIDITY_VAULT_SEED},
-    events::{AccountEventHeader, LendingAccountWithdrawEvent},
+    events::{AccountEventHeader, LendingAccountWithdrawEventV2},
     prelude::*,
     state::{
         marginfi_account::{BankAccountWrapper, MarginfiAccount, RiskEngine, DISABLED_FLAG},
         marginfi_group::{Bank, BankVaultType},
     },
+    utils::find_bank_oracle_ais,
 };
 use anchor_lang::prelude::*;
 use anchor_spl::token::{Token, TokenAccount, Transfer};
@@ -17,9 +18,17 @@ use solana_program::{clock::Clock, sysvar::Sysvar};
 /// 2. Find the user's existing bank account for the asset withdrawn
 /// 3. Record asset decrease in the bank account
 /// 4. Transfer funds from the bank's liquidity vault to the signer's token account
-/// 5. Verify that the user account is in a healthy state
+/// 5. Verify the bank USD outflow limit, if active
+/// 6. Verify that the user account is in a healthy state
 ///
 /// Will error if there is no existing asset <=> borrowing is not allowed.
+///
+/// Remaining accounts are the `[bank, oracle]` pairs of every active balance of the account,
+/// for the health check.
+///
+/// When withdrawing in full, the balance of the withdrawn bank is closed and its pair can be omitted.
+/// Banks with an active USD outflow limit (`outflow_value_limit`) need the pair of the withdrawn bank,
+/// kept in its place, for the bank oracle. It is left out of the health check of the closed balance.
 pub fn lending_account_withdraw(
     ctx: Context<LendingAccountWithdraw>,
     amount: u64,
@@ -37,6 +46,7 @@ pub fn lending_account_withdraw(
 
     let withdraw_all = withdraw_all.unwrap_or(false);
     let mut marginfi_account = marginfi_account_loader.load_mut()?;
+    let clock = Clock::get()?;
 
     check!(
         !marginfi_account.get_flag(DISABLED_FLAG),
@@ -44,7 +54,7 @@ pub fn lending_account_withdraw(
     );
 
     bank_loader.load_mut()?.accrue_interest(
-        Clock::get()?.unix_timestamp,
+        clock.unix_timestamp,
         #[cfg(not(feature = "client"))]
         bank_loader.key(),
     )?;
@@ -82,7 +92,14 @@ pub fn lending_account_withdraw(
             ),
         )?;
 
-        emit!(LendingAccountWithdrawEvent {
+        if bank.is_outflow_value_limit_active() {
+            let oracle_ais = find_bank_oracle_ais(&bank_loader.key(), ctx.remaining_accounts)?;
+            let price = bank.get_limit_price(oracle_ais, clock.unix_timestamp)?;
+
+            bank.check_outflow_value_limit(price)?;
+        }
+
+        emit!(LendingAccountWithdrawEventV2 {
             header: AccountEventHeader {
                 signer: Some(ctx.accounts.signer.key()),
                 marginfi_account: marginfi_account_loader.key(),
@@ -93,12 +110,33 @@ pub fn lending_account_withdraw(
             mint: bank.mint,
             amount: spl_withdraw_amount,
             close_balance: withdraw_all,
+            bank_state: bank.get_state_snapshot(),
         });
     }
 
+    // A balance withdrawn in full is not part of the health check anymore
+    let closed_bank_index = if withdraw_all {
+        ctx.remaining_accounts
+            .iter()
+            .position(|ai| ai.key == &bank_loader.key())
+    } else {
+        None
+    };
+
     // Check account health, if below threshold fail transaction
     // Assuming `ctx.remaining_accounts` holds only oracle accounts
-    RiskEngine::check_account_init_health(&marginfi_account, ctx.remaining_accounts)?;
+    match closed_bank_index {
+        Some(index) => {
+            let health_check_ais = [
+                &ctx.remaining_accounts[..index],
+                ctx.remaining_accounts.get(index + 2..).unwrap_or_default(),
+            ]
+            .concat();
+
+            RiskEngine::check_account_init_health(&marginfi_account, &health_check_ais)?;
+        }
+        None => RiskEngine::check_account_init_health(&marginfi_account, ctx.remaining_accounts)?,
+    }
 
     Ok(())
 }
@@ -0,0 +1,306 @@
+use crate::{
+    bank_signer, check,
+    constants::{LIQUIDITY_VAULT_AUTHORITY_SEED, LIQUIDITY_VAULT_SEED, MAX_PRICE_AGE_SEC},
+    events::{AccountEventHeader, LendingPoolBankBadDebtAuctionEvent},
+    math_error,
+    prelude::MarginfiError,
+    state::{
+        marginfi_account::{
+            calc_amount, calc_value, BankAccountWrapper, MarginfiAccount, DISABLED_FLAG,
+        },
+        marginfi_group::{Bank, BankVaultType, MarginfiGroup},
+        price::{OraclePriceFeedAdapter, OraclePriceType, PriceAdapter, PriceBias},
+    },
+    MarginfiResult,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Token, TokenAccount, Transfer};
+use fixed::types::I80F48;
+use std::cmp::min;
+
+/// Auction the collateral left in a bankrupt marginfi account to recover the bad debt it deferred.
+///
+/// The buyer receives the remaining `asset_bank` balance of the bankrupt account and pays
+/// its oracle value, discounted by the auction discount, in `debt_bank` tokens into the `debt_bank` liquidity vault.
+/// The discount grows from 0 to `BAD_DEBT_AUCTION_MAX_DISCOUNT` over `BAD_DEBT_AUCTION_DURATION_SEC`
+/// since the bad debt was last deferred.
+///
+/// Only accounts whose `debt_bank` bad debt was deferred in bankruptcy can be auctioned.
+/// The collateral sold is not counted against the `asset_bank` outflow limit.
+/// The payment is capped at the bad debt deferred by the account and still unrecovered by the bank,
+/// the buyer then receives only the collateral worth the capped payment.
+///
+/// Expects the asset bank oracle and the debt bank oracle as remaining accounts.
+pub fn lending_pool_auction_bad_debt_collateral(
+    ctx: Context<LendingPoolAuctionBadDebtCollateral>,
+) -> MarginfiResult {
+    check!(
+        ctx.accounts.asset_bank.key() != ctx.accounts.debt_bank.key(),
+        MarginfiError::BadDebtAuctionNotAvailable,
+        "Asset and debt bank cannot be the same"
+    );
+
+    let LendingPoolAuctionBadDebtCollateral {
+        marginfi_account: marginfi_account_loader,
+        asset_bank: asset_bank_loader,
+        debt_bank: debt_bank_loader,
+        signer,
+        signer_token_account,
+        destination_token_account,
+        asset_bank_liquidity_vault,
+        asset_bank_liquidity_vault_authority,
+        debt_bank_liquidity_vault,
+        token_program,
+        ..
+    } = ctx.accounts;
+
+    let mut marginfi_account = marginfi_account_loader.load_mut()?;
+
+    check!(
+        marginfi_account.get_flag(DISABLED_FLAG),
+        MarginfiError::BadDebtAuctionNotAvailable,
+        "Only collateral of bankrupt accounts can be auctioned"
+    );
+
+    let current_timestamp = Clock::get()?.unix_timestamp;
+
+    asset_bank_loader.load_mut()?.accrue_interest(
+        current_timestamp,
+        #[cfg(not(feature = "client"))]
+        asset_bank_loader.key(),
+    )?;
+    debt_bank_loader.load_mut()?.accrue_interest(
+        current_timestamp,
+        #[cfg(not(feature = "client"))]
+        debt_bank_loader.key(),
+    )?;
+
+    let mut asset_bank = asset_bank_loader.load_mut()?;
+    let mut debt_bank = debt_bank_loader.load_mut()?;
+
+    check!(
+        I80F48::from(debt_bank.unrecovered_bad_debt) > I80F48::ZERO,
+        MarginfiError::BadDebtAuctionNotAvailable,
+        "No unrecovered bad debt"
+    );
+
+    let deferred_bad_debt = marginfi_account
+        .lending_account
+        .balances
+        .iter()
+        .find(|balance| balance.active && balance.bank_pk == debt_bank_loader.key())
+        .map(|balance| balance.deferred_bad_debt)
+        .unwrap_or_default();
+
+    check!(
+        deferred_bad_debt > 0,
+        MarginfiError::BadDebtAuctionNotAvailable,
+        "No bad debt deferred by the account"
+    );
+
+    check!(
+        ctx.remaining_accounts.len() >= 2,
+        MarginfiError::MissingPythAccount
+    );
+
+    let asset_price = OraclePriceFeedAdapter::try_from_bank_config(
+        &asset_bank.config,
+        &ctx.remaining_accounts[0..1],
+        current_timestamp,
+        MAX_PRICE_AGE_SEC,
+    )?
+    .get_price_of_type(OraclePriceType::RealTime, Some(PriceBias::Low))?;
+
+    let debt_price = OraclePriceFeedAdapter::try_from_bank_config(
+        &debt_bank.config,
+        &ctx.remaining_accounts[1..2],
+        current_timestamp,
+        MAX_PRICE_AGE_SEC,
+    )?
+    .get_price_of_type(OraclePriceType::RealTime, Some(PriceBias::High))?;
+
+    let discount = debt_bank.get_bad_debt_auction_discount(current_timestamp)?;
+
+    let max_payment_amount: u64 = min(
+        I80F48::from(debt_bank.unrecovered_bad_debt)
+            .checked_ceil()
+            .ok_or_else(math_error!())?
+            .checked_to_num()
+            .ok_or_else(math_error!())?,
+        deferred_bad_debt,
+    );
+
+    let (asset_amount, payment_amount) = {
+        let mut bank_account = BankAccountWrapper::find(
+            &asset_bank_loader.key(),
+            &mut asset_bank,
+            &mut marginfi_account.lending_account,
+        )?;
+
+        let collateral_amount = bank_account
+            .bank
+            .get_asset_amount(bank_account.balance.asset_shares.into())?;
+
+        let collateral_payment_amount: u64 = calc_amount(
+            calc_value(
+                collateral_amount,
+                asset_price,
+                bank_account.bank.mint_decimals,
+                Some(I80F48::ONE - discount),
+            )?,
+            debt_price,
+            debt_bank.mint_decimals,
+        )?
+        .checked_ceil()
+        .ok_or_else(math_error!())?
+        .checked_to_num()
+        .ok_or_else(math_error!())?;
+
+        if collateral_payment_amount <= max_payment_amount {
+            (
+                bank_account.withdraw_all_in_bad_debt_auction()?,
+                collateral_payment_amount,
+            )
+        } else {
+            // Only the collateral covering the outstanding bad debt is sold
+            let asset_amount: u64 = calc_amount(
+                calc_value(
+                    I80F48::from_num(max_payment_amount),
+                    debt_price,
+                    debt_bank.mint_decimals,
+                    None,
+                )?
+                .checked_div(I80F48::ONE - discount)
+                .ok_or_else(math_error!())?,
+                asset_price,
+                bank_account.bank.mint_decimals,
+            )?
+            .checked_floor()
+            .ok_or_else(math_error!())?
+            .checked_to_num()
+            .ok_or_else(math_error!())?;
+
+            bank_account.withdraw_in_bad_debt_auction(I80F48::from_num(asset_amount))?;
+
+            (asset_amount, max_payment_amount)
+        }
+    };
+
+    debt_bank.deposit_spl_transfer(
+        payment_amount,
+        Transfer {
+            from: signer_token_account.to_account_info(),
+            to: debt_bank_liquidity_vault.to_account_info(),
+            authority: signer.to_account_info(),
+        },
+        token_program.to_account_info(),
+    )?;
+
+    asset_bank.withdraw_spl_transfer(
+        asset_amount,
+        Transfer {
+            from: asset_bank_liquidity_vault.to_account_info(),
+            to: destination_token_account.to_account_info(),
+            authority: asset_bank_liquidity_vault_authority.to_account_info(),
+        },
+        token_program.to_account_info(),
+        bank_signer!(
+            BankVaultType::Liquidity,
+            asset_bank_loader.key(),
+            asset_bank.liquidity_vault_authority_bump
+        ),
+    )?;
+
+    debt_bank.recover_bad_debt(I80F48::from_num(payment_amount))?;
+
+    if let Some(balance) = marginfi_account
+        .lending_account
+        .balances
+        .iter_mut()
+        .find(|balance| balance.active && balance.bank_pk == debt_bank_loader.key())
+    {
+        balance.deferred_bad_debt = balance.deferred_bad_debt.saturating_sub(payment_amount);
+    }
+
+    emit!(LendingPoolBankBadDebtAuctionEvent {
+        header: AccountEventHeader {
+            signer: Some(signer.key()),
+            marginfi_account: marginfi_account_loader.key(),
+            marginfi_account_authority: marginfi_account.authority,
+            marginfi_group: marginfi_account.group,
+        },
+        asset_bank: asset_bank_loader.key(),
+        debt_bank: debt_bank_loader.key(),
+        asset_amount: asset_amount as f64,
+        payment_amount: payment_amount as f64,
+        discount: discount.to_num::<f64>(),
+        unrecovered_bad_debt: I80F48::from(debt_bank.unrecovered_bad_debt).to_num::<f64>(),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolAuctionBadDebtCollateral<'info> {
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+
+    #[account(
+        mut,
+        constraint = asset_bank.load()?.group == marginfi_group.key(),
+    )]
+    pub asset_bank: AccountLoader<'info, Bank>,
+
+    #[account(
+        mut,
+        constraint = debt_bank.load()?.group == marginfi_group.key(),
+    )]
+    pub debt_bank: AccountLoader<'info, Bank>,
+
+    #[account(
+        mut,
+        constraint = marginfi_account.load()?.group == marginfi_group.key(),
+    )]
+    pub marginfi_account: AccountLoader<'info, MarginfiAccount>,
+
+    pub signer: Signer<'info>,
+
+    /// CHECK: Token mint/authority are checked at transfer
+    #[account(mut)]
+    pub signer_token_account: AccountInfo<'info>,
+
+    /// CHECK: Token mint is checked at transfer
+    #[account(mut)]
+    pub destination_token_account: AccountInfo<'info>,
+
+    /// CHECK: Seed constraint
+    #[account(
+        seeds = [
+            LIQUIDITY_VAULT_AUTHORITY_SEED.as_bytes(),
+            asset_bank.key().as_ref(),
+        ],
+        bump = asset_bank.load()?.liquidity_vault_authority_bump
+    )]
+    pub asset_bank_liquidity_vault_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_SEED.as_bytes(),
+            asset_bank.key().as_ref(),
+        ],
+        bump = asset_bank.load()?.liquidity_vault_bump
+    )]
+    pub asset_bank_liquidity_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_SEED.as_bytes(),
+            debt_bank.key().as_ref(),
+        ],
+        bump = debt_bank.load()?.liquidity_vault_bump
+    )]
+    pub debt_bank_liquidity_vault: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+}
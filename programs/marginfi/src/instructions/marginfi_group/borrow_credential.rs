@@ -0,0 +1,152 @@
+use crate::{
+    constants::BORROW_CREDENTIAL_SEED,
+    events::{GroupEventHeader, LendingPoolBankSetBorrowCredentialEvent},
+    state::{
+        borrow_credential::BorrowCredential,
+        marginfi_account::MarginfiAccount,
+        marginfi_group::{Bank, MarginfiGroup},
+    },
+    MarginfiResult,
+};
+use anchor_lang::prelude::*;
+
+/// Issue a credential allowing `marginfi_account` to borrow from a bank in permissioned borrowing mode,
+/// up to `borrow_limit` (native amount, u64::MAX for no per account cap).
+pub fn lending_pool_create_borrow_credential(
+    ctx: Context<LendingPoolCreateBorrowCredential>,
+    borrow_limit: u64,
+) -> MarginfiResult {
+    let mut borrow_credential = ctx.accounts.borrow_credential.load_init()?;
+
+    borrow_credential.initialize(
+        ctx.accounts.marginfi_group.key(),
+        ctx.accounts.bank.key(),
+        ctx.accounts.marginfi_account.key(),
+        borrow_limit,
+    );
+
+    emit!(LendingPoolBankSetBorrowCredentialEvent {
+        header: GroupEventHeader {
+            marginfi_group: ctx.accounts.marginfi_group.key(),
+            signer: Some(*ctx.accounts.admin.key)
+        },
+        bank: ctx.accounts.bank.key(),
+        marginfi_account: ctx.accounts.marginfi_account.key(),
+        borrow_limit,
+        revoked: false,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolCreateBorrowCredential<'info> {
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+
+    #[account(
+        mut,
+        address = marginfi_group.load()?.admin,
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        constraint = bank.load()?.group == marginfi_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    #[account(
+        constraint = marginfi_account.load()?.group == marginfi_group.key(),
+    )]
+    pub marginfi_account: AccountLoader<'info, MarginfiAccount>,
+
+    #[account(
+        init,
+        space = 8 + std::mem::size_of::<BorrowCredential>(),
+        payer = admin,
+        seeds = [
+            BORROW_CREDENTIAL_SEED.as_bytes(),
+            bank.key().as_ref(),
+            marginfi_account.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub borrow_credential: AccountLoader<'info, BorrowCredential>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn lending_pool_update_borrow_credential(
+    ctx: Context<LendingPoolUpdateBorrowCredential>,
+    borrow_limit: u64,
+) -> MarginfiResult {
+    let mut borrow_credential = ctx.accounts.borrow_credential.load_mut()?;
+
+    borrow_credential.borrow_limit = borrow_limit;
+
+    emit!(LendingPoolBankSetBorrowCredentialEvent {
+        header: GroupEventHeader {
+            marginfi_group: ctx.accounts.marginfi_group.key(),
+            signer: Some(*ctx.accounts.admin.key)
+        },
+        bank: borrow_credential.bank,
+        marginfi_account: borrow_credential.marginfi_account,
+        borrow_limit,
+        revoked: false,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolUpdateBorrowCredential<'info> {
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+
+    #[account(
+        address = marginfi_group.load()?.admin,
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = borrow_credential.load()?.group == marginfi_group.key(),
+    )]
+    pub borrow_credential: AccountLoader<'info, BorrowCredential>,
+}
+
+/// Revoke a borrow credential, existing liabilities are not affected.
+pub fn lending_pool_revoke_borrow_credential(
+    ctx: Context<LendingPoolRevokeBorrowCredential>,
+) -> MarginfiResult {
+    let borrow_credential = ctx.accounts.borrow_credential.load()?;
+
+    emit!(LendingPoolBankSetBorrowCredentialEvent {
+        header: GroupEventHeader {
+            marginfi_group: ctx.accounts.marginfi_group.key(),
+            signer: Some(*ctx.accounts.admin.key)
+        },
+        bank: borrow_credential.bank,
+        marginfi_account: borrow_credential.marginfi_account,
+        borrow_limit: 0,
+        revoked: true,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolRevokeBorrowCredential<'info> {
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+
+    #[account(
+        mut,
+        address = marginfi_group.load()?.admin,
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        close = admin,
+        constraint = borrow_credential.load()?.group == marginfi_group.key(),
+    )]
+    pub borrow_credential: AccountLoader<'info, BorrowCredential>,
+}
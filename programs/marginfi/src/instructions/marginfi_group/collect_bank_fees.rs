@@ -1,4 +1,6 @@
-use crate::events::{GroupEventHeader, LendingPoolBankCollectFeesEvent};
+use crate::events::{
+    GroupEventHeader, LendingPoolBankCollectFeesEvent, LendingPoolBankRecoverBadDebtEvent,
+};
 use crate::{
     bank_signer,
     constants::{
@@ -25,6 +27,23 @@ pub fn lending_pool_collect_bank_fees(ctx: Context<LendingPoolCollectBankFees>)
 
     let mut bank = ctx.accounts.bank.load_mut()?;
 
+    // Insurance fees cover unrecovered bad debt first
+    let bad_debt_recovered = bank.recover_bad_debt_from_insurance_fees()?;
+
+    if bad_debt_recovered > I80F48::ZERO {
+        emit!(LendingPoolBankRecoverBadDebtEvent {
+            header: GroupEventHeader {
+                marginfi_group: ctx.accounts.marginfi_group.key(),
+                signer: None
+            },
+            bank: ctx.accounts.bank.key(),
+            mint: liquidity_vault.mint,
+            recovered_amount: bad_debt_recovered.to_num::<f64>(),
+            socialized_amount: 0.0,
+            unrecovered_bad_debt: I80F48::from(bank.unrecovered_bad_debt).to_num::<f64>(),
+        });
+    }
+
     let mut available_liquidity = I80F48::from_num(liquidity_vault.amount);
 
     let (insurance_fee_transfer_amount, new_outstanding_insurance_fees) = {
@@ -1,5 +1,9 @@
 use crate::check;
-use crate::events::{GroupEventHeader, MarginfiGroupConfigureEvent};
+use crate::events::{
+    AccountEventHeader, GroupEventHeader, MarginfiAccountSetFlagEvent,
+    MarginfiAccountUnsetFlagEvent, MarginfiGroupConfigureEvent,
+    MarginfiGroupConfigureFlashloanCpiProgramEvent,
+};
 use crate::prelude::MarginfiError;
 use crate::state::marginfi_account::{
     MarginfiAccount, FLASHLOAN_ENABLED_FLAG, TRANSFER_AUTHORITY_ALLOWED_FLAG,
@@ -40,6 +44,30 @@ pub struct MarginfiGroupConfigure<'info> {
     pub admin: Signer<'info>,
 }
 
+/// Allow or disallow a program to start and end flashloans via CPI
+///
+/// Admin only
+pub fn configure_flashloan_cpi_program(
+    ctx: Context<MarginfiGroupConfigure>,
+    program_id: Pubkey,
+    allowed: bool,
+) -> MarginfiResult {
+    let marginfi_group = &mut ctx.accounts.marginfi_group.load_mut()?;
+
+    marginfi_group.configure_flashloan_cpi_program(program_id, allowed)?;
+
+    emit!(MarginfiGroupConfigureFlashloanCpiProgramEvent {
+        header: GroupEventHeader {
+            marginfi_group: ctx.accounts.marginfi_group.key(),
+            signer: Some(*ctx.accounts.admin.key)
+        },
+        program_id,
+        allowed,
+    });
+
+    Ok(())
+}
+
 /// Only these flags can be configured
 ///
 /// Example:
@@ -65,6 +93,17 @@ pub fn set_account_flag(ctx: Context<SetAccountFlag>, flag: u64) -> MarginfiResu
 
     marginfi_account.set_flag(flag);
 
+    emit!(MarginfiAccountSetFlagEvent {
+        header: AccountEventHeader {
+            signer: Some(ctx.accounts.admin.key()),
+            marginfi_account: ctx.accounts.marginfi_account.key(),
+            marginfi_account_authority: marginfi_account.authority,
+            marginfi_group: marginfi_account.group,
+        },
+        flag,
+        account_flags: marginfi_account.account_flags,
+    });
+
     Ok(())
 }
 
@@ -88,6 +127,17 @@ pub fn unset_account_flag(ctx: Context<UnsetAccountFlag>, flag: u64) -> Marginfi
 
     marginfi_account.unset_flag(flag);
 
+    emit!(MarginfiAccountUnsetFlagEvent {
+        header: AccountEventHeader {
+            signer: Some(ctx.accounts.admin.key()),
+            marginfi_account: ctx.accounts.marginfi_account.key(),
+            marginfi_account_authority: marginfi_account.authority,
+            marginfi_group: marginfi_account.group,
+        },
+        flag,
+        account_flags: marginfi_account.account_flags,
+    });
+
     Ok(())
 }
 
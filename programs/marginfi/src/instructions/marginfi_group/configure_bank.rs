@@ -1,5 +1,9 @@
 use crate::constants::{EMISSIONS_AUTH_SEED, EMISSIONS_TOKEN_ACCOUNT_SEED};
-use crate::events::{GroupEventHeader, LendingPoolBankConfigureEvent};
+use crate::events::{
+    GroupEventHeader, LendingPoolBankConfigureEvent, LendingPoolBankRecoverBadDebtEvent,
+    LendingPoolBankResetOutflowEvent, LendingPoolBankSetupEmissionsEvent,
+    LendingPoolBankUpdateEmissionsEvent,
+};
 use crate::prelude::MarginfiError;
 use crate::{check, math_error};
 use crate::{
@@ -51,6 +55,74 @@ pub struct LendingPoolConfigureBank<'info> {
     pub bank: AccountLoader<'info, Bank>,
 }
 
+/// Admin override for the bank outflow limit, clears the outflow accumulated in the current window.
+pub fn lending_pool_reset_bank_outflow(
+    ctx: Context<LendingPoolResetBankOutflow>,
+) -> MarginfiResult {
+    let mut bank = ctx.accounts.bank.load_mut()?;
+
+    let outflow_reset = I80F48::from(bank.outflow_window_amount);
+    bank.reset_outflow(Clock::get()?.unix_timestamp);
+
+    emit!(LendingPoolBankResetOutflowEvent {
+        header: GroupEventHeader {
+            marginfi_group: ctx.accounts.marginfi_group.key(),
+            signer: Some(*ctx.accounts.admin.key)
+        },
+        bank: ctx.accounts.bank.key(),
+        mint: bank.mint,
+        outflow_reset: outflow_reset.to_num::<f64>(),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolResetBankOutflow<'info> {
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+
+    #[account(
+        address = marginfi_group.load()?.admin,
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == marginfi_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+}
+
+/// Admin override for deferred bad debt, socializes the unrecovered bad debt among depositors.
+pub fn lending_pool_socialize_bad_debt(ctx: Context<LendingPoolConfigureBank>) -> MarginfiResult {
+    let mut bank = ctx.accounts.bank.load_mut()?;
+
+    bank.accrue_interest(
+        Clock::get()?.unix_timestamp,
+        #[cfg(not(feature = "client"))]
+        ctx.accounts.bank.key(),
+    )?;
+
+    let socialized_loss = I80F48::from(bank.unrecovered_bad_debt);
+
+    bank.socialize_loss(socialized_loss)?;
+    bank.unrecovered_bad_debt = I80F48::ZERO.into();
+
+    emit!(LendingPoolBankRecoverBadDebtEvent {
+        header: GroupEventHeader {
+            marginfi_group: ctx.accounts.marginfi_group.key(),
+            signer: Some(*ctx.accounts.admin.key)
+        },
+        bank: ctx.accounts.bank.key(),
+        mint: bank.mint,
+        recovered_amount: 0.0,
+        socialized_amount: socialized_loss.to_num::<f64>(),
+        unrecovered_bad_debt: 0.0,
+    });
+
+    Ok(())
+}
+
 pub fn lending_pool_setup_emissions(
     ctx: Context<LendingPoolSetupEmissions>,
     emissions_flags: u64,
@@ -81,6 +153,18 @@ pub fn lending_pool_setup_emissions(
         total_emissions,
     )?;
 
+    emit!(LendingPoolBankSetupEmissionsEvent {
+        header: GroupEventHeader {
+            marginfi_group: ctx.accounts.marginfi_group.key(),
+            signer: Some(*ctx.accounts.admin.key)
+        },
+        bank: ctx.accounts.bank.key(),
+        emissions_mint: bank.emissions_mint,
+        emissions_flags,
+        emissions_rate,
+        total_emissions,
+    });
+
     Ok(())
 }
 
@@ -188,6 +272,19 @@ pub fn lending_pool_update_emissions_parameters(
         );
     }
 
+    emit!(LendingPoolBankUpdateEmissionsEvent {
+        header: GroupEventHeader {
+            marginfi_group: ctx.accounts.marginfi_group.key(),
+            signer: Some(*ctx.accounts.admin.key)
+        },
+        bank: ctx.accounts.bank.key(),
+        emissions_mint: bank.emissions_mint,
+        emissions_flags: bank.emissions_flags,
+        emissions_rate: bank.emissions_rate,
+        additional_emissions: additional_emissions.unwrap_or(0),
+        emissions_remaining: I80F48::from(bank.emissions_remaining).to_num::<f64>(),
+    });
+
     Ok(())
 }
 
@@ -1,5 +1,7 @@
 use crate::constants::ZERO_AMOUNT_THRESHOLD;
-use crate::events::{AccountEventHeader, LendingPoolBankHandleBankruptcyEvent};
+use crate::events::{
+    AccountEventHeader, LendingPoolBankDeferBadDebtEvent, LendingPoolBankHandleBankruptcyEvent,
+};
 use crate::state::marginfi_account::DISABLED_FLAG;
 use crate::{
     bank_signer, check,
@@ -22,7 +24,8 @@ use std::cmp::{max, min};
 /// 2. Determine the amount of bad debt covered by the insurance fund and the amount socialized between depositors.
 /// 3. Cover the bad debt of the bankrupt account.
 /// 4. Transfer the insured amount from the insurance fund.
-/// 5. Socialize the loss between lenders if any.
+/// 5. Socialize the loss between lenders if any, or record it on the bank and the account balance
+///    in deferred bad debt mode.
 pub fn lending_pool_handle_bankruptcy(ctx: Context<LendingPoolHandleBankruptcy>) -> MarginfiResult {
     let LendingPoolHandleBankruptcy {
         marginfi_account: marginfi_account_loader,
@@ -38,8 +41,10 @@ pub fn lending_pool_handle_bankruptcy(ctx: Context<LendingPoolHandleBankruptcy>)
 
     let mut bank = bank_loader.load_mut()?;
 
+    let current_timestamp = Clock::get()?.unix_timestamp;
+
     bank.accrue_interest(
-        Clock::get()?.unix_timestamp,
+        current_timestamp,
         #[cfg(not(feature = "client"))]
         bank_loader.key(),
     )?;
@@ -64,13 +69,13 @@ pub fn lending_pool_handle_bankruptcy(ctx: Context<LendingPoolHandleBankruptcy>)
         MarginfiError::BalanceNotBadDebt
     );
 
-    let (covered_by_insurance, socialized_loss) = {
+    let (covered_by_insurance, uncovered_loss) = {
         let available_insurance_funds = I80F48::from_num(insurance_vault.amount);
 
         let covered_by_insurance = min(bad_debt, available_insurance_funds);
-        let socialized_loss = max(bad_debt - covered_by_insurance, I80F48::ZERO);
+        let uncovered_loss = max(bad_debt - covered_by_insurance, I80F48::ZERO);
 
-        (covered_by_insurance, socialized_loss)
+        (covered_by_insurance, uncovered_loss)
     };
 
     // Cover bad debt with insurance funds.
@@ -91,8 +96,41 @@ pub fn lending_pool_handle_bankruptcy(ctx: Context<LendingPoolHandleBankruptcy>)
         ),
     )?;
 
-    // Socialize bad debt among depositors.
-    bank.socialize_loss(socialized_loss)?;
+    // Socialize bad debt among depositors, or defer it.
+    let socialized_loss = if bank.config.is_bad_debt_deferred() {
+        bank.defer_bad_debt(uncovered_loss, current_timestamp)?;
+
+        // Links the account collateral to the deferred bad debt, for the bad debt auction
+        lending_account_balance.deferred_bad_debt = lending_account_balance
+            .deferred_bad_debt
+            .checked_add(
+                uncovered_loss
+                    .checked_ceil()
+                    .ok_or_else(math_error!())?
+                    .checked_to_num()
+                    .ok_or_else(math_error!())?,
+            )
+            .ok_or_else(math_error!())?;
+
+        emit!(LendingPoolBankDeferBadDebtEvent {
+            header: AccountEventHeader {
+                signer: Some(ctx.accounts.admin.key()),
+                marginfi_account: marginfi_account_loader.key(),
+                marginfi_account_authority: marginfi_account.authority,
+                marginfi_group: marginfi_account.group,
+            },
+            bank: bank_loader.key(),
+            mint: bank.mint,
+            deferred_amount: uncovered_loss.to_num::<f64>(),
+            unrecovered_bad_debt: I80F48::from(bank.unrecovered_bad_debt).to_num::<f64>(),
+        });
+
+        I80F48::ZERO
+    } else {
+        bank.socialize_loss(uncovered_loss)?;
+
+        uncovered_loss
+    };
 
     // Settle bad debt.
     // The liabilities of this account and global total liabilities are reduced by `bad_debt`
@@ -101,7 +139,7 @@ pub fn lending_pool_handle_bankruptcy(ctx: Context<LendingPoolHandleBankruptcy>)
         &mut bank,
         &mut marginfi_account.lending_account,
     )?
-    .repay(bad_debt)?;
+    .repay_bad_debt(bad_debt)?;
 
     marginfi_account.set_flag(DISABLED_FLAG);
 
@@ -0,0 +1,582 @@
+use crate::{
+    bank_signer, check,
+    constants::{
+        INSURANCE_SHARE_MINT_SEED, INSURANCE_UNSTAKE_REQUEST_SEED, INSURANCE_VAULT_AUTHORITY_SEED,
+        INSURANCE_VAULT_SEED,
+    },
+    events::{
+        GroupEventHeader, LendingPoolBankInsuranceRequestUnstakeEvent,
+        LendingPoolBankInsuranceStakeEvent, LendingPoolBankInsuranceUnstakeEvent,
+        LendingPoolBankResetInsuranceStakingEvent, LendingPoolBankSetupInsuranceStakingEvent,
+    },
+    math_error,
+    prelude::MarginfiError,
+    state::{
+        insurance_unstake_request::InsuranceUnstakeRequest,
+        marginfi_group::{Bank, BankVaultType, MarginfiGroup},
+    },
+    MarginfiResult,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token::{
+    burn, mint_to, transfer, Burn, Mint, MintTo, Token, TokenAccount, Transfer,
+};
+
+/// Enable insurance staking for a bank.
+///
+/// Creates the insurance share mint, the current insurance vault balance
+/// is credited to the protocol as shares 1:1. Protocol shares are never redeemed.
+pub fn lending_pool_setup_insurance_staking(
+    ctx: Context<LendingPoolSetupInsuranceStaking>,
+    unstake_cooldown: u64,
+) -> MarginfiResult {
+    let mut bank = ctx.accounts.bank.load_mut()?;
+
+    check!(
+        !bank.is_insurance_staking_active(),
+        MarginfiError::InvalidConfig
+    );
+
+    bank.insurance_share_mint = ctx.accounts.insurance_share_mint.key();
+    bank.insurance_share_mint_bump = *ctx.bumps.get("insurance_share_mint").unwrap();
+    bank.insurance_protocol_shares = ctx.accounts.insurance_vault.amount;
+    bank.insurance_unstake_cooldown = unstake_cooldown;
+
+    emit!(LendingPoolBankSetupInsuranceStakingEvent {
+        header: GroupEventHeader {
+            marginfi_group: ctx.accounts.marginfi_group.key(),
+            signer: Some(*ctx.accounts.admin.key)
+        },
+        bank: ctx.accounts.bank.key(),
+        share_mint: bank.insurance_share_mint,
+        protocol_shares: bank.insurance_protocol_shares,
+        unstake_cooldown,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolSetupInsuranceStaking<'info> {
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+
+    #[account(
+        mut,
+        address = marginfi_group.load()?.admin,
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == marginfi_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    #[account(
+        seeds = [
+            INSURANCE_VAULT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.insurance_vault_bump
+    )]
+    pub insurance_vault: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK: Seed constraint
+    #[account(
+        seeds = [
+            INSURANCE_VAULT_AUTHORITY_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.insurance_vault_authority_bump
+    )]
+    pub insurance_vault_authority: AccountInfo<'info>,
+
+    #[account(
+        init,
+        payer = admin,
+        mint::decimals = bank.load()?.mint_decimals,
+        mint::authority = insurance_vault_authority,
+        seeds = [
+            INSURANCE_SHARE_MINT_SEED.as_bytes(),
+            bank.key().as_ref(),
+            &[bank.load()?.insurance_share_generation],
+        ],
+        bump,
+    )]
+    pub insurance_share_mint: Box<Account<'info, Mint>>,
+
+    pub rent: Sysvar<'info, Rent>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Stake `amount` of the bank mint into the insurance vault for share tokens.
+///
+/// Stakers earn the insurance fees collected into the vault,
+/// and cover bad debt before it is socialized among depositors.
+///
+/// Will error if the stake mints no shares.
+pub fn lending_pool_insurance_stake(
+    ctx: Context<LendingPoolInsuranceStake>,
+    amount: u64,
+) -> MarginfiResult {
+    let LendingPoolInsuranceStake {
+        bank: bank_loader,
+        signer,
+        signer_token_account,
+        insurance_vault,
+        insurance_vault_authority,
+        insurance_share_mint,
+        destination_share_account,
+        token_program,
+        ..
+    } = ctx.accounts;
+
+    let mut bank = bank_loader.load_mut()?;
+
+    check!(
+        bank.is_insurance_staking_active(),
+        MarginfiError::InsuranceStakingNotEnabled
+    );
+
+    let shares =
+        bank.stake_insurance(amount, insurance_vault.amount, insurance_share_mint.supply)?;
+
+    transfer(
+        CpiContext::new(
+            token_program.to_account_info(),
+            Transfer {
+                from: signer_token_account.to_account_info(),
+                to: insurance_vault.to_account_info(),
+                authority: signer.to_account_info(),
+            },
+        ),
+        amount,
+    )?;
+
+    mint_to(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            MintTo {
+                mint: insurance_share_mint.to_account_info(),
+                to: destination_share_account.to_account_info(),
+                authority: insurance_vault_authority.to_account_info(),
+            },
+            bank_signer!(
+                BankVaultType::Insurance,
+                bank_loader.key(),
+                bank.insurance_vault_authority_bump
+            ),
+        ),
+        shares,
+    )?;
+
+    emit!(LendingPoolBankInsuranceStakeEvent {
+        header: GroupEventHeader {
+            marginfi_group: ctx.accounts.marginfi_group.key(),
+            signer: Some(signer.key())
+        },
+        bank: bank_loader.key(),
+        mint: bank.mint,
+        amount,
+        shares,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolInsuranceStake<'info> {
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == marginfi_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    pub signer: Signer<'info>,
+
+    /// CHECK: Token mint/authority are checked at transfer
+    #[account(mut)]
+    pub signer_token_account: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            INSURANCE_VAULT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.insurance_vault_bump
+    )]
+    pub insurance_vault: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK: Seed constraint
+    #[account(
+        seeds = [
+            INSURANCE_VAULT_AUTHORITY_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.insurance_vault_authority_bump
+    )]
+    pub insurance_vault_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        address = bank.load()?.insurance_share_mint,
+    )]
+    pub insurance_share_mint: Box<Account<'info, Mint>>,
+
+    /// CHECK: Token mint is checked at mint_to
+    #[account(mut)]
+    pub destination_share_account: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+/// Burn `shares` and start the unstake cooldown.
+///
+/// Shares in the cooldown keep their claim on the insurance vault and stay exposed to bad debt,
+/// they can be redeemed with `lending_pool_insurance_unstake` once the cooldown elapses.
+///
+/// A staker can have several pending unstake requests, told apart by `request_index`.
+pub fn lending_pool_insurance_request_unstake(
+    ctx: Context<LendingPoolInsuranceRequestUnstake>,
+    shares: u64,
+    request_index: u64,
+) -> MarginfiResult {
+    let LendingPoolInsuranceRequestUnstake {
+        bank: bank_loader,
+        signer,
+        insurance_share_mint,
+        signer_share_account,
+        unstake_request: unstake_request_loader,
+        token_program,
+        ..
+    } = ctx.accounts;
+
+    let mut bank = bank_loader.load_mut()?;
+
+    check!(
+        bank.is_insurance_staking_active(),
+        MarginfiError::InsuranceStakingNotEnabled
+    );
+
+    burn(
+        CpiContext::new(
+            token_program.to_account_info(),
+            Burn {
+                mint: insurance_share_mint.to_account_info(),
+                from: signer_share_account.to_account_info(),
+                authority: signer.to_account_info(),
+            },
+        ),
+        shares,
+    )?;
+
+    bank.insurance_cooldown_shares = bank
+        .insurance_cooldown_shares
+        .checked_add(shares)
+        .ok_or_else(math_error!())?;
+
+    let mut unstake_request = unstake_request_loader.load_init()?;
+
+    unstake_request.bank = bank_loader.key();
+    unstake_request.authority = signer.key();
+    unstake_request.share_mint = insurance_share_mint.key();
+    unstake_request.request_index = request_index;
+    unstake_request.shares = shares;
+    unstake_request.cooldown_start = Clock::get()?.unix_timestamp;
+
+    emit!(LendingPoolBankInsuranceRequestUnstakeEvent {
+        header: GroupEventHeader {
+            marginfi_group: ctx.accounts.marginfi_group.key(),
+            signer: Some(signer.key())
+        },
+        bank: bank_loader.key(),
+        shares,
+        cooldown_shares: bank.insurance_cooldown_shares,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(shares: u64, request_index: u64)]
+pub struct LendingPoolInsuranceRequestUnstake<'info> {
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == marginfi_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        address = bank.load()?.insurance_share_mint,
+    )]
+    pub insurance_share_mint: Box<Account<'info, Mint>>,
+
+    /// CHECK: Token mint/authority are checked at burn
+    #[account(mut)]
+    pub signer_share_account: AccountInfo<'info>,
+
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + std::mem::size_of::<InsuranceUnstakeRequest>(),
+        seeds = [
+            INSURANCE_UNSTAKE_REQUEST_SEED.as_bytes(),
+            bank.key().as_ref(),
+            signer.key().as_ref(),
+            request_index.to_le_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    pub unstake_request: AccountLoader<'info, InsuranceUnstakeRequest>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Redeem the shares of an unstake request for their value in the insurance vault,
+/// once the unstake cooldown has elapsed.
+///
+/// Requests for shares of a share mint written off by `lending_pool_reset_insurance_staking`
+/// redeem nothing, they are only closed.
+pub fn lending_pool_insurance_unstake(
+    ctx: Context<LendingPoolInsuranceUnstake>,
+    _request_index: u64,
+) -> MarginfiResult {
+    let LendingPoolInsuranceUnstake {
+        bank: bank_loader,
+        signer,
+        destination_token_account,
+        insurance_vault,
+        insurance_vault_authority,
+        insurance_share_mint,
+        unstake_request: unstake_request_loader,
+        token_program,
+        ..
+    } = ctx.accounts;
+
+    let mut bank = bank_loader.load_mut()?;
+    let unstake_request = unstake_request_loader.load()?;
+
+    let shares = unstake_request.shares;
+    let amount = if unstake_request.share_mint == bank.insurance_share_mint {
+        check!(
+            unstake_request.is_cooldown_elapsed(
+                bank.insurance_unstake_cooldown,
+                Clock::get()?.unix_timestamp
+            ),
+            MarginfiError::InsuranceUnstakeCooldownActive
+        );
+
+        let amount = bank.get_insurance_amount_for_shares(
+            shares,
+            insurance_vault.amount,
+            insurance_share_mint.supply,
+        )?;
+
+        bank.insurance_cooldown_shares = bank
+            .insurance_cooldown_shares
+            .checked_sub(shares)
+            .ok_or_else(math_error!())?;
+
+        amount
+    } else {
+        0
+    };
+
+    bank.withdraw_spl_transfer(
+        amount,
+        Transfer {
+            from: insurance_vault.to_account_info(),
+            to: destination_token_account.to_account_info(),
+            authority: insurance_vault_authority.to_account_info(),
+        },
+        token_program.to_account_info(),
+        bank_signer!(
+            BankVaultType::Insurance,
+            bank_loader.key(),
+            bank.insurance_vault_authority_bump
+        ),
+    )?;
+
+    emit!(LendingPoolBankInsuranceUnstakeEvent {
+        header: GroupEventHeader {
+            marginfi_group: ctx.accounts.marginfi_group.key(),
+            signer: Some(signer.key())
+        },
+        bank: bank_loader.key(),
+        mint: bank.mint,
+        amount,
+        shares,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(request_index: u64)]
+pub struct LendingPoolInsuranceUnstake<'info> {
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == marginfi_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    /// CHECK: Token mint is checked at transfer
+    #[account(mut)]
+    pub destination_token_account: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            INSURANCE_VAULT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.insurance_vault_bump
+    )]
+    pub insurance_vault: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK: Seed constraint
+    #[account(
+        seeds = [
+            INSURANCE_VAULT_AUTHORITY_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.insurance_vault_authority_bump
+    )]
+    pub insurance_vault_authority: AccountInfo<'info>,
+
+    #[account(address = bank.load()?.insurance_share_mint)]
+    pub insurance_share_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        mut,
+        close = signer,
+        seeds = [
+            INSURANCE_UNSTAKE_REQUEST_SEED.as_bytes(),
+            bank.key().as_ref(),
+            signer.key().as_ref(),
+            request_index.to_le_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    pub unstake_request: AccountLoader<'info, InsuranceUnstakeRequest>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+/// Start over with a new insurance share mint once the insurance vault got drained by bad debt.
+///
+/// Shares of a depleted insurance fund are worth nothing and would block new stakes,
+/// the outstanding share tokens, protocol shares and shares in the unstake cooldown are written off.
+/// The next stake seeds the new share mint like the first stake after enabling insurance staking.
+pub fn lending_pool_reset_insurance_staking(
+    ctx: Context<LendingPoolResetInsuranceStaking>,
+) -> MarginfiResult {
+    let mut bank = ctx.accounts.bank.load_mut()?;
+
+    check!(
+        bank.is_insurance_staking_active(),
+        MarginfiError::InsuranceStakingNotEnabled
+    );
+
+    let written_off_shares =
+        bank.get_insurance_total_shares(ctx.accounts.insurance_share_mint.supply)?;
+
+    check!(
+        ctx.accounts.insurance_vault.amount == 0 && written_off_shares > 0,
+        MarginfiError::InsuranceFundNotDepleted
+    );
+
+    bank.insurance_share_generation = bank
+        .insurance_share_generation
+        .checked_add(1)
+        .ok_or_else(math_error!())?;
+    bank.insurance_share_mint = ctx.accounts.new_insurance_share_mint.key();
+    bank.insurance_share_mint_bump = *ctx.bumps.get("new_insurance_share_mint").unwrap();
+    bank.insurance_protocol_shares = 0;
+    bank.insurance_cooldown_shares = 0;
+
+    emit!(LendingPoolBankResetInsuranceStakingEvent {
+        header: GroupEventHeader {
+            marginfi_group: ctx.accounts.marginfi_group.key(),
+            signer: Some(*ctx.accounts.admin.key)
+        },
+        bank: ctx.accounts.bank.key(),
+        share_mint: bank.insurance_share_mint,
+        written_off_shares,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolResetInsuranceStaking<'info> {
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+
+    #[account(
+        mut,
+        address = marginfi_group.load()?.admin,
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == marginfi_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    #[account(
+        seeds = [
+            INSURANCE_VAULT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.insurance_vault_bump
+    )]
+    pub insurance_vault: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK: Seed constraint
+    #[account(
+        seeds = [
+            INSURANCE_VAULT_AUTHORITY_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.insurance_vault_authority_bump
+    )]
+    pub insurance_vault_authority: AccountInfo<'info>,
+
+    #[account(address = bank.load()?.insurance_share_mint)]
+    pub insurance_share_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        init,
+        payer = admin,
+        mint::decimals = bank.load()?.mint_decimals,
+        mint::authority = insurance_vault_authority,
+        seeds = [
+            INSURANCE_SHARE_MINT_SEED.as_bytes(),
+            bank.key().as_ref(),
+            &[bank.load()?.insurance_share_generation.wrapping_add(1)],
+        ],
+        bump,
+    )]
+    pub new_insurance_share_mint: Box<Account<'info, Mint>>,
+
+    pub rent: Sysvar<'info, Rent>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
@@ -1,15 +1,21 @@
 mod accrue_bank_interest;
 mod add_pool;
+mod bad_debt_auction;
+mod borrow_credential;
 mod collect_bank_fees;
 mod configure;
 mod configure_bank;
 mod handle_bankruptcy;
 mod initialize;
+mod insurance_staking;
 
 pub use accrue_bank_interest::*;
 pub use add_pool::*;
+pub use bad_debt_auction::*;
+pub use borrow_credential::*;
 pub use collect_bank_fees::*;
 pub use configure::*;
 pub use configure_bank::*;
 pub use handle_bankruptcy::*;
 pub use initialize::*;
+pub use insurance_staking::*;
@@ -10,7 +10,10 @@ pub mod utils;
 use anchor_lang::prelude::*;
 use instructions::*;
 use prelude::*;
-use state::marginfi_group::{BankConfigCompact, BankConfigOpt};
+use state::{
+    marginfi_group::{BankConfigCompact, BankConfigOpt, WrappedI80F48},
+    order::OrderTrigger,
+};
 
 cfg_if::cfg_if! {
     if #[cfg(feature = "mainnet-beta")] {
@@ -37,6 +40,14 @@ pub mod marginfi {
         marginfi_group::configure(ctx, config)
     }
 
+    pub fn marginfi_group_configure_flashloan_cpi_program(
+        ctx: Context<MarginfiGroupConfigure>,
+        program_id: Pubkey,
+        allowed: bool,
+    ) -> MarginfiResult {
+        marginfi_group::configure_flashloan_cpi_program(ctx, program_id, allowed)
+    }
+
     pub fn lending_pool_add_bank(
         ctx: Context<LendingPoolAddBank>,
         bank_config: BankConfigCompact,
@@ -62,6 +73,13 @@ pub mod marginfi {
         marginfi_group::lending_pool_configure_bank(ctx, bank_config_opt)
     }
 
+    /// Admin override for the bank outflow limit, clears the accumulated outflow.
+    pub fn lending_pool_reset_bank_outflow(
+        ctx: Context<LendingPoolResetBankOutflow>,
+    ) -> MarginfiResult {
+        marginfi_group::lending_pool_reset_bank_outflow(ctx)
+    }
+
     pub fn lending_pool_setup_emissions(
         ctx: Context<LendingPoolSetupEmissions>,
         flags: u64,
@@ -85,6 +103,78 @@ pub mod marginfi {
         )
     }
 
+    /// Issue a borrow credential for a bank in permissioned borrowing mode.
+    pub fn lending_pool_create_borrow_credential(
+        ctx: Context<LendingPoolCreateBorrowCredential>,
+        borrow_limit: u64,
+    ) -> MarginfiResult {
+        marginfi_group::lending_pool_create_borrow_credential(ctx, borrow_limit)
+    }
+
+    pub fn lending_pool_update_borrow_credential(
+        ctx: Context<LendingPoolUpdateBorrowCredential>,
+        borrow_limit: u64,
+    ) -> MarginfiResult {
+        marginfi_group::lending_pool_update_borrow_credential(ctx, borrow_limit)
+    }
+
+    pub fn lending_pool_revoke_borrow_credential(
+        ctx: Context<LendingPoolRevokeBorrowCredential>,
+    ) -> MarginfiResult {
+        marginfi_group::lending_pool_revoke_borrow_credential(ctx)
+    }
+
+    /// Admin override for deferred bad debt, socializes the unrecovered bad debt among depositors.
+    pub fn lending_pool_socialize_bad_debt(
+        ctx: Context<LendingPoolConfigureBank>,
+    ) -> MarginfiResult {
+        marginfi_group::lending_pool_socialize_bad_debt(ctx)
+    }
+
+    /// Auction the collateral left in a bankrupt account to recover deferred bad debt.
+    pub fn lending_pool_auction_bad_debt_collateral(
+        ctx: Context<LendingPoolAuctionBadDebtCollateral>,
+    ) -> MarginfiResult {
+        marginfi_group::lending_pool_auction_bad_debt_collateral(ctx)
+    }
+
+    /// Enable insurance staking for a bank, creating the insurance share mint.
+    pub fn lending_pool_setup_insurance_staking(
+        ctx: Context<LendingPoolSetupInsuranceStaking>,
+        unstake_cooldown: u64,
+    ) -> MarginfiResult {
+        marginfi_group::lending_pool_setup_insurance_staking(ctx, unstake_cooldown)
+    }
+
+    pub fn lending_pool_insurance_stake(
+        ctx: Context<LendingPoolInsuranceStake>,
+        amount: u64,
+    ) -> MarginfiResult {
+        marginfi_group::lending_pool_insurance_stake(ctx, amount)
+    }
+
+    pub fn lending_pool_insurance_request_unstake(
+        ctx: Context<LendingPoolInsuranceRequestUnstake>,
+        shares: u64,
+        request_index: u64,
+    ) -> MarginfiResult {
+        marginfi_group::lending_pool_insurance_request_unstake(ctx, shares, request_index)
+    }
+
+    pub fn lending_pool_insurance_unstake(
+        ctx: Context<LendingPoolInsuranceUnstake>,
+        request_index: u64,
+    ) -> MarginfiResult {
+        marginfi_group::lending_pool_insurance_unstake(ctx, request_index)
+    }
+
+    /// Write off the shares of a depleted insurance fund, moving insurance staking to a new share mint.
+    pub fn lending_pool_reset_insurance_staking(
+        ctx: Context<LendingPoolResetInsuranceStaking>,
+    ) -> MarginfiResult {
+        marginfi_group::lending_pool_reset_insurance_staking(ctx)
+    }
+
     /// Handle bad debt of a bankrupt marginfi account for a given bank.
     pub fn lending_pool_handle_bankruptcy(
         ctx: Context<LendingPoolHandleBankruptcy>,
@@ -114,6 +204,10 @@ pub mod marginfi {
         marginfi_account::lending_account_repay(ctx, amount, repay_all)
     }
 
+    /// Withdraw from a lending account balance.
+    ///
+    /// Remaining accounts are the `[bank, oracle]` pairs of the account balances, followed,
+    /// when withdrawing in full from a bank with a USD outflow limit, by the bank oracle.
     pub fn lending_account_withdraw(
         ctx: Context<LendingAccountWithdraw>,
         amount: u64,
@@ -135,6 +229,14 @@ pub mod marginfi {
         marginfi_account::lending_account_close_balance(ctx)
     }
 
+    pub fn lending_account_transfer_balance(
+        ctx: Context<LendingAccountTransferBalance>,
+        amount: u64,
+        transfer_all: Option<bool>,
+    ) -> MarginfiResult {
+        marginfi_account::lending_account_transfer_balance(ctx, amount, transfer_all)
+    }
+
     pub fn lending_account_withdraw_emissions(
         ctx: Context<LendingAccountWithdrawEmissions>,
     ) -> MarginfiResult {
@@ -155,6 +257,26 @@ pub mod marginfi {
         marginfi_account::lending_account_liquidate(ctx, asset_amount)
     }
 
+    pub fn marginfi_account_create_order(
+        ctx: Context<MarginfiAccountCreateOrder>,
+        trigger: OrderTrigger,
+        trigger_value: WrappedI80F48,
+        keeper_tip: WrappedI80F48,
+    ) -> MarginfiResult {
+        marginfi_account::marginfi_account_create_order(ctx, trigger, trigger_value, keeper_tip)
+    }
+
+    pub fn marginfi_account_close_order(ctx: Context<MarginfiAccountCloseOrder>) -> MarginfiResult {
+        marginfi_account::marginfi_account_close_order(ctx)
+    }
+
+    /// Execute a triggered stop-loss / take-profit order of a marginfi account
+    pub fn lending_account_execute_order(
+        ctx: Context<LendingAccountExecuteOrder>,
+    ) -> MarginfiResult {
+        marginfi_account::lending_account_execute_order(ctx)
+    }
+
     pub fn lending_account_start_flashloan(
         ctx: Context<LendingAccountStartFlashloan>,
         end_index: u64,
@@ -0,0 +1,76 @@
+use crate::{assert_struct_size, check, prelude::*};
+use anchor_lang::prelude::*;
+use fixed::types::I80F48;
+#[cfg(any(feature = "test", feature = "client"))]
+use type_layout::TypeLayout;
+
+assert_struct_size!(BorrowCredential, 256);
+/// Group issued credential allowing a marginfi account to borrow from a bank
+/// in permissioned borrowing mode.
+///
+/// PDA seeds: [BORROW_CREDENTIAL_SEED, bank, marginfi_account]
+#[account(zero_copy)]
+#[repr(C)]
+#[cfg_attr(
+    any(feature = "test", feature = "client"),
+    derive(Debug, PartialEq, Eq, TypeLayout)
+)]
+pub struct BorrowCredential {
+    pub group: Pubkey,
+    pub bank: Pubkey,
+    pub marginfi_account: Pubkey,
+    /// Max liability of the marginfi account in the bank, in native amount.
+    /// u64::MAX -> no per account cap
+    pub borrow_limit: u64,
+    pub _padding: [u64; 19],
+}
+
+impl BorrowCredential {
+    pub fn initialize(
+        &mut self,
+        group: Pubkey,
+        bank: Pubkey,
+        marginfi_account: Pubkey,
+        borrow_limit: u64,
+    ) {
+        self.group = group;
+        self.bank = bank;
+        self.marginfi_account = marginfi_account;
+        self.borrow_limit = borrow_limit;
+    }
+
+    #[inline]
+    pub fn is_borrow_limit_active(&self) -> bool {
+        self.borrow_limit != u64::MAX
+    }
+
+    /// Verify that `borrow_credential_ai` is the credential of `marginfi_account` in `bank`,
+    /// and that the account liability in the bank, `liability_amount`, is within its borrow limit.
+    ///
+    /// Used wherever the liability of an account grows in a bank in permissioned borrowing mode.
+    pub fn check_liability(
+        borrow_credential_ai: &AccountInfo,
+        bank: &Pubkey,
+        marginfi_account: &Pubkey,
+        liability_amount: I80F48,
+    ) -> MarginfiResult {
+        let borrow_credential_loader =
+            AccountLoader::<BorrowCredential>::try_from(borrow_credential_ai)?;
+        let borrow_credential = borrow_credential_loader.load()?;
+
+        check!(
+            borrow_credential.bank == *bank
+                && borrow_credential.marginfi_account == *marginfi_account,
+            MarginfiError::InvalidBorrowCredential
+        );
+
+        if borrow_credential.is_borrow_limit_active() {
+            check!(
+                liability_amount <= I80F48::from_num(borrow_credential.borrow_limit),
+                MarginfiError::AccountBorrowLimitExceeded
+            );
+        }
+
+        Ok(())
+    }
+}
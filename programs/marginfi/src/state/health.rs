@@ -0,0 +1,762 @@
+use super::{
+    borrow_credential::BorrowCredential,
+    marginfi_account::{
+        calc_amount, calc_value, Balance, BalanceSide, MarginfiAccount, RequirementType,
+        RiskRequirementType,
+    },
+    marginfi_group::{Bank, RiskTier},
+    price::{OraclePriceFeedAdapter, OraclePriceType, PriceAdapter, PriceBias},
+};
+use crate::{
+    check,
+    constants::{BANKRUPT_THRESHOLD, ZERO_AMOUNT_THRESHOLD},
+    math_error,
+    prelude::{MarginfiError, MarginfiResult},
+};
+use anchor_lang::prelude::*;
+use fixed::types::I80F48;
+use std::{
+    cmp::{max, min},
+    collections::HashMap,
+    ops::Not,
+};
+
+/// A lending account balance together with its deserialized bank and oracle price feed.
+///
+/// This is the unit of account health math, shared by the on-chain [`RiskEngine`](super::marginfi_account::RiskEngine)
+/// and the off-chain [`HealthEngine`].
+/// `price_feed` is `None` when the oracle could not be loaded.
+pub struct BalanceWithPriceFeed<'a> {
+    pub bank: &'a Bank,
+    pub balance: &'a Balance,
+    pub price_feed: Option<&'a OraclePriceFeedAdapter>,
+}
+
+impl<'a> BalanceWithPriceFeed<'a> {
+    #[inline(always)]
+    /// Calculate the value of the assets and liabilities of the account in the form of (assets, liabilities)
+    ///
+    /// Nuances:
+    /// 1. Maintenance requirement is calculated using the real time price feed.
+    /// 2. Initial requirement is calculated using the time weighted price feed, if available.
+    /// 3. Initial requirement is discounted by the initial discount, if enabled and the usd limit is exceeded.
+    /// 4. Assets are only calculated for collateral risk tier.
+    /// 5. Oracle errors are ignored for deposits in isolated risk tier.
+    pub fn calc_weighted_assets_and_liabilities_values(
+        &self,
+        requirement_type: RequirementType,
+    ) -> MarginfiResult<(I80F48, I80F48)> {
+        match self.balance.get_side() {
+            Some(BalanceSide::Assets) => {
+                Ok((self.calc_weighted_assets(requirement_type)?, I80F48::ZERO))
+            }
+            Some(BalanceSide::Liabilities) => {
+                Ok((I80F48::ZERO, self.calc_weighted_liabs(requirement_type)?))
+            }
+            None => Ok((I80F48::ZERO, I80F48::ZERO)),
+        }
+    }
+
+    #[inline(always)]
+    fn calc_weighted_assets(&self, requirement_type: RequirementType) -> MarginfiResult<I80F48> {
+        match self.bank.config.risk_tier {
+            RiskTier::Collateral => {
+                let mut asset_weight = self
+                    .bank
+                    .config
+                    .get_weight(requirement_type, BalanceSide::Assets);
+
+                let lower_price = self.get_price(requirement_type, BalanceSide::Assets)?;
+
+                if matches!(requirement_type, RequirementType::Initial) {
+                    if let Some(discount) = self
+                        .bank
+                        .maybe_get_asset_weight_init_discount(lower_price)?
+                    {
+                        asset_weight = asset_weight
+                            .checked_mul(discount)
+                            .ok_or_else(math_error!())?;
+                    }
+                }
+
+                calc_value(
+                    self.bank
+                        .get_asset_amount(self.balance.asset_shares.into())?,
+                    lower_price,
+                    self.bank.mint_decimals,
+                    Some(asset_weight),
+                )
+            }
+            RiskTier::Isolated => Ok(I80F48::ZERO),
+        }
+    }
+
+    #[inline(always)]
+    fn calc_weighted_liabs(&self, requirement_type: RequirementType) -> MarginfiResult<I80F48> {
+        let liability_weight = self
+            .bank
+            .config
+            .get_weight(requirement_type, BalanceSide::Liabilities);
+
+        let higher_price = self.get_price(requirement_type, BalanceSide::Liabilities)?;
+
+        calc_value(
+            self.bank
+                .get_liability_amount(self.balance.liability_shares.into())?,
+            higher_price,
+            self.bank.mint_decimals,
+            Some(liability_weight),
+        )
+    }
+
+    /// Oracle price used to value a side of the balance, biased against the account.
+    pub fn get_price(
+        &self,
+        requirement_type: RequirementType,
+        side: BalanceSide,
+    ) -> MarginfiResult<I80F48> {
+        get_biased_price(self.price_feed, requirement_type, side)
+    }
+}
+
+fn get_biased_price(
+    price_feed: Option<&OraclePriceFeedAdapter>,
+    requirement_type: RequirementType,
+    side: BalanceSide,
+) -> MarginfiResult<I80F48> {
+    let price_feed = price_feed.ok_or(MarginfiError::StaleOracle)?;
+    let bias = match side {
+        BalanceSide::Assets => PriceBias::Low,
+        BalanceSide::Liabilities => PriceBias::High,
+    };
+
+    price_feed.get_price_of_type(requirement_type.get_oracle_price_type(), Some(bias))
+}
+
+/// An account is liquidatable once its maintenance health is no longer positive.
+#[inline]
+pub fn is_liquidatable(maintenance_health: I80F48) -> bool {
+    maintenance_health <= I80F48::ZERO
+}
+
+/// An account is bankrupt when it is insolvent and the value of its remaining assets is below the bankruptcy threshold.
+#[inline]
+pub fn is_bankrupt(total_assets: I80F48, total_liabilities: I80F48) -> bool {
+    total_assets < total_liabilities
+        && total_assets < BANKRUPT_THRESHOLD
+        && total_liabilities > ZERO_AMOUNT_THRESHOLD
+}
+
+/// An account borrowing from an isolated risk tier bank can't hold any other liability.
+#[inline]
+pub fn is_risk_tier_compliant(n_liabilities: usize, has_isolated_liability: bool) -> bool {
+    !has_isolated_liability || n_liabilities == 1
+}
+
+/// Off-chain risk engine, evaluating a marginfi account against deserialized banks and oracle price feeds.
+///
+/// Uses the same valuation as the on-chain [`RiskEngine`](super::marginfi_account::RiskEngine),
+/// so health reported by clients, the indexer and bots matches what the program enforces.
+/// `price_feeds` is keyed by the oracle address of each bank, `bank.config.oracle_keys[0]`.
+pub struct HealthEngine<'a> {
+    banks: &'a HashMap<Pubkey, Bank>,
+    price_feeds: &'a HashMap<Pubkey, OraclePriceFeedAdapter>,
+    balances: Vec<BalanceWithPriceFeed<'a>>,
+}
+
+impl<'a> HealthEngine<'a> {
+    pub fn load(
+        marginfi_account: &'a MarginfiAccount,
+        banks: &'a HashMap<Pubkey, Bank>,
+        price_feeds: &'a HashMap<Pubkey, OraclePriceFeedAdapter>,
+    ) -> MarginfiResult<Self> {
+        let balances = marginfi_account
+            .lending_account
+            .balances
+            .iter()
+            .filter(|balance| balance.active)
+            .map(|balance| {
+                let bank = banks
+                    .get(&balance.bank_pk)
+                    .ok_or(MarginfiError::BankNotFound)?;
+
+                Ok(BalanceWithPriceFeed {
+                    bank,
+                    balance,
+                    price_feed: price_feeds.get(&bank.config.oracle_keys[0]),
+                })
+            })
+            .collect::<MarginfiResult<Vec<_>>>()?;
+
+        Ok(Self {
+            banks,
+            price_feeds,
+            balances,
+        })
+    }
+
+    /// Returns the total assets and liabilities of the account in the form of (assets, liabilities)
+    pub fn get_account_health_components(
+        &self,
+        requirement_type: RiskRequirementType,
+    ) -> MarginfiResult<(I80F48, I80F48)> {
+        let mut total_assets = I80F48::ZERO;
+        let mut total_liabilities = I80F48::ZERO;
+
+        for a in &self.balances {
+            let (assets, liabilities) =
+                a.calc_weighted_assets_and_liabilities_values(requirement_type.to_weight_type())?;
+
+            total_assets = total_assets.checked_add(assets).ok_or_else(math_error!())?;
+            total_liabilities = total_liabilities
+                .checked_add(liabilities)
+                .ok_or_else(math_error!())?;
+        }
+
+        Ok((total_assets, total_liabilities))
+    }
+
+    pub fn get_account_health(
+        &self,
+        requirement_type: RiskRequirementType,
+    ) -> MarginfiResult<I80F48> {
+        let (total_weighted_assets, total_weighted_liabilities) =
+            self.get_account_health_components(requirement_type)?;
+
+        Ok(total_weighted_assets
+            .checked_sub(total_weighted_liabilities)
+            .ok_or_else(math_error!())?)
+    }
+
+    pub fn is_liquidatable(&self) -> MarginfiResult<bool> {
+        let (assets, liabs) =
+            self.get_account_health_components(RiskRequirementType::Maintenance)?;
+
+        Ok(liabs.is_positive()
+            && is_liquidatable(assets.checked_sub(liabs).ok_or_else(math_error!())?))
+    }
+
+    pub fn is_bankrupt(&self) -> MarginfiResult<bool> {
+        let (assets, liabs) = self.get_account_health_components(RiskRequirementType::Equity)?;
+
+        Ok(is_bankrupt(assets, liabs))
+    }
+
+    /// Maximum amount of `bank_pk` tokens, in native units, that can be withdrawn
+    /// while keeping the account above the initial requirement.
+    ///
+    /// The initial asset weight discount is treated as constant over the withdrawal,
+    /// and the amount is capped by the liquidity available in the bank.
+    pub fn max_withdraw(&self, bank_pk: &Pubkey) -> MarginfiResult<I80F48> {
+        let balance = match self.find_balance(bank_pk) {
+            Some(balance) if balance.balance.is_empty(BalanceSide::Assets).not() => balance,
+            _ => return Ok(I80F48::ZERO),
+        };
+
+        let health = self.get_account_health(RiskRequirementType::Initial)?;
+        let amount = balance
+            .bank
+            .get_asset_amount(balance.balance.asset_shares.into())?;
+        let (weighted_value, _) =
+            balance.calc_weighted_assets_and_liabilities_values(RequirementType::Initial)?;
+
+        let max_withdraw = if health.is_negative() {
+            I80F48::ZERO
+        } else if weighted_value == I80F48::ZERO {
+            amount
+        } else {
+            min(
+                amount,
+                health
+                    .checked_mul(amount)
+                    .ok_or_else(math_error!())?
+                    .checked_div(weighted_value)
+                    .ok_or_else(math_error!())?,
+            )
+        };
+
+        Ok(min(max_withdraw, available_liquidity(balance.bank)?))
+    }
+
+    /// Maximum amount of `bank_pk` tokens, in native units, that can be borrowed
+    /// while keeping the account above the initial requirement.
+    ///
+    /// Existing deposits in the bank are withdrawn first, as the program does.
+    /// The new liability is capped by [`Self::borrow_capacity`], and the amount by available liquidity.
+    pub fn max_borrow(
+        &self,
+        bank_pk: &Pubkey,
+        borrow_credential: Option<&BorrowCredential>,
+    ) -> MarginfiResult<I80F48> {
+        let bank = self.banks.get(bank_pk).ok_or(MarginfiError::BankNotFound)?;
+
+        // The program requires the credential of the account to borrow, even for a withdrawal
+        if bank.config.is_borrow_permissioned() && borrow_credential.is_none() {
+            return Ok(I80F48::ZERO);
+        }
+
+        let borrow_capacity = self.borrow_capacity(bank_pk, borrow_credential)?;
+
+        let mut free_health = self.get_account_health(RiskRequirementType::Initial)?;
+        if free_health.is_negative() {
+            return Ok(I80F48::ZERO);
+        }
+
+        let mut withdrawable_deposit = I80F48::ZERO;
+        if let Some(balance) = self.find_balance(bank_pk) {
+            if balance.balance.is_empty(BalanceSide::Assets).not() {
+                let amount = bank.get_asset_amount(balance.balance.asset_shares.into())?;
+                let (weighted_value, _) = balance
+                    .calc_weighted_assets_and_liabilities_values(RequirementType::Initial)?;
+
+                if weighted_value > free_health {
+                    return Ok(min(
+                        free_health
+                            .checked_mul(amount)
+                            .ok_or_else(math_error!())?
+                            .checked_div(weighted_value)
+                            .ok_or_else(math_error!())?,
+                        available_liquidity(bank)?,
+                    ));
+                }
+
+                withdrawable_deposit = amount;
+                free_health = free_health
+                    .checked_sub(weighted_value)
+                    .ok_or_else(math_error!())?;
+            }
+        }
+
+        let liability_weight = bank
+            .config
+            .get_weight(RequirementType::Initial, BalanceSide::Liabilities);
+        let liability_price = get_biased_price(
+            self.price_feeds.get(&bank.config.oracle_keys[0]),
+            RequirementType::Initial,
+            BalanceSide::Liabilities,
+        )?;
+
+        let borrowable = calc_amount(
+            free_health
+                .checked_div(liability_weight)
+                .ok_or_else(math_error!())?,
+            liability_price,
+            bank.mint_decimals,
+        )?;
+
+        Ok(min(
+            withdrawable_deposit
+                .checked_add(min(borrowable, borrow_capacity))
+                .ok_or_else(math_error!())?,
+            available_liquidity(bank)?,
+        ))
+    }
+
+    /// Maximum increase of the account liability in `bank_pk`, in native units,
+    /// allowed by the isolated risk tier rules, the bank borrow and borrow value limits
+    /// and, in permissioned borrowing mode, the borrow limit of `borrow_credential`.
+    ///
+    /// Account health and available liquidity are not taken into account, see [`Self::max_borrow`].
+    /// Returns zero in permissioned borrowing mode without a credential.
+    pub fn borrow_capacity(
+        &self,
+        bank_pk: &Pubkey,
+        borrow_credential: Option<&BorrowCredential>,
+    ) -> MarginfiResult<I80F48> {
+        let bank = self.banks.get(bank_pk).ok_or(MarginfiError::BankNotFound)?;
+
+        if let Some(borrow_credential) = borrow_credential {
+            check!(
+                borrow_credential.bank == *bank_pk,
+                MarginfiError::InvalidBorrowCredential
+            );
+        }
+
+        let mut other_liabilities = self.balances.iter().filter(|a| {
+            a.balance.bank_pk != *bank_pk && a.balance.is_empty(BalanceSide::Liabilities).not()
+        });
+        let has_other_liabilities = other_liabilities.clone().next().is_some();
+        let has_isolated_liability =
+            other_liabilities.any(|a| a.bank.config.risk_tier == RiskTier::Isolated);
+
+        if has_other_liabilities
+            && (has_isolated_liability || bank.config.risk_tier == RiskTier::Isolated)
+        {
+            return Ok(I80F48::ZERO);
+        }
+
+        let mut borrow_capacity = I80F48::MAX;
+        let total_liabilities = bank.get_liability_amount(bank.total_liability_shares.into())?;
+
+        if bank.config.is_borrow_limit_active() {
+            let remaining_borrow_capacity = max(
+                I80F48::from_num(bank.config.borrow_limit)
+                    .checked_sub(total_liabilities)
+                    .ok_or_else(math_error!())?,
+                I80F48::ZERO,
+            );
+            borrow_capacity = min(borrow_capacity, remaining_borrow_capacity);
+        }
+
+        if bank.config.is_borrow_value_limit_active() {
+            let limit_price = self
+                .price_feeds
+                .get(&bank.config.oracle_keys[0])
+                .ok_or(MarginfiError::StaleOracle)?
+                .get_price_of_type(OraclePriceType::TimeWeighted, Some(PriceBias::High))?;
+            let borrow_value_limit_amount = calc_amount(
+                I80F48::from_num(bank.config.total_borrow_value_limit),
+                limit_price,
+                bank.mint_decimals,
+            )?;
+            let remaining_borrow_value_capacity = max(
+                borrow_value_limit_amount
+                    .checked_sub(total_liabilities)
+                    .ok_or_else(math_error!())?,
+                I80F48::ZERO,
+            );
+            borrow_capacity = min(borrow_capacity, remaining_borrow_value_capacity);
+        }
+
+        if bank.config.is_borrow_permissioned() {
+            let borrow_credential = match borrow_credential {
+                Some(borrow_credential) => borrow_credential,
+                None => return Ok(I80F48::ZERO),
+            };
+
+            if borrow_credential.is_borrow_limit_active() {
+                let liability_amount = match self.find_balance(bank_pk) {
+                    Some(balance) => {
+                        bank.get_liability_amount(balance.balance.liability_shares.into())?
+                    }
+                    None => I80F48::ZERO,
+                };
+                let remaining_credential_capacity = max(
+                    I80F48::from_num(borrow_credential.borrow_limit)
+                        .checked_sub(liability_amount)
+                        .ok_or_else(math_error!())?,
+                    I80F48::ZERO,
+                );
+                borrow_capacity = min(borrow_capacity, remaining_credential_capacity);
+            }
+        }
+
+        Ok(borrow_capacity)
+    }
+
+    /// Oracle price of the `bank_pk` token at which the account becomes liquidatable,
+    /// holding every other position and price constant.
+    ///
+    /// The price is expressed the way the maintenance requirement sees it,
+    /// i.e. the real time price biased by the confidence interval against the account.
+    /// Returns `None` when no price of this token can make the account liquidatable.
+    pub fn liquidation_price(&self, bank_pk: &Pubkey) -> MarginfiResult<Option<I80F48>> {
+        let balance = match self.find_balance(bank_pk) {
+            Some(balance) => balance,
+            None => return Ok(None),
+        };
+        let side = match balance.balance.get_side() {
+            Some(side) => side,
+            None => return Ok(None),
+        };
+
+        let health = self.get_account_health(RiskRequirementType::Maintenance)?;
+        let (assets, liabs) =
+            balance.calc_weighted_assets_and_liabilities_values(RequirementType::Maintenance)?;
+        let price = balance.get_price(RequirementType::Maintenance, side)?;
+
+        match side {
+            BalanceSide::Assets => {
+                let health_without_balance =
+                    health.checked_sub(assets).ok_or_else(math_error!())?;
+
+                if assets == I80F48::ZERO || health_without_balance >= I80F48::ZERO {
+                    return Ok(None);
+                }
+
+                Ok(Some(
+                    price
+                        .checked_mul(-health_without_balance)
+                        .ok_or_else(math_error!())?
+                        .checked_div(assets)
+                        .ok_or_else(math_error!())?,
+                ))
+            }
+            BalanceSide::Liabilities => {
+                let health_without_balance = health.checked_add(liabs).ok_or_else(math_error!())?;
+
+                if liabs == I80F48::ZERO {
+                    return Ok(None);
+                }
+
+                Ok(Some(max(
+                    price
+                        .checked_mul(health_without_balance)
+                        .ok_or_else(math_error!())?
+                        .checked_div(liabs)
+                        .ok_or_else(math_error!())?,
+                    I80F48::ZERO,
+                )))
+            }
+        }
+    }
+
+    fn find_balance(&self, bank_pk: &Pubkey) -> Option<&BalanceWithPriceFeed<'a>> {
+        self.balances.iter().find(|a| a.balance.bank_pk == *bank_pk)
+    }
+}
+
+fn available_liquidity(bank: &Bank) -> MarginfiResult<I80F48> {
+    let total_assets = bank.get_asset_amount(bank.total_asset_shares.into())?;
+    let total_liabilities = bank.get_liability_amount(bank.total_liability_shares.into())?;
+
+    Ok(max(
+        total_assets
+            .checked_sub(total_liabilities)
+            .ok_or_else(math_error!())?,
+        I80F48::ZERO,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{
+        marginfi_group::{BankBorrowMode, WrappedI80F48},
+        price::PythEmaPriceFeed,
+    };
+    use bytemuck::Zeroable;
+    use fixed_macro::types::I80F48;
+    use pyth_sdk_solana::Price;
+
+    fn pyth_feed(price: i64) -> OraclePriceFeedAdapter {
+        let price = Price {
+            price: price * 1_000_000,
+            conf: 0,
+            expo: -6,
+            publish_time: 0,
+        };
+
+        OraclePriceFeedAdapter::PythEma(PythEmaPriceFeed::new(price, price))
+    }
+
+    fn bank(oracle: Pubkey, asset_weight: I80F48, liability_weight: I80F48) -> Bank {
+        let mut bank = Bank {
+            mint_decimals: 6,
+            asset_share_value: I80F48::ONE.into(),
+            liability_share_value: I80F48::ONE.into(),
+            total_asset_shares: I80F48!(1_000_000_000_000).into(),
+            ..Default::default()
+        };
+        bank.config.oracle_keys[0] = oracle;
+        bank.config.risk_tier = RiskTier::Collateral;
+        bank.config.borrow_limit = u64::MAX;
+        bank.config.total_asset_value_init_limit = 0;
+        bank.config.asset_weight_init = asset_weight.into();
+        bank.config.asset_weight_maint = asset_weight.into();
+        bank.config.liability_weight_init = liability_weight.into();
+        bank.config.liability_weight_maint = liability_weight.into();
+
+        bank
+    }
+
+    fn balance(bank_pk: Pubkey, asset_amount: I80F48, liability_amount: I80F48) -> Balance {
+        Balance {
+            active: true,
+            bank_pk,
+            asset_shares: asset_amount.into(),
+            liability_shares: liability_amount.into(),
+            emissions_outstanding: WrappedI80F48::from(I80F48::ZERO),
+            last_update: 0,
+            deferred_bad_debt: 0,
+        }
+    }
+
+    /// Account with 100 SOL deposited at $10 (weight 0.5) and 200 USDC borrowed at $1 (weight 1.25).
+    fn setup() -> (
+        Pubkey,
+        Pubkey,
+        MarginfiAccount,
+        HashMap<Pubkey, Bank>,
+        HashMap<Pubkey, OraclePriceFeedAdapter>,
+    ) {
+        let (sol_bank_pk, usdc_bank_pk) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (sol_oracle, usdc_oracle) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        let mut marginfi_account: MarginfiAccount = Zeroable::zeroed();
+        marginfi_account.lending_account.balances[0] =
+            balance(sol_bank_pk, I80F48!(100_000_000), I80F48::ZERO);
+        marginfi_account.lending_account.balances[1] =
+            balance(usdc_bank_pk, I80F48::ZERO, I80F48!(200_000_000));
+
+        let banks = HashMap::from([
+            (sol_bank_pk, bank(sol_oracle, I80F48!(0.5), I80F48!(1.25))),
+            (usdc_bank_pk, bank(usdc_oracle, I80F48!(1), I80F48!(1.25))),
+        ]);
+        let price_feeds = HashMap::from([(sol_oracle, pyth_feed(10)), (usdc_oracle, pyth_feed(1))]);
+
+        (
+            sol_bank_pk,
+            usdc_bank_pk,
+            marginfi_account,
+            banks,
+            price_feeds,
+        )
+    }
+
+    #[test]
+    fn health_limits_and_liquidation_price() {
+        let (sol_bank_pk, usdc_bank_pk, marginfi_account, banks, price_feeds) = setup();
+        let engine = HealthEngine::load(&marginfi_account, &banks, &price_feeds).unwrap();
+
+        // 500 - 250
+        assert_eq!(
+            engine
+                .get_account_health(RiskRequirementType::Initial)
+                .unwrap(),
+            I80F48!(250)
+        );
+        assert!(!engine.is_liquidatable().unwrap());
+        assert!(!engine.is_bankrupt().unwrap());
+
+        // 250 of free health at $10 * 0.5 per SOL
+        assert_eq!(
+            engine.max_withdraw(&sol_bank_pk).unwrap(),
+            I80F48!(50_000_000)
+        );
+        // 250 of free health at $1 * 1.25 per USDC
+        assert_eq!(
+            engine.max_borrow(&usdc_bank_pk, None).unwrap(),
+            I80F48!(200_000_000)
+        );
+        // 100 SOL * p * 0.5 = 250
+        assert_eq!(
+            engine.liquidation_price(&sol_bank_pk).unwrap(),
+            Some(I80F48!(5))
+        );
+        // 200 USDC * p * 1.25 = 500
+        assert_eq!(
+            engine.liquidation_price(&usdc_bank_pk).unwrap(),
+            Some(I80F48!(2))
+        );
+    }
+
+    #[test]
+    fn max_borrow_withdraws_deposit_first() {
+        let (sol_bank_pk, _, marginfi_account, banks, price_feeds) = setup();
+        let engine = HealthEngine::load(&marginfi_account, &banks, &price_feeds).unwrap();
+
+        // Borrowing SOL first withdraws the deposit, whose 500 of weighted value exceeds
+        // the 250 of free health, so only half of it is available.
+        assert_eq!(
+            engine.max_borrow(&sol_bank_pk, None).unwrap(),
+            I80F48!(50_000_000)
+        );
+    }
+
+    #[test]
+    fn max_borrow_isolated_risk_tier() {
+        let (sol_bank_pk, usdc_bank_pk, marginfi_account, mut banks, mut price_feeds) = setup();
+        let (other_bank_pk, other_oracle) = (Pubkey::new_unique(), Pubkey::new_unique());
+        banks.insert(other_bank_pk, bank(other_oracle, I80F48!(1), I80F48!(1.25)));
+        price_feeds.insert(other_oracle, pyth_feed(1));
+
+        // An isolated bank can't be borrowed from next to the USDC liability
+        banks.get_mut(&other_bank_pk).unwrap().config.risk_tier = RiskTier::Isolated;
+        let engine = HealthEngine::load(&marginfi_account, &banks, &price_feeds).unwrap();
+        assert_eq!(
+            engine.max_borrow(&other_bank_pk, None).unwrap(),
+            I80F48::ZERO
+        );
+
+        // An isolated liability can't be combined with any other liability
+        banks.get_mut(&other_bank_pk).unwrap().config.risk_tier = RiskTier::Collateral;
+        banks.get_mut(&usdc_bank_pk).unwrap().config.risk_tier = RiskTier::Isolated;
+        let engine = HealthEngine::load(&marginfi_account, &banks, &price_feeds).unwrap();
+        assert_eq!(
+            engine.max_borrow(&other_bank_pk, None).unwrap(),
+            I80F48::ZERO
+        );
+        // Borrowing SOL is limited to withdrawing the deposit
+        assert_eq!(
+            engine.max_borrow(&sol_bank_pk, None).unwrap(),
+            I80F48!(50_000_000)
+        );
+        assert_eq!(
+            engine.borrow_capacity(&sol_bank_pk, None).unwrap(),
+            I80F48::ZERO
+        );
+
+        // but can still grow on its own
+        assert_eq!(
+            engine.max_borrow(&usdc_bank_pk, None).unwrap(),
+            I80F48!(200_000_000)
+        );
+    }
+
+    #[test]
+    fn max_borrow_bank_limits() {
+        let (_, usdc_bank_pk, marginfi_account, mut banks, price_feeds) = setup();
+        let usdc_bank = banks.get_mut(&usdc_bank_pk).unwrap();
+        usdc_bank.total_liability_shares = I80F48!(200_000_000).into();
+        usdc_bank.config.borrow_limit = 350_000_000;
+
+        // 350 USDC borrow limit with 200 USDC borrowed
+        let engine = HealthEngine::load(&marginfi_account, &banks, &price_feeds).unwrap();
+        assert_eq!(
+            engine.max_borrow(&usdc_bank_pk, None).unwrap(),
+            I80F48!(150_000_000)
+        );
+
+        // $300 borrow value limit with $200 borrowed
+        let usdc_bank = banks.get_mut(&usdc_bank_pk).unwrap();
+        usdc_bank.config.borrow_limit = u64::MAX;
+        usdc_bank.config.total_borrow_value_limit = 300;
+        let engine = HealthEngine::load(&marginfi_account, &banks, &price_feeds).unwrap();
+        assert_eq!(
+            engine.max_borrow(&usdc_bank_pk, None).unwrap(),
+            I80F48!(100_000_000)
+        );
+    }
+
+    #[test]
+    fn max_borrow_borrow_credential_limit() {
+        let (sol_bank_pk, usdc_bank_pk, marginfi_account, mut banks, price_feeds) = setup();
+        banks.get_mut(&usdc_bank_pk).unwrap().config.borrow_mode = BankBorrowMode::Permissioned;
+        let engine = HealthEngine::load(&marginfi_account, &banks, &price_feeds).unwrap();
+
+        // No credential, no borrowing
+        assert_eq!(
+            engine.max_borrow(&usdc_bank_pk, None).unwrap(),
+            I80F48::ZERO
+        );
+
+        // Uncapped credential, only health applies
+        let mut borrow_credential = BorrowCredential {
+            bank: usdc_bank_pk,
+            borrow_limit: u64::MAX,
+            ..Zeroable::zeroed()
+        };
+        assert_eq!(
+            engine
+                .max_borrow(&usdc_bank_pk, Some(&borrow_credential))
+                .unwrap(),
+            I80F48!(200_000_000)
+        );
+
+        // 250 USDC credential limit with 200 USDC borrowed
+        borrow_credential.borrow_limit = 250_000_000;
+        assert_eq!(
+            engine
+                .max_borrow(&usdc_bank_pk, Some(&borrow_credential))
+                .unwrap(),
+            I80F48!(50_000_000)
+        );
+
+        // Credential of another bank
+        borrow_credential.bank = sol_bank_pk;
+        assert!(engine
+            .max_borrow(&usdc_bank_pk, Some(&borrow_credential))
+            .is_err());
+    }
+}
@@ -0,0 +1,32 @@
+use crate::assert_struct_size;
+use anchor_lang::prelude::*;
+#[cfg(any(feature = "test", feature = "client"))]
+use type_layout::TypeLayout;
+
+assert_struct_size!(InsuranceUnstakeRequest, 144);
+/// Insurance fund shares of a staker in the unstake cooldown.
+///
+/// PDA seeds: [INSURANCE_UNSTAKE_REQUEST_SEED, bank, authority, request_index]
+#[account(zero_copy)]
+#[repr(C)]
+#[cfg_attr(
+    any(feature = "test", feature = "client"),
+    derive(Debug, PartialEq, Eq, TypeLayout)
+)]
+pub struct InsuranceUnstakeRequest {
+    pub bank: Pubkey,
+    pub authority: Pubkey,
+    /// Share mint the shares were burned from, shares of a written off share mint redeem nothing
+    pub share_mint: Pubkey,
+    pub request_index: u64,
+    pub shares: u64,
+    /// Timestamp of the unstake request
+    pub cooldown_start: i64,
+    pub _padding: [u64; 3],
+}
+
+impl InsuranceUnstakeRequest {
+    pub fn is_cooldown_elapsed(&self, cooldown: u64, current_timestamp: i64) -> bool {
+        current_timestamp.saturating_sub(self.cooldown_start).max(0) as u64 >= cooldown
+    }
+}
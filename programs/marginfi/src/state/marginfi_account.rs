@@ -1,13 +1,14 @@
 use super::{
+    health::{is_bankrupt, is_liquidatable, is_risk_tier_compliant, BalanceWithPriceFeed},
     marginfi_group::{Bank, RiskTier, WrappedI80F48},
-    price::{OraclePriceFeedAdapter, OraclePriceType, PriceAdapter, PriceBias},
+    price::{OraclePriceFeedAdapter, OraclePriceType},
 };
 use crate::{
     assert_struct_size, check,
     constants::{
-        BANKRUPT_THRESHOLD, EMISSIONS_FLAG_BORROW_ACTIVE, EMISSIONS_FLAG_LENDING_ACTIVE,
-        EMPTY_BALANCE_THRESHOLD, EXP_10_I80F48, MAX_PRICE_AGE_SEC, MIN_EMISSIONS_START_TIME,
-        SECONDS_PER_YEAR, ZERO_AMOUNT_THRESHOLD,
+        EMISSIONS_FLAG_BORROW_ACTIVE, EMISSIONS_FLAG_LENDING_ACTIVE, EMPTY_BALANCE_THRESHOLD,
+        EXP_10_I80F48, MAX_PRICE_AGE_SEC, MIN_EMISSIONS_START_TIME, SECONDS_PER_YEAR,
+        ZERO_AMOUNT_THRESHOLD,
     },
     debug, math_error,
     prelude::{MarginfiError, MarginfiResult},
@@ -16,6 +17,10 @@ use crate::{
 use anchor_lang::prelude::*;
 use anchor_spl::token::Transfer;
 use fixed::types::I80F48;
+use solana_program::{
+    instruction::{get_stack_height, TRANSACTION_LEVEL_STACK_HEIGHT},
+    sysvar,
+};
 use std::{
     cmp::{max, min},
     ops::Not,
@@ -41,13 +46,24 @@ pub struct MarginfiAccount {
     /// - DISABLED_FLAG = 1 << 0 = 1 - This flag indicates that the account is disabled,
     /// and no further actions can be taken on it.
     pub account_flags: u64, // 8
-    pub _padding: [u64; 63],             // 8 * 63 = 512
+    /// Index of the top-level instruction that started a flashloan via CPI.
+    /// Only meaningful while `IN_FLASHLOAN_CPI_FLAG` is set.
+    pub flashloan_ix_index: u64, // 8
+    /// Program of the top-level instruction that started a flashloan via CPI.
+    /// Only meaningful while `IN_FLASHLOAN_CPI_FLAG` is set.
+    pub flashloan_cpi_program: Pubkey, // 32
+    /// Liability shares of each balance slot when the current flashloan started,
+    /// the flashloan fee is charged on their increase when the flashloan ends.
+    /// Only meaningful while `IN_FLASHLOAN_FLAG` is set.
+    pub flashloan_liability_shares: [WrappedI80F48; 16], // 16 * 16 = 256
+    pub _padding: [u64; 26],             // 8 * 26 = 208
 }
 
 pub const DISABLED_FLAG: u64 = 1 << 0;
 pub const IN_FLASHLOAN_FLAG: u64 = 1 << 1;
 pub const FLASHLOAN_ENABLED_FLAG: u64 = 1 << 2;
 pub const TRANSFER_AUTHORITY_ALLOWED_FLAG: u64 = 1 << 3;
+pub const IN_FLASHLOAN_CPI_FLAG: u64 = 1 << 4;
 
 impl MarginfiAccount {
     /// Set the initial data for the marginfi account.
@@ -79,6 +95,91 @@ impl MarginfiAccount {
         self.account_flags & flag != 0
     }
 
+    /// Checks that a flashloan started via CPI is still running within the top-level ix that
+    /// started it, invoked by the same program, and that the current ix is a CPI.
+    /// Noop for accounts not in a CPI flashloan.
+    ///
+    /// The instructions sysvar is looked up by key in `remaining_ais`.
+    pub fn check_flashloan_cpi_ix(&self, remaining_ais: &[AccountInfo]) -> MarginfiResult {
+        if !self.get_flag(IN_FLASHLOAN_CPI_FLAG) {
+            return Ok(());
+        }
+
+        check!(
+            get_stack_height() > TRANSACTION_LEVEL_STACK_HEIGHT,
+            MarginfiError::IllegalFlashloan,
+            "CPI flashloan can only be used via CPI"
+        );
+
+        let ixs_sysvar_ai = remaining_ais
+            .iter()
+            .find(|ai| ai.key == &sysvar::instructions::ID)
+            .ok_or(MarginfiError::IllegalFlashloan)?;
+
+        let current_ix_idx = sysvar::instructions::load_current_index_checked(ixs_sysvar_ai)?;
+
+        check!(
+            u64::from(current_ix_idx) == self.flashloan_ix_index,
+            MarginfiError::IllegalFlashloan,
+            "CPI flashloan should end within the same ix"
+        );
+
+        let current_ix = sysvar::instructions::load_instruction_at_checked(
+            current_ix_idx.into(),
+            ixs_sysvar_ai,
+        )?;
+
+        check!(
+            current_ix.program_id == self.flashloan_cpi_program,
+            MarginfiError::IllegalFlashloan,
+            "CPI flashloan can only be used by the program that started it"
+        );
+
+        Ok(())
+    }
+
+    /// Record the liability shares of every balance slot at the start of a flashloan,
+    /// and reserve the active slots until the flashloan ends.
+    pub fn start_flashloan_fee_tracking(&mut self) {
+        let mut flashloan_balance_slots = 0;
+
+        for (i, balance) in self.lending_account.balances.iter().enumerate() {
+            self.flashloan_liability_shares[i] = if balance.active {
+                flashloan_balance_slots |= 1 << i;
+                balance.liability_shares
+            } else {
+                WrappedI80F48::default()
+            };
+        }
+
+        self.lending_account.flashloan_balance_slots = flashloan_balance_slots;
+    }
+
+    /// Liability shares incurred in balance slot `index` since the start of the flashloan.
+    ///
+    /// A liability closed and reopened within the flashloan is counted in full.
+    pub fn get_flashloan_liability_shares_increase(&self, index: usize) -> I80F48 {
+        let balance = &self.lending_account.balances[index];
+
+        if !balance.active {
+            return I80F48::ZERO;
+        }
+
+        let start_liability_shares = if self.lending_account.is_flashloan_balance_slot(index) {
+            self.flashloan_liability_shares[index].into()
+        } else {
+            I80F48::ZERO
+        };
+
+        I80F48::from(balance.liability_shares).saturating_sub(start_liability_shares)
+    }
+
+    /// Release the balance slots reserved at the start of the flashloan.
+    pub fn stop_flashloan_fee_tracking(&mut self) {
+        self.flashloan_liability_shares = [WrappedI80F48::default(); MAX_LENDING_ACCOUNT_BALANCES];
+        self.lending_account.flashloan_balance_slots = 0;
+    }
+
     pub fn set_new_account_authority_checked(&mut self, new_authority: Pubkey) -> MarginfiResult {
         // check if new account authority flag is set
         if !self.get_flag(TRANSFER_AUTHORITY_ALLOWED_FLAG) || self.get_flag(DISABLED_FLAG) {
@@ -107,7 +208,11 @@ pub enum BalanceIncreaseType {
     Any,
     RepayOnly,
     DepositOnly,
+    /// Used in liquidations, also skips outflow tracking
     BypassDepositLimit,
+    /// Repay-only, used to settle bad debt where no funds are paid into the liquidity vault,
+    /// so outflow tracking is skipped
+    BadDebtRepayOnly,
 }
 
 #[derive(Debug)]
@@ -115,7 +220,12 @@ pub enum BalanceDecreaseType {
     Any,
     WithdrawOnly,
     BorrowOnly,
+    /// Used in liquidations, also skips outflow tracking
     BypassBorrowLimit,
+    /// Withdraw-only, used in liquidations where no funds leave the liquidity vault
+    /// and in bad debt collateral auctions, which must not be held back by the outflow limit,
+    /// so outflow tracking is skipped
+    BypassOutflowWithdrawOnly,
 }
 
 #[derive(Copy, Clone)]
@@ -207,105 +317,25 @@ impl<'a, 'b> BankAccountWithPriceFeed<'a, 'b> {
     }
 
     #[inline(always)]
-    /// Calculate the value of the assets and liabilities of the account in the form of (assets, liabilities)
-    ///
-    /// Nuances:
-    /// 1. Maintenance requirement is calculated using the real time price feed.
-    /// 2. Initial requirement is calculated using the time weighted price feed, if available.
-    /// 3. Initial requirement is discounted by the initial discount, if enabled and the usd limit is exceeded.
-    /// 4. Assets are only calculated for collateral risk tier.
-    /// 5. Oracle errors are ignored for deposits in isolated risk tier.
+    /// Calculate the value of the assets and liabilities of the account in the form of (assets, liabilities),
+    /// see [`BalanceWithPriceFeed::calc_weighted_assets_and_liabilities_values`].
     pub fn calc_weighted_assets_and_liabilities_values(
         &self,
         requirement_type: RequirementType,
     ) -> MarginfiResult<(I80F48, I80F48)> {
-        match self.balance.get_side() {
-            Some(side) => {
-                let bank_al = AccountLoader::<Bank>::try_from(&self.bank)?;
-                let bank = bank_al.load()?;
-                match side {
-                    BalanceSide::Assets => Ok((
-                        self.calc_weighted_assets(requirement_type, &bank)?,
-                        I80F48::ZERO,
-                    )),
-                    BalanceSide::Liabilities => Ok((
-                        I80F48::ZERO,
-                        self.calc_weighted_liabs(requirement_type, &bank)?,
-                    )),
-                }
-            }
-            None => Ok((I80F48::ZERO, I80F48::ZERO)),
-        }
-    }
-
-    #[inline(always)]
-    fn calc_weighted_assets(
-        &self,
-        requirement_type: RequirementType,
-        bank: &Bank,
-    ) -> MarginfiResult<I80F48> {
-        match bank.config.risk_tier {
-            RiskTier::Collateral => {
-                let price_feed = self.try_get_price_feed()?;
-                let mut asset_weight = bank
-                    .config
-                    .get_weight(requirement_type, BalanceSide::Assets);
-
-                let lower_price = price_feed.get_price_of_type(
-                    requirement_type.get_oracle_price_type(),
-                    Some(PriceBias::Low),
-                )?;
-
-                if matches!(requirement_type, RequirementType::Initial) {
-                    if let Some(discount) =
-                        bank.maybe_get_asset_weight_init_discount(lower_price)?
-                    {
-                        asset_weight = asset_weight
-                            .checked_mul(discount)
-                            .ok_or_else(math_error!())?;
-                    }
-                }
-
-                calc_value(
-                    bank.get_asset_amount(self.balance.asset_shares.into())?,
-                    lower_price,
-                    bank.mint_decimals,
-                    Some(asset_weight),
-                )
-            }
-            RiskTier::Isolated => Ok(I80F48::ZERO),
+        if self.balance.get_side().is_none() {
+            return Ok((I80F48::ZERO, I80F48::ZERO));
         }
-    }
-
-    #[inline(always)]
-    fn calc_weighted_liabs(
-        &self,
-        requirement_type: RequirementType,
-        bank: &Bank,
-    ) -> MarginfiResult<I80F48> {
-        let price_feed = self.try_get_price_feed()?;
-        let liability_weight = bank
-            .config
-            .get_weight(requirement_type, BalanceSide::Liabilities);
-
-        let higher_price = price_feed.get_price_of_type(
-            requirement_type.get_oracle_price_type(),
-            Some(PriceBias::High),
-        )?;
 
-        calc_value(
-            bank.get_liability_amount(self.balance.liability_shares.into())?,
-            higher_price,
-            bank.mint_decimals,
-            Some(liability_weight),
-        )
-    }
+        let bank_al = AccountLoader::<Bank>::try_from(&self.bank)?;
+        let bank = bank_al.load()?;
 
-    fn try_get_price_feed(&self) -> MarginfiResult<&OraclePriceFeedAdapter> {
-        match self.price_feed.as_ref() {
-            Ok(a) => Ok(a),
-            Err(_) => Err(MarginfiError::StaleOracle)?,
+        BalanceWithPriceFeed {
+            bank: &bank,
+            balance: self.balance,
+            price_feed: self.price_feed.as_ref().as_ref().ok(),
         }
+        .calc_weighted_assets_and_liabilities_values(requirement_type)
     }
 
     #[inline]
@@ -418,11 +448,15 @@ impl<'a, 'b> RiskEngine<'a, 'b> {
     /// `IN_FLASHLOAN_FLAG` behaviour.
     /// - Health check is skipped.
     /// - `remaining_ais` can be an empty vec.
+    /// - If the flashloan was started via CPI, `remaining_ais` must hold the instructions sysvar,
+    ///   and the check fails outside of the top-level ix that started the flashloan.
     pub fn check_account_init_health(
         marginfi_account: &'a MarginfiAccount,
         remaining_ais: &[AccountInfo<'b>],
     ) -> MarginfiResult<()> {
         if marginfi_account.get_flag(IN_FLASHLOAN_FLAG) {
+            marginfi_account.check_flashloan_cpi_ix(remaining_ais)?;
+
             return Ok(());
         }
 
@@ -528,7 +562,7 @@ impl<'a, 'b> RiskEngine<'a, 'b> {
         );
 
         check!(
-            account_health <= I80F48::ZERO,
+            is_liquidatable(account_health),
             MarginfiError::IllegalLiquidation,
             "Account not unhealthy"
         );
@@ -582,7 +616,7 @@ impl<'a, 'b> RiskEngine<'a, 'b> {
         let account_health = assets.checked_sub(liabs).ok_or_else(math_error!())?;
 
         check!(
-            account_health <= I80F48::ZERO,
+            is_liquidatable(account_health),
             MarginfiError::IllegalLiquidation,
             "Liquidation too severe, account above maintenance requirement"
         );
@@ -622,11 +656,7 @@ impl<'a, 'b> RiskEngine<'a, 'b> {
         );
 
         check!(
-            total_assets < total_liabilities,
-            MarginfiError::AccountNotBankrupt
-        );
-        check!(
-            total_assets < BANKRUPT_THRESHOLD && total_liabilities > ZERO_AMOUNT_THRESHOLD,
+            is_bankrupt(total_assets, total_liabilities),
             MarginfiError::AccountNotBankrupt
         );
 
@@ -652,7 +682,7 @@ impl<'a, 'b> RiskEngine<'a, 'b> {
         });
 
         check!(
-            !is_in_isolated_risk_tier || n_balances_with_liablities == 1,
+            is_risk_tier_compliant(n_balances_with_liablities, is_in_isolated_risk_tier),
             MarginfiError::IsolatedAccountIllegalState
         );
 
@@ -671,12 +701,22 @@ assert_struct_size!(LendingAccount, 1728);
 )]
 pub struct LendingAccount {
     pub balances: [Balance; MAX_LENDING_ACCOUNT_BALANCES], // 104 * 16 = 1664
-    pub _padding: [u64; 8],                                // 8 * 8 = 64
+    /// Bitmask of the balance slots active when the current flashloan started,
+    /// these slots are not reused for new balances until the flashloan ends.
+    pub flashloan_balance_slots: u64, // 8
+    pub _padding: [u64; 7],                                // 8 * 7 = 56
 }
 
 impl LendingAccount {
     pub fn get_first_empty_balance(&self) -> Option<usize> {
-        self.balances.iter().position(|b| !b.active)
+        self.balances
+            .iter()
+            .enumerate()
+            .position(|(i, b)| !b.active && !self.is_flashloan_balance_slot(i))
+    }
+
+    pub fn is_flashloan_balance_slot(&self, index: usize) -> bool {
+        self.flashloan_balance_slots & (1 << index) != 0
     }
 }
 
@@ -707,7 +747,9 @@ pub struct Balance {
     pub liability_shares: WrappedI80F48,
     pub emissions_outstanding: WrappedI80F48,
     pub last_update: u64,
-    pub _padding: [u64; 1],
+    /// Bad debt of the balance deferred in bankruptcy and not yet recovered by auctioning
+    /// the account collateral, in native amount.
+    pub deferred_bad_debt: u64,
 }
 
 impl Balance {
@@ -770,6 +812,51 @@ impl Balance {
         }
     }
 
+    /// Shares moved out of the balance by a balance transfer of `amount`, in the form of
+    /// (side, shares, closes balance).
+    ///
+    /// The balance is closed if `transfer_all` is set or `amount` covers the entire balance.
+    pub fn get_transfer_shares(
+        &self,
+        bank: &Bank,
+        amount: I80F48,
+        transfer_all: bool,
+    ) -> MarginfiResult<(BalanceSide, I80F48, bool)> {
+        let side = self
+            .get_side()
+            .ok_or_else(|| error!(MarginfiError::InvalidTransfer))?;
+
+        let balance_shares: I80F48 = match side {
+            BalanceSide::Assets => self.asset_shares,
+            BalanceSide::Liabilities => self.liability_shares,
+        }
+        .into();
+
+        if transfer_all {
+            return Ok((side, balance_shares, true));
+        }
+
+        let shares = match side {
+            BalanceSide::Assets => bank.get_asset_shares(amount)?,
+            BalanceSide::Liabilities => bank.get_liability_shares(amount)?,
+        };
+
+        let remaining_shares = balance_shares
+            .checked_sub(shares)
+            .ok_or_else(math_error!())?;
+
+        if remaining_shares.is_zero_with_tolerance(EMPTY_BALANCE_THRESHOLD) {
+            return Ok((side, balance_shares, true));
+        }
+
+        check!(
+            shares.is_positive() && shares <= balance_shares,
+            MarginfiError::InvalidTransfer
+        );
+
+        Ok((side, shares, false))
+    }
+
     pub fn empty_deactivated() -> Self {
         Balance {
             active: false,
@@ -778,7 +865,7 @@ impl Balance {
             liability_shares: WrappedI80F48::from(I80F48::ZERO),
             emissions_outstanding: WrappedI80F48::from(I80F48::ZERO),
             last_update: 0,
-            _padding: [0; 1],
+            deferred_bad_debt: 0,
         }
     }
 }
@@ -837,7 +924,7 @@ impl<'a> BankAccountWrapper<'a> {
                     liability_shares: I80F48::ZERO.into(),
                     emissions_outstanding: I80F48::ZERO.into(),
                     last_update: Clock::get()?.unix_timestamp as u64,
-                    _padding: [0; 1],
+                    deferred_bad_debt: 0,
                 };
 
                 Ok(Self {
@@ -860,16 +947,87 @@ impl<'a> BankAccountWrapper<'a> {
         self.increase_balance_internal(amount, BalanceIncreaseType::RepayOnly)
     }
 
+    /// Settle bad debt, will error if there is not enough liability - depositing is not allowed.
+    ///
+    /// Bad debt is written off rather than paid, so it is not counted as an inflow
+    /// offsetting the bank outflow limit.
+    pub fn repay_bad_debt(&mut self, amount: I80F48) -> MarginfiResult {
+        self.increase_balance_internal(amount, BalanceIncreaseType::BadDebtRepayOnly)
+    }
+
     /// Withdraw an asset, will error if there is not enough asset - borrowing is not allowed.
     pub fn withdraw(&mut self, amount: I80F48) -> MarginfiResult {
         self.decrease_balance_internal(amount, BalanceDecreaseType::WithdrawOnly)
     }
 
+    /// Withdraw an asset in a liquidation, will error if there is not enough asset.
+    ///
+    /// The asset is moved to the liquidator and doesn't leave the liquidity vault,
+    /// so it is not counted against the bank outflow limit.
+    pub fn withdraw_in_liquidation(&mut self, amount: I80F48) -> MarginfiResult {
+        self.decrease_balance_internal(amount, BalanceDecreaseType::BypassOutflowWithdrawOnly)
+    }
+
+    /// Withdraw an asset sold in a bad debt collateral auction, will error if there is not enough asset.
+    ///
+    /// Bad debt recovery is not counted against the bank outflow limit.
+    pub fn withdraw_in_bad_debt_auction(&mut self, amount: I80F48) -> MarginfiResult {
+        self.decrease_balance_internal(amount, BalanceDecreaseType::BypassOutflowWithdrawOnly)
+    }
+
     /// Incur a borrow, will withdraw any existing assets.
     pub fn borrow(&mut self, amount: I80F48) -> MarginfiResult {
         self.decrease_balance_internal(amount, BalanceDecreaseType::Any)
     }
 
+    /// Charge the bank flashloan fee on `borrowed_amount`, the net liability incurred within a flashloan.
+    ///
+    /// Called when the flashloan ends. The fee is added to the account liability, subject to the bank
+    /// borrow limit, and credited to the outstanding group and insurance fees,
+    /// so it must be covered by the health check at the end of the flashloan.
+    ///
+    /// Returns the total fee charged.
+    pub fn charge_flashloan_fee(&mut self, borrowed_amount: I80F48) -> MarginfiResult<I80F48> {
+        let balance = &mut self.balance;
+        let bank = &mut self.bank;
+
+        let ir_config = &bank.config.interest_rate_config;
+
+        let insurance_fee = borrowed_amount
+            .checked_mul(ir_config.insurance_flashloan_fee.into())
+            .ok_or_else(math_error!())?;
+        let group_fee = borrowed_amount
+            .checked_mul(ir_config.protocol_flashloan_fee.into())
+            .ok_or_else(math_error!())?;
+        let total_fee = insurance_fee
+            .checked_add(group_fee)
+            .ok_or_else(math_error!())?;
+
+        if total_fee.is_zero() {
+            return Ok(I80F48::ZERO);
+        }
+
+        let liability_shares_increase = bank.get_liability_shares(total_fee)?;
+        balance.change_liability_shares(liability_shares_increase)?;
+        bank.change_liability_shares(liability_shares_increase, false)?;
+
+        bank.collected_insurance_fees_outstanding = {
+            insurance_fee
+                .checked_add(bank.collected_insurance_fees_outstanding.into())
+                .ok_or_else(math_error!())?
+                .into()
+        };
+
+        bank.collected_group_fees_outstanding = {
+            group_fee
+                .checked_add(bank.collected_group_fees_outstanding.into())
+                .ok_or_else(math_error!())?
+                .into()
+        };
+
+        Ok(total_fee)
+    }
+
     // ------------ Hybrid operations for seamless repay + deposit / withdraw + borrow
 
     /// Repay liability and deposit/increase asset depending on
@@ -899,7 +1057,19 @@ impl<'a> BankAccountWrapper<'a> {
 
     /// Withdraw existing asset in full - will error if there is no asset.
     pub fn withdraw_all(&mut self) -> MarginfiResult<u64> {
-        self.claim_emissions(Clock::get()?.unix_timestamp as u64)?;
+        self.withdraw_all_internal(true)
+    }
+
+    /// Withdraw an asset sold in full in a bad debt collateral auction - will error if there is no asset.
+    ///
+    /// Bad debt recovery is not counted against the bank outflow limit.
+    pub fn withdraw_all_in_bad_debt_auction(&mut self) -> MarginfiResult<u64> {
+        self.withdraw_all_internal(false)
+    }
+
+    fn withdraw_all_internal(&mut self, track_outflow: bool) -> MarginfiResult<u64> {
+        let current_timestamp = Clock::get()?.unix_timestamp;
+        self.claim_emissions(current_timestamp as u64)?;
 
         let balance = &mut self.balance;
         let bank = &mut self.bank;
@@ -926,6 +1096,10 @@ impl<'a> BankAccountWrapper<'a> {
         balance.close()?;
         bank.change_asset_shares(-total_asset_shares, false)?;
 
+        if track_outflow {
+            bank.update_outflow(current_asset_amount, current_timestamp)?;
+        }
+
         bank.check_utilization_ratio()?;
 
         let spl_withdraw_amount = current_asset_amount
@@ -948,7 +1122,8 @@ impl<'a> BankAccountWrapper<'a> {
 
     /// Repay existing liability in full - will error if there is no liability.
     pub fn repay_all(&mut self) -> MarginfiResult<u64> {
-        self.claim_emissions(Clock::get()?.unix_timestamp as u64)?;
+        let current_timestamp = Clock::get()?.unix_timestamp;
+        self.claim_emissions(current_timestamp as u64)?;
 
         let balance = &mut self.balance;
         let bank = &mut self.bank;
@@ -973,6 +1148,7 @@ impl<'a> BankAccountWrapper<'a> {
 
         balance.close()?;
         bank.change_liability_shares(-total_liability_shares, false)?;
+        bank.update_outflow(-current_liability_amount, current_timestamp)?;
 
         let spl_deposit_amount = current_liability_amount
             .checked_ceil()
@@ -992,6 +1168,98 @@ impl<'a> BankAccountWrapper<'a> {
             .ok_or_else(math_error!())?)
     }
 
+    /// Remove shares from the balance to be moved to another account of the same authority.
+    ///
+    /// `amount` is denominated in the balance side tokens. If `transfer_all` is set, or `amount`
+    /// covers the entire balance, the entire balance including outstanding emissions is moved
+    /// and the balance is closed.
+    ///
+    /// Returns the (`asset_shares`, `liability_shares`, `emissions_outstanding`) removed.
+    pub fn transfer_out(
+        &mut self,
+        amount: I80F48,
+        transfer_all: bool,
+    ) -> MarginfiResult<(I80F48, I80F48, I80F48)> {
+        self.claim_emissions(Clock::get()?.unix_timestamp as u64)?;
+
+        let balance = &mut self.balance;
+        let bank = &mut self.bank;
+
+        bank.assert_operational_mode(None)?;
+
+        let (side, shares, transfer_all) =
+            balance.get_transfer_shares(bank, amount, transfer_all)?;
+
+        let emissions_outstanding = if transfer_all {
+            I80F48::from(balance.emissions_outstanding)
+        } else {
+            I80F48::ZERO
+        };
+
+        let transferred_shares = match side {
+            BalanceSide::Assets => {
+                balance.change_asset_shares(-shares)?;
+                (shares, I80F48::ZERO)
+            }
+            BalanceSide::Liabilities => {
+                balance.change_liability_shares(-shares)?;
+                (I80F48::ZERO, shares)
+            }
+        };
+
+        if transfer_all {
+            balance.emissions_outstanding = I80F48::ZERO.into();
+            balance.close()?;
+        }
+
+        Ok((
+            transferred_shares.0,
+            transferred_shares.1,
+            emissions_outstanding,
+        ))
+    }
+
+    /// Add shares moved from another account of the same authority to the balance.
+    ///
+    /// Will error if the balance has an existing position on the opposite side.
+    pub fn transfer_in(
+        &mut self,
+        asset_shares: I80F48,
+        liability_shares: I80F48,
+        emissions_outstanding: I80F48,
+    ) -> MarginfiResult {
+        self.claim_emissions(Clock::get()?.unix_timestamp as u64)?;
+
+        let balance = &mut self.balance;
+
+        if asset_shares.is_positive() {
+            check!(
+                balance.is_empty(BalanceSide::Liabilities),
+                MarginfiError::IllegalBalanceState,
+                "Balance has existing debt"
+            );
+            balance.change_asset_shares(asset_shares)?;
+        }
+
+        if liability_shares.is_positive() {
+            check!(
+                balance.is_empty(BalanceSide::Assets),
+                MarginfiError::IllegalBalanceState,
+                "Balance has existing assets"
+            );
+            balance.change_liability_shares(liability_shares)?;
+        }
+
+        balance.emissions_outstanding = {
+            I80F48::from(balance.emissions_outstanding)
+                .checked_add(emissions_outstanding)
+                .ok_or_else(math_error!())?
+        }
+        .into();
+
+        Ok(())
+    }
+
     pub fn close_balance(&mut self) -> MarginfiResult<()> {
         self.claim_emissions(Clock::get()?.unix_timestamp as u64)?;
 
@@ -1032,7 +1300,8 @@ impl<'a> BankAccountWrapper<'a> {
             operation_type
         );
 
-        self.claim_emissions(Clock::get()?.unix_timestamp as u64)?;
+        let current_timestamp = Clock::get()?.unix_timestamp;
+        self.claim_emissions(current_timestamp as u64)?;
 
         let balance = &mut self.balance;
         let bank = &mut self.bank;
@@ -1051,7 +1320,7 @@ impl<'a> BankAccountWrapper<'a> {
         );
 
         match operation_type {
-            BalanceIncreaseType::RepayOnly => {
+            BalanceIncreaseType::RepayOnly | BalanceIncreaseType::BadDebtRepayOnly => {
                 check!(
                     asset_amount_increase.is_zero_with_tolerance(ZERO_AMOUNT_THRESHOLD),
                     MarginfiError::OperationRepayOnly
@@ -1084,6 +1353,13 @@ impl<'a> BankAccountWrapper<'a> {
         balance.change_liability_shares(-liability_shares_decrease)?;
         bank.change_liability_shares(-liability_shares_decrease, true)?;
 
+        if !matches!(
+            operation_type,
+            BalanceIncreaseType::BypassDepositLimit | BalanceIncreaseType::BadDebtRepayOnly
+        ) {
+            bank.update_outflow(-balance_delta, current_timestamp)?;
+        }
+
         Ok(())
     }
 
@@ -1098,7 +1374,8 @@ impl<'a> BankAccountWrapper<'a> {
             operation_type
         );
 
-        self.claim_emissions(Clock::get()?.unix_timestamp as u64)?;
+        let current_timestamp = Clock::get()?.unix_timestamp;
+        self.claim_emissions(current_timestamp as u64)?;
 
         let balance = &mut self.balance;
         let bank = &mut self.bank;
@@ -1117,7 +1394,7 @@ impl<'a> BankAccountWrapper<'a> {
         );
 
         match operation_type {
-            BalanceDecreaseType::WithdrawOnly => {
+            BalanceDecreaseType::WithdrawOnly | BalanceDecreaseType::BypassOutflowWithdrawOnly => {
                 check!(
                     liability_amount_increase.is_zero_with_tolerance(ZERO_AMOUNT_THRESHOLD),
                     MarginfiError::OperationWithdrawOnly
@@ -1149,6 +1426,13 @@ impl<'a> BankAccountWrapper<'a> {
             matches!(operation_type, BalanceDecreaseType::BypassBorrowLimit),
         )?;
 
+        if !matches!(
+            operation_type,
+            BalanceDecreaseType::BypassBorrowLimit | BalanceDecreaseType::BypassOutflowWithdrawOnly
+        ) {
+            bank.update_outflow(balance_delta, current_timestamp)?;
+        }
+
         bank.check_utilization_ratio()?;
 
         Ok(())
@@ -1307,12 +1591,16 @@ mod test {
                     liability_shares: WrappedI80F48::default(),
                     emissions_outstanding: WrappedI80F48::default(),
                     last_update: 0,
-                    _padding: [0_u64],
+                    deferred_bad_debt: 0,
                 }; 16],
-                _padding: [0; 8],
+                flashloan_balance_slots: 0,
+                _padding: [0; 7],
             },
             account_flags: TRANSFER_AUTHORITY_ALLOWED_FLAG,
-            _padding: [0; 63],
+            flashloan_ix_index: 0,
+            flashloan_cpi_program: Pubkey::default(),
+            flashloan_liability_shares: [WrappedI80F48::default(); 16],
+            _padding: [0; 26],
         };
 
         assert!(acc.get_flag(TRANSFER_AUTHORITY_ALLOWED_FLAG));
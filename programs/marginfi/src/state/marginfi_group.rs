@@ -1,17 +1,22 @@
 use super::{
     marginfi_account::{BalanceSide, RequirementType},
-    price::{OraclePriceFeedAdapter, OracleSetup},
+    price::{OraclePriceFeedAdapter, OraclePriceType, OracleSetup, PriceAdapter, PriceBias},
 };
 #[cfg(not(feature = "client"))]
-use crate::events::{GroupEventHeader, LendingPoolBankAccrueInterestEvent};
+use crate::events::{GroupEventHeader, LendingPoolBankAccrueInterestEventV2};
 use crate::{
     assert_struct_size, check,
     constants::{
-        FEE_VAULT_AUTHORITY_SEED, FEE_VAULT_SEED, INSURANCE_VAULT_AUTHORITY_SEED,
+        BAD_DEBT_AUCTION_DURATION_SEC, BAD_DEBT_AUCTION_MAX_DISCOUNT, FEE_VAULT_AUTHORITY_SEED,
+        FEE_VAULT_SEED, INSURANCE_DEAD_SHARES, INSURANCE_VAULT_AUTHORITY_SEED,
         INSURANCE_VAULT_SEED, LIQUIDITY_VAULT_AUTHORITY_SEED, LIQUIDITY_VAULT_SEED,
-        MAX_ORACLE_KEYS, PYTH_ID, SECONDS_PER_YEAR, TOTAL_ASSET_VALUE_INIT_LIMIT_INACTIVE,
+        MAX_FLASHLOAN_CPI_PROGRAMS, MAX_ORACLE_KEYS, MAX_PRICE_AGE_SEC, OUTFLOW_LIMIT_INACTIVE,
+        PYTH_ID, SECONDS_PER_YEAR, TOTAL_ASSET_VALUE_INIT_LIMIT_INACTIVE,
+        TOTAL_BORROW_VALUE_LIMIT_INACTIVE, TOTAL_DEPOSIT_VALUE_LIMIT_INACTIVE,
     },
-    debug, math_error,
+    debug,
+    events::BankStateSnapshot,
+    math_error,
     prelude::MarginfiError,
     set_if_some,
     state::marginfi_account::calc_value,
@@ -24,6 +29,7 @@ use pyth_sdk_solana::{load_price_feed_from_account_info, PriceFeed};
 #[cfg(feature = "client")]
 use std::fmt::Display;
 use std::{
+    cmp::{max, min},
     fmt::{Debug, Formatter},
     ops::Not,
 };
@@ -39,7 +45,10 @@ use type_layout::TypeLayout;
 #[derive(Default)]
 pub struct MarginfiGroup {
     pub admin: Pubkey,
-    pub _padding_0: [u128; 32],
+    /// Programs allowed to start and end flashloans via CPI.
+    /// Unused slots are set to `Pubkey::default()`.
+    pub flashloan_cpi_programs: [Pubkey; MAX_FLASHLOAN_CPI_PROGRAMS],
+    pub _padding_0: [u128; 24],
     pub _padding_1: [u128; 32],
 }
 
@@ -60,6 +69,46 @@ impl MarginfiGroup {
     pub fn set_initial_configuration(&mut self, admin_pk: Pubkey) {
         self.admin = admin_pk;
     }
+
+    pub fn is_flashloan_cpi_program_allowed(&self, program_id: &Pubkey) -> bool {
+        program_id.ne(&Pubkey::default()) && self.flashloan_cpi_programs.contains(program_id)
+    }
+
+    /// Add or remove a program from the flashloan CPI allowlist.
+    pub fn configure_flashloan_cpi_program(
+        &mut self,
+        program_id: Pubkey,
+        allowed: bool,
+    ) -> MarginfiResult {
+        check!(
+            program_id.ne(&Pubkey::default()),
+            MarginfiError::InvalidConfig
+        );
+
+        if allowed {
+            if self.is_flashloan_cpi_program_allowed(&program_id) {
+                return Ok(());
+            }
+
+            let slot = self
+                .flashloan_cpi_programs
+                .iter_mut()
+                .find(|pk| pk.eq(&&Pubkey::default()))
+                .ok_or(MarginfiError::InvalidConfig)?;
+
+            *slot = program_id;
+        } else {
+            let slot = self
+                .flashloan_cpi_programs
+                .iter_mut()
+                .find(|pk| pk.eq(&&program_id))
+                .ok_or(MarginfiError::InvalidConfig)?;
+
+            *slot = Pubkey::default();
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg_attr(any(feature = "test", feature = "client"), derive(TypeLayout))]
@@ -106,7 +155,9 @@ impl From<InterestRateConfigCompact> for InterestRateConfig {
             insurance_ir_fee: ir_config.insurance_ir_fee,
             protocol_fixed_fee_apr: ir_config.protocol_fixed_fee_apr,
             protocol_ir_fee: ir_config.protocol_ir_fee,
-            _padding: [0; 8],
+            insurance_flashloan_fee: I80F48::ZERO.into(),
+            protocol_flashloan_fee: I80F48::ZERO.into(),
+            _padding: [0; 6],
         }
     }
 }
@@ -144,7 +195,12 @@ pub struct InterestRateConfig {
     pub protocol_fixed_fee_apr: WrappedI80F48,
     pub protocol_ir_fee: WrappedI80F48,
 
-    pub _padding: [u128; 8], // 16 * 8 = 128 bytes
+    // Flashloan fees, charged on the amount borrowed by each borrow within a flashloan.
+    // Repaying and borrowing again within the same flashloan is charged again.
+    pub insurance_flashloan_fee: WrappedI80F48,
+    pub protocol_flashloan_fee: WrappedI80F48,
+
+    pub _padding: [u128; 6], // 16 * 6 = 96 bytes
 }
 
 impl InterestRateConfig {
@@ -237,6 +293,17 @@ impl InterestRateConfig {
         check!(max_ir > I80F48::ZERO, MarginfiError::InvalidConfig);
         check!(plateau_ir < max_ir, MarginfiError::InvalidConfig);
 
+        let flashloan_fee = I80F48::from(self.insurance_flashloan_fee)
+            .checked_add(self.protocol_flashloan_fee.into())
+            .ok_or_else(math_error!())?;
+
+        check!(
+            I80F48::from(self.insurance_flashloan_fee) >= I80F48::ZERO
+                && I80F48::from(self.protocol_flashloan_fee) >= I80F48::ZERO
+                && flashloan_fee < I80F48::ONE,
+            MarginfiError::InvalidConfig
+        );
+
         Ok(())
     }
 
@@ -257,6 +324,20 @@ impl InterestRateConfig {
             ir_config.protocol_fixed_fee_apr
         );
         set_if_some!(self.protocol_ir_fee, ir_config.protocol_ir_fee);
+        set_if_some!(
+            self.insurance_flashloan_fee,
+            ir_config.insurance_flashloan_fee
+        );
+        set_if_some!(
+            self.protocol_flashloan_fee,
+            ir_config.protocol_flashloan_fee
+        );
+    }
+
+    #[inline]
+    pub fn is_flashloan_fee_active(&self) -> bool {
+        I80F48::from(self.insurance_flashloan_fee) > I80F48::ZERO
+            || I80F48::from(self.protocol_flashloan_fee) > I80F48::ZERO
     }
 }
 
@@ -274,6 +355,9 @@ pub struct InterestRateConfigOpt {
     pub insurance_ir_fee: Option<WrappedI80F48>,
     pub protocol_fixed_fee_apr: Option<WrappedI80F48>,
     pub protocol_ir_fee: Option<WrappedI80F48>,
+
+    pub insurance_flashloan_fee: Option<WrappedI80F48>,
+    pub protocol_flashloan_fee: Option<WrappedI80F48>,
 }
 
 assert_struct_size!(Bank, 1856);
@@ -326,7 +410,46 @@ pub struct Bank {
     pub emissions_remaining: WrappedI80F48,
     pub emissions_mint: Pubkey,
 
-    pub _padding_0: [u128; 28],
+    /// Net outflow (withdraws and borrows less deposits and repays) from the liquidity vault
+    /// accumulated in the current rate limit window, in native amount.
+    ///
+    /// The accumulated outflow decays linearly over `outflow_limit_window`,
+    /// approximating a rolling window without tracking individual outflows.
+    pub outflow_window_amount: WrappedI80F48,
+    pub outflow_last_update: i64,
+    /// Outflow rate limit window in seconds, 0 -> outflow limits inactive
+    pub outflow_limit_window: u64,
+    /// Max net outflow per window in native amount, 0 -> inactive
+    pub outflow_limit: u64,
+    /// Max net outflow per window in UI USD value, 0 -> inactive
+    pub outflow_value_limit: u64,
+
+    /// Mint of the insurance fund share tokens, default -> insurance staking inactive
+    pub insurance_share_mint: Pubkey,
+    /// Insurance fund shares owned by the protocol, backing the insurance vault balance
+    /// at the time insurance staking was enabled, any later vault balance without owning shares,
+    /// and the dead shares seeded by the first stake.
+    ///
+    /// Protocol shares can't be redeemed: their share of the vault, including the insurance fees
+    /// credited to them, stays in the insurance fund for good to cover bad debt.
+    pub insurance_protocol_shares: u64,
+    /// Shares burned by stakers in the unstake cooldown, still exposed to bad debt.
+    pub insurance_cooldown_shares: u64,
+    /// Unstake cooldown in seconds
+    pub insurance_unstake_cooldown: u64,
+    pub insurance_share_mint_bump: u8,
+    /// Number of times insurance staking was reset to a new share mint after the fund got depleted
+    pub insurance_share_generation: u8,
+    pub _padding_insurance: [u8; 6],
+
+    /// Bad debt not covered by the insurance fund and not socialized among depositors,
+    /// pending recovery from insurance fees and bad debt collateral auctions, in native amount.
+    pub unrecovered_bad_debt: WrappedI80F48,
+    /// Start of the current bad debt collateral auction, the auction discount grows from this timestamp.
+    pub bad_debt_auction_start: i64,
+    pub _padding_bad_debt: u64,
+
+    pub _padding_0: [u128; 19],
     pub _padding_1: [u128; 32], // 16 * 2 * 32 = 1024B
 }
 
@@ -373,7 +496,22 @@ impl Bank {
             emissions_rate: 0,
             emissions_remaining: I80F48::ZERO.into(),
             emissions_mint: Pubkey::default(),
-            _padding_0: [0; 28],
+            outflow_window_amount: I80F48::ZERO.into(),
+            outflow_last_update: 0,
+            outflow_limit_window: 0,
+            outflow_limit: OUTFLOW_LIMIT_INACTIVE,
+            outflow_value_limit: OUTFLOW_LIMIT_INACTIVE,
+            insurance_share_mint: Pubkey::default(),
+            insurance_protocol_shares: 0,
+            insurance_cooldown_shares: 0,
+            insurance_unstake_cooldown: 0,
+            insurance_share_mint_bump: 0,
+            insurance_share_generation: 0,
+            _padding_insurance: [0; 6],
+            unrecovered_bad_debt: I80F48::ZERO.into(),
+            bad_debt_auction_start: 0,
+            _padding_bad_debt: 0,
+            _padding_0: [0; 19],
             _padding_1: [0; 32],
         }
     }
@@ -402,6 +540,45 @@ impl Bank {
             .ok_or_else(math_error!())?)
     }
 
+    /// Current share values, share totals and interest rates, as reported in versioned events.
+    ///
+    /// Infallible, as it runs on every interest accrual: the informational interest rates
+    /// are reported as missing if they can't be computed.
+    pub fn get_state_snapshot(&self) -> BankStateSnapshot {
+        let interest_rates = self.get_interest_rates();
+
+        BankStateSnapshot {
+            asset_share_value: self.asset_share_value,
+            liability_share_value: self.liability_share_value,
+            total_asset_shares: self.total_asset_shares,
+            total_liability_shares: self.total_liability_shares,
+            lending_rate: interest_rates.map(|(lending_rate, _)| lending_rate.to_num::<f64>()),
+            borrowing_rate: interest_rates
+                .map(|(_, borrowing_rate)| borrowing_rate.to_num::<f64>()),
+        }
+    }
+
+    /// Current (lending rate, borrowing rate) of the bank.
+    fn get_interest_rates(&self) -> Option<(I80F48, I80F48)> {
+        let total_assets = self.get_asset_amount(self.total_asset_shares.into()).ok()?;
+        let total_liabilities = self
+            .get_liability_amount(self.total_liability_shares.into())
+            .ok()?;
+
+        let utilization_rate = if total_assets > I80F48::ZERO {
+            total_liabilities.checked_div(total_assets)?
+        } else {
+            I80F48::ZERO
+        };
+
+        let (lending_rate, borrowing_rate, _, _) = self
+            .config
+            .interest_rate_config
+            .calc_interest_rate(utilization_rate)?;
+
+        Some((lending_rate, borrowing_rate))
+    }
+
     pub fn change_asset_shares(
         &mut self,
         shares: I80F48,
@@ -496,6 +673,287 @@ impl Bank {
         Ok(())
     }
 
+    /// Check the USD value of total bank deposits against `total_deposit_value_limit`.
+    ///
+    /// `price` is expected to be biased high, so that the cap errs on the side of caution.
+    pub fn check_deposit_value_limit(&self, price: I80F48) -> MarginfiResult {
+        if !self.config.is_deposit_value_limit_active() {
+            return Ok(());
+        }
+
+        let total_deposits_value = calc_value(
+            self.get_asset_amount(self.total_asset_shares.into())?,
+            price,
+            self.mint_decimals,
+            None,
+        )?;
+        let deposit_value_limit = I80F48::from_num(self.config.total_deposit_value_limit);
+
+        debug!(
+            "Deposit value limit active, limit: {}, total_deposits: {}",
+            deposit_value_limit, total_deposits_value
+        );
+
+        check!(
+            total_deposits_value < deposit_value_limit,
+            MarginfiError::BankAssetValueCapacityExceeded
+        );
+
+        Ok(())
+    }
+
+    /// Check the USD value of total bank borrows against `total_borrow_value_limit`.
+    ///
+    /// `price` is expected to be biased high, so that the cap errs on the side of caution.
+    pub fn check_borrow_value_limit(&self, price: I80F48) -> MarginfiResult {
+        if !self.config.is_borrow_value_limit_active() {
+            return Ok(());
+        }
+
+        let total_liabilities_value = calc_value(
+            self.get_liability_amount(self.total_liability_shares.into())?,
+            price,
+            self.mint_decimals,
+            None,
+        )?;
+        let borrow_value_limit = I80F48::from_num(self.config.total_borrow_value_limit);
+
+        debug!(
+            "Borrow value limit active, limit: {}, total_liabilities: {}",
+            borrow_value_limit, total_liabilities_value
+        );
+
+        check!(
+            total_liabilities_value < borrow_value_limit,
+            MarginfiError::BankLiabilityValueCapacityExceeded
+        );
+
+        Ok(())
+    }
+
+    /// Check the USD deposit and borrow caps on the bank totals grown by an instruction,
+    /// given the bank total shares before the instruction.
+    ///
+    /// Caps are only checked for totals that grew, so instructions moving shares between accounts,
+    /// like liquidations, aren't blocked by a bank already over its cap after a price move.
+    ///
+    /// `oracle_ais` is the lookup of the bank oracle, only required when a cap needs checking.
+    pub fn check_value_limits(
+        &self,
+        pre_total_asset_shares: I80F48,
+        pre_total_liability_shares: I80F48,
+        oracle_ais: MarginfiResult<&[AccountInfo]>,
+        current_timestamp: i64,
+    ) -> MarginfiResult {
+        let check_deposits = self.config.is_deposit_value_limit_active()
+            && I80F48::from(self.total_asset_shares) > pre_total_asset_shares;
+        let check_borrows = self.config.is_borrow_value_limit_active()
+            && I80F48::from(self.total_liability_shares) > pre_total_liability_shares;
+
+        if !check_deposits && !check_borrows {
+            return Ok(());
+        }
+
+        let price = self.get_limit_price(oracle_ais?, current_timestamp)?;
+
+        if check_deposits {
+            self.check_deposit_value_limit(price)?;
+        }
+
+        if check_borrows {
+            self.check_borrow_value_limit(price)?;
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn is_outflow_limit_active(&self) -> bool {
+        self.outflow_limit_window != 0 && self.outflow_limit != OUTFLOW_LIMIT_INACTIVE
+    }
+
+    #[inline]
+    pub fn is_outflow_value_limit_active(&self) -> bool {
+        self.outflow_limit_window != 0 && self.outflow_value_limit != OUTFLOW_LIMIT_INACTIVE
+    }
+
+    /// Record a net outflow of `amount` (negative for inflows) from the liquidity vault,
+    /// and check the accumulated outflow against the native outflow limit.
+    ///
+    /// Inflows only offset earlier outflows, the accumulated outflow never goes below zero.
+    pub fn update_outflow(&mut self, amount: I80F48, current_timestamp: i64) -> MarginfiResult {
+        if self.outflow_limit_window == 0 {
+            return Ok(());
+        }
+
+        let window = I80F48::from_num(self.outflow_limit_window);
+        let elapsed = I80F48::from_num(max(
+            current_timestamp
+                .checked_sub(self.outflow_last_update)
+                .ok_or_else(math_error!())?,
+            0,
+        ));
+
+        let decayed_outflow = if elapsed >= window {
+            I80F48::ZERO
+        } else {
+            I80F48::from(self.outflow_window_amount)
+                .checked_mul(window - elapsed)
+                .ok_or_else(math_error!())?
+                .checked_div(window)
+                .ok_or_else(math_error!())?
+        };
+
+        let outflow = max(
+            decayed_outflow
+                .checked_add(amount)
+                .ok_or_else(math_error!())?,
+            I80F48::ZERO,
+        );
+
+        self.outflow_window_amount = outflow.into();
+        self.outflow_last_update = current_timestamp;
+
+        if amount.is_positive() && self.is_outflow_limit_active() {
+            debug!(
+                "Outflow limit active, limit: {}, outflow: {}",
+                self.outflow_limit, outflow
+            );
+
+            check!(
+                outflow <= I80F48::from_num(self.outflow_limit),
+                MarginfiError::BankOutflowLimitExceeded
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Check the USD value of the accumulated outflow against `outflow_value_limit`.
+    ///
+    /// `price` is expected to be biased high, so that the limit errs on the side of caution.
+    pub fn check_outflow_value_limit(&self, price: I80F48) -> MarginfiResult {
+        if !self.is_outflow_value_limit_active() {
+            return Ok(());
+        }
+
+        let outflow_value = calc_value(
+            self.outflow_window_amount.into(),
+            price,
+            self.mint_decimals,
+            None,
+        )?;
+
+        check!(
+            outflow_value <= I80F48::from_num(self.outflow_value_limit),
+            MarginfiError::BankOutflowValueLimitExceeded
+        );
+
+        Ok(())
+    }
+
+    /// Clear the accumulated outflow, lifting the outflow limits until new outflows come in.
+    pub fn reset_outflow(&mut self, current_timestamp: i64) {
+        self.outflow_window_amount = I80F48::ZERO.into();
+        self.outflow_last_update = current_timestamp;
+    }
+
+    pub fn is_insurance_staking_active(&self) -> bool {
+        self.insurance_share_mint != Pubkey::default()
+    }
+
+    /// Total insurance fund shares, including share tokens in circulation,
+    /// shares owned by the protocol and shares in the unstake cooldown.
+    pub fn get_insurance_total_shares(&self, share_supply: u64) -> MarginfiResult<u64> {
+        Ok(share_supply
+            .checked_add(self.insurance_protocol_shares)
+            .ok_or_else(math_error!())?
+            .checked_add(self.insurance_cooldown_shares)
+            .ok_or_else(math_error!())?)
+    }
+
+    /// Shares minted for staking `amount` into an insurance vault holding `vault_amount`, rounded down.
+    ///
+    /// Will error if the vault got drained while shares are outstanding,
+    /// see `lending_pool_reset_insurance_staking`.
+    pub fn get_insurance_shares_for_amount(
+        &self,
+        amount: u64,
+        vault_amount: u64,
+        share_supply: u64,
+    ) -> MarginfiResult<u64> {
+        let total_shares = self.get_insurance_total_shares(share_supply)?;
+
+        if total_shares == 0 {
+            return Ok(amount);
+        }
+
+        check!(vault_amount > 0, MarginfiError::InsuranceFundDepleted);
+
+        let shares = (amount as u128)
+            .checked_mul(total_shares as u128)
+            .ok_or_else(math_error!())?
+            .checked_div(vault_amount as u128)
+            .ok_or_else(math_error!())?;
+
+        Ok(u64::try_from(shares).map_err(|_| math_error!()())?)
+    }
+
+    /// Shares minted to a staker for staking `amount` into an insurance vault holding `vault_amount`.
+    ///
+    /// When the fund has no shares, the vault balance (e.g. fees collected after every staker left)
+    /// is credited to the protocol 1:1, along with `INSURANCE_DEAD_SHARES` taken from the stake.
+    ///
+    /// Will error if the stake mints no shares.
+    pub fn stake_insurance(
+        &mut self,
+        amount: u64,
+        vault_amount: u64,
+        share_supply: u64,
+    ) -> MarginfiResult<u64> {
+        let shares = if self.get_insurance_total_shares(share_supply)? == 0 {
+            self.insurance_protocol_shares = vault_amount
+                .checked_add(INSURANCE_DEAD_SHARES)
+                .ok_or_else(math_error!())?;
+
+            amount.saturating_sub(INSURANCE_DEAD_SHARES)
+        } else {
+            self.get_insurance_shares_for_amount(amount, vault_amount, share_supply)?
+        };
+
+        check!(shares > 0, MarginfiError::InsuranceStakeTooSmall);
+
+        Ok(shares)
+    }
+
+    /// Amount redeemed for `shares` from an insurance vault holding `vault_amount`, rounded down.
+    pub fn get_insurance_amount_for_shares(
+        &self,
+        shares: u64,
+        vault_amount: u64,
+        share_supply: u64,
+    ) -> MarginfiResult<u64> {
+        let total_shares = self.get_insurance_total_shares(share_supply)?;
+
+        let amount = (shares as u128)
+            .checked_mul(vault_amount as u128)
+            .ok_or_else(math_error!())?
+            .checked_div(total_shares as u128)
+            .ok_or_else(math_error!())?;
+
+        Ok(u64::try_from(amount).map_err(|_| math_error!()())?)
+    }
+
+    /// Load the bank oracle price biased high, used for evaluating USD denominated bank limits.
+    pub fn get_limit_price(
+        &self,
+        oracle_ais: &[AccountInfo],
+        current_timestamp: i64,
+    ) -> MarginfiResult<I80F48> {
+        self.load_price_feed_from_account_info(oracle_ais, current_timestamp, MAX_PRICE_AGE_SEC)?
+            .get_price_of_type(OraclePriceType::TimeWeighted, Some(PriceBias::High))
+    }
+
     pub fn check_utilization_ratio(&self) -> MarginfiResult {
         let total_assets = self.get_asset_amount(self.total_asset_shares.into())?;
         let total_liabilities = self.get_liability_amount(self.total_liability_shares.into())?;
@@ -540,6 +998,25 @@ impl Bank {
             config.total_asset_value_init_limit
         );
 
+        set_if_some!(
+            self.config.total_deposit_value_limit,
+            config.total_deposit_value_limit
+        );
+
+        set_if_some!(
+            self.config.total_borrow_value_limit,
+            config.total_borrow_value_limit
+        );
+
+        set_if_some!(self.outflow_limit_window, config.outflow_limit_window);
+
+        set_if_some!(self.outflow_limit, config.outflow_limit);
+
+        set_if_some!(self.outflow_value_limit, config.outflow_value_limit);
+
+        set_if_some!(self.config.borrow_mode, config.borrow_mode);
+        set_if_some!(self.config.bad_debt_mode, config.bad_debt_mode);
+
         self.config.validate()?;
 
         Ok(())
@@ -580,7 +1057,7 @@ impl Bank {
 
         if (total_assets == I80F48::ZERO) || (total_liabilities == I80F48::ZERO) {
             #[cfg(not(feature = "client"))]
-            emit!(LendingPoolBankAccrueInterestEvent {
+            emit!(LendingPoolBankAccrueInterestEventV2 {
                 header: GroupEventHeader {
                     marginfi_group: self.group,
                     signer: None
@@ -590,6 +1067,7 @@ impl Bank {
                 delta: time_delta,
                 fees_collected: 0.,
                 insurance_collected: 0.,
+                bank_state: self.get_state_snapshot(),
             });
 
             return Ok(());
@@ -630,7 +1108,7 @@ impl Bank {
         {
             solana_program::log::sol_log_compute_units();
 
-            emit!(LendingPoolBankAccrueInterestEvent {
+            emit!(LendingPoolBankAccrueInterestEventV2 {
                 header: GroupEventHeader {
                     marginfi_group: self.group,
                     signer: None
@@ -640,6 +1118,7 @@ impl Bank {
                 delta: time_delta,
                 fees_collected: fees_collected.to_num::<f64>(),
                 insurance_collected: insurance_collected.to_num::<f64>(),
+                bank_state: self.get_state_snapshot(),
             });
         }
 
@@ -709,6 +1188,61 @@ impl Bank {
         Ok(())
     }
 
+    /// Record `bad_debt` on the bank instead of socializing it among depositors,
+    /// restarting the bad debt collateral auction.
+    pub fn defer_bad_debt(&mut self, bad_debt: I80F48, current_timestamp: i64) -> MarginfiResult {
+        self.unrecovered_bad_debt = I80F48::from(self.unrecovered_bad_debt)
+            .checked_add(bad_debt)
+            .ok_or_else(math_error!())?
+            .into();
+        self.bad_debt_auction_start = current_timestamp;
+
+        Ok(())
+    }
+
+    /// Reduce the unrecovered bad debt by up to `amount` repaid into the liquidity vault,
+    /// returns the amount of bad debt recovered.
+    pub fn recover_bad_debt(&mut self, amount: I80F48) -> MarginfiResult<I80F48> {
+        let unrecovered_bad_debt: I80F48 = self.unrecovered_bad_debt.into();
+        let recovered = min(unrecovered_bad_debt, max(amount, I80F48::ZERO));
+
+        self.unrecovered_bad_debt = unrecovered_bad_debt
+            .checked_sub(recovered)
+            .ok_or_else(math_error!())?
+            .into();
+
+        Ok(recovered)
+    }
+
+    /// Discount of the bad debt collateral auction at `current_timestamp`.
+    pub fn get_bad_debt_auction_discount(&self, current_timestamp: i64) -> MarginfiResult<I80F48> {
+        let elapsed = max(current_timestamp - self.bad_debt_auction_start, 0);
+        let progress = min(
+            I80F48::from_num(elapsed)
+                .checked_div(I80F48::from_num(BAD_DEBT_AUCTION_DURATION_SEC))
+                .ok_or_else(math_error!())?,
+            I80F48::ONE,
+        );
+
+        Ok(progress
+            .checked_mul(BAD_DEBT_AUCTION_MAX_DISCOUNT)
+            .ok_or_else(math_error!())?)
+    }
+
+    /// Cover unrecovered bad debt with outstanding insurance fees,
+    /// the fees stay in the liquidity vault backing the deficit.
+    pub fn recover_bad_debt_from_insurance_fees(&mut self) -> MarginfiResult<I80F48> {
+        let outstanding_fees: I80F48 = self.collected_insurance_fees_outstanding.into();
+        let recovered = self.recover_bad_debt(outstanding_fees)?;
+
+        self.collected_insurance_fees_outstanding = outstanding_fees
+            .checked_sub(recovered)
+            .ok_or_else(math_error!())?
+            .into();
+
+        Ok(recovered)
+    }
+
     pub fn assert_operational_mode(
         &self,
         is_asset_or_liability_amount_increasing: Option<bool>,
@@ -861,6 +1395,32 @@ pub enum RiskTier {
     Isolated,
 }
 
+#[repr(u64)]
+#[derive(Copy, Clone, Debug, AnchorSerialize, AnchorDeserialize, PartialEq, Eq)]
+pub enum BankBorrowMode {
+    Permissionless,
+    /// ## Permissioned Borrowing
+    /// Only marginfi accounts holding a `BorrowCredential` issued by the group admin
+    /// for the bank can borrow from it, optionally up to a per account limit.
+    ///
+    /// Depositing, repaying and withdrawing are not affected.
+    Permissioned,
+}
+
+#[repr(u64)]
+#[derive(Copy, Clone, Debug, AnchorSerialize, AnchorDeserialize, PartialEq, Eq)]
+pub enum BankBadDebtMode {
+    /// Bad debt not covered by the insurance fund is socialized among depositors immediately.
+    Socialize,
+    /// ## Deferred Bad Debt
+    /// Bad debt not covered by the insurance fund is recorded on the bank as `unrecovered_bad_debt`
+    /// and covered over time from insurance fees and bad debt collateral auctions,
+    /// instead of cutting the asset share value at once.
+    ///
+    /// Until recovered, the deficit is borne by the last depositors to withdraw.
+    Defer,
+}
+
 #[zero_copy(unsafe)]
 #[repr(C)]
 #[cfg_attr(
@@ -897,6 +1457,30 @@ pub struct BankConfigCompact {
     ///
     /// Value is UI USD value, for example value 100 -> $100
     pub total_asset_value_init_limit: u64,
+
+    /// USD denominated cap on total bank deposits, evaluated with the bank oracle on deposit.
+    /// Applies alongside `deposit_limit`.
+    ///
+    /// Value is UI USD value, for example value 100 -> $100, 0 -> inactive
+    pub total_deposit_value_limit: u64,
+
+    /// USD denominated cap on total bank borrows, evaluated with the bank oracle on borrow.
+    /// Applies alongside `borrow_limit`.
+    ///
+    /// Value is UI USD value, for example value 100 -> $100, 0 -> inactive
+    pub total_borrow_value_limit: u64,
+
+    /// Who can borrow from the bank, see `BankBorrowMode`.
+    pub borrow_mode: BankBorrowMode,
+
+    /// How uninsured bad debt is handled, see `BankBadDebtMode`.
+    pub bad_debt_mode: BankBadDebtMode,
+
+    /// Flashloan fees, see `InterestRateConfig`.
+    ///
+    /// Appended here rather than to `InterestRateConfigCompact`, keeping the offsets of existing fields.
+    pub insurance_flashloan_fee: WrappedI80F48,
+    pub protocol_flashloan_fee: WrappedI80F48,
 }
 
 impl From<BankConfigCompact> for BankConfig {
@@ -914,14 +1498,22 @@ impl From<BankConfigCompact> for BankConfig {
             liability_weight_init: config.liability_weight_init,
             liability_weight_maint: config.liability_weight_maint,
             deposit_limit: config.deposit_limit,
-            interest_rate_config: config.interest_rate_config.into(),
+            interest_rate_config: InterestRateConfig {
+                insurance_flashloan_fee: config.insurance_flashloan_fee,
+                protocol_flashloan_fee: config.protocol_flashloan_fee,
+                ..config.interest_rate_config.into()
+            },
             operational_state: config.operational_state,
             oracle_setup: config.oracle_setup,
             oracle_keys: keys,
             borrow_limit: config.borrow_limit,
             risk_tier: config.risk_tier,
             total_asset_value_init_limit: config.total_asset_value_init_limit,
-            _padding: [0; 5],
+            total_deposit_value_limit: config.total_deposit_value_limit,
+            total_borrow_value_limit: config.total_borrow_value_limit,
+            borrow_mode: config.borrow_mode,
+            bad_debt_mode: config.bad_debt_mode,
+            _padding: [0; 1],
         }
     }
 }
@@ -941,6 +1533,12 @@ impl From<BankConfig> for BankConfigCompact {
             borrow_limit: config.borrow_limit,
             risk_tier: config.risk_tier,
             total_asset_value_init_limit: config.total_asset_value_init_limit,
+            total_deposit_value_limit: config.total_deposit_value_limit,
+            total_borrow_value_limit: config.total_borrow_value_limit,
+            borrow_mode: config.borrow_mode,
+            bad_debt_mode: config.bad_debt_mode,
+            insurance_flashloan_fee: config.interest_rate_config.insurance_flashloan_fee,
+            protocol_flashloan_fee: config.interest_rate_config.protocol_flashloan_fee,
         }
     }
 }
@@ -983,7 +1581,25 @@ pub struct BankConfig {
     /// Value is UI USD value, for example value 100 -> $100
     pub total_asset_value_init_limit: u64,
 
-    pub _padding: [u64; 5], // 16 * 4 = 64 bytes
+    /// USD denominated cap on total bank deposits, evaluated with the bank oracle on deposit.
+    /// Applies alongside `deposit_limit`.
+    ///
+    /// Value is UI USD value, for example value 100 -> $100, 0 -> inactive
+    pub total_deposit_value_limit: u64,
+
+    /// USD denominated cap on total bank borrows, evaluated with the bank oracle on borrow.
+    /// Applies alongside `borrow_limit`.
+    ///
+    /// Value is UI USD value, for example value 100 -> $100, 0 -> inactive
+    pub total_borrow_value_limit: u64,
+
+    /// Who can borrow from the bank, see `BankBorrowMode`.
+    pub borrow_mode: BankBorrowMode,
+
+    /// How uninsured bad debt is handled, see `BankBadDebtMode`.
+    pub bad_debt_mode: BankBadDebtMode,
+
+    pub _padding: [u64; 1], // 8 * 1 = 8 bytes
 }
 
 impl Default for BankConfig {
@@ -1001,7 +1617,11 @@ impl Default for BankConfig {
             oracle_keys: [Pubkey::default(); MAX_ORACLE_KEYS],
             risk_tier: RiskTier::Isolated,
             total_asset_value_init_limit: TOTAL_ASSET_VALUE_INIT_LIMIT_INACTIVE,
-            _padding: [0; 5],
+            total_deposit_value_limit: TOTAL_DEPOSIT_VALUE_LIMIT_INACTIVE,
+            total_borrow_value_limit: TOTAL_BORROW_VALUE_LIMIT_INACTIVE,
+            borrow_mode: BankBorrowMode::Permissionless,
+            bad_debt_mode: BankBadDebtMode::Socialize,
+            _padding: [0; 1],
         }
     }
 }
@@ -1088,6 +1708,26 @@ impl BankConfig {
     pub fn usd_init_limit_active(&self) -> bool {
         self.total_asset_value_init_limit != TOTAL_ASSET_VALUE_INIT_LIMIT_INACTIVE
     }
+
+    #[inline]
+    pub fn is_deposit_value_limit_active(&self) -> bool {
+        self.total_deposit_value_limit != TOTAL_DEPOSIT_VALUE_LIMIT_INACTIVE
+    }
+
+    #[inline]
+    pub fn is_borrow_value_limit_active(&self) -> bool {
+        self.total_borrow_value_limit != TOTAL_BORROW_VALUE_LIMIT_INACTIVE
+    }
+
+    #[inline]
+    pub fn is_borrow_permissioned(&self) -> bool {
+        self.borrow_mode == BankBorrowMode::Permissioned
+    }
+
+    #[inline]
+    pub fn is_bad_debt_deferred(&self) -> bool {
+        self.bad_debt_mode == BankBadDebtMode::Defer
+    }
 }
 
 #[zero_copy]
@@ -1143,6 +1783,17 @@ pub struct BankConfigOpt {
     pub risk_tier: Option<RiskTier>,
 
     pub total_asset_value_init_limit: Option<u64>,
+
+    pub total_deposit_value_limit: Option<u64>,
+    pub total_borrow_value_limit: Option<u64>,
+
+    pub outflow_limit_window: Option<u64>,
+    pub outflow_limit: Option<u64>,
+    pub outflow_value_limit: Option<u64>,
+
+    pub borrow_mode: Option<BankBorrowMode>,
+
+    pub bad_debt_mode: Option<BankBadDebtMode>,
 }
 
 #[cfg_attr(
@@ -1512,4 +2163,157 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    /// Tests that the accumulated outflow decays linearly over the window and is bounded by the limit.
+    fn outflow_limit_decay() {
+        let mut bank = Bank {
+            outflow_limit_window: 100,
+            outflow_limit: 1_000,
+            ..Default::default()
+        };
+
+        bank.update_outflow(I80F48!(1_000), 0).unwrap();
+
+        let mut bank_over_limit = bank;
+        assert!(bank_over_limit.update_outflow(I80F48!(1), 0).is_err());
+
+        // Half of the window passed, half of the outflow decayed
+        bank.update_outflow(I80F48!(500), 50).unwrap();
+        assert_eq!(I80F48::from(bank.outflow_window_amount), I80F48!(1_000));
+
+        // Inflows offset the accumulated outflow, but never below zero
+        bank.update_outflow(I80F48!(-2_000), 50).unwrap();
+        assert_eq!(I80F48::from(bank.outflow_window_amount), I80F48::ZERO);
+
+        bank.update_outflow(I80F48!(1_000), 50).unwrap();
+        bank.update_outflow(I80F48!(1_000), 200).unwrap();
+        assert_eq!(I80F48::from(bank.outflow_window_amount), I80F48!(1_000));
+    }
+
+    #[test]
+    fn insurance_share_value() {
+        let mut bank = Bank {
+            insurance_protocol_shares: 1_000,
+            ..Default::default()
+        };
+
+        // Protocol shares back the initial vault balance 1:1
+        assert_eq!(
+            bank.get_insurance_shares_for_amount(500, 1_000, 0).unwrap(),
+            500
+        );
+
+        // Collected fees raise the share value
+        assert_eq!(
+            bank.get_insurance_shares_for_amount(500, 2_000, 0).unwrap(),
+            250
+        );
+        assert_eq!(
+            bank.get_insurance_amount_for_shares(250, 2_500, 250)
+                .unwrap(),
+            500
+        );
+
+        // Shares in the cooldown share the loss
+        bank.insurance_cooldown_shares = 250;
+        assert_eq!(
+            bank.get_insurance_amount_for_shares(250, 1_250, 0).unwrap(),
+            250
+        );
+
+        assert!(bank.get_insurance_shares_for_amount(500, 0, 0).is_err());
+    }
+
+    #[test]
+    /// Tests that a vault balance without owning shares goes to the protocol, not to the first staker.
+    fn insurance_stake_unowned_vault_balance() {
+        let mut bank = Bank::default();
+
+        // Fees collected into the vault after every staker left
+        let shares = bank.stake_insurance(10_000, 5_000, 0).unwrap();
+
+        assert_eq!(shares, 10_000 - INSURANCE_DEAD_SHARES);
+        assert_eq!(
+            bank.insurance_protocol_shares,
+            5_000 + INSURANCE_DEAD_SHARES
+        );
+
+        // The staker redeems their stake, not the collected fees
+        assert_eq!(
+            bank.get_insurance_amount_for_shares(shares, 15_000, shares)
+                .unwrap(),
+            10_000 - INSURANCE_DEAD_SHARES
+        );
+    }
+
+    #[test]
+    /// Tests that donating to the vault can't make later stakes round down to nothing.
+    fn insurance_stake_donation_inflation() {
+        let mut bank = Bank::default();
+
+        // Stakes below the dead shares mint nothing
+        assert!(bank.stake_insurance(INSURANCE_DEAD_SHARES, 0, 0).is_err());
+
+        let mut bank = Bank::default();
+
+        // Attacker stakes the minimum, then donates to the vault
+        let attacker_shares = bank
+            .stake_insurance(INSURANCE_DEAD_SHARES + 1, 0, 0)
+            .unwrap();
+        assert_eq!(attacker_shares, 1);
+
+        let vault_amount = INSURANCE_DEAD_SHARES + 1 + 1_000_000;
+
+        // A stake below the share value is rejected instead of minting zero shares
+        assert!(bank
+            .stake_insurance(1_000, vault_amount, attacker_shares)
+            .is_err());
+
+        // The attacker loses nearly all of the donation to the dead shares
+        let victim_shares = bank
+            .stake_insurance(10_000_000, vault_amount, attacker_shares)
+            .unwrap();
+        let attacker_amount = bank
+            .get_insurance_amount_for_shares(
+                attacker_shares,
+                vault_amount + 10_000_000,
+                attacker_shares + victim_shares,
+            )
+            .unwrap();
+
+        assert!(attacker_amount < 1_000_000 / 100);
+    }
+
+    #[test]
+    fn deferred_bad_debt_recovery() {
+        let mut bank = Bank {
+            collected_insurance_fees_outstanding: I80F48!(300).into(),
+            ..Default::default()
+        };
+
+        bank.defer_bad_debt(I80F48!(1_000), 0).unwrap();
+
+        // Outstanding insurance fees cover the deficit first
+        assert_eq!(
+            bank.recover_bad_debt_from_insurance_fees().unwrap(),
+            I80F48!(300)
+        );
+        assert_eq!(I80F48::from(bank.unrecovered_bad_debt), I80F48!(700));
+        assert_eq!(
+            I80F48::from(bank.collected_insurance_fees_outstanding),
+            I80F48::ZERO
+        );
+
+        // Recovery never exceeds the deficit
+        assert_eq!(bank.recover_bad_debt(I80F48!(1_000)).unwrap(), I80F48!(700));
+        assert_eq!(I80F48::from(bank.unrecovered_bad_debt), I80F48::ZERO);
+
+        assert_eq!(bank.get_bad_debt_auction_discount(0).unwrap(), I80F48::ZERO);
+        assert_eq!(
+            bank.get_bad_debt_auction_discount(BAD_DEBT_AUCTION_DURATION_SEC * 2)
+                .unwrap(),
+            BAD_DEBT_AUCTION_MAX_DISCOUNT
+        );
+    }
 }
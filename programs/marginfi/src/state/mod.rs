@@ -1,3 +1,7 @@
+pub mod borrow_credential;
+pub mod health;
+pub mod insurance_unstake_request;
 pub mod marginfi_account;
 pub mod marginfi_group;
+pub mod order;
 pub mod price;
@@ -0,0 +1,75 @@
+use super::marginfi_group::WrappedI80F48;
+use crate::assert_struct_size;
+use anchor_lang::prelude::*;
+use fixed::types::I80F48;
+#[cfg(any(feature = "test", feature = "client"))]
+use type_layout::TypeLayout;
+
+assert_struct_size!(Order, 256);
+/// Conditional order repaying the `liab_bank` liability of a marginfi account in full
+/// with its `asset_bank` collateral, executable by any keeper once the trigger is met.
+///
+/// PDA seeds: [ORDER_SEED, marginfi_account, asset_bank, liab_bank, trigger]
+#[account(zero_copy(unsafe))]
+#[repr(C)]
+#[cfg_attr(
+    any(feature = "test", feature = "client"),
+    derive(Debug, PartialEq, Eq, TypeLayout)
+)]
+pub struct Order {
+    pub marginfi_account: Pubkey,
+    /// Authority of the marginfi account when the order was created,
+    /// the order can't be executed once the account authority changes.
+    pub authority: Pubkey,
+    pub asset_bank: Pubkey,
+    pub liab_bank: Pubkey,
+    /// Maintenance health ratio for `OrderTrigger::HealthBelow`,
+    /// asset price denominated in the liability token for the price triggers.
+    pub trigger_value: WrappedI80F48,
+    /// Share of the repaid liability value paid to the keeper in additional collateral.
+    pub keeper_tip: WrappedI80F48,
+    pub trigger: OrderTrigger,
+    pub _padding: [u64; 11],
+}
+
+impl Order {
+    pub fn initialize(
+        &mut self,
+        marginfi_account: Pubkey,
+        authority: Pubkey,
+        asset_bank: Pubkey,
+        liab_bank: Pubkey,
+        trigger: OrderTrigger,
+        trigger_value: WrappedI80F48,
+        keeper_tip: WrappedI80F48,
+    ) {
+        self.marginfi_account = marginfi_account;
+        self.authority = authority;
+        self.asset_bank = asset_bank;
+        self.liab_bank = liab_bank;
+        self.trigger = trigger;
+        self.trigger_value = trigger_value;
+        self.keeper_tip = keeper_tip;
+    }
+
+    /// Check the order trigger against the observed health ratio or price.
+    pub fn is_triggered(&self, observed_value: I80F48) -> bool {
+        let trigger_value: I80F48 = self.trigger_value.into();
+
+        match self.trigger {
+            OrderTrigger::HealthBelow | OrderTrigger::PriceBelow => observed_value < trigger_value,
+            OrderTrigger::PriceAbove => observed_value > trigger_value,
+        }
+    }
+}
+
+#[repr(u64)]
+#[derive(Copy, Clone, Debug, AnchorSerialize, AnchorDeserialize, PartialEq, Eq)]
+pub enum OrderTrigger {
+    /// Maintenance health ratio `(assets - liabilities) / assets` drops below the trigger value.
+    HealthBelow,
+    /// Asset price in liability tokens drops below the trigger value (e.g. stop-loss on a long).
+    PriceBelow,
+    /// Asset price in liability tokens rises above the trigger value (e.g. take-profit on a long).
+    PriceAbove,
+}
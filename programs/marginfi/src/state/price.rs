@@ -126,6 +126,52 @@ impl OraclePriceFeedAdapter {
     }
 }
 
+#[cfg(feature = "client")]
+impl OraclePriceFeedAdapter {
+    /// Copy of the price feed with prices and confidence intervals scaled by `factor`,
+    /// used to simulate price moves off-chain.
+    pub fn with_price_factor(&self, factor: f64) -> Self {
+        match self {
+            OraclePriceFeedAdapter::PythEma(feed) => {
+                OraclePriceFeedAdapter::PythEma(PythEmaPriceFeed::new(
+                    scale_pyth_price(&feed.ema_price, factor),
+                    scale_pyth_price(&feed.price, factor),
+                ))
+            }
+            OraclePriceFeedAdapter::SwitchboardV2(feed) => {
+                let mut aggregator_account = feed.aggregator_account.clone();
+                aggregator_account.latest_confirmed_round_result = scale_switchboard_decimal(
+                    aggregator_account.latest_confirmed_round_result,
+                    factor,
+                );
+                aggregator_account.latest_confirmed_round_std_deviation = scale_switchboard_decimal(
+                    aggregator_account.latest_confirmed_round_std_deviation,
+                    factor,
+                );
+
+                OraclePriceFeedAdapter::SwitchboardV2(SwitchboardV2PriceFeed { aggregator_account })
+            }
+        }
+    }
+}
+
+#[cfg(feature = "client")]
+fn scale_pyth_price(price: &Price, factor: f64) -> Price {
+    Price {
+        price: (price.price as f64 * factor).round() as i64,
+        conf: (price.conf as f64 * factor).round() as u64,
+        ..*price
+    }
+}
+
+#[cfg(feature = "client")]
+fn scale_switchboard_decimal(decimal: SwitchboardDecimal, factor: f64) -> SwitchboardDecimal {
+    SwitchboardDecimal {
+        mantissa: (decimal.mantissa as f64 * factor).round() as i128,
+        ..decimal
+    }
+}
+
 #[cfg_attr(feature = "client", derive(Clone, Debug))]
 pub struct PythEmaPriceFeed {
     ema_price: Box<Price>,
@@ -133,6 +179,13 @@ pub struct PythEmaPriceFeed {
 }
 
 impl PythEmaPriceFeed {
+    pub fn new(ema_price: Price, price: Price) -> Self {
+        Self {
+            ema_price: Box::new(ema_price),
+            price: Box::new(price),
+        }
+    }
+
     pub fn load_checked(ai: &AccountInfo, current_time: i64, max_age: u64) -> MarginfiResult<Self> {
         let price_feed = load_pyth_price_feed(ai)?;
         let ema_price = price_feed
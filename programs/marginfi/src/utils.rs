@@ -1,5 +1,8 @@
-use crate::{bank_authority_seed, bank_seed, state::marginfi_group::BankVaultType};
-use anchor_lang::prelude::Pubkey;
+use crate::{
+    bank_authority_seed, bank_seed, constants::BORROW_CREDENTIAL_SEED, prelude::MarginfiError,
+    state::marginfi_group::BankVaultType, MarginfiResult,
+};
+use anchor_lang::prelude::{AccountInfo, Pubkey};
 use fixed::types::I80F48;
 
 pub fn find_bank_vault_pda(bank_pk: &Pubkey, vault_type: BankVaultType) -> (Pubkey, u8) {
@@ -10,6 +13,33 @@ pub fn find_bank_vault_authority_pda(bank_pk: &Pubkey, vault_type: BankVaultType
     Pubkey::find_program_address(bank_authority_seed!(vault_type, bank_pk), &crate::id())
 }
 
+pub fn find_borrow_credential_pda(bank_pk: &Pubkey, marginfi_account_pk: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            BORROW_CREDENTIAL_SEED.as_bytes(),
+            bank_pk.as_ref(),
+            marginfi_account_pk.as_ref(),
+        ],
+        &crate::id(),
+    )
+}
+
+/// Find the oracle account of `bank_pk` in `remaining_ais`,
+/// laid out as (bank, oracle) pairs for the account health check.
+pub fn find_bank_oracle_ais<'a, 'info>(
+    bank_pk: &Pubkey,
+    remaining_ais: &'a [AccountInfo<'info>],
+) -> MarginfiResult<&'a [AccountInfo<'info>]> {
+    let bank_index = remaining_ais
+        .iter()
+        .position(|ai| ai.key == bank_pk)
+        .ok_or(MarginfiError::MissingPythOrBankAccount)?;
+
+    Ok(remaining_ais
+        .get(bank_index + 1..bank_index + 2)
+        .ok_or(MarginfiError::MissingPythAccount)?)
+}
+
 pub trait NumTraitsWithTolerance<T> {
     fn is_zero_with_tolerance(&self, t: T) -> bool;
     fn is_positive_with_tolerance(&self, t: T) -> bool;
@@ -1,19 +1,24 @@
-use anchor_lang::prelude::{AnchorError, Clock};
+use anchor_lang::prelude::{AccountMeta, AnchorError, Clock};
 use anchor_lang::{InstructionData, ToAccountMetas};
 use fixed::types::I80F48;
 use fixed_macro::types::I80F48;
+use fixtures::flashloan_cpi;
 use fixtures::prelude::*;
 use fixtures::{assert_custom_error, assert_eq_noise, native};
 use marginfi::constants::{
-    EMISSIONS_FLAG_BORROW_ACTIVE, EMISSIONS_FLAG_LENDING_ACTIVE, MIN_EMISSIONS_START_TIME,
+    EMISSIONS_FLAG_BORROW_ACTIVE, EMISSIONS_FLAG_LENDING_ACTIVE, MAX_ORDER_KEEPER_TIP,
+    MIN_EMISSIONS_START_TIME,
 };
 use marginfi::state::marginfi_account::{
-    BankAccountWrapper, DISABLED_FLAG, FLASHLOAN_ENABLED_FLAG, IN_FLASHLOAN_FLAG,
-    TRANSFER_AUTHORITY_ALLOWED_FLAG,
+    BankAccountWrapper, DISABLED_FLAG, FLASHLOAN_ENABLED_FLAG, IN_FLASHLOAN_CPI_FLAG,
+    IN_FLASHLOAN_FLAG, TRANSFER_AUTHORITY_ALLOWED_FLAG,
 };
 use marginfi::state::{
     marginfi_account::MarginfiAccount,
-    marginfi_group::{Bank, BankConfig, BankConfigOpt, BankVaultType},
+    marginfi_group::{
+        Bank, BankBorrowMode, BankConfig, BankConfigOpt, BankVaultType, InterestRateConfig,
+    },
+    order::OrderTrigger,
 };
 use marginfi::{assert_eq_with_tolerance, prelude::*};
 use pretty_assertions::assert_eq;
@@ -164,6 +169,49 @@ async fn marginfi_account_deposit_failure_capacity_exceeded() -> anyhow::Result<
     Ok(())
 }
 
+#[tokio::test]
+async fn marginfi_account_deposit_failure_value_capacity_exceeded() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings {
+        banks: vec![TestBankSetting {
+            mint: BankMint::SOL,
+            config: Some(BankConfig {
+                total_deposit_value_limit: 1_000,
+                ..*DEFAULT_SOL_TEST_BANK_CONFIG
+            }),
+        }],
+        group_config: Some(GroupConfig { admin: None }),
+    }))
+    .await;
+
+    let sol_bank = test_f.get_bank(&BankMint::SOL);
+
+    // Fund user account
+    let user_mfi_account_f = test_f.create_marginfi_account().await;
+    let user_token_account = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+
+    // Make unlawful deposit, 100 SOL @ $10 is at the $1000 cap
+    let res = user_mfi_account_f
+        .try_bank_deposit(user_token_account.key, sol_bank, 100)
+        .await;
+
+    assert_custom_error!(
+        res.unwrap_err(),
+        MarginfiError::BankAssetValueCapacityExceeded
+    );
+
+    // Make lawful deposit
+    let res = user_mfi_account_f
+        .try_bank_deposit(user_token_account.key, sol_bank, 99)
+        .await;
+
+    assert!(res.is_ok());
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn marginfi_account_withdraw_success() -> anyhow::Result<()> {
     let mut test_f = TestFixture::new(Some(TestSettings {
@@ -322,202 +370,485 @@ async fn marginfi_account_withdraw_failure_borrow_limit() -> anyhow::Result<()>
 }
 
 #[tokio::test]
-async fn marginfi_account_withdraw_all_success() -> anyhow::Result<()> {
-    let mut test_f = TestFixture::new(Some(TestSettings {
-        banks: vec![TestBankSetting {
-            mint: BankMint::USDC,
-            ..TestBankSetting::default()
-        }],
-        ..TestSettings::default()
-    }))
-    .await;
+async fn marginfi_account_borrow_failure_borrow_value_limit() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
 
-    let marginfi_account_f = test_f.create_marginfi_account().await;
+    let usdc_bank = test_f.get_bank(&BankMint::USDC);
+    let sol_bank = test_f.get_bank(&BankMint::SOL);
 
-    let owner = test_f.payer();
-    let token_account_f =
-        TokenAccountFixture::new(test_f.context.clone(), &test_f.usdc_mint.key, &owner).await;
-    test_f.usdc_mint.mint_to(&token_account_f.key, 1_000).await;
+    sol_bank
+        .update_config(BankConfigOpt {
+            total_borrow_value_limit: Some(1_000),
+            ..Default::default()
+        })
+        .await?;
 
-    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
+    let lender = test_f.create_marginfi_account().await;
+    let lender_sol_account = sol_bank.mint.create_token_account_and_mint_to(1_000).await;
 
-    marginfi_account_f
-        .try_bank_deposit(token_account_f.key, usdc_bank_f, 1_000)
-        .await
-        .unwrap();
+    lender
+        .try_bank_deposit(lender_sol_account.key, sol_bank, 1_000)
+        .await?;
 
-    let res = marginfi_account_f
-        .try_bank_withdraw(token_account_f.key, usdc_bank_f, 0, Some(true))
+    let borrower = test_f.create_marginfi_account().await;
+    let borrower_usdc_account = usdc_bank
+        .mint
+        .create_token_account_and_mint_to(100_000)
         .await;
+    let borrower_sol_account = sol_bank.mint.create_token_account_and_mint_to(0).await;
 
-    assert!(res.is_ok());
-
-    let marginfi_account = marginfi_account_f.load().await;
+    borrower
+        .try_bank_deposit(borrower_usdc_account.key, usdc_bank, 100_000)
+        .await?;
 
-    // Check balance is correct
-    assert!(marginfi_account
-        .lending_account
-        .get_balance(&usdc_bank_f.key)
-        .is_none());
+    // 100 SOL @ $10 is at the $1000 cap
+    let res = borrower
+        .try_bank_borrow(borrower_sol_account.key, sol_bank, 100)
+        .await;
 
-    assert_eq!(
-        marginfi_account
-            .lending_account
-            .get_active_balances_iter()
-            .collect::<Vec<_>>()
-            .len(),
-        0
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err(),
+        MarginfiError::BankLiabilityValueCapacityExceeded
     );
 
+    let res = borrower
+        .try_bank_borrow(borrower_sol_account.key, sol_bank, 99)
+        .await;
+
+    assert!(res.is_ok());
+
     Ok(())
 }
 
 #[tokio::test]
-async fn marginfi_account_repay_success() -> anyhow::Result<()> {
+async fn marginfi_account_borrow_permissioned() -> anyhow::Result<()> {
     let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
 
     let usdc_bank = test_f.get_bank(&BankMint::USDC);
     let sol_bank = test_f.get_bank(&BankMint::SOL);
 
-    // Fund SOL lender
-    let lender_mfi_account_f = test_f.create_marginfi_account().await;
-    let lender_token_account_sol = test_f
-        .sol_mint
-        .create_token_account_and_mint_to(1_000)
+    sol_bank
+        .update_config(BankConfigOpt {
+            borrow_mode: Some(BankBorrowMode::Permissioned),
+            ..Default::default()
+        })
+        .await?;
+
+    let lender = test_f.create_marginfi_account().await;
+    let lender_sol_account = sol_bank.mint.create_token_account_and_mint_to(1_000).await;
+
+    lender
+        .try_bank_deposit(lender_sol_account.key, sol_bank, 1_000)
+        .await?;
+
+    let borrower = test_f.create_marginfi_account().await;
+    let borrower_usdc_account = usdc_bank
+        .mint
+        .create_token_account_and_mint_to(10_000)
         .await;
-    lender_mfi_account_f
-        .try_bank_deposit(lender_token_account_sol.key, sol_bank, 1_000)
+    let borrower_sol_account = sol_bank.mint.create_token_account_and_mint_to(0).await;
+
+    borrower
+        .try_bank_deposit(borrower_usdc_account.key, usdc_bank, 10_000)
         .await?;
 
-    // Fund SOL borrower
-    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
-    let borrower_token_account_f_usdc = test_f
-        .usdc_mint
-        .create_token_account_and_mint_to(1_000)
+    // No credential issued
+    let res = borrower
+        .try_bank_borrow(borrower_sol_account.key, sol_bank, 10)
         .await;
-    let borrower_token_account_f_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
-    borrower_mfi_account_f
-        .try_bank_deposit(borrower_token_account_f_usdc.key, usdc_bank, 1_000)
+
+    assert!(res.is_err());
+
+    sol_bank
+        .try_create_borrow_credential(borrower.key, native!(50, "SOL"))
         .await?;
 
-    // Borrow SOL
-    borrower_mfi_account_f
-        .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank, 99)
-        .await
-        .unwrap();
+    let res = borrower
+        .try_bank_borrow(borrower_sol_account.key, sol_bank, 51)
+        .await;
 
-    let res = borrower_mfi_account_f
-        .try_bank_repay(borrower_token_account_f_sol.key, sol_bank, 80, None)
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::AccountBorrowLimitExceeded);
+
+    let res = borrower
+        .try_bank_borrow(borrower_sol_account.key, sol_bank, 50)
         .await;
 
     assert!(res.is_ok());
 
-    // Check token balances are correct
-    assert_eq!(
-        borrower_token_account_f_usdc.balance().await,
-        native!(0, "USDC")
-    );
+    // Revoked credential
+    sol_bank.try_revoke_borrow_credential(borrower.key).await?;
 
-    assert_eq!(
-        borrower_token_account_f_sol.balance().await,
-        native!(19, "SOL")
-    );
+    let res = borrower
+        .try_bank_borrow(borrower_sol_account.key, sol_bank, 1)
+        .await;
 
-    // TODO: check health is sane
+    assert!(res.is_err());
 
     Ok(())
 }
 
 #[tokio::test]
-async fn marginfi_account_repay_failure_repaying_too_much() -> anyhow::Result<()> {
-    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+async fn marginfi_account_liquidation_permissioned_liability() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings {
+        banks: vec![
+            TestBankSetting {
+                mint: BankMint::USDC,
+                ..TestBankSetting::default()
+            },
+            TestBankSetting {
+                mint: BankMint::SOL,
+                config: Some(BankConfig {
+                    asset_weight_init: I80F48!(1).into(),
+                    asset_weight_maint: I80F48!(1).into(),
+                    ..*DEFAULT_SOL_TEST_BANK_CONFIG
+                }),
+            },
+        ],
+        group_config: Some(GroupConfig { admin: None }),
+    }))
+    .await;
 
-    let usdc_bank = test_f.get_bank(&BankMint::USDC);
-    let sol_bank = test_f.get_bank(&BankMint::SOL);
+    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
+    let sol_bank_f = test_f.get_bank(&BankMint::SOL);
+
+    usdc_bank_f
+        .update_config(BankConfigOpt {
+            borrow_mode: Some(BankBorrowMode::Permissioned),
+            ..Default::default()
+        })
+        .await?;
 
-    // Fund SOL lender
     let lender_mfi_account_f = test_f.create_marginfi_account().await;
-    let lender_token_account_sol = test_f
-        .sol_mint
-        .create_token_account_and_mint_to(1_000)
+    let lender_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(2_000)
         .await;
     lender_mfi_account_f
-        .try_bank_deposit(lender_token_account_sol.key, sol_bank, 1_000)
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank_f, 2_000)
         .await?;
 
-    // Fund SOL borrower
     let borrower_mfi_account_f = test_f.create_marginfi_account().await;
-    let borrower_token_account_f_usdc = test_f
-        .usdc_mint
-        .create_token_account_and_mint_to(1_000)
-        .await;
-    let borrower_token_account_f_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+    let borrower_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let borrower_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
     borrower_mfi_account_f
-        .try_bank_deposit(borrower_token_account_f_usdc.key, usdc_bank, 1_000)
+        .try_bank_deposit(borrower_token_account_sol.key, sol_bank_f, 100)
+        .await?;
+    usdc_bank_f
+        .try_create_borrow_credential(borrower_mfi_account_f.key, u64::MAX)
         .await?;
-
-    // Borrow SOL
     borrower_mfi_account_f
-        .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank, 99)
-        .await
-        .unwrap();
+        .try_bank_borrow(borrower_token_account_usdc.key, usdc_bank_f, 999)
+        .await?;
 
-    let res = borrower_mfi_account_f
-        .try_bank_repay(borrower_token_account_f_sol.key, sol_bank, 110, None)
+    // Liquidator has no USDC, paying off the liability opens a USDC liability
+    let liquidator_mfi_account_f = test_f.create_marginfi_account().await;
+    let liquidator_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    liquidator_mfi_account_f
+        .try_bank_deposit(liquidator_token_account_sol.key, sol_bank_f, 100)
+        .await?;
+
+    sol_bank_f
+        .update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.25).into()),
+            asset_weight_maint: Some(I80F48!(0.5).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    // No credential issued
+    let res = liquidator_mfi_account_f
+        .try_liquidate(&borrower_mfi_account_f, sol_bank_f, 1, usdc_bank_f)
         .await;
 
     assert!(res.is_err());
 
-    assert_custom_error!(res.unwrap_err(), MarginfiError::OperationRepayOnly);
+    // 1 SOL pays off 9.75 USDC, above the credential borrow limit
+    usdc_bank_f
+        .try_create_borrow_credential(liquidator_mfi_account_f.key, native!(5, "USDC"))
+        .await?;
+
+    let res = liquidator_mfi_account_f
+        .try_liquidate(&borrower_mfi_account_f, sol_bank_f, 1, usdc_bank_f)
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::AccountBorrowLimitExceeded);
+
+    let res = liquidator_mfi_account_f
+        .try_liquidate(&borrower_mfi_account_f, sol_bank_f, 0.5, usdc_bank_f)
+        .await;
+
+    assert!(res.is_ok());
 
     Ok(())
 }
 
 #[tokio::test]
-async fn marginfi_account_repay_all_success() -> anyhow::Result<()> {
-    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+async fn marginfi_account_liquidation_borrow_value_limit() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings {
+        banks: vec![
+            TestBankSetting {
+                mint: BankMint::USDC,
+                ..TestBankSetting::default()
+            },
+            TestBankSetting {
+                mint: BankMint::SOL,
+                config: Some(BankConfig {
+                    asset_weight_init: I80F48!(1).into(),
+                    asset_weight_maint: I80F48!(1).into(),
+                    ..*DEFAULT_SOL_TEST_BANK_CONFIG
+                }),
+            },
+        ],
+        group_config: Some(GroupConfig { admin: None }),
+    }))
+    .await;
 
-    let usdc_bank = test_f.get_bank(&BankMint::USDC);
+    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
     let sol_bank_f = test_f.get_bank(&BankMint::SOL);
 
-    // Fund SOL lender
     let lender_mfi_account_f = test_f.create_marginfi_account().await;
-    let lender_token_account_sol = test_f
-        .sol_mint
-        .create_token_account_and_mint_to(1_000)
+    let lender_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(2_000)
         .await;
     lender_mfi_account_f
-        .try_bank_deposit(lender_token_account_sol.key, sol_bank_f, 1_000)
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank_f, 2_000)
         .await?;
 
-    // Fund SOL borrower
     let borrower_mfi_account_f = test_f.create_marginfi_account().await;
-    let borrower_token_account_f_usdc = test_f
-        .usdc_mint
+    let borrower_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let borrower_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_sol.key, sol_bank_f, 100)
+        .await?;
+    borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_usdc.key, usdc_bank_f, 999)
+        .await?;
+
+    let liquidator_mfi_account_f = test_f.create_marginfi_account().await;
+    let liquidator_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    liquidator_mfi_account_f
+        .try_bank_deposit(liquidator_token_account_sol.key, sol_bank_f, 100)
+        .await?;
+
+    // Total USDC borrows are at the cap
+    usdc_bank_f
+        .update_config(BankConfigOpt {
+            total_borrow_value_limit: Some(999),
+            ..Default::default()
+        })
+        .await?;
+    sol_bank_f
+        .update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.25).into()),
+            asset_weight_maint: Some(I80F48!(0.5).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    // Liquidator has no USDC, the 9.75 USDC liability opened exceeds the 9.5 USDC paid off
+    let res = liquidator_mfi_account_f
+        .try_liquidate(&borrower_mfi_account_f, sol_bank_f, 1, usdc_bank_f)
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err(),
+        MarginfiError::BankLiabilityValueCapacityExceeded
+    );
+
+    // Paying off the liability with USDC deposits lowers total borrows, despite the bank being at the cap
+    let liquidator_token_account_usdc =
+        test_f.usdc_mint.create_token_account_and_mint_to(100).await;
+    liquidator_mfi_account_f
+        .try_bank_deposit(liquidator_token_account_usdc.key, usdc_bank_f, 100)
+        .await?;
+
+    let res = liquidator_mfi_account_f
+        .try_liquidate(&borrower_mfi_account_f, sol_bank_f, 1, usdc_bank_f)
+        .await;
+
+    assert!(res.is_ok());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_account_withdraw_failure_outflow_limit() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::USDC);
+
+    usdc_bank
+        .update_config(BankConfigOpt {
+            outflow_limit_window: Some(3600),
+            outflow_limit: Some(native!(1000, "USDC")),
+            ..Default::default()
+        })
+        .await?;
+
+    let marginfi_account_f = test_f.create_marginfi_account().await;
+    let usdc_token_account = usdc_bank
+        .mint
+        .create_token_account_and_mint_to(10_000)
+        .await;
+
+    marginfi_account_f
+        .try_bank_deposit(usdc_token_account.key, usdc_bank, 5_000)
+        .await?;
+
+    let res = marginfi_account_f
+        .try_bank_withdraw(usdc_token_account.key, usdc_bank, 1_001, None)
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::BankOutflowLimitExceeded);
+
+    marginfi_account_f
+        .try_bank_withdraw(usdc_token_account.key, usdc_bank, 1_000, None)
+        .await?;
+
+    let res = marginfi_account_f
+        .try_bank_withdraw(usdc_token_account.key, usdc_bank, 1, None)
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::BankOutflowLimitExceeded);
+
+    // Inflows offset the accumulated outflow
+    marginfi_account_f
+        .try_bank_deposit(usdc_token_account.key, usdc_bank, 500)
+        .await?;
+
+    marginfi_account_f
+        .try_bank_withdraw(usdc_token_account.key, usdc_bank, 500, None)
+        .await?;
+
+    let res = marginfi_account_f
+        .try_bank_withdraw(usdc_token_account.key, usdc_bank, 2, None)
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::BankOutflowLimitExceeded);
+
+    // Admin override clears the accumulated outflow
+    usdc_bank.try_reset_outflow().await?;
+
+    let res = marginfi_account_f
+        .try_bank_withdraw(usdc_token_account.key, usdc_bank, 999, None)
+        .await;
+
+    assert!(res.is_ok());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_account_withdraw_failure_outflow_value_limit() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let sol_bank = test_f.get_bank(&BankMint::SOL);
+
+    // $1000 per window, SOL @ $10
+    sol_bank
+        .update_config(BankConfigOpt {
+            outflow_limit_window: Some(3600),
+            outflow_value_limit: Some(1_000),
+            ..Default::default()
+        })
+        .await?;
+
+    let marginfi_account_f = test_f.create_marginfi_account().await;
+    let sol_token_account = test_f
+        .sol_mint
         .create_token_account_and_mint_to(1_000)
         .await;
-    let borrower_token_account_f_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
-    borrower_mfi_account_f
-        .try_bank_deposit(borrower_token_account_f_usdc.key, usdc_bank, 1_000)
+
+    marginfi_account_f
+        .try_bank_deposit(sol_token_account.key, sol_bank, 500)
         .await?;
 
-    // Borrow SOL
-    borrower_mfi_account_f
-        .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank_f, 99)
+    let res = marginfi_account_f
+        .try_bank_withdraw(sol_token_account.key, sol_bank, 101, None)
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err(),
+        MarginfiError::BankOutflowValueLimitExceeded
+    );
+
+    marginfi_account_f
+        .try_bank_withdraw(sol_token_account.key, sol_bank, 100, None)
+        .await?;
+
+    // Withdrawing in full takes the bank oracle from the pair of the closed balance
+    let res = marginfi_account_f
+        .try_bank_withdraw(sol_token_account.key, sol_bank, 0, Some(true))
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err(),
+        MarginfiError::BankOutflowValueLimitExceeded
+    );
+
+    sol_bank.try_reset_outflow().await?;
+
+    let small_marginfi_account_f = test_f.create_marginfi_account().await;
+    small_marginfi_account_f
+        .try_bank_deposit(sol_token_account.key, sol_bank, 50)
+        .await?;
+
+    let res = small_marginfi_account_f
+        .try_bank_withdraw(sol_token_account.key, sol_bank, 0, Some(true))
+        .await;
+
+    assert!(res.is_ok());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_account_withdraw_all_success() -> anyhow::Result<()> {
+    let mut test_f = TestFixture::new(Some(TestSettings {
+        banks: vec![TestBankSetting {
+            mint: BankMint::USDC,
+            ..TestBankSetting::default()
+        }],
+        ..TestSettings::default()
+    }))
+    .await;
+
+    let marginfi_account_f = test_f.create_marginfi_account().await;
+
+    let owner = test_f.payer();
+    let token_account_f =
+        TokenAccountFixture::new(test_f.context.clone(), &test_f.usdc_mint.key, &owner).await;
+    test_f.usdc_mint.mint_to(&token_account_f.key, 1_000).await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
+
+    marginfi_account_f
+        .try_bank_deposit(token_account_f.key, usdc_bank_f, 1_000)
         .await
         .unwrap();
 
-    let res = borrower_mfi_account_f
-        .try_bank_repay(borrower_token_account_f_sol.key, sol_bank_f, 0, Some(true))
+    let res = marginfi_account_f
+        .try_bank_withdraw(token_account_f.key, usdc_bank_f, 0, Some(true))
         .await;
 
     assert!(res.is_ok());
 
-    let marginfi_account = borrower_mfi_account_f.load().await;
+    let marginfi_account = marginfi_account_f.load().await;
 
+    // Check balance is correct
     assert!(marginfi_account
         .lending_account
-        .get_balance(&sol_bank_f.key)
+        .get_balance(&usdc_bank_f.key)
         .is_none());
 
     assert_eq!(
@@ -526,14 +857,14 @@ async fn marginfi_account_repay_all_success() -> anyhow::Result<()> {
             .get_active_balances_iter()
             .collect::<Vec<_>>()
             .len(),
-        1
+        0
     );
 
     Ok(())
 }
 
 #[tokio::test]
-async fn marginfi_account_borrow_success() -> anyhow::Result<()> {
+async fn marginfi_account_repay_success() -> anyhow::Result<()> {
     let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
 
     let usdc_bank = test_f.get_bank(&BankMint::USDC);
@@ -561,8 +892,13 @@ async fn marginfi_account_borrow_success() -> anyhow::Result<()> {
         .await?;
 
     // Borrow SOL
-    let res = borrower_mfi_account_f
+    borrower_mfi_account_f
         .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank, 99)
+        .await
+        .unwrap();
+
+    let res = borrower_mfi_account_f
+        .try_bank_repay(borrower_token_account_f_sol.key, sol_bank, 80, None)
         .await;
 
     assert!(res.is_ok());
@@ -575,7 +911,7 @@ async fn marginfi_account_borrow_success() -> anyhow::Result<()> {
 
     assert_eq!(
         borrower_token_account_f_sol.balance().await,
-        native!(99, "SOL")
+        native!(19, "SOL")
     );
 
     // TODO: check health is sane
@@ -584,8 +920,8 @@ async fn marginfi_account_borrow_success() -> anyhow::Result<()> {
 }
 
 #[tokio::test]
-async fn marginfi_account_borrow_success_swb() -> anyhow::Result<()> {
-    let test_f = TestFixture::new(Some(TestSettings::all_banks_swb_payer_not_admin())).await;
+async fn marginfi_account_repay_failure_repaying_too_much() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
 
     let usdc_bank = test_f.get_bank(&BankMint::USDC);
     let sol_bank = test_f.get_bank(&BankMint::SOL);
@@ -612,77 +948,322 @@ async fn marginfi_account_borrow_success_swb() -> anyhow::Result<()> {
         .await?;
 
     // Borrow SOL
-    let res = borrower_mfi_account_f
+    borrower_mfi_account_f
         .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank, 99)
-        .await;
-
-    assert!(res.is_ok());
+        .await
+        .unwrap();
 
-    // Check token balances are correct
-    assert_eq!(
-        borrower_token_account_f_usdc.balance().await,
-        native!(0, "USDC")
-    );
+    let res = borrower_mfi_account_f
+        .try_bank_repay(borrower_token_account_f_sol.key, sol_bank, 110, None)
+        .await;
 
-    assert_eq!(
-        borrower_token_account_f_sol.balance().await,
-        native!(99, "SOL")
-    );
+    assert!(res.is_err());
 
-    // TODO: check health is sane
+    assert_custom_error!(res.unwrap_err(), MarginfiError::OperationRepayOnly);
 
     Ok(())
 }
 
 #[tokio::test]
-async fn marginfi_account_borrow_failure_not_enough_collateral() -> anyhow::Result<()> {
-    // Setup test executor with non-admin payer
+async fn marginfi_account_repay_all_success() -> anyhow::Result<()> {
     let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
 
     let usdc_bank = test_f.get_bank(&BankMint::USDC);
-    let sol_bank = test_f.get_bank(&BankMint::SOL);
+    let sol_bank_f = test_f.get_bank(&BankMint::SOL);
 
     // Fund SOL lender
     let lender_mfi_account_f = test_f.create_marginfi_account().await;
-    let lender_token_account_f_sol = test_f
+    let lender_token_account_sol = test_f
         .sol_mint
         .create_token_account_and_mint_to(1_000)
         .await;
     lender_mfi_account_f
-        .try_bank_deposit(lender_token_account_f_sol.key, sol_bank, 1_000)
+        .try_bank_deposit(lender_token_account_sol.key, sol_bank_f, 1_000)
         .await?;
 
     // Fund SOL borrower
     let borrower_mfi_account_f = test_f.create_marginfi_account().await;
-    let borrower_token_account_f_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
     let borrower_token_account_f_usdc = test_f
         .usdc_mint
         .create_token_account_and_mint_to(1_000)
         .await;
+    let borrower_token_account_f_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
     borrower_mfi_account_f
         .try_bank_deposit(borrower_token_account_f_usdc.key, usdc_bank, 1_000)
         .await?;
 
     // Borrow SOL
-    let res = borrower_mfi_account_f
-        .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank, 101)
-        .await;
-
-    assert_custom_error!(res.unwrap_err(), MarginfiError::BadAccountHealth);
+    borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank_f, 99)
+        .await
+        .unwrap();
 
     let res = borrower_mfi_account_f
-        .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank, 100)
+        .try_bank_repay(borrower_token_account_f_sol.key, sol_bank_f, 0, Some(true))
         .await;
 
     assert!(res.is_ok());
 
-    Ok(())
-}
+    let marginfi_account = borrower_mfi_account_f.load().await;
 
-#[tokio::test]
-async fn marginfi_account_liquidation_success() -> anyhow::Result<()> {
-    let test_f = TestFixture::new(Some(TestSettings {
-        banks: vec![
+    assert!(marginfi_account
+        .lending_account
+        .get_balance(&sol_bank_f.key)
+        .is_none());
+
+    assert_eq!(
+        marginfi_account
+            .lending_account
+            .get_active_balances_iter()
+            .collect::<Vec<_>>()
+            .len(),
+        1
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_account_borrow_success() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::USDC);
+    let sol_bank = test_f.get_bank(&BankMint::SOL);
+
+    // Fund SOL lender
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_sol.key, sol_bank, 1_000)
+        .await?;
+
+    // Fund SOL borrower
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_f_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    let borrower_token_account_f_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_f_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    // Borrow SOL
+    let res = borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank, 99)
+        .await;
+
+    assert!(res.is_ok());
+
+    // Check token balances are correct
+    assert_eq!(
+        borrower_token_account_f_usdc.balance().await,
+        native!(0, "USDC")
+    );
+
+    assert_eq!(
+        borrower_token_account_f_sol.balance().await,
+        native!(99, "SOL")
+    );
+
+    // TODO: check health is sane
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_account_borrow_success_swb() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_swb_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::USDC);
+    let sol_bank = test_f.get_bank(&BankMint::SOL);
+
+    // Fund SOL lender
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_sol.key, sol_bank, 1_000)
+        .await?;
+
+    // Fund SOL borrower
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_f_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    let borrower_token_account_f_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_f_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    // Borrow SOL
+    let res = borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank, 99)
+        .await;
+
+    assert!(res.is_ok());
+
+    // Check token balances are correct
+    assert_eq!(
+        borrower_token_account_f_usdc.balance().await,
+        native!(0, "USDC")
+    );
+
+    assert_eq!(
+        borrower_token_account_f_sol.balance().await,
+        native!(99, "SOL")
+    );
+
+    // TODO: check health is sane
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_account_borrow_failure_not_enough_collateral() -> anyhow::Result<()> {
+    // Setup test executor with non-admin payer
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::USDC);
+    let sol_bank = test_f.get_bank(&BankMint::SOL);
+
+    // Fund SOL lender
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_f_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_f_sol.key, sol_bank, 1_000)
+        .await?;
+
+    // Fund SOL borrower
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_f_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+    let borrower_token_account_f_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_f_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    // Borrow SOL
+    let res = borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank, 101)
+        .await;
+
+    assert_custom_error!(res.unwrap_err(), MarginfiError::BadAccountHealth);
+
+    let res = borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank, 100)
+        .await;
+
+    assert!(res.is_ok());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_account_liquidation_ignores_outflow_limits() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings {
+        banks: vec![
+            TestBankSetting {
+                mint: BankMint::USDC,
+                ..TestBankSetting::default()
+            },
+            TestBankSetting {
+                mint: BankMint::SOL,
+                config: Some(BankConfig {
+                    asset_weight_init: I80F48!(1).into(),
+                    asset_weight_maint: I80F48!(1).into(),
+                    ..*DEFAULT_SOL_TEST_BANK_CONFIG
+                }),
+            },
+        ],
+        group_config: Some(GroupConfig { admin: None }),
+    }))
+    .await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
+    let sol_bank_f = test_f.get_bank(&BankMint::SOL);
+
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(2_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank_f, 2_000)
+        .await?;
+
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let borrower_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_sol.key, sol_bank_f, 100)
+        .await?;
+    borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_usdc.key, usdc_bank_f, 999)
+        .await?;
+
+    let liquidator_mfi_account_f = test_f.create_marginfi_account().await;
+    let liquidator_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    liquidator_mfi_account_f
+        .try_bank_deposit(liquidator_token_account_sol.key, sol_bank_f, 100)
+        .await?;
+
+    for bank_f in [usdc_bank_f, sol_bank_f] {
+        bank_f
+            .update_config(BankConfigOpt {
+                outflow_limit_window: Some(3600),
+                outflow_limit: Some(1),
+                ..Default::default()
+            })
+            .await?;
+    }
+    sol_bank_f
+        .update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.25).into()),
+            asset_weight_maint: Some(I80F48!(0.5).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    liquidator_mfi_account_f
+        .try_liquidate(&borrower_mfi_account_f, sol_bank_f, 1, usdc_bank_f)
+        .await?;
+
+    assert_eq!(
+        I80F48::from(usdc_bank_f.load().await.outflow_window_amount),
+        I80F48::ZERO
+    );
+    assert_eq!(
+        I80F48::from(sol_bank_f.load().await.outflow_window_amount),
+        I80F48::ZERO
+    );
+
+    // Withdrawing the seized collateral is subject to the outflow limit
+    let res = liquidator_mfi_account_f
+        .try_bank_withdraw(liquidator_token_account_sol.key, sol_bank_f, 1, None)
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::BankOutflowLimitExceeded);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_account_liquidation_success() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings {
+        banks: vec![
             TestBankSetting {
                 mint: BankMint::USDC,
                 ..TestBankSetting::default()
@@ -1783,6 +2364,11 @@ async fn account_flags() -> anyhow::Result<()> {
 // 7. Flashloan fails because of invalid `end_flashloan` ix order
 // 8. Flashloan fails because `end_flashloan` ix is for another account
 // 9. Flashloan fails because account is already in a flashloan
+// 10. Flashloan fee is charged on the amount borrowed and must be covered by the account
+// 11. Flashloan fee is charged on every borrow, including amounts repaid and borrowed again
+// 12. Flashloan via CPI from an allowed program success
+// 13. Flashloan via CPI fails because the program is not allowed
+// 14. Flashloan via CPI fails when used outside of the top-level ix that started it
 
 #[tokio::test]
 async fn flashloan_success_1op() -> anyhow::Result<()> {
@@ -2304,104 +2890,645 @@ async fn flashloan_fail_already_in_flashloan() -> anyhow::Result<()> {
 }
 
 #[tokio::test]
-async fn lending_account_close_balance() -> anyhow::Result<()> {
-    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+async fn flashloan_fee_charged() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings {
+        group_config: Some(GroupConfig { admin: None }),
+        banks: vec![
+            TestBankSetting {
+                mint: BankMint::USDC,
+                config: None,
+            },
+            TestBankSetting {
+                mint: BankMint::SOL,
+                config: Some(BankConfig {
+                    interest_rate_config: InterestRateConfig {
+                        insurance_flashloan_fee: I80F48!(0.005).into(),
+                        protocol_flashloan_fee: I80F48!(0.01).into(),
+                        ..*DEFAULT_TEST_BANK_INTEREST_RATE_CONFIG
+                    },
+                    ..*DEFAULT_SOL_TEST_BANK_CONFIG
+                }),
+            },
+        ],
+    }))
+    .await;
 
     let usdc_bank = test_f.get_bank(&BankMint::USDC);
-    let sol_eq_bank = test_f.get_bank(&BankMint::SolEquivalent);
     let sol_bank = test_f.get_bank(&BankMint::SOL);
 
     // Fund SOL lender
     let lender_mfi_account_f = test_f.create_marginfi_account().await;
-    let lender_token_account_sol = test_f
-        .sol_equivalent_mint
+    let lender_token_account_f_sol = test_f
+        .sol_mint
         .create_token_account_and_mint_to(1_000)
         .await;
     lender_mfi_account_f
-        .try_bank_deposit(lender_token_account_sol.key, sol_eq_bank, 1_000)
+        .try_bank_deposit(lender_token_account_f_sol.key, sol_bank, 1_000)
         .await?;
 
-    let lender_token_account_sol = test_f
-        .sol_mint
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_f_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+
+    // Borrowing and repaying the same amount within the flashloan is free
+    let borrow_ix = borrower_mfi_account_f
+        .make_bank_borrow_ix(borrower_token_account_f_sol.key, sol_bank, 100)
+        .await;
+    let repay_ix = borrower_mfi_account_f
+        .make_bank_repay_ix(borrower_token_account_f_sol.key, sol_bank, 100, Some(true))
+        .await;
+
+    borrower_mfi_account_f
+        .try_flashloan(vec![borrow_ix, repay_ix], vec![sol_bank.key], vec![])
+        .await?;
+
+    let sol_bank_state = sol_bank.load().await;
+
+    assert_eq!(
+        I80F48::from(sol_bank_state.collected_group_fees_outstanding),
+        I80F48::ZERO
+    );
+
+    // The liability left at the end of the flashloan is charged, the fee is carried as a liability
+    let borrower_token_account_f_usdc = test_f
+        .usdc_mint
         .create_token_account_and_mint_to(1_000)
         .await;
-    lender_mfi_account_f
-        .try_bank_deposit(lender_token_account_sol.key, sol_bank, 1_000)
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_f_usdc.key, usdc_bank, 1_000)
         .await?;
 
-    let res = lender_mfi_account_f.try_balance_close(sol_bank).await;
+    let borrow_ix = borrower_mfi_account_f
+        .make_bank_borrow_ix(borrower_token_account_f_sol.key, sol_bank, 20)
+        .await;
 
-    assert!(res.is_err());
-    assert_custom_error!(res.unwrap_err(), MarginfiError::IllegalBalanceState);
+    borrower_mfi_account_f
+        .try_flashloan(vec![borrow_ix], vec![], vec![sol_bank.key])
+        .await?;
+
+    let sol_bank_state = sol_bank.load().await;
+    let borrower_ma = borrower_mfi_account_f.load().await;
+
+    assert_eq_noise!(
+        sol_bank_state
+            .get_liability_amount(
+                borrower_ma.lending_account.balances[1]
+                    .liability_shares
+                    .into()
+            )
+            .unwrap(),
+        I80F48::from(native!(20.3, "SOL", f64)),
+        I80F48!(1)
+    );
+
+    assert_eq_noise!(
+        I80F48::from(sol_bank_state.collected_group_fees_outstanding),
+        I80F48::from(native!(0.2, "SOL", f64)),
+        I80F48!(1)
+    );
+
+    assert_eq_noise!(
+        I80F48::from(sol_bank_state.collected_insurance_fees_outstanding),
+        I80F48::from(native!(0.1, "SOL", f64)),
+        I80F48!(1)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn flashloan_fee_charged_on_net_liability() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings {
+        group_config: Some(GroupConfig { admin: None }),
+        banks: vec![
+            TestBankSetting {
+                mint: BankMint::USDC,
+                config: None,
+            },
+            TestBankSetting {
+                mint: BankMint::SOL,
+                config: Some(BankConfig {
+                    interest_rate_config: InterestRateConfig {
+                        insurance_flashloan_fee: I80F48!(0.005).into(),
+                        protocol_flashloan_fee: I80F48!(0.01).into(),
+                        ..*DEFAULT_TEST_BANK_INTEREST_RATE_CONFIG
+                    },
+                    ..*DEFAULT_SOL_TEST_BANK_CONFIG
+                }),
+            },
+        ],
+    }))
+    .await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::USDC);
+    let sol_bank = test_f.get_bank(&BankMint::SOL);
+
+    // Fund SOL lender
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_f_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_f_sol.key, sol_bank, 1_000)
+        .await?;
 
-    // Fund SOL borrower
     let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_f_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
     let borrower_token_account_f_usdc = test_f
         .usdc_mint
         .create_token_account_and_mint_to(1_000)
         .await;
-    let borrower_token_account_f_sol = test_f
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_f_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    // Borrowing 200 SOL, repaying it and borrowing 20 SOL is charged on the 20 SOL net liability
+    let ixs = vec![
+        borrower_mfi_account_f
+            .make_bank_borrow_ix(borrower_token_account_f_sol.key, sol_bank, 200)
+            .await,
+        borrower_mfi_account_f
+            .make_bank_repay_ix(borrower_token_account_f_sol.key, sol_bank, 200, None)
+            .await,
+        borrower_mfi_account_f
+            .make_bank_borrow_ix(borrower_token_account_f_sol.key, sol_bank, 20)
+            .await,
+    ];
+
+    borrower_mfi_account_f
+        .try_flashloan(ixs, vec![], vec![sol_bank.key])
+        .await?;
+
+    let sol_bank_state = sol_bank.load().await;
+    let borrower_ma = borrower_mfi_account_f.load().await;
+
+    assert_eq_noise!(
+        sol_bank_state
+            .get_liability_amount(
+                borrower_ma.lending_account.balances[1]
+                    .liability_shares
+                    .into()
+            )
+            .unwrap(),
+        I80F48::from(native!(20.3, "SOL", f64)),
+        I80F48!(1)
+    );
+
+    assert_eq_noise!(
+        I80F48::from(sol_bank_state.collected_group_fees_outstanding),
+        I80F48::from(native!(0.2, "SOL", f64)),
+        I80F48!(1)
+    );
+
+    assert_eq_noise!(
+        I80F48::from(sol_bank_state.collected_insurance_fees_outstanding),
+        I80F48::from(native!(0.1, "SOL", f64)),
+        I80F48!(1)
+    );
+
+    // A liability held before the flashloan is not charged again
+    let ixs = vec![
+        borrower_mfi_account_f
+            .make_bank_repay_ix(borrower_token_account_f_sol.key, sol_bank, 10, None)
+            .await,
+        borrower_mfi_account_f
+            .make_bank_borrow_ix(borrower_token_account_f_sol.key, sol_bank, 10)
+            .await,
+    ];
+
+    borrower_mfi_account_f
+        .try_flashloan(ixs, vec![], vec![])
+        .await?;
+
+    let sol_bank_state = sol_bank.load().await;
+
+    assert_eq_noise!(
+        I80F48::from(sol_bank_state.collected_group_fees_outstanding),
+        I80F48::from(native!(0.2, "SOL", f64)),
+        I80F48!(1)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn flashloan_fee_failure_borrow_limit() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings {
+        group_config: Some(GroupConfig { admin: None }),
+        banks: vec![
+            TestBankSetting {
+                mint: BankMint::USDC,
+                config: None,
+            },
+            TestBankSetting {
+                mint: BankMint::SOL,
+                config: Some(BankConfig {
+                    borrow_limit: native!(20.1, "SOL", f64),
+                    interest_rate_config: InterestRateConfig {
+                        insurance_flashloan_fee: I80F48!(0.005).into(),
+                        protocol_flashloan_fee: I80F48!(0.01).into(),
+                        ..*DEFAULT_TEST_BANK_INTEREST_RATE_CONFIG
+                    },
+                    ..*DEFAULT_SOL_TEST_BANK_CONFIG
+                }),
+            },
+        ],
+    }))
+    .await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::USDC);
+    let sol_bank = test_f.get_bank(&BankMint::SOL);
+
+    // Fund SOL lender
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_f_sol = test_f
         .sol_mint
         .create_token_account_and_mint_to(1_000)
         .await;
-    let borrower_token_account_f_sol_eq = test_f
-        .sol_equivalent_mint
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_f_sol.key, sol_bank, 1_000)
+        .await?;
+
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_f_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+    let borrower_token_account_f_usdc = test_f
+        .usdc_mint
         .create_token_account_and_mint_to(1_000)
         .await;
     borrower_mfi_account_f
         .try_bank_deposit(borrower_token_account_f_usdc.key, usdc_bank, 1_000)
         .await?;
 
-    // Borrow SOL EQ
+    // Borrowing up to the borrow limit leaves no room for the flashloan fee
+    let borrow_ix = borrower_mfi_account_f
+        .make_bank_borrow_ix(borrower_token_account_f_sol.key, sol_bank, 20)
+        .await;
+
     let res = borrower_mfi_account_f
-        .try_bank_borrow(borrower_token_account_f_sol_eq.key, sol_eq_bank, 0.01)
+        .try_flashloan(vec![borrow_ix], vec![], vec![sol_bank.key])
         .await;
 
-    assert!(res.is_ok());
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err(),
+        MarginfiError::BankLiabilityCapacityExceeded
+    );
 
-    // Borrow SOL
-    let res = borrower_mfi_account_f
-        .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank, 0.01)
+    Ok(())
+}
+
+#[tokio::test]
+async fn flashloan_cpi_success() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let sol_bank = test_f.get_bank(&BankMint::SOL);
+
+    test_f
+        .marginfi_group
+        .try_configure_flashloan_cpi_program(flashloan_cpi::ID, true)
+        .await?;
+
+    // Fund SOL lender
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_f_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
         .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_f_sol.key, sol_bank, 1_000)
+        .await?;
 
-    assert!(res.is_ok());
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_f_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
 
-    // This issue is not that bad, because the user can still borrow other assets (isolated liab < empty threshold)
-    let res = borrower_mfi_account_f.try_balance_close(sol_bank).await;
-    assert!(res.is_err());
-    assert_custom_error!(res.unwrap_err(), MarginfiError::IllegalBalanceState);
+    let start_ix = borrower_mfi_account_f
+        .make_lending_account_start_flashloan_cpi_ix(1)
+        .await;
+    let mut borrow_ix = borrower_mfi_account_f
+        .make_bank_borrow_ix(borrower_token_account_f_sol.key, sol_bank, 1_000)
+        .await;
+    borrow_ix.accounts.push(AccountMeta::new_readonly(
+        solana_program::sysvar::instructions::id(),
+        false,
+    ));
+    let repay_ix = borrower_mfi_account_f
+        .make_bank_repay_ix(
+            borrower_token_account_f_sol.key,
+            sol_bank,
+            1_000,
+            Some(true),
+        )
+        .await;
+    let end_ix = borrower_mfi_account_f
+        .make_lending_account_end_flashloan_ix(vec![], vec![])
+        .await;
 
-    // Let a second go b
+    let cpi_ix = flashloan_cpi::make_cpi_ix(&[start_ix, borrow_ix, repay_ix, end_ix.clone()]);
+
+    // The flashloan is ended within the CPI, the top-level end ix bounds it to the transaction
     {
         let mut ctx = test_f.context.borrow_mut();
-        let mut clock: Clock = ctx.banks_client.get_sysvar().await?;
-        // Advance clock by 1 second
-        clock.unix_timestamp += 1;
-        ctx.set_sysvar(&clock);
+        let tx = Transaction::new_signed_with_payer(
+            &[cpi_ix, end_ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client.process_transaction(tx).await?;
     }
 
-    // Repay isolated SOL EQ borrow successfully
-    let res = borrower_mfi_account_f
-        .try_bank_repay(
-            borrower_token_account_f_sol_eq.key,
-            sol_eq_bank,
-            0.01,
-            Some(false),
-        )
-        .await;
-    assert!(res.is_ok());
+    let borrower_marginfi_account = borrower_mfi_account_f.load().await;
 
-    // Liability share in balance is smaller than 0.0001, so repay all should fail
-    let res = borrower_mfi_account_f
-        .try_bank_repay(
-            borrower_token_account_f_sol_eq.key,
-            sol_eq_bank,
-            1,
-            Some(true),
-        )
+    assert!(!borrower_marginfi_account.get_flag(IN_FLASHLOAN_FLAG));
+    assert!(!borrower_marginfi_account.get_flag(IN_FLASHLOAN_CPI_FLAG));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn flashloan_cpi_fail_program_not_allowed() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+
+    let start_ix = borrower_mfi_account_f
+        .make_lending_account_start_flashloan_cpi_ix(1)
+        .await;
+    let end_ix = borrower_mfi_account_f
+        .make_lending_account_end_flashloan_ix(vec![], vec![])
         .await;
-    assert!(res.is_err());
-    assert_custom_error!(res.unwrap_err(), MarginfiError::NoLiabilityFound);
+
+    let cpi_ix = flashloan_cpi::make_cpi_ix(&[start_ix, end_ix.clone()]);
+
+    let mut ctx = test_f.context.borrow_mut();
+    let tx = Transaction::new_signed_with_payer(
+        &[cpi_ix, end_ix],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer],
+        ctx.last_blockhash,
+    );
+    let res = ctx.banks_client.process_transaction(tx).await;
+
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err(),
+        MarginfiError::FlashloanCpiProgramNotAllowed
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn flashloan_cpi_fail_outside_start_ix() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let sol_bank = test_f.get_bank(&BankMint::SOL);
+
+    test_f
+        .marginfi_group
+        .try_configure_flashloan_cpi_program(flashloan_cpi::ID, true)
+        .await?;
+
+    // Fund SOL lender
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_f_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_f_sol.key, sol_bank, 1_000)
+        .await?;
+
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_f_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+
+    let start_ix = borrower_mfi_account_f
+        .make_lending_account_start_flashloan_cpi_ix(2)
+        .await;
+    let mut borrow_ix = borrower_mfi_account_f
+        .make_bank_borrow_ix(borrower_token_account_f_sol.key, sol_bank, 100)
+        .await;
+    borrow_ix.accounts.push(AccountMeta::new_readonly(
+        solana_program::sysvar::instructions::id(),
+        false,
+    ));
+    let end_ix = borrower_mfi_account_f
+        .make_lending_account_end_flashloan_ix(vec![sol_bank.key], vec![])
+        .await;
+
+    // Borrowing via CPI from a later top-level ix than the one starting the flashloan fails
+    let res = {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[
+                flashloan_cpi::make_cpi_ix(&[start_ix.clone(), borrow_ix.clone()]),
+                flashloan_cpi::make_cpi_ix(&[borrow_ix.clone()]),
+                end_ix.clone(),
+            ],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client.process_transaction(tx).await
+    };
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::IllegalFlashloan);
+
+    // Borrowing from a top-level ix within a CPI flashloan is not allowed
+    let res = {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[flashloan_cpi::make_cpi_ix(&[start_ix]), borrow_ix, end_ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client.process_transaction(tx).await
+    };
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::IllegalFlashloan);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn flashloan_cpi_fail_end_ix_not_in_tx() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let sol_bank = test_f.get_bank(&BankMint::SOL);
+
+    test_f
+        .marginfi_group
+        .try_configure_flashloan_cpi_program(flashloan_cpi::ID, true)
+        .await?;
+
+    // Fund SOL lender
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_f_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_f_sol.key, sol_bank, 1_000)
+        .await?;
+
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_f_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+
+    let start_ix = borrower_mfi_account_f
+        .make_lending_account_start_flashloan_cpi_ix(1)
+        .await;
+    let mut borrow_ix = borrower_mfi_account_f
+        .make_bank_borrow_ix(borrower_token_account_f_sol.key, sol_bank, 100)
+        .await;
+    borrow_ix.accounts.push(AccountMeta::new_readonly(
+        solana_program::sysvar::instructions::id(),
+        false,
+    ));
+    let cpi_ix = flashloan_cpi::make_cpi_ix(&[start_ix, borrow_ix]);
+
+    // The end ix is left to a later transaction
+    let res = {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[cpi_ix.clone()],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client.process_transaction(tx).await
+    };
+
+    assert!(res.is_err());
+
+    // The end ix is for another marginfi account
+    let end_ix = lender_mfi_account_f
+        .make_lending_account_end_flashloan_ix(vec![], vec![])
+        .await;
+
+    let res = {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[cpi_ix, end_ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client.process_transaction(tx).await
+    };
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::IllegalFlashloan);
+
+    let borrower_marginfi_account = borrower_mfi_account_f.load().await;
+
+    assert!(!borrower_marginfi_account.get_flag(IN_FLASHLOAN_FLAG));
+    assert!(!borrower_marginfi_account.get_flag(IN_FLASHLOAN_CPI_FLAG));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn lending_account_close_balance() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::USDC);
+    let sol_eq_bank = test_f.get_bank(&BankMint::SolEquivalent);
+    let sol_bank = test_f.get_bank(&BankMint::SOL);
+
+    // Fund SOL lender
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_sol = test_f
+        .sol_equivalent_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_sol.key, sol_eq_bank, 1_000)
+        .await?;
+
+    let lender_token_account_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_sol.key, sol_bank, 1_000)
+        .await?;
+
+    let res = lender_mfi_account_f.try_balance_close(sol_bank).await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::IllegalBalanceState);
+
+    // Fund SOL borrower
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_f_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    let borrower_token_account_f_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    let borrower_token_account_f_sol_eq = test_f
+        .sol_equivalent_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_f_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    // Borrow SOL EQ
+    let res = borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_f_sol_eq.key, sol_eq_bank, 0.01)
+        .await;
+
+    assert!(res.is_ok());
+
+    // Borrow SOL
+    let res = borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank, 0.01)
+        .await;
+
+    assert!(res.is_ok());
+
+    // This issue is not that bad, because the user can still borrow other assets (isolated liab < empty threshold)
+    let res = borrower_mfi_account_f.try_balance_close(sol_bank).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::IllegalBalanceState);
+
+    // Let a second go b
+    {
+        let mut ctx = test_f.context.borrow_mut();
+        let mut clock: Clock = ctx.banks_client.get_sysvar().await?;
+        // Advance clock by 1 second
+        clock.unix_timestamp += 1;
+        ctx.set_sysvar(&clock);
+    }
+
+    // Repay isolated SOL EQ borrow successfully
+    let res = borrower_mfi_account_f
+        .try_bank_repay(
+            borrower_token_account_f_sol_eq.key,
+            sol_eq_bank,
+            0.01,
+            Some(false),
+        )
+        .await;
+    assert!(res.is_ok());
+
+    // Liability share in balance is smaller than 0.0001, so repay all should fail
+    let res = borrower_mfi_account_f
+        .try_bank_repay(
+            borrower_token_account_f_sol_eq.key,
+            sol_eq_bank,
+            1,
+            Some(true),
+        )
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::NoLiabilityFound);
 
     // This issue is not that bad, because the user can still borrow other assets (isolated liab < empty threshold)
     let res = borrower_mfi_account_f.try_balance_close(sol_eq_bank).await;
@@ -2410,6 +3537,618 @@ async fn lending_account_close_balance() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn lending_account_transfer_balance() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::USDC);
+    let sol_bank = test_f.get_bank(&BankMint::SOL);
+
+    // Fund SOL lender
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_sol.key, sol_bank, 1_000)
+        .await?;
+
+    // Open a leveraged position in the source account
+    let source_mfi_account_f = test_f.create_marginfi_account().await;
+    let source_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    let source_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+    source_mfi_account_f
+        .try_bank_deposit(source_token_account_usdc.key, usdc_bank, 1_000)
+        .await?;
+    source_mfi_account_f
+        .try_bank_borrow(source_token_account_sol.key, sol_bank, 20)
+        .await?;
+
+    let destination_mfi_account_f = test_f.create_marginfi_account().await;
+
+    // Moving the debt without collateral leaves the destination unhealthy
+    let res = source_mfi_account_f
+        .try_transfer_balance(&destination_mfi_account_f, sol_bank, 0, Some(true))
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::BadAccountHealth);
+
+    // Moving more than the balance fails
+    let res = source_mfi_account_f
+        .try_transfer_balance(&destination_mfi_account_f, usdc_bank, 1_001, None)
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::InvalidTransfer);
+
+    // Split the collateral, then move the debt
+    source_mfi_account_f
+        .try_transfer_balance(&destination_mfi_account_f, usdc_bank, 600, None)
+        .await?;
+    source_mfi_account_f
+        .try_transfer_balance(&destination_mfi_account_f, sol_bank, 0, Some(true))
+        .await?;
+
+    let usdc_bank_state = usdc_bank.load().await;
+    let sol_bank_state = sol_bank.load().await;
+    let source_ma = source_mfi_account_f.load().await;
+    let destination_ma = destination_mfi_account_f.load().await;
+
+    assert_eq!(
+        usdc_bank_state
+            .get_asset_amount(source_ma.lending_account.balances[0].asset_shares.into())
+            .unwrap(),
+        I80F48::from(native!(400, "USDC"))
+    );
+    assert!(!source_ma.lending_account.balances[1].active);
+
+    assert_eq!(
+        usdc_bank_state
+            .get_asset_amount(
+                destination_ma.lending_account.balances[0]
+                    .asset_shares
+                    .into()
+            )
+            .unwrap(),
+        I80F48::from(native!(600, "USDC"))
+    );
+    assert_eq_noise!(
+        sol_bank_state
+            .get_liability_amount(
+                destination_ma.lending_account.balances[1]
+                    .liability_shares
+                    .into()
+            )
+            .unwrap(),
+        I80F48::from(native!(20, "SOL")),
+        I80F48!(1)
+    );
+
+    // Moving the whole remaining balance by amount closes it
+    source_mfi_account_f
+        .try_transfer_balance(&destination_mfi_account_f, usdc_bank, 400, None)
+        .await?;
+
+    let source_ma = source_mfi_account_f.load().await;
+
+    assert!(!source_ma.lending_account.balances[0].active);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn lending_account_transfer_balance_permissioned_liability() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::USDC);
+    let sol_bank = test_f.get_bank(&BankMint::SOL);
+
+    usdc_bank
+        .update_config(BankConfigOpt {
+            borrow_mode: Some(BankBorrowMode::Permissioned),
+            ..Default::default()
+        })
+        .await?;
+
+    // Fund USDC lender
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(2_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank, 2_000)
+        .await?;
+
+    let source_mfi_account_f = test_f.create_marginfi_account().await;
+    let source_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let source_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    source_mfi_account_f
+        .try_bank_deposit(source_token_account_sol.key, sol_bank, 100)
+        .await?;
+    usdc_bank
+        .try_create_borrow_credential(source_mfi_account_f.key, u64::MAX)
+        .await?;
+    source_mfi_account_f
+        .try_bank_borrow(source_token_account_usdc.key, usdc_bank, 100)
+        .await?;
+
+    let destination_mfi_account_f = test_f.create_marginfi_account().await;
+    let destination_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    destination_mfi_account_f
+        .try_bank_deposit(destination_token_account_sol.key, sol_bank, 100)
+        .await?;
+
+    // No credential issued to the destination
+    let res = source_mfi_account_f
+        .try_transfer_balance(&destination_mfi_account_f, usdc_bank, 0, Some(true))
+        .await;
+
+    assert!(res.is_err());
+
+    // The destination credential caps the liability moved
+    usdc_bank
+        .try_create_borrow_credential(destination_mfi_account_f.key, native!(50, "USDC"))
+        .await?;
+
+    let res = source_mfi_account_f
+        .try_transfer_balance(&destination_mfi_account_f, usdc_bank, 0, Some(true))
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::AccountBorrowLimitExceeded);
+
+    source_mfi_account_f
+        .try_transfer_balance(&destination_mfi_account_f, usdc_bank, 50, None)
+        .await?;
+
+    let usdc_bank_state = usdc_bank.load().await;
+    let destination_ma = destination_mfi_account_f.load().await;
+
+    assert_eq_noise!(
+        usdc_bank_state
+            .get_liability_amount(
+                destination_ma.lending_account.balances[1]
+                    .liability_shares
+                    .into()
+            )
+            .unwrap(),
+        I80F48::from(native!(50, "USDC")),
+        I80F48!(1)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn lending_account_execute_order() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::USDC);
+    let sol_bank = test_f.get_bank(&BankMint::SOL);
+
+    // Fund SOL lender
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_sol.key, sol_bank, 1_000)
+        .await?;
+
+    // Borrow $200 of SOL against $1000 of USDC
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    let borrower_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_usdc.key, usdc_bank, 1_000)
+        .await?;
+    borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_sol.key, sol_bank, 20)
+        .await?;
+
+    // Keeper tip above the max is rejected
+    let res = borrower_mfi_account_f
+        .try_create_order(
+            usdc_bank,
+            sol_bank,
+            OrderTrigger::PriceBelow,
+            I80F48!(0.2),
+            I80F48!(0.1),
+        )
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::InvalidOrder);
+
+    // USDC is priced at 0.1 SOL, a take-profit above 0.2 SOL can't be executed yet
+    borrower_mfi_account_f
+        .try_create_order(
+            usdc_bank,
+            sol_bank,
+            OrderTrigger::PriceAbove,
+            I80F48!(0.2),
+            I80F48!(0.01),
+        )
+        .await?;
+
+    let keeper_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let keeper_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+
+    let res = borrower_mfi_account_f
+        .try_execute_order(
+            usdc_bank,
+            sol_bank,
+            OrderTrigger::PriceAbove,
+            keeper_token_account_sol.key,
+            keeper_token_account_usdc.key,
+        )
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::OrderTriggerNotMet);
+
+    let take_profit_order = borrower_mfi_account_f
+        .get_order(usdc_bank, sol_bank, OrderTrigger::PriceAbove)
+        .0;
+    borrower_mfi_account_f
+        .try_close_order(take_profit_order)
+        .await?;
+
+    // Stop-loss below 0.2 SOL is triggered
+    borrower_mfi_account_f
+        .try_create_order(
+            usdc_bank,
+            sol_bank,
+            OrderTrigger::PriceBelow,
+            I80F48!(0.2),
+            I80F48!(0.01),
+        )
+        .await?;
+
+    borrower_mfi_account_f
+        .try_execute_order(
+            usdc_bank,
+            sol_bank,
+            OrderTrigger::PriceBelow,
+            keeper_token_account_sol.key,
+            keeper_token_account_usdc.key,
+        )
+        .await?;
+
+    // Keeper repaid the 20 SOL debt and received $200 of USDC plus the 1% tip
+    assert_eq_noise!(
+        keeper_token_account_sol.balance().await as i64,
+        native!(80, "SOL") as i64,
+        1
+    );
+    assert_eq_noise!(
+        keeper_token_account_usdc.balance().await as i64,
+        native!(202, "USDC") as i64,
+        native!(1, "USDC") as i64
+    );
+
+    let borrower_ma = borrower_mfi_account_f.load().await;
+
+    assert!(borrower_ma
+        .lending_account
+        .get_balance(&sol_bank.key)
+        .is_none());
+    assert_eq_noise!(
+        usdc_bank
+            .load()
+            .await
+            .get_asset_amount(
+                borrower_ma
+                    .lending_account
+                    .get_balance(&usdc_bank.key)
+                    .unwrap()
+                    .asset_shares
+                    .into()
+            )
+            .unwrap(),
+        I80F48::from(native!(798, "USDC")),
+        I80F48::from(native!(1, "USDC"))
+    );
+
+    // Executed orders are closed
+    let stop_loss_order = borrower_mfi_account_f
+        .get_order(usdc_bank, sol_bank, OrderTrigger::PriceBelow)
+        .0;
+    assert!(test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .get_account(stop_loss_order)
+        .await?
+        .is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn lending_account_execute_order_triggers() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::USDC);
+    let sol_bank = test_f.get_bank(&BankMint::SOL);
+
+    // Fund SOL and USDC lender
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    let lender_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_sol.key, sol_bank, 1_000)
+        .await?;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let keeper_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let keeper_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+
+    // Borrow $200 of SOL against $1000 of USDC, maintenance health ratio is 0.8
+    let health_mfi_account_f = test_f.create_marginfi_account().await;
+    let health_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    let health_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+    health_mfi_account_f
+        .try_bank_deposit(health_token_account_usdc.key, usdc_bank, 1_000)
+        .await?;
+    health_mfi_account_f
+        .try_bank_borrow(health_token_account_sol.key, sol_bank, 20)
+        .await?;
+
+    // Keeper tip is capped at `MAX_ORDER_KEEPER_TIP`
+    let res = health_mfi_account_f
+        .try_create_order(
+            usdc_bank,
+            sol_bank,
+            OrderTrigger::HealthBelow,
+            I80F48!(0.5),
+            MAX_ORDER_KEEPER_TIP + I80F48!(0.001),
+        )
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::InvalidOrder);
+
+    health_mfi_account_f
+        .try_create_order(
+            usdc_bank,
+            sol_bank,
+            OrderTrigger::HealthBelow,
+            I80F48!(0.5),
+            MAX_ORDER_KEEPER_TIP,
+        )
+        .await?;
+
+    let res = health_mfi_account_f
+        .try_execute_order(
+            usdc_bank,
+            sol_bank,
+            OrderTrigger::HealthBelow,
+            keeper_token_account_sol.key,
+            keeper_token_account_usdc.key,
+        )
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::OrderTriggerNotMet);
+
+    let health_order = health_mfi_account_f
+        .get_order(usdc_bank, sol_bank, OrderTrigger::HealthBelow)
+        .0;
+    health_mfi_account_f.try_close_order(health_order).await?;
+
+    // Health ratio below 0.9 is triggered
+    health_mfi_account_f
+        .try_create_order(
+            usdc_bank,
+            sol_bank,
+            OrderTrigger::HealthBelow,
+            I80F48!(0.9),
+            MAX_ORDER_KEEPER_TIP,
+        )
+        .await?;
+
+    health_mfi_account_f
+        .try_execute_order(
+            usdc_bank,
+            sol_bank,
+            OrderTrigger::HealthBelow,
+            keeper_token_account_sol.key,
+            keeper_token_account_usdc.key,
+        )
+        .await?;
+
+    // Keeper repaid the 20 SOL debt and received $200 of USDC plus the 5% tip
+    assert_eq_noise!(
+        keeper_token_account_sol.balance().await as i64,
+        native!(80, "SOL") as i64,
+        1
+    );
+    assert_eq_noise!(
+        keeper_token_account_usdc.balance().await as i64,
+        native!(1_210, "USDC") as i64,
+        native!(1, "USDC") as i64
+    );
+
+    // Borrow $200 of USDC against $1000 of SOL, SOL is priced at 10 USDC
+    let price_mfi_account_f = test_f.create_marginfi_account().await;
+    let price_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let price_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    price_mfi_account_f
+        .try_bank_deposit(price_token_account_sol.key, sol_bank, 100)
+        .await?;
+    price_mfi_account_f
+        .try_bank_borrow(price_token_account_usdc.key, usdc_bank, 200)
+        .await?;
+
+    // Take-profit above 5 USDC is triggered
+    price_mfi_account_f
+        .try_create_order(
+            sol_bank,
+            usdc_bank,
+            OrderTrigger::PriceAbove,
+            I80F48!(5),
+            I80F48!(0.01),
+        )
+        .await?;
+
+    // Collateral paid to the keeper counts towards the asset bank outflow limit
+    sol_bank
+        .update_config(BankConfigOpt {
+            outflow_limit_window: Some(3600),
+            outflow_value_limit: Some(100),
+            ..Default::default()
+        })
+        .await?;
+
+    let res = price_mfi_account_f
+        .try_execute_order(
+            sol_bank,
+            usdc_bank,
+            OrderTrigger::PriceAbove,
+            keeper_token_account_usdc.key,
+            keeper_token_account_sol.key,
+        )
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err(),
+        MarginfiError::BankOutflowValueLimitExceeded
+    );
+
+    sol_bank
+        .update_config(BankConfigOpt {
+            outflow_value_limit: Some(1_000),
+            ..Default::default()
+        })
+        .await?;
+
+    price_mfi_account_f
+        .try_execute_order(
+            sol_bank,
+            usdc_bank,
+            OrderTrigger::PriceAbove,
+            keeper_token_account_usdc.key,
+            keeper_token_account_sol.key,
+        )
+        .await?;
+
+    // Keeper repaid the 200 USDC debt and received $200 of SOL plus the 1% tip
+    assert_eq_noise!(
+        keeper_token_account_usdc.balance().await as i64,
+        native!(1_010, "USDC") as i64,
+        native!(1, "USDC") as i64
+    );
+    assert_eq_noise!(
+        keeper_token_account_sol.balance().await as i64,
+        native!(100.2, "SOL", f64) as i64,
+        native!(0.01, "SOL", f64) as i64
+    );
+
+    let price_ma = price_mfi_account_f.load().await;
+
+    assert!(price_ma
+        .lending_account
+        .get_balance(&usdc_bank.key)
+        .is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn lending_account_execute_order_failure_authority_changed() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::USDC);
+    let sol_bank = test_f.get_bank(&BankMint::SOL);
+
+    // Fund SOL lender
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_sol.key, sol_bank, 1_000)
+        .await?;
+
+    // Borrow $200 of SOL against $1000 of USDC
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    let borrower_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_usdc.key, usdc_bank, 1_000)
+        .await?;
+    borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_sol.key, sol_bank, 20)
+        .await?;
+
+    // Stop-loss below 0.2 SOL is triggered
+    borrower_mfi_account_f
+        .try_create_order(
+            usdc_bank,
+            sol_bank,
+            OrderTrigger::PriceBelow,
+            I80F48!(0.2),
+            I80F48!(0.01),
+        )
+        .await?;
+
+    // Orders created by the previous authority can't be executed once the account is transferred
+    borrower_mfi_account_f
+        .try_set_flag(TRANSFER_AUTHORITY_ALLOWED_FLAG)
+        .await?;
+    borrower_mfi_account_f
+        .try_transfer_account_authority(Keypair::new().pubkey(), None)
+        .await?;
+
+    let keeper_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let keeper_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+
+    let res = borrower_mfi_account_f
+        .try_execute_order(
+            usdc_bank,
+            sol_bank,
+            OrderTrigger::PriceBelow,
+            keeper_token_account_sol.key,
+            keeper_token_account_usdc.key,
+        )
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::OrderAuthorityChanged);
+
+    Ok(())
+}
+
 // Test transfer account authority.
 // No transfer flag set -- tx should fail.
 // Set the flag and try again -- tx should succeed.
@@ -1,15 +1,21 @@
-use anchor_lang::{prelude::Clock, InstructionData, ToAccountMetas};
+use anchor_lang::{
+    prelude::{Clock, Pubkey},
+    InstructionData, ToAccountMetas,
+};
 
 use fixed::types::I80F48;
 use fixed_macro::types::I80F48;
 use fixtures::prelude::*;
 use fixtures::{assert_custom_error, assert_eq_noise, native};
-use marginfi::constants::TOTAL_ASSET_VALUE_INIT_LIMIT_INACTIVE;
+use marginfi::constants::{INSURANCE_DEAD_SHARES, TOTAL_ASSET_VALUE_INIT_LIMIT_INACTIVE};
 use marginfi::prelude::GroupConfig;
-use marginfi::state::marginfi_group::{BankVaultType, InterestRateConfig};
+use marginfi::state::marginfi_group::{BankBadDebtMode, BankVaultType, InterestRateConfig};
 use marginfi::{
     prelude::{MarginfiError, MarginfiGroup},
-    state::marginfi_group::{Bank, BankConfig, BankConfigOpt, BankOperationalState},
+    state::{
+        marginfi_account::DISABLED_FLAG,
+        marginfi_group::{Bank, BankConfig, BankConfigOpt, BankOperationalState},
+    },
 };
 use pretty_assertions::assert_eq;
 
@@ -107,6 +113,8 @@ async fn marginfi_group_config_check() -> anyhow::Result<()> {
                 insurance_ir_fee: Some(I80F48::from_num(0.11).into()),
                 protocol_fixed_fee_apr: Some(I80F48::from_num(0.51).into()),
                 protocol_ir_fee: Some(I80F48::from_num(0.011).into()),
+                insurance_flashloan_fee: Some(I80F48::from_num(0.0005).into()),
+                protocol_flashloan_fee: Some(I80F48::from_num(0.001).into()),
             }),
             ..BankConfigOpt::default()
         })
@@ -153,6 +161,57 @@ async fn marginfi_group_config_check() -> anyhow::Result<()> {
         I80F48::from_num(0.011)
     );
 
+    assert_eq!(
+        I80F48::from(bank.config.interest_rate_config.insurance_flashloan_fee),
+        I80F48::from_num(0.0005)
+    );
+
+    assert_eq!(
+        I80F48::from(bank.config.interest_rate_config.protocol_flashloan_fee),
+        I80F48::from_num(0.001)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_group_configure_flashloan_cpi_program() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    let program_a = Pubkey::new_unique();
+    let program_b = Pubkey::new_unique();
+
+    test_f
+        .marginfi_group
+        .try_configure_flashloan_cpi_program(program_a, true)
+        .await?;
+    test_f
+        .marginfi_group
+        .try_configure_flashloan_cpi_program(program_b, true)
+        .await?;
+
+    let marginfi_group = test_f.marginfi_group.load().await;
+    assert!(marginfi_group.is_flashloan_cpi_program_allowed(&program_a));
+    assert!(marginfi_group.is_flashloan_cpi_program_allowed(&program_b));
+
+    test_f
+        .marginfi_group
+        .try_configure_flashloan_cpi_program(program_a, false)
+        .await?;
+
+    let marginfi_group = test_f.marginfi_group.load().await;
+    assert!(!marginfi_group.is_flashloan_cpi_program_allowed(&program_a));
+    assert!(marginfi_group.is_flashloan_cpi_program_allowed(&program_b));
+
+    // Removing a program that is not allowed should fail
+    let res = test_f
+        .marginfi_group
+        .try_configure_flashloan_cpi_program(Pubkey::new_unique(), false)
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::InvalidConfig);
+
     Ok(())
 }
 
@@ -884,6 +943,375 @@ async fn marginfi_group_handle_bankruptcy_success_not_insured() -> anyhow::Resul
     Ok(())
 }
 
+#[tokio::test]
+async fn marginfi_group_handle_bankruptcy_keeps_outflow() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings {
+        banks: vec![
+            TestBankSetting {
+                mint: BankMint::USDC,
+                config: None,
+            },
+            TestBankSetting {
+                mint: BankMint::SOL,
+                config: Some(BankConfig {
+                    asset_weight_init: I80F48!(1).into(),
+                    ..*DEFAULT_SOL_TEST_BANK_CONFIG
+                }),
+            },
+        ],
+        group_config: Some(GroupConfig { admin: None }),
+    }))
+    .await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
+    let sol_bank_f = test_f.get_bank(&BankMint::SOL);
+
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(100_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank_f, 100_000)
+        .await?;
+
+    usdc_bank_f
+        .update_config(BankConfigOpt {
+            outflow_limit_window: Some(86_400),
+            outflow_limit: Some(native!(20_000, "USDC")),
+            ..Default::default()
+        })
+        .await?;
+
+    let borrower_account = test_f.create_marginfi_account().await;
+    let borrower_deposit_account = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_001)
+        .await;
+    borrower_account
+        .try_bank_deposit(borrower_deposit_account.key, sol_bank_f, 1_001)
+        .await?;
+    let borrower_borrow_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_account
+        .try_bank_borrow(borrower_borrow_account.key, usdc_bank_f, 10_000)
+        .await?;
+
+    let mut borrower_mfi_account = borrower_account.load().await;
+    borrower_mfi_account.lending_account.balances[0]
+        .asset_shares
+        .value = 0;
+
+    borrower_account.set_account(&borrower_mfi_account).await?;
+
+    test_f
+        .marginfi_group
+        .try_handle_bankruptcy(usdc_bank_f, &borrower_account)
+        .await?;
+
+    // The bad debt written off is not an inflow, the borrowed amount still counts against the limit
+    let usdc_bank = usdc_bank_f.load().await;
+
+    assert_eq_noise!(
+        I80F48::from(usdc_bank.outflow_window_amount),
+        I80F48::from(native!(10_000, "USDC")),
+        I80F48::from(native!(10, "USDC"))
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_group_handle_bankruptcy_success_deferred() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings {
+        banks: vec![
+            TestBankSetting {
+                mint: BankMint::USDC,
+                config: Some(BankConfig {
+                    bad_debt_mode: BankBadDebtMode::Defer,
+                    ..*DEFAULT_USDC_TEST_BANK_CONFIG
+                }),
+            },
+            TestBankSetting {
+                mint: BankMint::SOL,
+                config: Some(BankConfig {
+                    asset_weight_init: I80F48!(1).into(),
+                    ..*DEFAULT_SOL_TEST_BANK_CONFIG
+                }),
+            },
+        ],
+        group_config: Some(GroupConfig { admin: None }),
+    }))
+    .await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
+    let sol_bank_f = test_f.get_bank(&BankMint::SOL);
+
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(100_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank_f, 100_000)
+        .await?;
+
+    let borrower_account = test_f.create_marginfi_account().await;
+    let borrower_deposit_account = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_001)
+        .await;
+    borrower_account
+        .try_bank_deposit(borrower_deposit_account.key, sol_bank_f, 1_001)
+        .await?;
+    let borrower_borrow_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_account
+        .try_bank_borrow(borrower_borrow_account.key, usdc_bank_f, 10_000)
+        .await?;
+
+    let mut borrower_mfi_account = borrower_account.load().await;
+    borrower_mfi_account.lending_account.balances[0]
+        .asset_shares
+        .value = 0;
+
+    borrower_account.set_account(&borrower_mfi_account).await?;
+
+    test_f
+        .marginfi_group
+        .try_handle_bankruptcy(usdc_bank_f, &borrower_account)
+        .await?;
+
+    // Bad debt is recorded on the bank, depositors keep their share value
+    let lender_mfi_account = lender_mfi_account_f.load().await;
+    let usdc_bank = usdc_bank_f.load().await;
+
+    let lender_usdc_value = usdc_bank.get_asset_amount(
+        lender_mfi_account.lending_account.balances[0]
+            .asset_shares
+            .into(),
+    )?;
+
+    assert_eq_noise!(
+        lender_usdc_value,
+        I80F48::from(native!(100_000, "USDC")),
+        I80F48::ONE
+    );
+    assert_eq_noise!(
+        I80F48::from(usdc_bank.unrecovered_bad_debt),
+        I80F48::from(native!(10_000, "USDC")),
+        I80F48::ONE
+    );
+
+    usdc_bank_f.try_socialize_bad_debt().await?;
+
+    let usdc_bank = usdc_bank_f.load().await;
+
+    let lender_usdc_value = usdc_bank.get_asset_amount(
+        lender_mfi_account.lending_account.balances[0]
+            .asset_shares
+            .into(),
+    )?;
+
+    assert_eq_noise!(
+        lender_usdc_value,
+        I80F48::from(native!(90_000, "USDC")),
+        I80F48::ONE
+    );
+    assert_eq!(I80F48::from(usdc_bank.unrecovered_bad_debt), I80F48::ZERO);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_group_deferred_bad_debt_recovery() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings {
+        banks: vec![
+            TestBankSetting {
+                mint: BankMint::USDC,
+                config: Some(BankConfig {
+                    bad_debt_mode: BankBadDebtMode::Defer,
+                    ..*DEFAULT_USDC_TEST_BANK_CONFIG
+                }),
+            },
+            TestBankSetting {
+                mint: BankMint::SOL,
+                config: Some(BankConfig {
+                    asset_weight_init: I80F48!(1).into(),
+                    ..*DEFAULT_SOL_TEST_BANK_CONFIG
+                }),
+            },
+        ],
+        group_config: Some(GroupConfig { admin: None }),
+    }))
+    .await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
+    let sol_bank_f = test_f.get_bank(&BankMint::SOL);
+
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(100_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank_f, 100_000)
+        .await?;
+
+    let borrower_account = test_f.create_marginfi_account().await;
+    let borrower_deposit_account = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_001)
+        .await;
+    borrower_account
+        .try_bank_deposit(borrower_deposit_account.key, sol_bank_f, 1_001)
+        .await?;
+    let borrower_borrow_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_account
+        .try_bank_borrow(borrower_borrow_account.key, usdc_bank_f, 10_000)
+        .await?;
+
+    let mut borrower_mfi_account = borrower_account.load().await;
+    borrower_mfi_account.lending_account.balances[0]
+        .asset_shares
+        .value = 0;
+    borrower_account.set_account(&borrower_mfi_account).await?;
+
+    test_f
+        .marginfi_group
+        .try_handle_bankruptcy(usdc_bank_f, &borrower_account)
+        .await?;
+
+    let borrower_mfi_account = borrower_account.load().await;
+    assert_eq_noise!(
+        I80F48::from(borrower_mfi_account.lending_account.balances[1].deferred_bad_debt),
+        I80F48::from(native!(10_000, "USDC")),
+        I80F48::ONE
+    );
+
+    // Outstanding insurance fees cover the bad debt first
+    let mut usdc_bank = usdc_bank_f.load().await;
+    usdc_bank.collected_insurance_fees_outstanding = I80F48::from(native!(4_000, "USDC")).into();
+    usdc_bank_f.set_account(&usdc_bank).await?;
+
+    test_f.marginfi_group.try_collect_fees(usdc_bank_f).await?;
+
+    let usdc_bank = usdc_bank_f.load().await;
+    assert_eq_noise!(
+        I80F48::from(usdc_bank.unrecovered_bad_debt),
+        I80F48::from(native!(6_000, "USDC")),
+        I80F48::ONE
+    );
+    assert_eq!(
+        I80F48::from(usdc_bank.collected_insurance_fees_outstanding),
+        I80F48::ZERO
+    );
+    assert_eq!(
+        usdc_bank_f
+            .get_vault_token_account(BankVaultType::Insurance)
+            .await
+            .balance()
+            .await,
+        0
+    );
+
+    let buyer_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(10_000)
+        .await;
+    let buyer_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+
+    // Collateral of a disabled account without deferred bad debt can't be auctioned
+    let other_mfi_account_f = test_f.create_marginfi_account().await;
+    let other_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(1).await;
+    other_mfi_account_f
+        .try_bank_deposit(other_token_account_sol.key, sol_bank_f, 1)
+        .await?;
+    let mut other_mfi_account = other_mfi_account_f.load().await;
+    other_mfi_account.account_flags = DISABLED_FLAG;
+    other_mfi_account_f.set_account(&other_mfi_account).await?;
+
+    let res = test_f
+        .marginfi_group
+        .try_auction_bad_debt_collateral(
+            sol_bank_f,
+            usdc_bank_f,
+            &other_mfi_account_f,
+            buyer_token_account_usdc.key,
+            buyer_token_account_sol.key,
+        )
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::BadDebtAuctionNotAvailable);
+
+    // Collateral worth $20,000 recovered into the bankrupt account
+    let mut borrower_mfi_account = borrower_account.load().await;
+    borrower_mfi_account.lending_account.balances[0].asset_shares =
+        I80F48::from(native!(2_000, "SOL")).into();
+    borrower_account.set_account(&borrower_mfi_account).await?;
+
+    // Bad debt recovery is not held back by the asset bank outflow limit
+    sol_bank_f
+        .update_config(BankConfigOpt {
+            outflow_limit_window: Some(3600),
+            outflow_limit: Some(1),
+            ..Default::default()
+        })
+        .await?;
+
+    test_f
+        .marginfi_group
+        .try_auction_bad_debt_collateral(
+            sol_bank_f,
+            usdc_bank_f,
+            &borrower_account,
+            buyer_token_account_usdc.key,
+            buyer_token_account_sol.key,
+        )
+        .await?;
+
+    // The payment is capped at the unrecovered bad debt, only the matching collateral is sold
+    assert_eq_noise!(
+        I80F48::from(buyer_token_account_usdc.balance().await),
+        I80F48::from(native!(4_000, "USDC")),
+        I80F48::ONE
+    );
+    assert_eq_noise!(
+        I80F48::from(buyer_token_account_sol.balance().await),
+        I80F48::from(native!(600, "SOL")),
+        I80F48::ONE
+    );
+
+    let usdc_bank = usdc_bank_f.load().await;
+    assert_eq!(I80F48::from(usdc_bank.unrecovered_bad_debt), I80F48::ZERO);
+    assert_eq!(
+        I80F48::from(sol_bank_f.load().await.outflow_window_amount),
+        I80F48::ZERO
+    );
+
+    let borrower_mfi_account = borrower_account.load().await;
+    assert_eq_noise!(
+        I80F48::from(borrower_mfi_account.lending_account.balances[1].deferred_bad_debt),
+        I80F48::from(native!(4_000, "USDC")),
+        I80F48::ONE
+    );
+
+    let res = test_f
+        .marginfi_group
+        .try_auction_bad_debt_collateral(
+            sol_bank_f,
+            usdc_bank_f,
+            &borrower_account,
+            buyer_token_account_usdc.key,
+            buyer_token_account_sol.key,
+        )
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::BadDebtAuctionNotAvailable);
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn marginfi_group_handle_bankruptcy_success_not_insured_3_depositors() -> anyhow::Result<()> {
     let test_f = TestFixture::new(Some(TestSettings {
@@ -987,6 +1415,296 @@ async fn marginfi_group_handle_bankruptcy_success_not_insured_3_depositors() ->
     Ok(())
 }
 
+#[tokio::test]
+async fn marginfi_group_insurance_staking() -> anyhow::Result<()> {
+    let mut test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let (insurance_vault, _) = test_f
+        .get_bank(&BankMint::USDC)
+        .get_vault(BankVaultType::Insurance);
+
+    // Protocol owned insurance funds
+    test_f
+        .get_bank_mut(&BankMint::USDC)
+        .mint
+        .mint_to(&insurance_vault, 1_000)
+        .await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
+
+    usdc_bank_f.try_setup_insurance_staking(3_600).await?;
+
+    let usdc_bank = usdc_bank_f.load().await;
+    assert_eq!(usdc_bank.insurance_protocol_shares, native!(1_000, "USDC"));
+
+    let staker_token_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    let staker_share_account = TokenAccountFixture::new(
+        test_f.context.clone(),
+        &usdc_bank_f.get_insurance_share_mint(0).0,
+        &test_f.payer(),
+    )
+    .await;
+
+    usdc_bank_f
+        .try_insurance_stake(staker_token_account.key, staker_share_account.key, 1_000)
+        .await?;
+
+    let shares = staker_share_account.balance().await;
+    assert_eq!(shares, native!(1_000, "USDC"));
+
+    // Collected insurance fees accrue to stakers
+    test_f
+        .get_bank_mut(&BankMint::USDC)
+        .mint
+        .mint_to(&insurance_vault, 500)
+        .await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
+
+    // A staker can have several unstake requests in the cooldown
+    usdc_bank_f
+        .try_insurance_request_unstake(staker_share_account.key, shares / 2, 0)
+        .await?;
+    usdc_bank_f
+        .try_insurance_request_unstake(staker_share_account.key, shares - shares / 2, 1)
+        .await?;
+
+    assert_eq!(staker_share_account.balance().await, 0);
+
+    let early_destination_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    let res = usdc_bank_f
+        .try_insurance_unstake(early_destination_account.key, 0)
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err(),
+        MarginfiError::InsuranceUnstakeCooldownActive
+    );
+
+    test_f.advance_time(3_600).await;
+
+    usdc_bank_f
+        .try_insurance_unstake(staker_token_account.key, 0)
+        .await?;
+
+    assert_eq!(staker_token_account.balance().await, native!(625, "USDC"));
+
+    usdc_bank_f
+        .try_insurance_unstake(staker_token_account.key, 1)
+        .await?;
+
+    assert_eq!(staker_token_account.balance().await, native!(1_250, "USDC"));
+
+    let usdc_bank = usdc_bank_f.load().await;
+    assert_eq!(usdc_bank.insurance_cooldown_shares, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_group_insurance_staking_unowned_vault_balance() -> anyhow::Result<()> {
+    let mut test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let (insurance_vault, _) = test_f
+        .get_bank(&BankMint::USDC)
+        .get_vault(BankVaultType::Insurance);
+
+    test_f
+        .get_bank(&BankMint::USDC)
+        .try_setup_insurance_staking(3_600)
+        .await?;
+
+    // Fees collected before the first stake
+    test_f
+        .get_bank_mut(&BankMint::USDC)
+        .mint
+        .mint_to(&insurance_vault, 500)
+        .await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
+
+    let staker_token_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    let staker_share_account = TokenAccountFixture::new(
+        test_f.context.clone(),
+        &usdc_bank_f.get_insurance_share_mint(0).0,
+        &test_f.payer(),
+    )
+    .await;
+
+    // Stakes too small to mint shares are rejected
+    let res = usdc_bank_f
+        .try_insurance_stake(staker_token_account.key, staker_share_account.key, 0.001)
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::InsuranceStakeTooSmall);
+
+    usdc_bank_f
+        .try_insurance_stake(staker_token_account.key, staker_share_account.key, 1_000)
+        .await?;
+
+    // The fees go to the protocol, the first staker pays for the dead shares
+    assert_eq!(
+        staker_share_account.balance().await,
+        native!(1_000, "USDC") - INSURANCE_DEAD_SHARES
+    );
+
+    let usdc_bank = usdc_bank_f.load().await;
+    assert_eq!(
+        usdc_bank.insurance_protocol_shares,
+        native!(500, "USDC") + INSURANCE_DEAD_SHARES
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_group_insurance_staking_reset_after_depletion() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings {
+        banks: vec![
+            TestBankSetting {
+                mint: BankMint::USDC,
+                config: None,
+            },
+            TestBankSetting {
+                mint: BankMint::SOL,
+                config: Some(BankConfig {
+                    asset_weight_init: I80F48!(1).into(),
+                    ..*DEFAULT_SOL_TEST_BANK_CONFIG
+                }),
+            },
+        ],
+        group_config: Some(GroupConfig { admin: None }),
+    }))
+    .await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
+
+    test_f
+        .usdc_mint
+        .mint_to(&usdc_bank_f.load().await.insurance_vault, 5_000)
+        .await;
+
+    usdc_bank_f.try_setup_insurance_staking(0).await?;
+
+    let staker_token_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(2_000)
+        .await;
+    let staker_share_account = TokenAccountFixture::new(
+        test_f.context.clone(),
+        &usdc_bank_f.get_insurance_share_mint(0).0,
+        &test_f.payer(),
+    )
+    .await;
+
+    usdc_bank_f
+        .try_insurance_stake(staker_token_account.key, staker_share_account.key, 1_000)
+        .await?;
+    usdc_bank_f
+        .try_insurance_request_unstake(staker_share_account.key, native!(400, "USDC"), 0)
+        .await?;
+
+    // Bad debt drains the insurance fund
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(100_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank_f, 100_000)
+        .await?;
+
+    let borrower_account = test_f.create_marginfi_account().await;
+    let borrower_token_account_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_001)
+        .await;
+    borrower_account
+        .try_bank_deposit(
+            borrower_token_account_sol.key,
+            test_f.get_bank(&BankMint::SOL),
+            1_001,
+        )
+        .await?;
+    let borrower_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_account
+        .try_bank_borrow(borrower_token_account_usdc.key, usdc_bank_f, 10_000)
+        .await?;
+
+    let mut borrower_mfi_account = borrower_account.load().await;
+    borrower_mfi_account.lending_account.balances[0]
+        .asset_shares
+        .value = 0;
+    borrower_account.set_account(&borrower_mfi_account).await?;
+
+    test_f
+        .marginfi_group
+        .try_handle_bankruptcy(usdc_bank_f, &borrower_account)
+        .await?;
+
+    let insurance_vault = usdc_bank_f
+        .get_vault_token_account(BankVaultType::Insurance)
+        .await;
+    assert_eq!(insurance_vault.balance().await, 0);
+
+    // The outstanding shares are worthless and block new stakes
+    let res = usdc_bank_f
+        .try_insurance_stake(staker_token_account.key, staker_share_account.key, 1_000)
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::InsuranceFundDepleted);
+
+    usdc_bank_f.try_reset_insurance_staking().await?;
+
+    let usdc_bank = usdc_bank_f.load().await;
+    assert_eq!(usdc_bank.insurance_share_generation, 1);
+    assert_eq!(
+        usdc_bank.insurance_share_mint,
+        usdc_bank_f.get_insurance_share_mint(1).0
+    );
+    assert_eq!(usdc_bank.insurance_protocol_shares, 0);
+    assert_eq!(usdc_bank.insurance_cooldown_shares, 0);
+
+    let res = usdc_bank_f.try_reset_insurance_staking().await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::InsuranceFundNotDepleted);
+
+    // Unstake requests for written off shares are closed without redeeming anything
+    usdc_bank_f
+        .try_insurance_unstake(staker_token_account.key, 0)
+        .await?;
+
+    assert_eq!(staker_token_account.balance().await, native!(1_000, "USDC"));
+
+    let new_share_account = TokenAccountFixture::new(
+        test_f.context.clone(),
+        &usdc_bank_f.get_insurance_share_mint(1).0,
+        &test_f.payer(),
+    )
+    .await;
+
+    usdc_bank_f
+        .try_insurance_stake(staker_token_account.key, new_share_account.key, 1_000)
+        .await?;
+
+    assert_eq!(
+        new_share_account.balance().await,
+        native!(1_000, "USDC") - INSURANCE_DEAD_SHARES
+    );
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn marginfi_group_bank_paused_should_error() -> anyhow::Result<()> {
     let test_f = TestFixture::new(Some(TestSettings {
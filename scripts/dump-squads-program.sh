@@ -0,0 +1,9 @@
+#!/usr/bin/env sh
+ROOT=$(git rev-parse --show-toplevel)
+cd $ROOT
+
+squads_program_id="SQDS4ep65T869zMMBKyuUq6aD6EgTu8psMjkvj52pCf"
+out_dir="clients/rust/marginfi-cli/tests/fixtures"
+
+mkdir -p $out_dir
+solana program dump -u mainnet-beta $squads_program_id $out_dir/squads_multisig_program.so
@@ -3,14 +3,16 @@ use crate::prelude::{
     get_emissions_authority_address, get_emissions_token_account_address, MintFixture,
     TokenAccountFixture,
 };
+use crate::ui_to_native;
 use anchor_lang::{
     prelude::{AccountMeta, Pubkey},
     InstructionData, ToAccountMetas,
 };
 use fixed::types::I80F48;
 use marginfi::{
+    constants::{INSURANCE_SHARE_MINT_SEED, INSURANCE_UNSTAKE_REQUEST_SEED},
     state::marginfi_group::{Bank, BankConfigOpt, BankVaultType},
-    utils::{find_bank_vault_authority_pda, find_bank_vault_pda},
+    utils::{find_bank_vault_authority_pda, find_bank_vault_pda, find_borrow_credential_pda},
 };
 use solana_program::instruction::Instruction;
 use solana_program_test::BanksClientError;
@@ -48,6 +50,37 @@ impl BankFixture {
         find_bank_vault_authority_pda(&self.key, vault_type)
     }
 
+    pub fn get_borrow_credential(&self, marginfi_account: &Pubkey) -> (Pubkey, u8) {
+        find_borrow_credential_pda(&self.key, marginfi_account)
+    }
+
+    pub fn get_insurance_share_mint(&self, generation: u8) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                INSURANCE_SHARE_MINT_SEED.as_bytes(),
+                self.key.as_ref(),
+                &[generation],
+            ],
+            &marginfi::id(),
+        )
+    }
+
+    pub fn get_insurance_unstake_request(
+        &self,
+        authority: &Pubkey,
+        request_index: u64,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                INSURANCE_UNSTAKE_REQUEST_SEED.as_bytes(),
+                self.key.as_ref(),
+                authority.as_ref(),
+                &request_index.to_le_bytes(),
+            ],
+            &marginfi::id(),
+        )
+    }
+
     pub async fn load(&self) -> Bank {
         load_and_deserialize::<Bank>(self.ctx.clone(), &self.key).await
     }
@@ -94,6 +127,334 @@ impl BankFixture {
         Ok(())
     }
 
+    pub async fn try_reset_outflow(&self) -> anyhow::Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::LendingPoolResetBankOutflow {
+                marginfi_group: self.load().await.group,
+                admin: self.ctx.borrow().payer.pubkey(),
+                bank: self.key,
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::LendingPoolResetBankOutflow {}.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&self.ctx.borrow().payer.pubkey()),
+            &[&self.ctx.borrow().payer],
+            self.ctx.borrow().last_blockhash,
+        );
+
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn try_create_borrow_credential(
+        &self,
+        marginfi_account: Pubkey,
+        borrow_limit: u64,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::LendingPoolCreateBorrowCredential {
+                marginfi_group: self.load().await.group,
+                admin: self.ctx.borrow().payer.pubkey(),
+                bank: self.key,
+                marginfi_account,
+                borrow_credential: self.get_borrow_credential(&marginfi_account).0,
+                system_program: solana_program::system_program::id(),
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::LendingPoolCreateBorrowCredential { borrow_limit }.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&self.ctx.borrow().payer.pubkey()),
+            &[&self.ctx.borrow().payer],
+            self.ctx.borrow().last_blockhash,
+        );
+
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn try_revoke_borrow_credential(
+        &self,
+        marginfi_account: Pubkey,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::LendingPoolRevokeBorrowCredential {
+                marginfi_group: self.load().await.group,
+                admin: self.ctx.borrow().payer.pubkey(),
+                borrow_credential: self.get_borrow_credential(&marginfi_account).0,
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::LendingPoolRevokeBorrowCredential {}.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&self.ctx.borrow().payer.pubkey()),
+            &[&self.ctx.borrow().payer],
+            self.ctx.borrow().last_blockhash,
+        );
+
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn try_socialize_bad_debt(&self) -> anyhow::Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::LendingPoolConfigureBank {
+                marginfi_group: self.load().await.group,
+                admin: self.ctx.borrow().payer.pubkey(),
+                bank: self.key,
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::LendingPoolSocializeBadDebt {}.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&self.ctx.borrow().payer.pubkey()),
+            &[&self.ctx.borrow().payer],
+            self.ctx.borrow().last_blockhash,
+        );
+
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn try_setup_insurance_staking(
+        &self,
+        unstake_cooldown: u64,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let bank = self.load().await;
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::LendingPoolSetupInsuranceStaking {
+                marginfi_group: bank.group,
+                admin: self.ctx.borrow().payer.pubkey(),
+                bank: self.key,
+                insurance_vault: self.get_vault(BankVaultType::Insurance).0,
+                insurance_vault_authority: self.get_vault_authority(BankVaultType::Insurance).0,
+                insurance_share_mint: self
+                    .get_insurance_share_mint(bank.insurance_share_generation)
+                    .0,
+                rent: solana_program::sysvar::rent::id(),
+                token_program: anchor_spl::token::ID,
+                system_program: solana_program::system_program::id(),
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::LendingPoolSetupInsuranceStaking { unstake_cooldown }
+                .data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&self.ctx.borrow().payer.pubkey()),
+            &[&self.ctx.borrow().payer],
+            self.ctx.borrow().last_blockhash,
+        );
+
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn try_insurance_stake<T: Into<f64>>(
+        &self,
+        source_account: Pubkey,
+        destination_share_account: Pubkey,
+        ui_amount: T,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let bank = self.load().await;
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::LendingPoolInsuranceStake {
+                marginfi_group: bank.group,
+                bank: self.key,
+                signer: self.ctx.borrow().payer.pubkey(),
+                signer_token_account: source_account,
+                insurance_vault: self.get_vault(BankVaultType::Insurance).0,
+                insurance_vault_authority: self.get_vault_authority(BankVaultType::Insurance).0,
+                insurance_share_mint: bank.insurance_share_mint,
+                destination_share_account,
+                token_program: anchor_spl::token::ID,
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::LendingPoolInsuranceStake {
+                amount: ui_to_native!(ui_amount.into(), self.mint.mint.decimals),
+            }
+            .data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&self.ctx.borrow().payer.pubkey()),
+            &[&self.ctx.borrow().payer],
+            self.ctx.borrow().last_blockhash,
+        );
+
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn try_insurance_request_unstake(
+        &self,
+        share_account: Pubkey,
+        shares: u64,
+        request_index: u64,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let payer = self.ctx.borrow().payer.pubkey();
+        let bank = self.load().await;
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::LendingPoolInsuranceRequestUnstake {
+                marginfi_group: bank.group,
+                bank: self.key,
+                signer: payer,
+                insurance_share_mint: bank.insurance_share_mint,
+                signer_share_account: share_account,
+                unstake_request: self.get_insurance_unstake_request(&payer, request_index).0,
+                token_program: anchor_spl::token::ID,
+                system_program: solana_program::system_program::id(),
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::LendingPoolInsuranceRequestUnstake {
+                shares,
+                request_index,
+            }
+            .data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&self.ctx.borrow().payer.pubkey()),
+            &[&self.ctx.borrow().payer],
+            self.ctx.borrow().last_blockhash,
+        );
+
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn try_insurance_unstake(
+        &self,
+        destination_account: Pubkey,
+        request_index: u64,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let payer = self.ctx.borrow().payer.pubkey();
+        let bank = self.load().await;
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::LendingPoolInsuranceUnstake {
+                marginfi_group: bank.group,
+                bank: self.key,
+                signer: payer,
+                destination_token_account: destination_account,
+                insurance_vault: self.get_vault(BankVaultType::Insurance).0,
+                insurance_vault_authority: self.get_vault_authority(BankVaultType::Insurance).0,
+                insurance_share_mint: bank.insurance_share_mint,
+                unstake_request: self.get_insurance_unstake_request(&payer, request_index).0,
+                token_program: anchor_spl::token::ID,
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::LendingPoolInsuranceUnstake { request_index }.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&self.ctx.borrow().payer.pubkey()),
+            &[&self.ctx.borrow().payer],
+            self.ctx.borrow().last_blockhash,
+        );
+
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn try_reset_insurance_staking(&self) -> anyhow::Result<(), BanksClientError> {
+        let bank = self.load().await;
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::LendingPoolResetInsuranceStaking {
+                marginfi_group: bank.group,
+                admin: self.ctx.borrow().payer.pubkey(),
+                bank: self.key,
+                insurance_vault: self.get_vault(BankVaultType::Insurance).0,
+                insurance_vault_authority: self.get_vault_authority(BankVaultType::Insurance).0,
+                insurance_share_mint: bank.insurance_share_mint,
+                new_insurance_share_mint: self
+                    .get_insurance_share_mint(bank.insurance_share_generation.wrapping_add(1))
+                    .0,
+                rent: solana_program::sysvar::rent::id(),
+                token_program: anchor_spl::token::ID,
+                system_program: solana_program::system_program::id(),
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::LendingPoolResetInsuranceStaking {}.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&self.ctx.borrow().payer.pubkey()),
+            &[&self.ctx.borrow().payer],
+            self.ctx.borrow().last_blockhash,
+        );
+
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await?;
+
+        Ok(())
+    }
+
     #[cfg(feature = "lip")]
     pub async fn try_create_campaign(
         &self,
@@ -268,6 +629,15 @@ impl BankFixture {
         TokenAccountFixture::fetch(self.ctx.clone(), vault).await
     }
 
+    pub async fn set_account(&self, bank: &Bank) -> anyhow::Result<()> {
+        let mut ctx = self.ctx.borrow_mut();
+        let mut account = ctx.banks_client.get_account(self.key).await?.unwrap();
+        account.data[8..].copy_from_slice(bytemuck::bytes_of(bank));
+        ctx.set_account(&self.key, &account.into());
+
+        Ok(())
+    }
+
     pub async fn set_asset_share_value(&self, value: I80F48) {
         let mut bank_ai = self
             .ctx
@@ -0,0 +1,56 @@
+//! Minimal program invoking marginfi instructions via CPI, used to test CPI flashloans.
+//!
+//! The instruction data is a bincode serialized list of instructions, invoked in order
+//! with all the accounts passed to the program.
+
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program::invoke,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+solana_program::declare_id!("FLcpi11111111111111111111111111111111111111");
+
+pub fn process_instruction(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let ixs: Vec<Instruction> =
+        bincode::deserialize(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    for ix in ixs.iter() {
+        invoke(ix, accounts)?;
+    }
+
+    Ok(())
+}
+
+/// Build an instruction invoking `ixs` via CPI, passing the union of their accounts
+/// and the invoked programs.
+pub fn make_cpi_ix(ixs: &[Instruction]) -> Instruction {
+    let mut accounts: Vec<AccountMeta> = vec![];
+
+    for ix in ixs {
+        let program_meta = AccountMeta::new_readonly(ix.program_id, false);
+
+        for meta in ix.accounts.iter().chain(std::iter::once(&program_meta)) {
+            match accounts.iter_mut().find(|a| a.pubkey == meta.pubkey) {
+                Some(existing) => {
+                    existing.is_signer |= meta.is_signer;
+                    existing.is_writable |= meta.is_writable;
+                }
+                None => accounts.push(meta.clone()),
+            }
+        }
+    }
+
+    Instruction {
+        program_id: ID,
+        accounts,
+        data: bincode::serialize(ixs).unwrap(),
+    }
+}
@@ -2,9 +2,14 @@ use super::{bank::BankFixture, prelude::*};
 use crate::ui_to_native;
 use anchor_lang::{prelude::*, system_program, InstructionData, ToAccountMetas};
 use anchor_spl::token;
-use marginfi::state::{
-    marginfi_account::MarginfiAccount,
-    marginfi_group::{Bank, BankVaultType},
+use fixed::types::I80F48;
+use marginfi::{
+    constants::ORDER_SEED,
+    state::{
+        marginfi_account::{BalanceSide, MarginfiAccount},
+        marginfi_group::{Bank, BankVaultType},
+        order::OrderTrigger,
+    },
 };
 use solana_program::{instruction::Instruction, sysvar};
 use solana_program_test::{BanksClientError, ProgramTestContext};
@@ -68,9 +73,10 @@ impl MarginfiAccountFixture {
         ui_amount: T,
     ) -> Instruction {
         let marginfi_account = self.load().await;
+        let bank_config = bank.load().await.config;
         let ctx = self.ctx.borrow_mut();
 
-        Instruction {
+        let mut ix = Instruction {
             program_id: marginfi::id(),
             accounts: marginfi::accounts::LendingAccountDeposit {
                 marginfi_group: marginfi_account.group,
@@ -86,7 +92,16 @@ impl MarginfiAccountFixture {
                 amount: ui_to_native!(ui_amount.into(), bank.mint.mint.decimals),
             }
             .data(),
+        };
+
+        if bank_config.is_deposit_value_limit_active() {
+            ix.accounts.extend([
+                AccountMeta::new_readonly(bank.key, false),
+                AccountMeta::new_readonly(bank_config.oracle_keys[0], false),
+            ]);
         }
+
+        ix
     }
 
     pub async fn try_bank_deposit<T: Into<f64>>(
@@ -143,7 +158,10 @@ impl MarginfiAccountFixture {
             .data(),
         };
 
-        let exclude_vec = match withdraw_all.unwrap_or(false) {
+        // The withdrawn bank pair is kept for the USD outflow limit of a closed balance
+        let exclude_vec = match withdraw_all.unwrap_or(false)
+            && !bank.load().await.is_outflow_value_limit_active()
+        {
             true => vec![bank.key],
             false => vec![],
         };
@@ -180,6 +198,103 @@ impl MarginfiAccountFixture {
         Ok(())
     }
 
+    pub async fn make_transfer_balance_ix<T: Into<f64>>(
+        &self,
+        destination: &MarginfiAccountFixture,
+        bank: &BankFixture,
+        ui_amount: T,
+        transfer_all: Option<bool>,
+    ) -> Instruction {
+        let marginfi_account = self.load().await;
+        let ui_amount: f64 = ui_amount.into();
+
+        let mut ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::LendingAccountTransferBalance {
+                marginfi_group: marginfi_account.group,
+                source_marginfi_account: self.key,
+                destination_marginfi_account: destination.key,
+                signer: self.ctx.borrow().payer.pubkey(),
+                bank: bank.key,
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::LendingAccountTransferBalance {
+                amount: ui_to_native!(ui_amount, bank.mint.mint.decimals),
+                transfer_all,
+            }
+            .data(),
+        };
+
+        // Invalid transfers are left for the program to reject
+        let bank_state = bank.load().await;
+        let (moves_liability, closes_balance) = marginfi_account
+            .lending_account
+            .balances
+            .iter()
+            .find(|balance| balance.active && balance.bank_pk == bank.key)
+            .and_then(|balance| {
+                balance
+                    .get_transfer_shares(
+                        &bank_state,
+                        I80F48::from(ui_to_native!(ui_amount, bank.mint.mint.decimals)),
+                        transfer_all.unwrap_or(false),
+                    )
+                    .ok()
+            })
+            .map(|(side, _, closes_balance)| {
+                (matches!(side, BalanceSide::Liabilities), closes_balance)
+            })
+            .unwrap_or((false, transfer_all.unwrap_or(false)));
+
+        let exclude_vec = match closes_balance {
+            true => vec![bank.key],
+            false => vec![],
+        };
+        ix.accounts.extend_from_slice(
+            &self
+                .load_observation_account_metas(vec![], exclude_vec)
+                .await,
+        );
+        ix.accounts.extend_from_slice(
+            &destination
+                .load_observation_account_metas(vec![bank.key], vec![])
+                .await,
+        );
+
+        if moves_liability && bank_state.config.is_borrow_permissioned() {
+            ix.accounts.push(AccountMeta::new_readonly(
+                bank.get_borrow_credential(&destination.key).0,
+                false,
+            ));
+        }
+
+        ix
+    }
+
+    pub async fn try_transfer_balance<T: Into<f64>>(
+        &self,
+        destination: &MarginfiAccountFixture,
+        bank: &BankFixture,
+        ui_amount: T,
+        transfer_all: Option<bool>,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let ix = self
+            .make_transfer_balance_ix(destination, bank, ui_amount, transfer_all)
+            .await;
+
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await?;
+
+        Ok(())
+    }
+
     pub async fn make_bank_borrow_ix<T: Into<f64>>(
         &self,
         destination_account: Pubkey,
@@ -214,6 +329,13 @@ impl MarginfiAccountFixture {
                 .await,
         );
 
+        if bank.load().await.config.is_borrow_permissioned() {
+            ix.accounts.push(AccountMeta::new_readonly(
+                bank.get_borrow_credential(&self.key).0,
+                false,
+            ));
+        }
+
         ix
     }
 
@@ -386,6 +508,159 @@ impl MarginfiAccountFixture {
                 .await,
         );
 
+        if liab_bank.config.is_borrow_permissioned() {
+            ix.accounts.push(AccountMeta::new_readonly(
+                liab_bank_fixture.get_borrow_credential(&self.key).0,
+                false,
+            ));
+        }
+
+        let compute_budget_ix = ComputeBudgetInstruction::set_compute_unit_limit(1_400_000);
+
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[compute_budget_ix, ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
+    pub fn get_order(
+        &self,
+        asset_bank: &BankFixture,
+        liab_bank: &BankFixture,
+        trigger: OrderTrigger,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                ORDER_SEED.as_bytes(),
+                self.key.as_ref(),
+                asset_bank.key.as_ref(),
+                liab_bank.key.as_ref(),
+                &[trigger as u8],
+            ],
+            &marginfi::id(),
+        )
+    }
+
+    pub async fn try_create_order(
+        &self,
+        asset_bank: &BankFixture,
+        liab_bank: &BankFixture,
+        trigger: OrderTrigger,
+        trigger_value: I80F48,
+        keeper_tip: I80F48,
+    ) -> std::result::Result<(), BanksClientError> {
+        let marginfi_account = self.load().await;
+
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::MarginfiAccountCreateOrder {
+                marginfi_group: marginfi_account.group,
+                marginfi_account: self.key,
+                signer: self.ctx.borrow().payer.pubkey(),
+                asset_bank: asset_bank.key,
+                liab_bank: liab_bank.key,
+                order: self.get_order(asset_bank, liab_bank, trigger).0,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::MarginfiAccountCreateOrder {
+                trigger,
+                trigger_value: trigger_value.into(),
+                keeper_tip: keeper_tip.into(),
+            }
+            .data(),
+        };
+
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
+    pub async fn try_close_order(
+        &self,
+        order: Pubkey,
+    ) -> std::result::Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::MarginfiAccountCloseOrder {
+                marginfi_account: self.key,
+                signer: self.ctx.borrow().payer.pubkey(),
+                order,
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::MarginfiAccountCloseOrder {}.data(),
+        };
+
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
+    pub async fn try_execute_order(
+        &self,
+        asset_bank_fixture: &BankFixture,
+        liab_bank_fixture: &BankFixture,
+        trigger: OrderTrigger,
+        signer_token_account: Pubkey,
+        destination_token_account: Pubkey,
+    ) -> std::result::Result<(), BanksClientError> {
+        let marginfi_account = self.load().await;
+
+        let asset_bank = asset_bank_fixture.load().await;
+        let liab_bank = liab_bank_fixture.load().await;
+
+        let mut accounts = marginfi::accounts::LendingAccountExecuteOrder {
+            marginfi_group: marginfi_account.group,
+            marginfi_account: self.key,
+            marginfi_account_authority: marginfi_account.authority,
+            order: self
+                .get_order(asset_bank_fixture, liab_bank_fixture, trigger)
+                .0,
+            asset_bank: asset_bank_fixture.key,
+            liab_bank: liab_bank_fixture.key,
+            signer: self.ctx.borrow().payer.pubkey(),
+            signer_token_account,
+            destination_token_account,
+            asset_bank_liquidity_vault_authority: asset_bank_fixture
+                .get_vault_authority(BankVaultType::Liquidity)
+                .0,
+            asset_bank_liquidity_vault: asset_bank_fixture.get_vault(BankVaultType::Liquidity).0,
+            liab_bank_liquidity_vault: liab_bank_fixture.get_vault(BankVaultType::Liquidity).0,
+            token_program: token::ID,
+        }
+        .to_account_metas(Some(true));
+
+        accounts.extend(vec![
+            AccountMeta::new_readonly(asset_bank.config.oracle_keys[0], false),
+            AccountMeta::new_readonly(liab_bank.config.oracle_keys[0], false),
+        ]);
+
+        let mut ix = Instruction {
+            program_id: marginfi::id(),
+            accounts,
+            data: marginfi::instruction::LendingAccountExecuteOrder {}.data(),
+        };
+
+        ix.accounts
+            .extend_from_slice(&self.load_observation_account_metas(vec![], vec![]).await);
+
         let compute_budget_ix = ComputeBudgetInstruction::set_compute_unit_limit(1_400_000);
 
         let mut ctx = self.ctx.borrow_mut();
@@ -498,6 +773,18 @@ impl MarginfiAccountFixture {
         }
     }
 
+    /// Start flashloan ix to be invoked via CPI, with the marginfi group as remaining account
+    pub async fn make_lending_account_start_flashloan_cpi_ix(&self, end_index: u64) -> Instruction {
+        let mut ix = self
+            .make_lending_account_start_flashloan_ix(end_index)
+            .await;
+
+        ix.accounts
+            .push(AccountMeta::new_readonly(self.load().await.group, false));
+
+        ix
+    }
+
     pub async fn make_lending_account_end_flashloan_ix(
         &self,
         include_banks: Vec<Pubkey>,
@@ -509,10 +796,17 @@ impl MarginfiAccountFixture {
         }
         .to_account_metas(Some(true));
 
-        account_metas.extend(
-            self.load_observation_account_metas(include_banks, exclude_banks)
-                .await,
-        );
+        let mut observation_account_metas = self
+            .load_observation_account_metas(include_banks, exclude_banks)
+            .await;
+
+        // Banks are charged the flashloan fee when the flashloan ends
+        observation_account_metas
+            .iter_mut()
+            .step_by(2)
+            .for_each(|bank_meta| bank_meta.is_writable = true);
+
+        account_metas.extend(observation_account_metas);
 
         Instruction {
             program_id: marginfi::id(),
@@ -241,6 +241,39 @@ impl MarginfiGroupFixture {
         Ok(())
     }
 
+    pub async fn try_configure_flashloan_cpi_program(
+        &self,
+        program_id: Pubkey,
+        allowed: bool,
+    ) -> Result<(), BanksClientError> {
+        let mut ctx = self.ctx.borrow_mut();
+
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::MarginfiGroupConfigure {
+                marginfi_group: self.key,
+                admin: ctx.payer.pubkey(),
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::MarginfiGroupConfigureFlashloanCpiProgram {
+                program_id,
+                allowed,
+            }
+            .data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await?;
+
+        Ok(())
+    }
+
     pub async fn try_accrue_interest(&self, bank: &BankFixture) -> Result<()> {
         let mut ctx = self.ctx.borrow_mut();
 
@@ -337,6 +370,54 @@ impl MarginfiGroupFixture {
         ctx.banks_client.process_transaction(tx).await
     }
 
+    pub async fn try_auction_bad_debt_collateral(
+        &self,
+        asset_bank: &BankFixture,
+        debt_bank: &BankFixture,
+        marginfi_account: &MarginfiAccountFixture,
+        signer_token_account: Pubkey,
+        destination_token_account: Pubkey,
+    ) -> Result<(), BanksClientError> {
+        let mut accounts = marginfi::accounts::LendingPoolAuctionBadDebtCollateral {
+            marginfi_group: self.key,
+            asset_bank: asset_bank.key,
+            debt_bank: debt_bank.key,
+            marginfi_account: marginfi_account.key,
+            signer: self.ctx.borrow().payer.pubkey(),
+            signer_token_account,
+            destination_token_account,
+            asset_bank_liquidity_vault_authority: asset_bank
+                .get_vault_authority(BankVaultType::Liquidity)
+                .0,
+            asset_bank_liquidity_vault: asset_bank.get_vault(BankVaultType::Liquidity).0,
+            debt_bank_liquidity_vault: debt_bank.get_vault(BankVaultType::Liquidity).0,
+            token_program: token::ID,
+        }
+        .to_account_metas(Some(true));
+
+        accounts.extend(vec![
+            AccountMeta::new_readonly(asset_bank.load().await.config.oracle_keys[0], false),
+            AccountMeta::new_readonly(debt_bank.load().await.config.oracle_keys[0], false),
+        ]);
+
+        let mut ctx = self.ctx.borrow_mut();
+
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts,
+            data: marginfi::instruction::LendingPoolAuctionBadDebtCollateral {}.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
     pub fn get_size() -> usize {
         8 + mem::size_of::<MarginfiGroup>()
     }
@@ -1,4 +1,4 @@
-use crate::{marginfi_group::*, native, spl::*, utils::*};
+use crate::{flashloan_cpi, marginfi_group::*, native, spl::*, utils::*};
 use anchor_lang::prelude::*;
 use bincode::deserialize;
 use solana_sdk::account::AccountSharedData;
@@ -276,6 +276,12 @@ impl TestFixture {
     pub async fn new(test_settings: Option<TestSettings>) -> TestFixture {
         let mut program = ProgramTest::new("marginfi", marginfi::ID, processor!(marginfi::entry));
 
+        program.add_program(
+            "flashloan_cpi",
+            flashloan_cpi::ID,
+            processor!(flashloan_cpi::process_instruction),
+        );
+
         #[cfg(feature = "lip")]
         program.add_program(
             "liquidity_incentive_program",